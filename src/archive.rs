@@ -0,0 +1,104 @@
+//! アーカイブ出力モジュール
+//!
+//! 分類結果をカテゴリフォルダ構造のままzipファイルに書き出します。
+//! ファイルはストリーミングで書き込むため、大きなファイルでもメモリを圧迫しません。
+
+use crate::file_ops::generate_unique_path_with;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// zipアーカイブへの書き込みを管理する構造体
+pub struct ArchiveWriter {
+    writer: ZipWriter<File>,
+    /// 書き込み済みのエントリ名（重複回避用）
+    entries: HashSet<PathBuf>,
+}
+
+impl ArchiveWriter {
+    /// 新しいzipファイルを作成する（既存ファイルは上書き）
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create archive: {}", path.display()))?;
+        Ok(Self {
+            writer: ZipWriter::new(file),
+            entries: HashSet::new(),
+        })
+    }
+
+    /// ファイルを `<folder>/<filename>` としてアーカイブに追加する
+    ///
+    /// 同名のエントリが既にある場合は `generate_unique_path` と同じ規則で連番を付けます。
+    ///
+    /// # Returns
+    /// 実際に使用したエントリ名
+    pub fn add_file(&mut self, source: &Path, folder: &str, filename: &str) -> Result<String> {
+        let entry_path =
+            generate_unique_path_with(Path::new(folder), filename, |p| self.entries.contains(p));
+        // zipのエントリ名は常に '/' 区切り
+        let entry_name = format!(
+            "{}/{}",
+            folder,
+            entry_path.file_name().unwrap_or_default().to_string_lossy()
+        );
+
+        let size = fs::metadata(source)
+            .with_context(|| format!("Failed to read metadata: {}", source.display()))?
+            .len();
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(size >= u32::MAX as u64);
+        self.writer
+            .start_file(entry_name.as_str(), options)
+            .with_context(|| format!("Failed to start archive entry: {}", entry_name))?;
+
+        let mut reader = BufReader::new(
+            File::open(source)
+                .with_context(|| format!("Failed to open file: {}", source.display()))?,
+        );
+        io::copy(&mut reader, &mut self.writer)
+            .with_context(|| format!("Failed to write {} to archive", source.display()))?;
+
+        debug!("Archived: {} -> {}", source.display(), entry_name);
+        self.entries.insert(entry_path);
+        Ok(entry_name)
+    }
+
+    /// アーカイブを書き終えて閉じる
+    pub fn finish(self) -> Result<()> {
+        self.writer.finish().context("Failed to finalize archive")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_archive_dedups_entry_names() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a.txt"), "first").unwrap();
+        fs::write(dir.path().join("sub").join("a.txt"), "second").unwrap();
+
+        let zip_path = dir.path().join("out.zip");
+        let mut archive = ArchiveWriter::create(&zip_path).unwrap();
+        let first = archive
+            .add_file(&dir.path().join("a.txt"), "Documents", "a.txt")
+            .unwrap();
+        let second = archive
+            .add_file(&dir.path().join("sub").join("a.txt"), "Documents", "a.txt")
+            .unwrap();
+        archive.finish().unwrap();
+
+        assert_eq!(first, "Documents/a.txt");
+        assert_eq!(second, "Documents/a_1.txt");
+    }
+}
@@ -2,8 +2,27 @@
 //!
 //! clapのderiveパターンを使用して、型安全なCLIインターフェースを定義します。
 
-use clap::Parser;
+use clap::builder::ArgPredicate;
+use clap::{ArgGroup, Parser, ValueEnum};
+use smart_sorter::config::{self, Category};
+use smart_sorter::date::parse_cutoff;
+use smart_sorter::file_ops::ConflictStrategy;
+use smart_sorter::output::OutputFormat;
+use smart_sorter::size::parse_size;
+use smart_sorter::sorter::{CollapseTarget, EmptyFiles};
+use smart_sorter::stability::parse_duration;
+use smart_sorter::template::{DedupTemplate, DestTemplate, RenameTemplate};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// `--older-than`・`--newer-than` の値を現在時刻を基準に解析する
+fn parse_age(value: &str) -> anyhow::Result<SystemTime> {
+    parse_cutoff(value, SystemTime::now())
+}
+
+/// `--max-filename-bytes` に指定できる最小値
+const MIN_FILENAME_BYTES: i64 = 16;
 
 /// smart-sorter: ファイルを拡張子に基づいて自動整理するCLIツール
 ///
@@ -19,12 +38,23 @@ use std::path::PathBuf;
     long_about = "指定されたディレクトリ内のファイルを、拡張子に基づいて\n\
                   Images, Videos, Documents, Music, Archives, Code, Others などの\n\
                   カテゴリフォルダに自動的に振り分けます。\n\n\
-                  安全のため、--dry-run オプションで事前確認することを推奨します。"
+                  安全のため、--dry-run オプションで事前確認することを推奨します。",
+    group(ArgGroup::new("descend").args(["recursive", "unflatten", "flatten"]).multiple(true))
 )]
 pub struct Args {
-    /// 整理対象のディレクトリパス
-    #[arg(value_name = "TARGET_DIR", help = "整理対象のディレクトリパス")]
-    pub target_dir: PathBuf,
+    /// 整理対象のディレクトリパス（複数指定した場合は順に整理する）
+    #[arg(
+        value_name = "TARGET_DIR",
+        num_args = 1..,
+        required = false,
+        required_unless_present_any = ["lifetime_stats", "print_schema"],
+        default_value_ifs([
+            ("lifetime_stats", ArgPredicate::IsPresent, Some(".")),
+            ("print_schema", ArgPredicate::IsPresent, Some(".")),
+        ]),
+        help = "整理対象のディレクトリパス（複数指定した場合は順に整理する）"
+    )]
+    pub target_dirs: Vec<PathBuf>,
 
     /// Dry Runモード（実際には移動せず、プレビューのみ表示）
     #[arg(
@@ -34,6 +64,17 @@ pub struct Args {
     )]
     pub dry_run: bool,
 
+    /// 整理が必要かを調べ、終了コードで返す（Dry Run）
+    #[arg(
+        long = "check",
+        conflicts_with_all = [
+            "report_name_collisions", "fragmentation", "extension_report", "unflatten", "flatten",
+        ],
+        help = "整理が必要かを調べ、終了コードで返す（Dry Run、--verbose 以外では出力なし）\n\
+                0: 整理済み / 2: 移動されるファイルがある / 1: エラー"
+    )]
+    pub check: bool,
+
     /// サブディレクトリも再帰的に処理する
     #[arg(
         short = 'r',
@@ -42,9 +83,1003 @@ pub struct Args {
     )]
     pub recursive: bool,
 
+    /// 再帰処理で降りるサブディレクトリの深さの上限
+    #[arg(
+        long = "max-depth",
+        value_name = "N",
+        requires = "descend",
+        help = "--recursive・--unflatten・--flatten で降りるサブディレクトリの深さの上限\n\
+                （0 は対象ディレクトリ直下のみ、1 はその1階層下まで）"
+    )]
+    pub max_depth: Option<usize>,
+
+    /// サブディレクトリの構成を保ったままカテゴリフォルダに振り分ける
+    #[arg(
+        long = "preserve-tree",
+        requires = "recursive",
+        help = "サブディレクトリのファイルを、対象ディレクトリからの相対パスを保って振り分ける\n\
+                （例: a/b/photo.jpg → Images/a/b/photo.jpg）"
+    )]
+    pub preserve_tree: bool,
+
+    /// カテゴリフォルダの中を拡張子ごとのサブフォルダに分ける
+    #[arg(
+        long = "subfolder-by-ext",
+        help = "カテゴリフォルダの中を小文字の拡張子ごとのサブフォルダに分ける\n\
+                （例: Code/rs/main.rs、Images/png/logo.png。拡張子がなければ _noext）"
+    )]
+    pub subfolder_by_ext: bool,
+
+    /// Others のファイルだけを拡張子ごとのサブフォルダに分ける
+    #[arg(
+        long = "others-by-ext",
+        conflicts_with = "subfolder_by_ext",
+        help = "Others のファイルだけを拡張子ごとのサブフォルダに分ける\n\
+                （例: Others/xyz/data.xyz。拡張子がなければ Others/none/）"
+    )]
+    pub others_by_ext: bool,
+
+    /// シンボリックリンクのファイルとディレクトリもたどって整理する
+    #[arg(
+        long = "follow-symlinks",
+        help = "シンボリックリンクのファイルとディレクトリもたどって整理する（循環するリンクは1度だけたどる）\n\
+                （移動されるのはリンク自体でリンク先は残る。リンクしたディレクトリの中のファイルは実体が移動される。\n\
+                システムやホームディレクトリへのリンクは --force なしではたどらない）"
+    )]
+    pub follow_symlinks: bool,
+
+    /// これより小さいファイルを除外する
+    #[arg(
+        long = "min-size",
+        value_name = "SIZE",
+        value_parser = parse_size,
+        help = "これより小さいファイルを除外する（例: 100MB、2GiB、512。KB・MB は1000倍、KiB・MiB は1024倍）"
+    )]
+    pub min_size: Option<u64>,
+
+    /// これより大きいファイルを除外する
+    #[arg(
+        long = "max-size",
+        value_name = "SIZE",
+        value_parser = parse_size,
+        help = "これより大きいファイルを除外する（書式は --min-size と同じ）"
+    )]
+    pub max_size: Option<u64>,
+
+    /// この期間より前（またはこの日付より前）に更新されたファイルだけを整理する
+    #[arg(
+        long = "older-than",
+        value_name = "AGE",
+        value_parser = parse_age,
+        help = "この期間より前に更新されたファイルだけを整理する（例: 30d、6mo、1y、2024-01-31）\n\
+                （単位は h・d・w・mo（30日）・y（365日）。日付はその日の0時（UTC）。更新日時を読めないファイルは除外）"
+    )]
+    pub older_than: Option<SystemTime>,
+
+    /// この期間内（またはこの日付以降）に更新されたファイルだけを整理する
+    #[arg(
+        long = "newer-than",
+        value_name = "AGE",
+        value_parser = parse_age,
+        help = "この期間内に更新されたファイルだけを整理する（書式は --older-than と同じ）"
+    )]
+    pub newer_than: Option<SystemTime>,
+
+    /// 整理の対象から外すファイル名のグロブパターン
+    #[arg(
+        long = "exclude",
+        value_name = "GLOB",
+        value_parser = parse_glob,
+        help = "整理の対象から外すファイル名のグロブパターン（複数指定可、例: '*.part'、desktop.ini）\n\
+                一致したファイルはプレビューにも表示せず、件数だけをサマリーに表示する"
+    )]
+    pub exclude: Vec<String>,
+
+    /// いずれかに一致するファイルだけを整理の対象にするグロブパターン
+    #[arg(
+        long = "include",
+        value_name = "GLOB",
+        value_parser = parse_glob,
+        help = "指定した場合、いずれかに一致するファイルだけを整理の対象にする（複数指定可、例: '*.jpg'）\n\
+                --exclude にも一致するファイルは除外を優先する。一致しないファイルはスキップとして数える"
+    )]
+    pub include: Vec<String>,
+
+    /// --exclude / --include をファイル名ではなく対象ディレクトリからの相対パスに照合する
+    #[arg(
+        long = "match-path",
+        help = "--exclude / --include をファイル名ではなく対象ディレクトリからの相対パスに照合する（例: 'build/*'）"
+    )]
+    pub match_path: bool,
+
+    /// Dry Runのプレビューで移動元と移動先を列で揃えて表示する
+    #[arg(
+        long = "columns",
+        help = "Dry Runのプレビューで移動元と移動先を列で揃えて表示する"
+    )]
+    pub columns: bool,
+
+    /// Dry Runのプレビューを移動先のツリーで表示する
+    #[arg(
+        long = "tree",
+        conflicts_with = "columns",
+        help = "Dry Runのプレビューを1行ずつではなく、移動後のフォルダ構成のツリーで表示する"
+    )]
+    pub tree: bool,
+
+    /// Dry Runで各移動先フォルダに既に存在するものとみなすファイル名のリスト
+    #[arg(
+        long = "assume-existing",
+        value_name = "PATH",
+        requires = "dry_run",
+        help = "Dry Runで各移動先フォルダに既に存在するものとみなすファイル名のリスト（1行1ファイル名）"
+    )]
+    pub assume_existing: Option<PathBuf>,
+
+    /// Codeカテゴリのファイルを言語ごとのサブフォルダに振り分ける
+    #[arg(
+        long = "code-by-language",
+        help = "Codeカテゴリのファイルを言語ごとのサブフォルダ（Code/Rust/ など）に振り分ける"
+    )]
+    pub code_by_language: bool,
+
+    /// Documentsカテゴリのテキストファイルを内容の言語ごとに振り分ける
+    #[arg(
+        long = "doc-by-language",
+        help = "Documentsカテゴリのテキストファイルを内容から判定した言語ごとのサブフォルダ\n\
+                （Documents/English/ など）に振り分ける（判定できない場合は Documents 直下）"
+    )]
+    pub doc_by_language: bool,
+
+    /// 末尾の拡張子が未知の場合、その前のドット区切りの部分でも分類を試みる
+    #[arg(
+        long = "deep-ext-match",
+        help = "末尾の拡張子が未知の場合、その前のドット区切りの部分でも分類を試みる（例: dump.sql.bak → Code）"
+    )]
+    pub deep_ext_match: bool,
+
+    /// 拡張子のないファイルを先頭のバイトから分類する
+    #[arg(
+        long = "detect-content",
+        help = "拡張子のないファイルを先頭のバイト（マジックナンバー）から分類する\n\
+                （例: 拡張子のないPNG画像 → Images。判定できない場合は Others、ファイルの読み込みが発生する）"
+    )]
+    pub detect_content: bool,
+
+    /// 既知の拡張子の打ち間違いを訂正して分類し、移動先の拡張子も直す
+    #[arg(
+        long = "fix-extensions",
+        help = "既知の拡張子の打ち間違いを訂正して分類し、移動先の拡張子も直す\n\
+                （例: photo.jgp → Images/photo.jpg。候補が1つに絞れない場合や、.rst・.pem など実在する拡張子は訂正しない）"
+    )]
+    pub fix_extensions: bool,
+
+    /// 再帰時、.category マーカーのあるディレクトリのファイルを記されたカテゴリに振り分ける
+    #[arg(
+        long = "dir-category-files",
+        help = "ディレクトリに .category マーカー（カテゴリ名を1行）があれば、\n\
+                そのディレクトリのファイルを拡張子に関係なく記されたカテゴリに振り分ける"
+    )]
+    pub dir_category_files: bool,
+
+    /// ファイル名の日付（なければ更新日時）でカテゴリフォルダ内の 年/月/ に振り分ける
+    #[arg(
+        long = "date-from-name",
+        help = "ファイル名の日付（なければ更新日時）でカテゴリフォルダ内の 年/月/ に振り分ける\n\
+                （例: IMG_20230715_1234.jpg → Images/2023/07/、2023-07-15_report.pdf → Documents/2023/07/）"
+    )]
+    pub date_from_name: bool,
+
+    /// 更新日時でカテゴリフォルダ内の 年/月/ に振り分ける
+    #[arg(
+        long = "by-date",
+        conflicts_with = "date_from_name",
+        help = "更新日時（取得できなければ作成日時）でカテゴリフォルダ内の 年/月/ に振り分ける\n\
+                （例: Images/2023/07/。日付が得られない場合はカテゴリ直下）"
+    )]
+    pub by_date: bool,
+
+    /// 拡張子による分類より先に評価する振り分けルール
+    #[arg(
+        long = "rule",
+        value_name = "RULE",
+        help = "拡張子による分類より先に評価する振り分けルール（複数指定可、上から順に評価）\n\
+                書式: '<条件> => <カテゴリ>[/<サブフォルダ>]'  例: 'size > 5MB && ext == jpg => Images/Large'\n\
+                フィールド: size, age, ext, name  演算子: == != < <= > >= ~（正規表現）&& ||"
+    )]
+    pub rule: Vec<String>,
+
+    /// ファイル名が区切り文字を含む場合、最初の区切りより前の部分をフォルダ名として振り分ける
+    #[arg(
+        long = "group-by-prefix",
+        value_name = "DELIM",
+        value_parser = clap::builder::NonEmptyStringValueParser::new(),
+        help = "ファイル名が区切り文字を含む場合、最初の区切りより前の部分をフォルダ名として振り分ける\n\
+                （例: _ で ProjectX_report.pdf → ProjectX/report.pdf、拡張子による分類より優先）"
+    )]
+    pub group_by_prefix: Option<String>,
+
+    /// 更新日時の新しい順に、最大N件のファイルだけを処理する
+    #[arg(
+        long = "latest",
+        value_name = "N",
+        help = "更新日時の新しい順に、最大N件のファイルだけを処理する（最近のダウンロードの整理向け）"
+    )]
+    pub latest: Option<NonZeroUsize>,
+
+    /// ファイルがN件未満のカテゴリは移動せずに残す
+    #[arg(
+        long = "min-files",
+        value_name = "N",
+        conflicts_with = "watch",
+        help = "振り分け先のファイルがN件未満のカテゴリは、フォルダを作らずにファイルをその場に残す\n\
+                カテゴリフォルダが既にあるカテゴリは件数に関係なく移動する"
+    )]
+    pub min_files: Option<usize>,
+
+    /// 移動先のファイル名をテンプレートで組み立てる
+    #[arg(
+        long = "rename-template",
+        value_name = "TEMPLATE",
+        help = "移動先のファイル名をテンプレートで組み立てる（例: {date}_{category}_{n}{ext}）\n\
+                使用可能: {date}, {category}, {n}, {ext}, {stem}"
+    )]
+    pub rename_template: Option<RenameTemplate>,
+
+    /// カテゴリに関係なく、N件ずつ Batch_001/ などの連番フォルダに振り分ける
+    #[arg(
+        long = "batch-folders",
+        value_name = "N",
+        conflicts_with_all = ["dest_template", "group_by_prefix", "rule"],
+        help = "カテゴリに関係なく、パス順にN件ずつ Batch_001/, Batch_002/ ... に振り分ける"
+    )]
+    pub batch_folders: Option<NonZeroUsize>,
+
+    /// 移動先ディレクトリの構成をテンプレートで組み立てる
+    #[arg(
+        long = "dest-template",
+        value_name = "TEMPLATE",
+        conflicts_with_all = ["code_by_language", "doc_by_language", "by_date"],
+        help = "移動先ディレクトリ（対象ディレクトリからの相対パス）をテンプレートで組み立てる\n\
+                例: {category}/{year}/{ext}  使用可能: {category}, {date}, {year}, {month}, {day}, {ext}\n\
+                （日付は更新日時、--date-from-name 指定時はファイル名の日付を優先）"
+    )]
+    pub dest_template: Option<DestTemplate>,
+
+    /// 移動後、ファイルが1つだけのカテゴリフォルダを解消する
+    #[arg(
+        long = "collapse-singletons",
+        value_name = "WHERE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "root",
+        help = "移動後、今回移動した1ファイルだけが入ったカテゴリフォルダを解消する\n\
+                root: ルートに戻す（既定） / origin: 移動前の場所に戻す\n\
+                実行前からあったファイルだけのフォルダには触れない"
+    )]
+    pub collapse_singletons: Option<CollapseTarget>,
+
+    /// ファイル名に関係なく内容が同一のファイルは最初の1つだけを移動する
+    #[arg(
+        long = "merge-content-duplicates",
+        help = "ファイル名に関係なく内容が同一のファイルは最初の1つだけを移動する"
+    )]
+    pub merge_content_duplicates: bool,
+
+    /// 整理の前に、内容が同一のファイルを最初の1つだけ残してゴミ箱に移す
+    #[arg(
+        long = "dedupe-first",
+        conflicts_with = "merge_content_duplicates",
+        help = "整理の前に、内容が同一のファイルを最初の1つだけ残してゴミ箱に移す（パス順で最初のものを残す）\n\
+                ゴミ箱に移せなかったファイルは残してエラーとして数える"
+    )]
+    pub dedupe_first: bool,
+
+    /// --dedupe-first で重複をゴミ箱に移さず完全に削除する
+    #[arg(
+        long = "dedupe-delete",
+        requires = "dedupe_first",
+        help = "--dedupe-first で重複をゴミ箱に移さず完全に削除する（元に戻せない）"
+    )]
+    pub dedupe_delete: bool,
+
+    /// 内容の重複検出で保持するハッシュ数の上限（直近に参照されたN個）
+    #[arg(
+        long = "dedup-window",
+        value_name = "N",
+        help = "--merge-content-duplicates / --dedupe-first で保持するハッシュ数の上限（直近に参照されたN個）\n\
+                メモリ使用量を抑える代わりに、パス順で離れた位置にある重複は見逃されることがある"
+    )]
+    pub dedup_window: Option<NonZeroUsize>,
+
+    /// 移動先に同名で内容も同一のファイルがある場合は移動しない
+    #[arg(
+        long = "dedup-content",
+        help = "移動先に同名で内容も同一（SHA-256）のファイルがある場合は、連番でリネームして\n\
+                移動せずに重複としてスキップする（元のファイルはそのまま残す）"
+    )]
+    pub dedup_content: bool,
+
+    /// 移動の代わりに、カテゴリフォルダ構造でzipファイルに書き出す
+    #[arg(
+        long = "archive-output",
+        value_name = "PATH.zip",
+        help = "移動の代わりに、カテゴリフォルダ構造でzipファイルに書き出す"
+    )]
+    pub archive_output: Option<PathBuf>,
+
+    /// zipへの書き出し後に元ファイルを削除する
+    #[arg(
+        long = "delete-originals",
+        requires = "archive_output",
+        help = "--archive-output の書き出し後に元ファイルを削除する"
+    )]
+    pub delete_originals: bool,
+
+    /// 移動の代わりに、指定ディレクトリにカテゴリフォルダ構造のシンボリックリンクを作成する
+    #[arg(
+        long = "symlink-farm",
+        value_name = "DIR",
+        conflicts_with_all = ["archive_output", "dedupe_first", "collapse_singletons"],
+        help = "移動の代わりに、指定ディレクトリにカテゴリフォルダ構造のシンボリックリンクを作成する\n\
+                （元のファイルは移動しない）"
+    )]
+    pub symlink_farm: Option<PathBuf>,
+
+    /// 移動の代わりにコピーする
+    #[arg(
+        short = 'c',
+        long = "copy",
+        conflicts_with_all = [
+            "archive_output",
+            "symlink_farm",
+            "hardlink_view",
+            "dedupe_first",
+            "collapse_singletons",
+            "unflatten",
+            "flatten",
+        ],
+        help = "移動の代わりにコピーする（元のファイルはそのまま残す）"
+    )]
+    pub copy: bool,
+
+    /// 移動の代わりにハードリンクを作成する
+    #[arg(
+        long = "hardlink",
+        conflicts_with_all = [
+            "copy",
+            "archive_output",
+            "symlink_farm",
+            "hardlink_view",
+            "dedupe_first",
+            "collapse_singletons",
+            "unflatten",
+            "flatten",
+        ],
+        help = "移動の代わりにカテゴリフォルダへハードリンクを作成する\n\
+                （元のファイルはそのまま残し、容量を使わない。同じファイルシステム内のみ）"
+    )]
+    pub hardlink: bool,
+
+    /// 移動の代わりに、指定ディレクトリにカテゴリフォルダ構造のハードリンクを作成する
+    #[arg(
+        long = "hardlink-view",
+        value_name = "DIR",
+        conflicts_with_all = ["archive_output", "symlink_farm", "dedupe_first", "collapse_singletons"],
+        help = "移動の代わりに、指定ディレクトリにカテゴリフォルダ構造のハードリンクを作成する\n\
+                （元のファイルは移動しない。別のファイルシステムの場合は警告してコピーする）"
+    )]
+    pub hardlink_view: Option<PathBuf>,
+
+    /// 対象ディレクトリを受信箱として、アーカイブのカテゴリ構成に振り分ける
+    #[arg(
+        long = "sync-into",
+        value_name = "ARCHIVE_DIR",
+        conflicts_with_all = ["archive_output", "symlink_farm", "hardlink_view", "collapse_singletons"],
+        help = "対象ディレクトリを受信箱として、指定したアーカイブのカテゴリ構成に振り分ける\n\
+                （整理後に突き合わせ結果を表示し、アーカイブにしかないファイルは変更しない）"
+    )]
+    pub sync_into: Option<PathBuf>,
+
+    /// 対象ディレクトリではなく、指定ディレクトリの下にカテゴリフォルダを作って振り分ける
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "DIR",
+        conflicts_with_all = [
+            "sync_into",
+            "archive_output",
+            "symlink_farm",
+            "hardlink_view",
+            "collapse_singletons",
+        ],
+        help = "対象ディレクトリではなく、指定ディレクトリの下にカテゴリフォルダを作って振り分ける\n\
+                （対象ディレクトリ内のカテゴリ名のフォルダも通常のフォルダとして扱う）"
+    )]
+    pub output: Option<PathBuf>,
+
+    /// 移動を1件ずつ操作ログに追記する
+    #[arg(
+        long = "log-file",
+        value_name = "PATH",
+        help = "移動（Dry Runでは移動の予定）を1件ずつ JSON Lines で指定ファイルに追記する\n\
+                （時刻、移動元、移動先、カテゴリ、リネームの有無、結果。開けない場合は何も移動せずにエラー、\n\
+                途中の書き込みに失敗しても整理は続行）"
+    )]
+    pub log_file: Option<PathBuf>,
+
+    /// 移動先のファイル名のバイト数の上限
+    #[arg(
+        long = "max-filename-bytes",
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(MIN_FILENAME_BYTES..),
+        help = "移動先のファイル名を N バイト以内に収める（16以上）\n\
+                （重複回避の連番は残し、元の名前を文字の境界で切り詰める。\
+                拡張子は連番と合わせて収まらない場合だけ切り詰める）"
+    )]
+    pub max_filename_bytes: Option<u16>,
+
+    /// 移動先で名前が重複したときに連番を付けるファイル名のテンプレート
+    #[arg(
+        long = "dedup-template",
+        value_name = "TEMPLATE",
+        help = "移動先で名前が重複したときのファイル名をテンプレートで組み立てる\n\
+                （既定: {stem}_{n}{ext}、例: \"{stem} ({n}){ext}\"）\n\
+                使用可能: {stem}, {n}（必須）, {ext}, {date}（実行した日）"
+    )]
+    pub dedup_template: Option<DedupTemplate>,
+
+    /// 実行権限を持つファイルを拡張子より優先して振り分ける（Unixのみ）
+    #[arg(
+        long = "detect-executables",
+        help = "実行権限を持つファイルを拡張子より優先して振り分ける（Unixのみ）"
+    )]
+    pub detect_executables: bool,
+
+    /// --detect-executables で使用するカテゴリ
+    #[arg(
+        long = "executables-category",
+        value_name = "CATEGORY",
+        default_value = "Programs",
+        help = "--detect-executables で使用するカテゴリ"
+    )]
+    pub executables_category: Category,
+
+    /// 異なるディレクトリにある同名ファイルを報告する（ファイルは移動しない）
+    #[arg(
+        long = "report-name-collisions",
+        help = "異なるディレクトリにある同名ファイルを報告する（ファイルは移動しない）"
+    )]
+    pub report_name_collisions: bool,
+
+    /// 拡張子ごとに、ファイルがいくつのディレクトリに散らばっているかを報告する（ファイルは移動しない）
+    #[arg(
+        long = "fragmentation",
+        conflicts_with = "report_name_collisions",
+        help = "拡張子ごとに、ファイルがいくつのディレクトリに散らばっているかを報告する（ファイルは移動しない）"
+    )]
+    pub fragmentation: bool,
+
+    /// どのカテゴリにも当てはまらない拡張子をファイル数の多い順に一覧する（ファイルは移動しない）
+    #[arg(
+        long = "extension-report",
+        conflicts_with_all = ["report_name_collisions", "fragmentation"],
+        help = "どのカテゴリにも当てはまらない（Others になる）拡張子をファイル数の多い順に一覧する\n\
+                （ファイルは移動しない。設定ファイルに追加する拡張子を探す用途向け）"
+    )]
+    pub extension_report: bool,
+
+    /// ファイルを分類し、カテゴリごとの件数とサイズだけを表示する（ファイルは移動しない）
+    #[arg(
+        long = "stats-only",
+        conflicts_with_all = [
+            "check", "report_name_collisions", "fragmentation", "extension_report", "unflatten",
+            "flatten", "undo", "apply_plan", "export_plan",
+        ],
+        help = "ファイルを分類し、カテゴリごとの件数とサイズだけを表示する（ファイルは移動しない）\n\
+                （Dry Runと違い移動先を調べず、1ファイルずつの表示もしない）"
+    )]
+    pub stats_only: bool,
+
+    /// 対象ディレクトリを監視し、新しいファイルを書き込みが終わり次第振り分ける
+    #[arg(
+        long = "watch",
+        conflicts_with_all = [
+            "check", "report_name_collisions", "fragmentation", "extension_report", "stats_only",
+            "unflatten", "flatten", "undo", "apply_plan", "export_plan", "recursive",
+            "archive_output", "symlink_farm", "hardlink_view",
+        ],
+        help = "対象ディレクトリを監視し、新しく現れたファイルを書き込みが終わり次第振り分ける\n\
+                （直下のファイルのみ。サイズが約2秒変化しなくなるまで待つ。Ctrl+C で終了）"
+    )]
+    pub watch: bool,
+
+    /// カテゴリフォルダ内のファイルをすべてルートに戻し、空になったカテゴリフォルダを削除する
+    #[arg(
+        long = "unflatten",
+        conflicts_with_all = ["report_name_collisions", "fragmentation"],
+        help = "カテゴリフォルダ内のファイルをすべてルートに戻し、空になったカテゴリフォルダを削除する\n\
+                （同名は --on-conflict に従う）"
+    )]
+    pub unflatten: bool,
+
+    /// サブフォルダ内のすべてのファイルを対象ディレクトリ直下に移動する
+    #[arg(
+        long = "flatten",
+        conflicts_with_all = ["report_name_collisions", "fragmentation", "unflatten"],
+        help = "サブフォルダ内のすべてのファイルを対象ディレクトリ直下に移動する（同名は --on-conflict に従う）\n\
+                （カテゴリフォルダに限らない。隠しディレクトリは --include-hidden なしでは対象外。\n\
+                --exclude・--include・--max-depth も --unflatten と同じく適用される）"
+    )]
+    pub flatten: bool,
+
+    /// 移動の後、対象ディレクトリ以下の空のフォルダを削除する
+    #[arg(
+        long = "prune-empty",
+        help = "移動（または --flatten）の後、対象ディレクトリ以下の空のフォルダを深い階層から削除する\n\
+                （対象ディレクトリ自体と隠しディレクトリは削除しない）"
+    )]
+    pub prune_empty: bool,
+
+    /// 前回の整理で書き出したジャーナルを読み込み、移動をすべて元に戻す
+    #[arg(
+        long = "undo",
+        conflicts_with_all = ["report_name_collisions", "fragmentation", "unflatten", "flatten", "copy", "hardlink"],
+        help = "前回の整理で書き出したジャーナル（.smart-sorter-journal.json）を読み込み、\n\
+                移動をすべて元に戻す（元の場所が埋まっている場合は連番を付けて戻す）"
+    )]
+    pub undo: bool,
+
+    /// Dry Runで重複回避後の移動計画をJSONファイルに書き出す
+    #[arg(
+        long = "export-plan",
+        value_name = "PATH",
+        requires = "dry_run",
+        help = "Dry Runで重複回避後の移動計画をJSONファイルに書き出す（--dry-run が必要）\n\
+                （エディタで確認・編集してから --apply-plan で実行できる）"
+    )]
+    pub export_plan: Option<PathBuf>,
+
+    /// --export-plan で書き出した移動計画をそのまま実行する
+    #[arg(
+        long = "apply-plan",
+        value_name = "PATH",
+        conflicts_with_all = [
+            "check", "report_name_collisions", "fragmentation", "extension_report", "unflatten",
+            "flatten", "undo", "export_plan", "archive_output", "symlink_farm", "hardlink_view",
+        ],
+        help = "--export-plan で書き出した移動計画を、ディレクトリを走査し直さずにそのまま実行する\n\
+                （計画の作成後に移動元がなくなっている場合や、移動先が対象ディレクトリの外を指す場合は\n\
+                何も移動せずにエラー）"
+    )]
+    pub apply_plan: Option<PathBuf>,
+
+    /// ディレクトリを走査せず、一覧に書かれたファイルだけを整理する
+    #[arg(
+        long = "files-from",
+        value_name = "PATH",
+        conflicts_with_all = [
+            "report_name_collisions", "fragmentation", "extension_report", "watch", "unflatten",
+            "flatten", "undo", "apply_plan", "recursive",
+        ],
+        help = "ディレクトリを走査せず、1行に1つずつパスを書いた一覧のファイルだけを整理する\n\
+                （- で標準入力から読む。相対パスは現在のディレクトリから解決する。\n\
+                対象ディレクトリの外にあるファイルはスキップする。例: fd -e jpg | smart-sorter --files-from - -y .）"
+    )]
+    pub files_from: Option<PathBuf>,
+
+    /// 移動したHEIC/HEIF画像をJPEGに変換する（heic フィーチャー）
+    #[cfg(feature = "heic")]
+    #[arg(
+        long = "convert-heic",
+        help = "移動したHEIC/HEIF画像をJPEGに変換する（元の画像は削除）"
+    )]
+    pub convert_heic: bool,
+
+    /// --convert-heic で元のHEIC/HEIF画像を残す
+    #[cfg(feature = "heic")]
+    #[arg(
+        long = "keep-heic",
+        requires = "convert_heic",
+        help = "--convert-heic で元のHEIC/HEIF画像を残す"
+    )]
+    pub keep_heic: bool,
+
+    /// システムのMIMEデータベースで拡張子を分類する（Unixのみ）
+    #[cfg(unix)]
+    #[arg(
+        long = "use-system-mime",
+        help = "システムのMIMEデータベース（shared-mime-info、/etc/mime.types）で拡張子を分類する（Unixのみ）\n\
+                （image/* → Images など。判定できない場合は組み込みマッピングを使用）"
+    )]
+    pub use_system_mime: bool,
+
+    /// 進行状況と結果をJSON Lines形式で送信するUnixドメインソケット（Unixのみ）
+    #[cfg(unix)]
+    #[arg(
+        long = "ipc-socket",
+        value_name = "PATH",
+        help = "進行状況と結果をJSON Lines形式で送信するUnixドメインソケットを作成する（Unixのみ）"
+    )]
+    pub ipc_socket: Option<PathBuf>,
+
+    /// 最寄りの git / hg リポジトリごとに振り分ける
+    #[arg(
+        long = "by-repo",
+        help = "最寄りの git / hg リポジトリ（.git / .hg を持つディレクトリ）ごとに\n\
+                Repos/<リポジトリ名>/ に振り分ける（リポジトリ外のファイルは拡張子で分類）"
+    )]
+    pub by_repo: bool,
+
+    /// 種類に関係なく移動せずその場に残すファイル名（複数指定可）
+    #[arg(
+        long = "keep-at-root",
+        value_name = "NAME",
+        help = "種類に関係なく移動せずその場に残すファイル名（複数指定可、完全一致）\n\
+                例: --keep-at-root index.html --keep-at-root README.md"
+    )]
+    pub keep_at_root: Vec<String>,
+
+    /// --keep-at-root の照合で大文字・小文字を区別しない
+    #[arg(
+        long = "keep-at-root-ignore-case",
+        help = "--keep-at-root（設定ファイルの keep_at_root を含む）の照合で大文字・小文字を区別しない"
+    )]
+    pub keep_at_root_ignore_case: bool,
+
+    /// 移動したファイルに整理済みの印（拡張属性）を付ける
+    #[arg(
+        long = "mark-sorted",
+        help = "移動したファイルに整理済みの印（拡張属性 user.smart-sorter.sorted）を付ける\n\
+                （拡張属性に対応していないファイルシステムでは何もしない）"
+    )]
+    pub mark_sorted: bool,
+
+    /// 整理済みの印が付いたファイルをスキップする
+    #[arg(
+        long = "skip-marked",
+        help = "--mark-sorted で整理済みの印が付いたファイルをスキップする"
+    )]
+    pub skip_marked: bool,
+
+    /// 隠しファイル・隠しディレクトリも整理の対象にする
+    #[arg(
+        long = "include-hidden",
+        conflicts_with = "safe",
+        help = "隠しファイル（. で始まる名前、Windowsでは隠し属性）も整理し、隠しディレクトリにも再帰する\n\
+                （指定しない場合はスキップして、スキップしたファイル数に含める）"
+    )]
+    pub include_hidden: bool,
+
+    /// 空（0バイト）のファイルの扱い
+    #[arg(
+        long = "empty-files",
+        value_name = "MODE",
+        default_value = "sort",
+        help = "空（0バイト）のファイルの扱い\n\
+                sort: 拡張子どおりに分類（既定）、skip: 移動しない、\n\
+                others: Others に振り分け、folder:NAME: NAME フォルダに振り分け\n\
+                例: --empty-files folder:Empty"
+    )]
+    pub empty_files: EmptyFiles,
+
+    /// ダウンロードフォルダ向けの処理を有効にする
+    #[arg(
+        long = "handle-downloads",
+        help = "ダウンロードフォルダ向けの処理を有効にする\n\
+                （.torrent を Torrents に振り分け、ダウンロード途中のファイルはスキップ）"
+    )]
+    pub handle_downloads: bool,
+
+    /// --handle-downloads でダウンロード途中とみなす拡張子（複数指定可、既定のリストを置き換える）
+    #[arg(
+        long = "fragment-ext",
+        value_name = "EXT",
+        help = "--handle-downloads でダウンロード途中とみなす拡張子（複数指定可、既定のリストを置き換える）\n\
+                既定: part, crdownload, !ut, download, partial, opdownload, aria2, bc!"
+    )]
+    pub fragment_ext: Vec<String>,
+
+    /// 一時的なファイルシステムエラー（EAGAIN, EBUSY など）の最大再試行回数
+    #[arg(
+        long = "retries",
+        value_name = "N",
+        default_value_t = 0,
+        help = "一時的なファイルシステムエラー（EAGAIN, EBUSY など）の最大再試行回数"
+    )]
+    pub retries: u32,
+
+    /// 初回の再試行までの待ち時間（ミリ秒、以降は試行ごとに倍）
+    #[arg(
+        long = "retry-delay",
+        value_name = "MS",
+        default_value_t = 100,
+        help = "初回の再試行までの待ち時間（ミリ秒、以降は試行ごとに倍）"
+    )]
+    pub retry_delay: u64,
+
+    /// 移動を並列に行うスレッド数
+    #[arg(
+        short = 'j',
+        long = "jobs",
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "移動を並列に行うスレッド数（ネットワークドライブ上の大量のファイル向け）\n\
+                並列時は結果の表示順が移動元の順序と一致しない"
+    )]
+    pub jobs: u16,
+
+    /// 1件でも移動に失敗したら、それまでの移動をすべて元に戻す
+    #[arg(
+        long = "atomic",
+        conflicts_with_all = [
+            "copy", "hardlink", "archive_output", "symlink_farm", "hardlink_view", "watch", "undo",
+        ],
+        help = "1件でも移動に失敗したら、この実行で完了した移動をすべて元に戻してエラーで終了する\n\
+                （別のファイルシステムへの移動はコピーで戻すため、inode や作成日時は元に戻らない。\n\
+                既存のファイルを置き換える --on-conflict とは併用不可）"
+    )]
+    pub atomic: bool,
+
+    /// 移動先に同名のファイルが既にある場合の扱い
+    #[arg(
+        long = "on-conflict",
+        value_name = "STRATEGY",
+        value_enum,
+        default_value_t = ConflictStrategy::Rename,
+        help = "移動先に同名のファイルが既にある場合の扱い\n\
+                （rename: 連番を付けて両方残す / skip: 移動しない / overwrite: 上書きする /\n\
+                trash: 既存のファイルをゴミ箱に移す。移せなければ連番を付けて両方残し、エラーとして数える /\n\
+                keep-newer: 移動元の方が新しければ上書き、そうでなければ移動しない /\n\
+                keep-larger: 移動元の方が大きければ上書き、そうでなければ移動しない）"
+    )]
+    pub on_conflict: ConflictStrategy,
+
+    /// サイズが変化しなくなるまで待ってから移動する
+    #[arg(
+        long = "stabilize-wait",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        help = "指定した間隔でファイルサイズを確認し、3回続けて変化しなかったファイルだけを移動する\n\
+                （書き込み中のファイルはスキップ。例: --stabilize-wait 2s、500ms、1m）"
+    )]
+    pub stabilize_wait: Option<Duration>,
+
+    /// 初めて使う人向けの保守的な設定をまとめて有効にする
+    #[arg(
+        long = "safe",
+        help = "初めて使う人向けの保守的な設定をまとめて有効にする\n\
+                （移動前に確認、隠しファイルを除外、更新から1分未満のファイルを除外、\n\
+                上書きしない、ジャーナルを .smart-sorter-journal.json に書き出す）"
+    )]
+    pub safe: bool,
+
+    /// ルートやホームディレクトリなど危険なディレクトリでも実行する
+    #[arg(
+        long = "force",
+        help = "ルートやホームディレクトリ、システムフォルダなど危険なディレクトリでも実行する"
+    )]
+    pub force: bool,
+
+    /// 移動前に実行内容をまとめて表示し、続行するか確認する
+    #[arg(
+        long = "preflight",
+        help = "移動前に実行内容（件数・合計サイズ・作成するフォルダ・名前の重複・\n\
+                別のファイルシステムへのコピー・スキップされるファイル）をまとめて表示し、続行するか確認する"
+    )]
+    pub preflight: bool,
+
+    /// 続行の確認を省略する
+    #[arg(
+        short = 'y',
+        long = "yes",
+        help = "続行の確認（移動前の確認・--preflight・--safe）を省略して続行する"
+    )]
+    pub yes: bool,
+
+    /// 移動前にカテゴリごとに続行するか確認する
+    #[arg(
+        long = "confirm-per-category",
+        conflicts_with = "dry_run",
+        help = "移動前にカテゴリごとに続行するか確認する（断ったカテゴリのファイルはスキップ）"
+    )]
+    pub confirm_per_category: bool,
+
+    /// 実行後、ファイルを受け取ったフォルダの絶対パスを1行ずつ出力する
+    #[arg(
+        long = "print-folders",
+        help = "実行後、ファイルを受け取ったフォルダの絶対パスを1行ずつ出力する（スクリプト向け）"
+    )]
+    pub print_folders: bool,
+
+    /// --print-folders の各行にタブ区切りでファイル数を付ける
+    #[arg(
+        long = "folder-counts",
+        requires = "print_folders",
+        help = "--print-folders の各行にタブ区切りでファイル数を付ける"
+    )]
+    pub folder_counts: bool,
+
+    /// 実行結果の出力形式
+    #[arg(
+        long = "format",
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        conflicts_with = "print_folders",
+        help = "実行結果の出力形式（text / json）\n\
+                json ではバナーや途中経過を表示せず、最後に統計だけをJSONで標準出力に出す\n\
+                （カテゴリごとの件数はフォルダ名をキーにする）"
+    )]
+    pub format: OutputFormat,
+
+    /// 移動後、移動先の存在とサイズ、移動元が消えていることを検証する
+    #[arg(
+        long = "validate-after-move",
+        help = "移動後、移動先の存在とサイズ、移動元が消えていることを検証する"
+    )]
+    pub validate_after_move: bool,
+
+    /// --validate-after-move 時、コピーで移動したファイルを再ハッシュして内容も検証する
+    #[arg(
+        long = "rehash",
+        requires = "validate_after_move",
+        help = "--validate-after-move 時、コピーで移動したファイル（別ファイルシステム間など）を再ハッシュして内容も検証する"
+    )]
+    pub rehash: bool,
+
+    /// 拡張子マッピングを記述したTOML設定ファイル（複数指定可、後のものが優先）
+    #[arg(
+        long = "config",
+        value_name = "PATH",
+        help = "拡張子マッピングを記述したTOML設定ファイル（複数指定可、後のものが優先）"
+    )]
+    pub config: Vec<PathBuf>,
+
+    /// 各ファイルの祖先ディレクトリにある .smartsorter.toml で拡張子マッピングを上書きする
+    #[arg(
+        long = "local-configs",
+        help = "各ファイルの祖先ディレクトリにある .smartsorter.toml で拡張子マッピングを上書きする\n\
+                （最も近いディレクトリの設定が優先、--config より優先）"
+    )]
+    pub local_configs: bool,
+
+    /// この実行に限り拡張子のマッピングを追加・上書きする（最優先）
+    #[arg(
+        long = "map",
+        value_name = "EXT=CATEGORY",
+        value_parser = parse_mapping,
+        help = "この実行に限り拡張子のマッピングを追加・上書きする（複数指定可、最優先）\n\
+                例: --map log=Documents --map kra=Images"
+    )]
+    pub map: Vec<(String, Category)>,
+
+    /// カテゴリのフォルダ名を変える
+    #[arg(
+        long = "category-name",
+        value_name = "CATEGORY=NAME",
+        value_parser = parse_category_name,
+        help = "カテゴリのフォルダ名を変える（複数指定可、設定ファイルの [folder_names] より優先）\n\
+                他のカテゴリと同じフォルダ名（大文字小文字は問わない）はエラー\n\
+                例: --category-name Images=画像 --category-name Videos=動画"
+    )]
+    pub category_name: Vec<(Category, String)>,
+
+    /// 組み込みの拡張子のカテゴリを変える上書きをエラーにする
+    #[arg(
+        long = "strict-config",
+        help = "設定ファイルや --map で組み込みの拡張子のカテゴリを変えている場合、警告ではなくエラーにする"
+    )]
+    pub strict_config: bool,
+
+    /// 最終的に有効な設定を出力して終了する
+    #[arg(
+        long = "dump-config",
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "toml",
+        help = "最終的に有効な設定を出力して終了する（toml / json）"
+    )]
+    pub dump_config: Option<DumpFormat>,
+
+    /// JSON出力（--format json の統計、--ipc-socket のイベント、--export-plan の計画）のJSON Schemaを出力して終了する
+    #[arg(
+        long = "print-schema",
+        help = "JSON出力のJSON Schemaを出力して終了する\n\
+                （--format json の統計、--ipc-socket のイベント、--export-plan の計画のいずれかに一致する）"
+    )]
+    pub print_schema: bool,
+
+    /// サマリーの後にカテゴリの内訳を横棒グラフで表示する
+    #[arg(
+        long = "visual-summary",
+        help = "サマリーの後にカテゴリの内訳をターミナル幅に合わせた横棒グラフで表示する\n\
+                （NO_COLOR 指定時やターミナル以外への出力では件数と割合のみ）"
+    )]
+    pub visual_summary: bool,
+
+    /// これまでの実行の累計統計を表示して終了する
+    #[arg(
+        long = "lifetime-stats",
+        help = "これまでの実行の累計統計を表示して終了する"
+    )]
+    pub lifetime_stats: bool,
+
     /// 詳細なログを出力する
     #[arg(short = 'v', long = "verbose", help = "詳細なログを出力する")]
     pub verbose: bool,
+
+    /// バナーやファイルごとの行を出さず、サマリーとエラーだけを出力する
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        help = "バナーやファイルごとの行を出さず、サマリーとエラーだけを出力する（--verbose より優先）"
+    )]
+    pub quiet: bool,
+
+    /// 出力を色付けしない
+    #[arg(
+        long = "no-color",
+        help = "出力を色付けしない（環境変数 NO_COLOR の設定時や、ターミナル以外への出力時も色付けしない）"
+    )]
+    pub no_color: bool,
+
+    /// 進捗バーを表示しない
+    #[arg(
+        long = "no-progress",
+        help = "進捗バーを表示しない（進捗バーは標準エラー出力がターミナルの場合のみ表示、--quiet でも非表示）"
+    )]
+    pub no_progress: bool,
+}
+
+/// 設定ダンプの出力形式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    Toml,
+    Json,
+}
+
+/// `EXT=CATEGORY` 形式のマッピングをパースする
+///
+/// 拡張子は小文字化し、先頭のドットは取り除きます。
+fn parse_mapping(s: &str) -> Result<(String, Category), String> {
+    let (ext, category) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected EXT=CATEGORY, got '{}'", s))?;
+
+    let ext = ext.trim().trim_start_matches('.').to_lowercase();
+    if ext.is_empty() {
+        return Err(format!("missing extension in '{}'", s));
+    }
+
+    let category = category
+        .trim()
+        .parse::<Category>()
+        .map_err(|e| e.to_string())?;
+    Ok((ext, category))
+}
+
+/// `CATEGORY=NAME` 形式のフォルダ名の上書きをパースする
+fn parse_category_name(s: &str) -> Result<(Category, String), String> {
+    let (category, name) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected CATEGORY=NAME, got '{}'", s))?;
+
+    let category = category
+        .trim()
+        .parse::<Category>()
+        .map_err(|e| e.to_string())?;
+    let name = config::parse_folder_name(name).map_err(|e| e.to_string())?;
+    Ok((category, name))
+}
+
+/// グロブパターンとして正しいかを検証する
+fn parse_glob(s: &str) -> Result<String, String> {
+    glob::Pattern::new(s)
+        .map(|_| s.to_string())
+        .map_err(|e| format!("invalid glob pattern '{}': {}", s, e))
 }
 
 impl Args {
@@ -62,18 +1097,157 @@ mod tests {
     fn test_args_default_values() {
         // デフォルト値のテスト
         let args = Args::try_parse_from(["smart-sorter", "/tmp/test"]).unwrap();
-        assert_eq!(args.target_dir, PathBuf::from("/tmp/test"));
+        assert_eq!(args.target_dirs, vec![PathBuf::from("/tmp/test")]);
         assert!(!args.dry_run);
         assert!(!args.recursive);
         assert!(!args.verbose);
+        assert!(!args.quiet);
     }
 
     #[test]
     fn test_args_with_flags() {
-        let args = Args::try_parse_from(["smart-sorter", "-d", "-r", "-v", "/home/user/Downloads"])
-            .unwrap();
+        let args = Args::try_parse_from([
+            "smart-sorter",
+            "-d",
+            "-r",
+            "-v",
+            "-q",
+            "/home/user/Downloads",
+        ])
+        .unwrap();
         assert!(args.dry_run);
         assert!(args.recursive);
         assert!(args.verbose);
+        assert!(args.quiet);
+    }
+
+    #[test]
+    fn test_args_config_and_dump() {
+        let args = Args::try_parse_from([
+            "smart-sorter",
+            "--config",
+            "base.toml",
+            "--config",
+            "override.toml",
+            "--dump-config",
+            "/tmp/test",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.config,
+            vec![PathBuf::from("base.toml"), PathBuf::from("override.toml")]
+        );
+        assert_eq!(args.dump_config, Some(DumpFormat::Toml));
+
+        let args =
+            Args::try_parse_from(["smart-sorter", "--dump-config=json", "/tmp/test"]).unwrap();
+        assert_eq!(args.dump_config, Some(DumpFormat::Json));
+    }
+
+    #[test]
+    fn test_args_executables_category() {
+        let args =
+            Args::try_parse_from(["smart-sorter", "--detect-executables", "/tmp/test"]).unwrap();
+        assert!(args.detect_executables);
+        assert_eq!(args.executables_category, Category::Programs);
+
+        let args = Args::try_parse_from([
+            "smart-sorter",
+            "--executables-category",
+            "code",
+            "/tmp/test",
+        ])
+        .unwrap();
+        assert_eq!(args.executables_category, Category::Code);
+
+        assert!(
+            Args::try_parse_from(["smart-sorter", "--executables-category", "x", "/tmp"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_args_multiple_target_dirs() {
+        let args = Args::try_parse_from(["smart-sorter", "/tmp/a", "/tmp/b"]).unwrap();
+        assert_eq!(
+            args.target_dirs,
+            vec![PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b")]
+        );
+    }
+
+    #[test]
+    fn test_args_latest_rejects_zero() {
+        let args = Args::try_parse_from(["smart-sorter", "--latest", "5", "/tmp"]).unwrap();
+        assert_eq!(args.latest, NonZeroUsize::new(5));
+        assert!(Args::try_parse_from(["smart-sorter", "--latest", "0", "/tmp"]).is_err());
+    }
+
+    #[test]
+    fn test_args_lifetime_stats_without_target() {
+        let args = Args::try_parse_from(["smart-sorter", "--lifetime-stats"]).unwrap();
+        assert!(args.lifetime_stats);
+        assert!(Args::try_parse_from(["smart-sorter"]).is_err());
+    }
+
+    #[test]
+    fn test_args_inline_mappings() {
+        let args = Args::try_parse_from([
+            "smart-sorter",
+            "--map",
+            "xyz=Images",
+            "--map",
+            ".LOG=documents",
+            "/tmp/test",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.map,
+            vec![
+                ("xyz".to_string(), Category::Images),
+                ("log".to_string(), Category::Documents),
+            ]
+        );
+
+        assert!(Args::try_parse_from(["smart-sorter", "--map", "xyz", "/tmp"]).is_err());
+        assert!(Args::try_parse_from(["smart-sorter", "--map", "xyz=Bogus", "/tmp"]).is_err());
+        assert!(Args::try_parse_from(["smart-sorter", "--map", "=Images", "/tmp"]).is_err());
+    }
+
+    #[test]
+    fn test_args_rename_template_validated() {
+        let args = Args::try_parse_from([
+            "smart-sorter",
+            "--rename-template",
+            "{date}_{n}{ext}",
+            "/tmp/test",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.rename_template,
+            Some("{date}_{n}{ext}".parse().unwrap())
+        );
+
+        assert!(
+            Args::try_parse_from(["smart-sorter", "--rename-template", "{bogus}", "/tmp"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_args_dedup_template_requires_counter() {
+        let args = Args::try_parse_from([
+            "smart-sorter",
+            "--dedup-template",
+            "{stem} ({n}){ext}",
+            "/tmp/test",
+        ])
+        .unwrap();
+        assert!(args.dedup_template.is_some());
+
+        assert!(Args::try_parse_from([
+            "smart-sorter",
+            "--dedup-template",
+            "{stem}-copy{ext}",
+            "/tmp"
+        ])
+        .is_err());
     }
 }
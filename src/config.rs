@@ -1,14 +1,35 @@
 //! 設定モジュール
 //!
 //! 拡張子とカテゴリのマッピングを定義します。
-//! 将来的に外部設定ファイル（TOML/JSON）から読み込む形に拡張可能な設計です。
+//! 組み込みのマッピングの上に、TOML設定ファイル（`--config`）のマッピングを重ねられます。
 
+use crate::file_ops::sanitize_folder_name;
+use anyhow::{bail, Context, Result};
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// デフォルトの設定ファイルパスを指定する環境変数
+pub const CONFIG_ENV_VAR: &str = "SMART_SORTER_CONFIG";
+
+/// サブディレクトリ単位で拡張子マッピングを上書きするローカル設定ファイル名
+pub const LOCAL_CONFIG_FILE_NAME: &str = ".smartsorter.toml";
+
+/// ディレクトリ内のファイルをまとめて振り分けるカテゴリを記したマーカーファイル名
+pub const DIR_CATEGORY_FILE_NAME: &str = ".category";
 
 /// ファイルカテゴリの列挙型
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+///
+/// 組み込みのカテゴリに加え、設定ファイルの `categories` で宣言した
+/// 任意の名前のカテゴリ（`Custom`）を扱えます。
+#[derive(
+    Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub enum Category {
     Images,
     Videos,
@@ -16,12 +37,30 @@ pub enum Category {
     Music,
     Archives,
     Code,
+    Programs,
+    Torrents,
     Others,
+    /// 設定ファイルで定義されたカテゴリ（値はフォルダ名）
+    #[serde(untagged)]
+    Custom(String),
 }
 
+/// 組み込みカテゴリ（表示順）
+static BUILT_IN_CATEGORIES: [Category; 9] = [
+    Category::Images,
+    Category::Videos,
+    Category::Documents,
+    Category::Music,
+    Category::Archives,
+    Category::Code,
+    Category::Programs,
+    Category::Torrents,
+    Category::Others,
+];
+
 impl Category {
     /// カテゴリ名をフォルダ名として取得
-    pub fn folder_name(&self) -> &'static str {
+    pub fn folder_name(&self) -> &str {
         match self {
             Category::Images => "Images",
             Category::Videos => "Videos",
@@ -29,21 +68,48 @@ impl Category {
             Category::Music => "Music",
             Category::Archives => "Archives",
             Category::Code => "Code",
+            Category::Programs => "Programs",
+            Category::Torrents => "Torrents",
             Category::Others => "Others",
+            Category::Custom(name) => name,
         }
     }
 
-    /// 全カテゴリのリストを取得
+    /// `names` で上書きされていればその名前、なければ既定のフォルダ名を取得
+    pub fn folder_name_in<'a>(&'a self, names: &'a HashMap<Category, String>) -> &'a str {
+        names.get(self).map_or(self.folder_name(), String::as_str)
+    }
+
+    /// 全組み込みカテゴリのリストを取得
+    ///
+    /// 設定ファイルで定義されたカテゴリは含みません。
     pub fn all() -> &'static [Category] {
-        &[
-            Category::Images,
-            Category::Videos,
-            Category::Documents,
-            Category::Music,
-            Category::Archives,
-            Category::Code,
-            Category::Others,
-        ]
+        &BUILT_IN_CATEGORIES
+    }
+
+    /// 設定ファイルで定義されたカテゴリを作成する
+    ///
+    /// 名前はフォルダ名として使えるものに限ります。組み込みカテゴリと同じ名前
+    /// （大文字小文字は問わない）の場合は組み込みカテゴリを返します。
+    pub fn custom(name: &str) -> Result<Self> {
+        if let Ok(category) = name.parse::<Category>() {
+            return Ok(category);
+        }
+        match sanitize_folder_name(name) {
+            Some(folder) if folder == name.trim() => Ok(Category::Custom(folder)),
+            _ => bail!("Invalid category name: '{}'", name),
+        }
+    }
+
+    /// 組み込みカテゴリ、または `custom` に含まれるカテゴリの名前を解析する
+    pub fn parse_with_custom(name: &str, custom: &[Category]) -> Result<Self> {
+        name.parse::<Category>().or_else(|e| {
+            custom
+                .iter()
+                .find(|c| c.folder_name().eq_ignore_ascii_case(name.trim()))
+                .cloned()
+                .ok_or(e)
+        })
     }
 }
 
@@ -53,6 +119,38 @@ impl fmt::Display for Category {
     }
 }
 
+impl FromStr for Category {
+    type Err = anyhow::Error;
+
+    /// フォルダ名からカテゴリを取得（大文字小文字は問わない）
+    fn from_str(s: &str) -> Result<Self> {
+        Category::all()
+            .iter()
+            .find(|c| c.folder_name().eq_ignore_ascii_case(s.trim()))
+            .cloned()
+            .with_context(|| {
+                let names: Vec<&str> = Category::all().iter().map(|c| c.folder_name()).collect();
+                format!(
+                    "Unknown category: '{}' (expected one of: {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// ダウンロード途中のファイルとみなす拡張子（`--handle-downloads` の既定値）
+pub const DEFAULT_FRAGMENT_EXTENSIONS: [&str; 8] = [
+    "part",
+    "crdownload",
+    "!ut",
+    "download",
+    "partial",
+    "opdownload",
+    "aria2",
+    "bc!",
+];
+
 /// 拡張子からカテゴリへのマッピング
 ///
 /// 小文字の拡張子をキーとして、対応するカテゴリを値として持つHashMap。
@@ -182,6 +280,73 @@ pub static EXTENSION_MAP: Lazy<HashMap<&'static str, Category>> = Lazy::new(|| {
     map
 });
 
+/// コード拡張子からプログラミング言語名へのマッピング
+///
+/// 複数の言語で使われる拡張子は、以下を既定の言語とします。
+/// - `h` → C（C++ヘッダーの場合もある）
+/// - `m` → Objective-C（MATLABの場合もある）
+/// - `pl` → Perl（Prologの場合もある）
+/// - `fs` → FSharp（GLSLシェーダーの場合もある）
+static CODE_LANGUAGE_MAP: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
+    let languages: [(&[&str], &str); 34] = [
+        (&["rs"], "Rust"),
+        (&["py"], "Python"),
+        (&["js", "jsx"], "JavaScript"),
+        (&["ts", "tsx"], "TypeScript"),
+        (&["html", "htm"], "HTML"),
+        (&["css", "scss", "sass", "less"], "CSS"),
+        (&["json"], "JSON"),
+        (&["xml"], "XML"),
+        (&["yaml", "yml"], "YAML"),
+        (&["toml"], "TOML"),
+        (&["md", "markdown"], "Markdown"),
+        (&["sh", "bash", "zsh", "fish"], "Shell"),
+        (&["c", "h"], "C"),
+        (&["cpp", "cc", "cxx", "hpp", "hxx"], "CPP"),
+        (&["go"], "Go"),
+        (&["java"], "Java"),
+        (&["kt", "kts"], "Kotlin"),
+        (&["scala", "sbt"], "Scala"),
+        (&["rb"], "Ruby"),
+        (&["php"], "PHP"),
+        (&["pl", "pm"], "Perl"),
+        (&["swift"], "Swift"),
+        (&["m", "mm"], "ObjectiveC"),
+        (&["sql"], "SQL"),
+        (&["r"], "R"),
+        (&["lua"], "Lua"),
+        (&["vim"], "Vim"),
+        (&["el"], "EmacsLisp"),
+        (&["clj", "cljs", "edn"], "Clojure"),
+        (&["ex", "exs"], "Elixir"),
+        (&["erl", "hrl"], "Erlang"),
+        (&["hs", "lhs", "cabal"], "Haskell"),
+        (&["ml", "mli"], "OCaml"),
+        (&["fs", "fsi", "fsx"], "FSharp"),
+    ];
+
+    let mut map = HashMap::new();
+    for (extensions, language) in languages {
+        for ext in extensions {
+            map.insert(*ext, language);
+        }
+    }
+    map
+});
+
+/// コード拡張子からプログラミング言語名を取得する
+///
+/// # Arguments
+/// * `extension` - ファイルの拡張子（ドットなし、大文字小文字は問わない）
+///
+/// # Returns
+/// 対応する言語名。言語に対応しない拡張子（`dockerfile` など）の場合は `None`。
+pub fn code_language(extension: &str) -> Option<&'static str> {
+    CODE_LANGUAGE_MAP
+        .get(extension.to_lowercase().as_str())
+        .copied()
+}
+
 /// 拡張子からカテゴリを取得する
 ///
 /// # Arguments
@@ -193,15 +358,417 @@ pub fn get_category(extension: &str) -> Category {
     let ext_lower = extension.to_lowercase();
     EXTENSION_MAP
         .get(ext_lower.as_str())
-        .copied()
+        .cloned()
         .unwrap_or(Category::Others)
 }
 
+/// 組み込みの拡張子のカテゴリを変える上書き
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverrideCollision {
+    /// 拡張子（小文字、ドットなし）
+    pub extension: String,
+    /// 組み込みのカテゴリ
+    pub built_in: Category,
+    /// 上書き後のカテゴリ
+    pub overridden: Category,
+}
+
+impl fmt::Display for OverrideCollision {
+    /// `.log: Others → Documents` 形式で表示
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            ".{}: {} → {}",
+            self.extension, self.built_in, self.overridden
+        )
+    }
+}
+
+/// マージ後のマッピングから、組み込みの拡張子のカテゴリを変えているものを拡張子順に返す
+///
+/// 組み込みにない拡張子の追加と、組み込みと同じカテゴリへの上書きは含めません。
+pub fn override_collisions(merged: &BTreeMap<String, Category>) -> Vec<OverrideCollision> {
+    merged
+        .iter()
+        .filter_map(|(ext, category)| {
+            let built_in = EXTENSION_MAP.get(ext.to_lowercase().as_str())?;
+            (built_in != category).then(|| OverrideCollision {
+                extension: ext.clone(),
+                built_in: built_in.clone(),
+                overridden: category.clone(),
+            })
+        })
+        .collect()
+}
+
+/// 組み込みマッピングにはないが実在する、よく使われる拡張子
+///
+/// ドキュメント・スクリプト・設定・証明書と鍵・フォント・一時ファイルなど、組み込みの
+/// 拡張子と編集距離が近くても打ち間違いではないため、訂正しません。
+const COMMON_EXTENSIONS: &[&str] = &[
+    "rst", "adoc", "org", "tex", "bib", "nfo", "diz", "me", "cmd", "ps1", "psm1", "psd1", "vbs",
+    "mk", "cmake", "gradle", "pm", "pl", "tcl", "awk", "ini", "cfg", "conf", "env", "plist", "reg",
+    "desktop", "lock", "pem", "crt", "cer", "csr", "der", "key", "pub", "p12", "pfx", "p7b", "asc",
+    "gpg", "sig", "otf", "ttf", "ttc", "woff", "woff2", "fnt", "fon", "tmp", "temp", "bak", "old",
+    "orig", "swp", "swo", "part", "partial", "dat", "bin", "db", "sqlite", "log", "pid", "sock",
+    "lnk", "url", "torrent", "ics", "vcf",
+];
+
+/// 既知の拡張子の打ち間違いと思われる拡張子を訂正する
+///
+/// 組み込みマッピングにも、よく使われる実在の拡張子（`rst`・`pem`・`tmp` など）にも
+/// ない拡張子だけが対象です。組み込みマッピングの拡張子のうち、編集距離
+/// （隣接文字の入れ替えを1回と数える）が1のものを候補とします。最もよくある打ち間違いである隣接文字の入れ替えの候補を優先し、
+/// 優先される候補がちょうど1つだけの場合に、その拡張子を返します。
+/// 既知・実在の拡張子、候補がない拡張子、候補が複数ある（曖昧な）拡張子は `None` です。
+///
+/// # Examples
+/// `jgp` → `jpg`（`3gp` への置換より入れ替えを優先）、`pnng` → `png`
+pub fn correct_extension(extension: &str) -> Option<&'static str> {
+    let ext_lower = extension.to_lowercase();
+    if EXTENSION_MAP.contains_key(ext_lower.as_str())
+        || COMMON_EXTENSIONS.contains(&ext_lower.as_str())
+    {
+        return None;
+    }
+
+    let candidates: Vec<&'static str> = EXTENSION_MAP
+        .keys()
+        .copied()
+        .filter(|known| edit_distance(&ext_lower, known) == 1)
+        .collect();
+    let transposed: Vec<&'static str> = candidates
+        .iter()
+        .copied()
+        .filter(|known| is_transposition(&ext_lower, known))
+        .collect();
+    let preferred = if transposed.is_empty() {
+        candidates
+    } else {
+        transposed
+    };
+    match preferred.as_slice() {
+        [known] => Some(known),
+        _ => None,
+    }
+}
+
+/// 隣接する2文字の入れ替えだけで `a` が `b` になるかどうか
+fn is_transposition(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len() != b.len() {
+        return false;
+    }
+    let diffs: Vec<usize> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+    matches!(diffs.as_slice(), [i, j] if *j == i + 1 && a[*i] == b[*j] && a[*j] == b[*i])
+}
+
+/// 2つの文字列の編集距離（挿入・削除・置換・隣接文字の入れ替え）
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
 /// 拡張子なしのファイルに対するデフォルトカテゴリ
 pub fn get_default_category() -> Category {
     Category::Others
 }
 
+/// 設定ファイル（TOML）の内容
+///
+/// ```toml
+/// keep_at_root = ["index.html", "README.md"]
+/// categories = ["Fonts", "3D"]
+///
+/// [extensions]
+/// log = "Documents"
+/// blend = "3D"
+///
+/// [folder_names]
+/// Images = "画像"
+/// Videos = "動画"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExtensionConfig {
+    /// 種類に関係なく移動しないファイル名
+    #[serde(default)]
+    pub keep_at_root: Vec<String>,
+    /// 組み込み以外に定義するカテゴリ名（フォルダ名）
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// 拡張子 → カテゴリ名 のマッピング
+    #[serde(default)]
+    pub extensions: BTreeMap<String, String>,
+    /// カテゴリ名 → 代わりに使うフォルダ名
+    #[serde(default)]
+    pub folder_names: BTreeMap<String, String>,
+}
+
+impl ExtensionConfig {
+    /// 別の設定を上に重ねる（マッピングは後から重ねた方が優先、移動しないファイル名は合算）
+    pub fn merge(&mut self, other: ExtensionConfig) {
+        self.keep_at_root.extend(other.keep_at_root);
+        for category in other.categories {
+            if !self.categories.contains(&category) {
+                self.categories.push(category);
+            }
+        }
+        self.extensions.extend(other.extensions);
+        self.folder_names.extend(other.folder_names);
+    }
+
+    /// `categories` で定義されたカテゴリ（組み込みカテゴリと同じ名前のものは除く）
+    pub fn custom_categories(&self) -> Result<Vec<Category>> {
+        let mut custom = Vec::new();
+        for name in &self.categories {
+            let category = Category::custom(name)?;
+            if matches!(category, Category::Custom(_)) && !custom.contains(&category) {
+                custom.push(category);
+            }
+        }
+        Ok(custom)
+    }
+
+    /// 組み込みマッピングに上書きする拡張子マッピングに変換する
+    ///
+    /// 拡張子は小文字化し、先頭のドットは取り除きます。
+    /// 組み込みカテゴリにも `categories` にもないカテゴリ名が含まれる場合はエラーを返します。
+    pub fn to_overrides(&self) -> Result<HashMap<String, Category>> {
+        self.to_overrides_with(&[])
+    }
+
+    /// `inherited`（上位の設定で定義されたカテゴリ）も使えるものとして拡張子マッピングに変換する
+    pub fn to_overrides_with(&self, inherited: &[Category]) -> Result<HashMap<String, Category>> {
+        let mut custom = self.custom_categories()?;
+        custom.extend(inherited.iter().cloned());
+        self.extensions
+            .iter()
+            .map(|(ext, category)| {
+                let category = Category::parse_with_custom(category, &custom)
+                    .with_context(|| format!("Invalid mapping for extension '{}'", ext))?;
+                Ok((ext.trim_start_matches('.').to_lowercase(), category))
+            })
+            .collect()
+    }
+
+    /// カテゴリごとのフォルダ名の上書きに変換する
+    ///
+    /// 組み込みカテゴリにも `categories` にもないカテゴリ名や、フォルダ名として
+    /// 使えない名前が含まれる場合はエラーを返します。
+    pub fn to_folder_names(&self) -> Result<HashMap<Category, String>> {
+        let custom = self.custom_categories()?;
+        self.folder_names
+            .iter()
+            .map(|(category, folder)| {
+                let category = Category::parse_with_custom(category, &custom)
+                    .with_context(|| format!("Invalid folder name for category '{}'", category))?;
+                Ok((category, parse_folder_name(folder)?))
+            })
+            .collect()
+    }
+}
+
+/// カテゴリフォルダの名前として使えるかを検証する
+///
+/// パス区切りや `..` など、フォルダ名として使えない名前はエラーになります。
+pub fn parse_folder_name(name: &str) -> Result<String> {
+    match sanitize_folder_name(name) {
+        Some(folder) if folder == name.trim() => Ok(folder),
+        _ => bail!("Invalid folder name: '{}'", name),
+    }
+}
+
+/// カテゴリのフォルダ名の上書きで、2つのカテゴリが同じフォルダを使わないことを確認する
+///
+/// 組み込みカテゴリと `custom` のカテゴリについて、上書き後のフォルダ名を
+/// 大文字小文字を区別せずに比べます（`Images=Videos` などはエラーになります）。
+pub fn check_folder_names(names: &HashMap<Category, String>, custom: &[Category]) -> Result<()> {
+    let mut seen: HashMap<String, &Category> = HashMap::new();
+    for category in Category::all().iter().chain(custom) {
+        let folder = category.folder_name_in(names);
+        if let Some(other) = seen.insert(folder.to_lowercase(), category) {
+            bail!(
+                "Categories {} and {} would share the folder name '{}'",
+                other,
+                category,
+                folder
+            );
+        }
+    }
+    Ok(())
+}
+
+/// TOML設定ファイルを読み込む
+///
+/// 存在しないカテゴリ名を指定したマッピングは、ファイル名と行番号を付けたエラーになります。
+///
+/// # Arguments
+/// * `path` - 設定ファイルのパス
+pub fn load_config(path: &Path) -> Result<ExtensionConfig> {
+    load_config_with(path, &[])
+}
+
+/// `inherited`（上位の設定で定義されたカテゴリ）も使えるものとしてTOML設定ファイルを読み込む
+pub fn load_config_with(path: &Path, inherited: &[Category]) -> Result<ExtensionConfig> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let config: ExtensionConfig = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    let mut custom = config
+        .custom_categories()
+        .with_context(|| format!("Invalid categories in config file: {}", path.display()))?;
+    custom.extend(inherited.iter().cloned());
+    validate_extensions(&content, path, &custom)?;
+    Ok(config)
+}
+
+/// 設定ファイルの拡張子マッピングのカテゴリ名を検証する
+///
+/// 誤りがあれば `<ファイル>:<行>` の形で該当行を示します。
+fn validate_extensions(content: &str, path: &Path, custom: &[Category]) -> Result<()> {
+    #[derive(Deserialize)]
+    struct SpannedExtensions {
+        #[serde(default)]
+        extensions: BTreeMap<String, toml::Spanned<String>>,
+    }
+
+    let spanned: SpannedExtensions = toml::from_str(content)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    for (ext, category) in &spanned.extensions {
+        if let Err(e) = Category::parse_with_custom(category.get_ref(), custom) {
+            let line = content[..category.span().start].matches('\n').count() + 1;
+            return Err(e.context(format!(
+                "{}:{}: invalid mapping for extension '{}' (custom categories must be listed in 'categories')",
+                path.display(),
+                line,
+                ext
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// 複数の設定ファイルを順に読み込み、重ね合わせる
+///
+/// 後に指定されたファイルほど優先されます。
+pub fn load_layered_config(paths: &[PathBuf]) -> Result<ExtensionConfig> {
+    let mut merged = ExtensionConfig::default();
+    for path in paths {
+        merged.merge(load_config(path)?);
+    }
+    Ok(merged)
+}
+
+/// ディレクトリの `.category` マーカーに記されたカテゴリを読み込む
+///
+/// マーカーにはカテゴリ名を1つだけ書きます（前後の空白は無視）。組み込みカテゴリのほか
+/// `custom` のカテゴリも使えます。マーカーがない場合は `None`、未知のカテゴリ名の場合はエラーを返します。
+pub fn read_dir_category(dir: &Path, custom: &[Category]) -> Result<Option<Category>> {
+    let path = dir.join(DIR_CATEGORY_FILE_NAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read category marker: {}", path.display()))?;
+    let category = Category::parse_with_custom(&content, custom)
+        .with_context(|| format!("Invalid category marker: {}", path.display()))?;
+    Ok(Some(category))
+}
+
+/// ディレクトリごとのローカル設定（`.smartsorter.toml`）の読み込みとキャッシュ
+///
+/// ファイルの親ディレクトリから `root` までの各階層にあるローカル設定を、
+/// `root` に近いものから順に重ねます。つまり最も近いローカル設定が優先されます。
+#[derive(Debug)]
+pub struct LocalConfigs {
+    /// 探索を打ち切るルートディレクトリ（このディレクトリ自身も対象）
+    root: PathBuf,
+    /// 全体の設定で定義されたカテゴリ（ローカル設定のマッピングでも使える）
+    custom: Vec<Category>,
+    /// ディレクトリ → そのディレクトリのローカル設定（存在しない場合は `None`）
+    cache: HashMap<PathBuf, Option<HashMap<String, Category>>>,
+}
+
+impl LocalConfigs {
+    /// `root` 以下を対象とするローカル設定の読み込み器を作成
+    ///
+    /// `custom` は全体の設定で定義されたカテゴリで、ローカル設定で定義し直さなくても使えます。
+    pub fn new(root: &Path, custom: &[Category]) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            custom: custom.to_vec(),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// ファイルに適用されるローカル設定の拡張子マッピングを返す
+    pub fn overrides_for(&mut self, path: &Path) -> Result<HashMap<String, Category>> {
+        let dirs: Vec<PathBuf> = path
+            .parent()
+            .into_iter()
+            .flat_map(Path::ancestors)
+            .take_while(|dir| dir.starts_with(&self.root))
+            .map(Path::to_path_buf)
+            .collect();
+
+        let mut overrides = HashMap::new();
+        for dir in dirs.into_iter().rev() {
+            if let Some(local) = self.load(dir)? {
+                overrides.extend(local.iter().map(|(ext, c)| (ext.clone(), c.clone())));
+            }
+        }
+        Ok(overrides)
+    }
+
+    /// ディレクトリのローカル設定を読み込む（結果はキャッシュする）
+    fn load(&mut self, dir: PathBuf) -> Result<&Option<HashMap<String, Category>>> {
+        if !self.cache.contains_key(&dir) {
+            let path = dir.join(LOCAL_CONFIG_FILE_NAME);
+            let local = if path.is_file() {
+                Some(load_config_with(&path, &self.custom)?.to_overrides_with(&self.custom)?)
+            } else {
+                None
+            };
+            self.cache.insert(dir.clone(), local);
+        }
+        Ok(&self.cache[&dir])
+    }
+}
+
+/// 読み込む設定ファイルの一覧を優先度の低い順に返す
+///
+/// 環境変数 `SMART_SORTER_CONFIG` で指定されたファイルを最下層とし、
+/// その上にCLIの `--config` で指定されたファイルを指定順に重ねます。
+pub fn config_layers(cli_paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut layers: Vec<PathBuf> = std::env::var_os(CONFIG_ENV_VAR)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    layers.extend(cli_paths.iter().cloned());
+    layers
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -255,4 +822,161 @@ mod tests {
         assert_eq!(Category::Images.folder_name(), "Images");
         assert_eq!(Category::Others.folder_name(), "Others");
     }
+
+    #[test]
+    fn test_code_language() {
+        assert_eq!(code_language("rs"), Some("Rust"));
+        assert_eq!(code_language("PY"), Some("Python"));
+        assert_eq!(code_language("h"), Some("C"));
+        assert_eq!(code_language("dockerfile"), None);
+    }
+
+    #[test]
+    fn test_category_from_str() {
+        assert_eq!("images".parse::<Category>().unwrap(), Category::Images);
+        assert_eq!(
+            "Documents".parse::<Category>().unwrap(),
+            Category::Documents
+        );
+        assert!("Unknown".parse::<Category>().is_err());
+    }
+
+    #[test]
+    fn test_correct_extension() {
+        assert_eq!(correct_extension("jgp"), Some("jpg"));
+        assert_eq!(correct_extension("PNNG"), Some("png"));
+        // 既知の拡張子、近い候補がない拡張子はそのまま
+        assert_eq!(correct_extension("jpg"), None);
+        assert_eq!(correct_extension("xyzzy"), None);
+        // 候補が複数ある（mp3 と mp4）場合は訂正しない
+        assert_eq!(correct_extension("mp5"), None);
+        // 組み込みの拡張子に近くても、実在する拡張子は訂正しない
+        for ext in [
+            "rst", "cmd", "tmp", "pem", "crt", "otf", "pub", "ps1", "PEM",
+        ] {
+            assert_eq!(correct_extension(ext), None, "{}", ext);
+        }
+    }
+
+    #[test]
+    fn test_load_layered_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.toml");
+        let over = dir.path().join("override.toml");
+        fs::write(
+            &base,
+            "keep_at_root = [\"index.html\"]\n[extensions]\nlog = \"Documents\"\nkra = \"Images\"\n",
+        )
+        .unwrap();
+        fs::write(
+            &over,
+            "keep_at_root = [\"README.md\"]\n[extensions]\nkra = \"Code\"\n",
+        )
+        .unwrap();
+
+        let config = load_layered_config(&[base, over]).unwrap();
+        let overrides = config.to_overrides().unwrap();
+        assert_eq!(overrides.get("log"), Some(&Category::Documents));
+        assert_eq!(overrides.get("kra"), Some(&Category::Code));
+        assert_eq!(config.keep_at_root, vec!["index.html", "README.md"]);
+    }
+
+    #[test]
+    fn test_invalid_category_in_config() {
+        let config = ExtensionConfig {
+            extensions: BTreeMap::from([("blend".to_string(), "3D".to_string())]),
+            ..Default::default()
+        };
+        assert!(config.to_overrides().is_err());
+    }
+
+    #[test]
+    fn test_folder_names() {
+        let config = ExtensionConfig {
+            folder_names: BTreeMap::from([("images".to_string(), "画像".to_string())]),
+            ..Default::default()
+        };
+        let folder_names = config.to_folder_names().unwrap();
+        assert_eq!(
+            folder_names.get(&Category::Images).map(String::as_str),
+            Some("画像")
+        );
+
+        let config = ExtensionConfig {
+            folder_names: BTreeMap::from([("Images".to_string(), "a/b".to_string())]),
+            ..Default::default()
+        };
+        assert!(config.to_folder_names().is_err());
+    }
+
+    #[test]
+    fn test_check_folder_names() {
+        let fonts = Category::Custom("Fonts".to_string());
+        let names = HashMap::from([(Category::Images, "画像".to_string())]);
+        assert!(check_folder_names(&names, std::slice::from_ref(&fonts)).is_ok());
+
+        // 既存のカテゴリのフォルダ名と重なる
+        let names = HashMap::from([(Category::Images, "videos".to_string())]);
+        assert!(check_folder_names(&names, &[]).is_err());
+        let names = HashMap::from([(Category::Images, "Fonts".to_string())]);
+        assert!(check_folder_names(&names, &[fonts]).is_err());
+
+        // 2つのカテゴリを同じ名前にする
+        let names = HashMap::from([
+            (Category::Images, "Media".to_string()),
+            (Category::Videos, "Media".to_string()),
+        ]);
+        assert!(check_folder_names(&names, &[]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_category_reports_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[extensions]\nlog = \"Documents\"\nblend = \"3D\"\n").unwrap();
+
+        let message = format!("{:#}", load_config(&path).unwrap_err());
+        assert!(
+            message.contains(&format!("{}:3:", path.display())),
+            "{}",
+            message
+        );
+        assert!(message.contains("'blend'"), "{}", message);
+        assert!(message.contains("Unknown category: '3D'"), "{}", message);
+    }
+
+    #[test]
+    fn test_custom_categories() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "categories = [\"Fonts\", \"Images\"]\n[extensions]\nttf = \"fonts\"\n",
+        )
+        .unwrap();
+
+        let config = load_config(&path).unwrap();
+        let font = Category::Custom("Fonts".to_string());
+        // 組み込みと同名のものはカスタムカテゴリに含めない
+        assert_eq!(config.custom_categories().unwrap(), vec![font.clone()]);
+        assert_eq!(config.to_overrides().unwrap().get("ttf"), Some(&font));
+
+        fs::write(&path, "categories = [\"../x\"]\n").unwrap();
+        assert!(load_config(&path).is_err());
+    }
+
+    #[test]
+    fn test_override_collisions() {
+        let merged = BTreeMap::from([
+            ("log".to_string(), Category::Documents),
+            ("pdf".to_string(), Category::Documents),
+            ("kra".to_string(), Category::Images),
+            ("png".to_string(), Category::Custom("Screens".to_string())),
+        ]);
+        let collisions = override_collisions(&merged);
+
+        // 組み込みにない拡張子（kra）と同じカテゴリへの上書き（pdf）は含めない
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].to_string(), ".png: Images → Screens");
+    }
 }
@@ -0,0 +1,182 @@
+//! 画像変換モジュール（`heic` フィーチャー）
+//!
+//! 移動後のHEIC/HEIF画像をJPEGに変換するポストムーブフックを提供します。
+//! 変換処理は `ImageConverter` トレイトで抽象化しており、
+//! 既定では `libheif-rs` で復号し、`jpeg-encoder` でJPEGに書き出します。
+
+use crate::file_ops::generate_unique_path;
+use anyhow::{Context, Result};
+use jpeg_encoder::{ColorType, Encoder};
+use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// 変換対象とする拡張子（小文字）
+const HEIC_EXTENSIONS: [&str; 2] = ["heic", "heif"];
+
+/// 書き出すJPEGの品質
+const JPEG_QUALITY: u8 = 90;
+
+/// HEIC/HEIF画像をJPEGに変換する処理
+pub trait ImageConverter {
+    /// `source` を変換し、JPEGとして `destination` に書き出す
+    fn convert_to_jpeg(&self, source: &Path, destination: &Path) -> Result<()>;
+}
+
+/// libheif による変換
+///
+/// 主画像をRGBに復号し（回転などの変換も適用されます）、JPEGとして書き出します。
+#[derive(Debug, Default)]
+pub struct LibheifConverter;
+
+impl ImageConverter for LibheifConverter {
+    fn convert_to_jpeg(&self, source: &Path, destination: &Path) -> Result<()> {
+        let name = source
+            .to_str()
+            .with_context(|| format!("Invalid filename: {}", source.display()))?;
+        let context = HeifContext::read_from_file(name)
+            .with_context(|| format!("Failed to read HEIF image: {}", source.display()))?;
+        let handle = context
+            .primary_image_handle()
+            .with_context(|| format!("No primary image in {}", source.display()))?;
+        let image = LibHeif::new()
+            .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+            .with_context(|| format!("Failed to decode HEIF image: {}", source.display()))?;
+
+        let planes = image.planes();
+        let plane = planes
+            .interleaved
+            .with_context(|| format!("Decoded image has no RGB plane: {}", source.display()))?;
+        let (width, height) = (u16::try_from(plane.width), u16::try_from(plane.height));
+        let (Ok(width), Ok(height)) = (width, height) else {
+            anyhow::bail!(
+                "Image is too large for JPEG ({}x{}): {}",
+                plane.width,
+                plane.height,
+                source.display()
+            );
+        };
+        let pixels = pack_rows(plane.data, plane.stride, usize::from(width) * 3, height);
+
+        Encoder::new_file(destination, JPEG_QUALITY)
+            .and_then(|encoder| encoder.encode(&pixels, width, height, ColorType::Rgb))
+            .with_context(|| format!("Failed to write JPEG: {}", destination.display()))
+    }
+}
+
+/// 行ごとに `stride` バイトの間隔で並ぶ画素から、各行の先頭 `row_bytes` バイトだけを詰めて返す
+fn pack_rows(data: &[u8], stride: usize, row_bytes: usize, height: u16) -> Vec<u8> {
+    data.chunks(stride)
+        .take(usize::from(height))
+        .flat_map(|row| &row[..row_bytes.min(row.len())])
+        .copied()
+        .collect()
+}
+
+/// パスがHEIC/HEIF画像かどうかを判定
+pub fn is_heic(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| HEIC_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// 移動済みのHEIC/HEIF画像を同じフォルダにJPEGとして書き出す
+///
+/// # Arguments
+/// * `path` - 移動後の画像のパス
+/// * `converter` - 変換処理
+/// * `keep_original` - `false` の場合、変換成功後に元の画像を削除する
+///
+/// # Returns
+/// 書き出したJPEGのパス。HEIC/HEIFでない場合は `None`。
+pub fn convert_moved_heic(
+    path: &Path,
+    converter: &dyn ImageConverter,
+    keep_original: bool,
+) -> Result<Option<PathBuf>> {
+    if !is_heic(path) {
+        return Ok(None);
+    }
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .with_context(|| format!("Invalid filename: {}", path.display()))?;
+    let jpeg = generate_unique_path(dir, &format!("{}.jpg", stem));
+
+    converter.convert_to_jpeg(path, &jpeg)?;
+    debug!("Converted {} -> {}", path.display(), jpeg.display());
+
+    if !keep_original {
+        fs::remove_file(path)
+            .with_context(|| format!("Failed to remove original: {}", path.display()))?;
+    }
+
+    Ok(Some(jpeg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// 入力をそのままコピーするだけのモック
+    struct MockConverter;
+
+    impl ImageConverter for MockConverter {
+        fn convert_to_jpeg(&self, source: &Path, destination: &Path) -> Result<()> {
+            fs::copy(source, destination)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_convert_moved_heic() {
+        let dir = tempdir().unwrap();
+        let heic = dir.path().join("IMG_0001.HEIC");
+        fs::write(&heic, "heic data").unwrap();
+
+        let jpeg = convert_moved_heic(&heic, &MockConverter, true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(jpeg, dir.path().join("IMG_0001.jpg"));
+        assert!(jpeg.exists());
+        assert!(heic.exists());
+    }
+
+    #[test]
+    fn test_convert_moved_heic_removes_original() {
+        let dir = tempdir().unwrap();
+        let heic = dir.path().join("photo.heif");
+        fs::write(&heic, "heif data").unwrap();
+
+        convert_moved_heic(&heic, &MockConverter, false).unwrap();
+        assert!(dir.path().join("photo.jpg").exists());
+        assert!(!heic.exists());
+    }
+
+    #[test]
+    fn test_pack_rows() {
+        // 幅2画素（6バイト）の行が8バイト間隔で並ぶ
+        let data = [1, 2, 3, 4, 5, 6, 0, 0, 7, 8, 9, 10, 11, 12, 0, 0];
+        assert_eq!(
+            pack_rows(&data, 8, 6, 2),
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        );
+    }
+
+    #[test]
+    fn test_non_heic_is_ignored() {
+        let dir = tempdir().unwrap();
+        let png = dir.path().join("photo.png");
+        fs::write(&png, "png").unwrap();
+
+        assert_eq!(
+            convert_moved_heic(&png, &MockConverter, true).unwrap(),
+            None
+        );
+    }
+}
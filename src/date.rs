@@ -0,0 +1,276 @@
+//! 日付処理モジュール
+//!
+//! ファイルの更新日時などの `SystemTime` やファイル名に含まれる日付を年月日に変換します。
+//! 外部クレートに依存しないよう、UTC基準の簡易的な暦計算のみを行います。
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// ファイル名から日付を探すパターン（上から順に試す）
+///
+/// 年・月・日の順のキャプチャを持ち、前後が数字でない位置にのみ一致します。
+static NAME_DATE_PATTERNS: Lazy<Vec<Regex>> = Lazy::new(|| {
+    [
+        // 2023-07-15_report.pdf, Screenshot 2023_07_15.png, 2023.07.15.txt
+        r"(?:^|\D)(\d{4})[-_.](\d{1,2})[-_.](\d{1,2})(?:\D|$)",
+        // IMG_20230715_123456.jpg, VID-20230715-WA0001.mp4
+        r"(?:^|\D)(\d{4})(\d{2})(\d{2})(?:\D|$)",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).expect("invalid built-in date pattern"))
+    .collect()
+});
+
+/// ファイル名の日付として受け付ける年の範囲（連番などの誤検出を避けるため）
+const NAME_YEAR_RANGE: std::ops::RangeInclusive<i32> = 1970..=2100;
+
+/// 年月日（UTC）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// `SystemTime` をUTCの年月日に変換する
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64) - 1,
+        };
+        Self::from_days_since_epoch(secs.div_euclid(86_400))
+    }
+
+    /// 1970-01-01 からの経過日数を年月日に変換する
+    ///
+    /// Howard Hinnant の `civil_from_days` アルゴリズムによる変換です。
+    pub fn from_days_since_epoch(days: i64) -> Self {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+        Self { year, month, day }
+    }
+
+    /// 1970-01-01 からの経過日数（`from_days_since_epoch` の逆変換）
+    ///
+    /// Howard Hinnant の `days_from_civil` アルゴリズムによる変換です。
+    pub fn days_since_epoch(&self) -> i64 {
+        let month = i64::from(self.month);
+        let year = i64::from(self.year) - i64::from(month <= 2);
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + i64::from(self.day) - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    /// その日の0時（UTC）の `SystemTime`
+    pub fn start_of_day(&self) -> SystemTime {
+        let secs = self.days_since_epoch() * 86_400;
+        if secs >= 0 {
+            UNIX_EPOCH + Duration::from_secs(secs as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+        }
+    }
+
+    /// 暦として正しい年月日の場合のみ `Date` を作る
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return None,
+        };
+        (1..=days_in_month)
+            .contains(&day)
+            .then_some(Self { year, month, day })
+    }
+
+    /// ファイル名に含まれる日付を取り出す
+    ///
+    /// `2023-07-15`・`2023_07_15`・`2023.07.15` のような区切り付きの形式と、
+    /// `IMG_20230715_...` のような8桁の形式に対応します。
+    /// 暦として正しくない数字の並びや、1970〜2100年以外の年は日付とみなしません。
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        NAME_DATE_PATTERNS.iter().find_map(|pattern| {
+            pattern.captures_iter(name).find_map(|caps| {
+                let year: i32 = caps[1].parse().ok()?;
+                if !NAME_YEAR_RANGE.contains(&year) {
+                    return None;
+                }
+                Self::new(year, caps[2].parse().ok()?, caps[3].parse().ok()?)
+            })
+        })
+    }
+}
+
+/// `--older-than`・`--newer-than` の基準時刻を解析する
+///
+/// `30d`・`6mo` のような `now` からさかのぼる期間（`h` 時間・`d` 日・`w` 週・
+/// `mo` 月（30日）・`y` 年（365日））か、`2024-01-31` 形式の日付（その日の0時、UTC）を受け付けます。
+pub fn parse_cutoff(value: &str, now: SystemTime) -> Result<SystemTime> {
+    let value = value.trim();
+    if let Some(date) = parse_iso_date(value) {
+        return Ok(date.start_of_day());
+    }
+
+    let digits = value.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        bail!(
+            "Invalid age '{}' (expected e.g. 30d, 6mo, 1y or 2024-01-31)",
+            value
+        );
+    }
+    let number: u64 = value[..digits]
+        .parse()
+        .with_context(|| format!("Age out of range: '{}'", value))?;
+    let unit_secs: u64 = match value[digits..].trim().to_lowercase().as_str() {
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "mo" => 30 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        "" => bail!("Missing unit in age '{}' (h, d, w, mo or y)", value),
+        unit => bail!(
+            "Unknown age unit '{}' in '{}' (h, d, w, mo or y)",
+            unit,
+            value
+        ),
+    };
+    number
+        .checked_mul(unit_secs)
+        .and_then(|secs| now.checked_sub(Duration::from_secs(secs)))
+        .with_context(|| format!("Age out of range: '{}'", value))
+}
+
+/// `YYYY-MM-DD` 形式の日付を解析する（暦として正しくなければ `None`）
+fn parse_iso_date(value: &str) -> Option<Date> {
+    let mut parts = value.split('-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    Date::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+}
+
+impl fmt::Display for Date {
+    /// `YYYY-MM-DD` 形式で表示
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_from_days_since_epoch() {
+        assert_eq!(
+            Date::from_days_since_epoch(0),
+            Date {
+                year: 1970,
+                month: 1,
+                day: 1
+            }
+        );
+        assert_eq!(
+            Date::from_days_since_epoch(19_723),
+            Date {
+                year: 2024,
+                month: 1,
+                day: 1
+            }
+        );
+        assert_eq!(
+            Date::from_days_since_epoch(19_782),
+            Date {
+                year: 2024,
+                month: 2,
+                day: 29
+            }
+        );
+        assert_eq!(
+            Date::from_days_since_epoch(-1),
+            Date {
+                year: 1969,
+                month: 12,
+                day: 31
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_file_name() {
+        let expected = Date::new(2023, 7, 15);
+        assert!(expected.is_some());
+        for name in [
+            "IMG_20230715_123456.jpg",
+            "VID-20230715-WA0001.mp4",
+            "2023-07-15_report.pdf",
+            "Screenshot 2023_07_15 at 10.00.00.png",
+            "backup.2023.7.15.tar.gz",
+        ] {
+            assert_eq!(Date::from_file_name(name), expected, "{}", name);
+        }
+
+        // 日付を含まない、または暦として正しくない
+        assert_eq!(Date::from_file_name("notes.txt"), None);
+        assert_eq!(Date::from_file_name("IMG_20231345_0001.jpg"), None);
+        assert_eq!(Date::from_file_name("2023-02-30.txt"), None);
+        assert_eq!(Date::from_file_name("order_123456789.pdf"), None);
+    }
+
+    #[test]
+    fn test_days_since_epoch_round_trip() {
+        for days in [-1, 0, 19_723, 19_782, 47_482] {
+            assert_eq!(Date::from_days_since_epoch(days).days_since_epoch(), days);
+        }
+    }
+
+    #[test]
+    fn test_parse_cutoff() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let day = Duration::from_secs(86_400);
+        assert_eq!(parse_cutoff("30d", now).unwrap(), now - day * 30);
+        assert_eq!(parse_cutoff("6mo", now).unwrap(), now - day * 180);
+        assert_eq!(parse_cutoff("2w", now).unwrap(), now - day * 14);
+        assert_eq!(
+            parse_cutoff("2024-01-01", now).unwrap(),
+            UNIX_EPOCH + day * 19_723
+        );
+        assert!(parse_cutoff("30", now).is_err());
+        assert!(parse_cutoff("3 parsecs", now).is_err());
+        assert!(parse_cutoff("2024-02-30", now).is_err());
+    }
+
+    #[test]
+    fn test_from_system_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_689_420_000); // 2023-07-15
+        let date = Date::from_system_time(time);
+        assert_eq!(
+            date,
+            Date {
+                year: 2023,
+                month: 7,
+                day: 15
+            }
+        );
+        assert_eq!(date.to_string(), "2023-07-15");
+    }
+}
@@ -0,0 +1,183 @@
+//! コンテンツ重複検出モジュール
+//!
+//! ファイル内容のハッシュをキーとしたインデックスを保持し、
+//! ファイル名に関係なく内容が同一のファイルを検出します。
+
+use crate::file_ops::hash_file;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// コンテンツハッシュのインデックス
+///
+/// 最初に登録されたファイルを「オリジナル」として保持し、
+/// 以降に同じハッシュを持つファイルを重複として扱います。
+///
+/// 保持するハッシュ数に上限（ウィンドウ）を設けると、最も長く参照されていない
+/// ハッシュから忘れていきます（LRU）。メモリ使用量は上限に比例して抑えられますが、
+/// 忘れたハッシュと同じ内容のファイルは重複として検出されず、新たなオリジナルになります。
+#[derive(Debug, Default)]
+pub struct ContentIndex {
+    /// ハッシュ → (最初に登録されたファイルのパス, 最後に参照された順番)
+    seen: HashMap<String, (PathBuf, u64)>,
+    /// 最後に参照された順番 → ハッシュ（古いものから忘れるため）
+    recency: BTreeMap<u64, String>,
+    /// 次に割り当てる参照の順番
+    tick: u64,
+    /// 保持するハッシュ数の上限（`None` なら無制限）
+    window: Option<usize>,
+}
+
+impl ContentIndex {
+    /// 空のインデックスを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 直近に参照された最大 `window` 個のハッシュだけを保持するインデックスを作成
+    pub fn with_window(window: usize) -> Self {
+        Self {
+            window: Some(window.max(1)),
+            ..Self::default()
+        }
+    }
+
+    /// ファイルを登録し、既に同一内容のファイルがあればそのパスを返す
+    ///
+    /// # Arguments
+    /// * `path` - 登録するファイルのパス
+    ///
+    /// # Returns
+    /// 重複している場合は `Some(オリジナルのパス)`、初出の場合は `None`
+    pub fn check_or_insert(&mut self, path: &Path) -> Result<Option<PathBuf>> {
+        let hash = hash_file(path)?;
+        let tick = self.tick;
+        self.tick += 1;
+
+        if let Some((original, last_used)) = self.seen.get_mut(&hash) {
+            self.recency.remove(last_used);
+            *last_used = tick;
+            let original = original.clone();
+            self.recency.insert(tick, hash);
+            return Ok(Some(original));
+        }
+
+        if let Some(window) = self.window {
+            while self.seen.len() >= window {
+                match self.recency.pop_first() {
+                    Some((_, oldest)) => self.seen.remove(&oldest),
+                    None => break,
+                };
+            }
+        }
+        self.recency.insert(tick, hash.clone());
+        self.seen.insert(hash, (path.to_path_buf(), tick));
+        Ok(None)
+    }
+}
+
+/// 内容が重複していたファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentDuplicate {
+    /// 重複と判定されたファイル
+    pub path: PathBuf,
+    /// 同一内容で先に登録されていたファイル
+    pub original: PathBuf,
+}
+
+/// ファイル一覧から内容が重複するファイルを取り除く
+///
+/// ファイルはパス順に並べ替えてから評価するため、どのファイルが残るかは決定的です。
+/// `window` を指定した場合は [`ContentIndex::with_window`] のとおり、
+/// パス順で離れた位置にある重複は見逃されることがあります。
+///
+/// # Returns
+/// `(残すファイル, 重複ファイルのリスト)`
+pub fn partition_content_duplicates(
+    files: &[PathBuf],
+    window: Option<usize>,
+) -> Result<(Vec<PathBuf>, Vec<ContentDuplicate>)> {
+    let mut sorted = files.to_vec();
+    sorted.sort();
+
+    let mut index = match window {
+        Some(window) => ContentIndex::with_window(window),
+        None => ContentIndex::new(),
+    };
+    let mut unique = Vec::new();
+    let mut duplicates = Vec::new();
+
+    for file in sorted {
+        match index.check_or_insert(&file)? {
+            Some(original) => duplicates.push(ContentDuplicate {
+                path: file,
+                original,
+            }),
+            None => unique.push(file),
+        }
+    }
+
+    Ok((unique, duplicates))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_partition_content_duplicates() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("song.mp3");
+        let b = dir.path().join("song (1).mp3");
+        let c = dir.path().join("other.mp3");
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+        fs::write(&c, "different").unwrap();
+
+        let (unique, duplicates) =
+            partition_content_duplicates(&[a.clone(), b.clone(), c.clone()], None).unwrap();
+
+        // パス順で先に来る "song (1).mp3" がオリジナルとして残る
+        assert_eq!(unique, vec![c, b.clone()]);
+        assert_eq!(
+            duplicates,
+            vec![ContentDuplicate {
+                path: a,
+                original: b
+            }]
+        );
+    }
+
+    #[test]
+    fn test_window_forgets_least_recently_used() {
+        let dir = tempdir().unwrap();
+        // パス順: a1, a2, b, c, d, e
+        let files: Vec<PathBuf> = [
+            ("a1.txt", "A"),
+            ("a2.txt", "A"),
+            ("b.txt", "B"),
+            ("c.txt", "A"),
+            ("d.txt", "C"),
+            ("e.txt", "B"),
+        ]
+        .iter()
+        .map(|(name, content)| {
+            let path = dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        })
+        .collect();
+
+        let (_, duplicates) = partition_content_duplicates(&files, Some(2)).unwrap();
+        let found: Vec<&Path> = duplicates.iter().map(|d| d.path.as_path()).collect();
+
+        // ウィンドウ内の重複（a2, c）は検出されるが、
+        // d の登録で忘れられた B の重複（e）は見逃される
+        assert_eq!(found, vec![files[1].as_path(), files[3].as_path()]);
+
+        let (_, duplicates) = partition_content_duplicates(&files, None).unwrap();
+        assert_eq!(duplicates.len(), 3);
+    }
+}
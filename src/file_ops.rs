@@ -3,14 +3,22 @@
 //! ファイルの移動、重複ファイル名の生成、ディレクトリ作成などの
 //! 低レベルなファイル操作を担当します。
 
-use anyhow::{Context, Result};
+use crate::template::DedupTemplate;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
 use std::fs;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
 use tracing::{debug, info, warn};
 
-/// ファイル移動の結果を表す構造体
+/// ファイルの移動・コピーの結果を表す構造体
 #[derive(Debug, Clone)]
-pub struct MoveResult {
+pub struct OpResult {
     /// 移動元のパス
     #[allow(dead_code)]
     pub source: PathBuf,
@@ -18,6 +26,107 @@ pub struct MoveResult {
     pub destination: PathBuf,
     /// 重複回避のためにリネームされたかどうか
     pub was_renamed: bool,
+    /// 移動先の既存ファイルを置き換えた場合、その方法
+    pub replaced: Option<Replaced>,
+    /// 行った操作
+    pub kind: OpKind,
+    /// 移動に使われた方法（コピーの場合は常に `Copy`）
+    pub method: MoveMethod,
+}
+
+/// ファイルに対して行う操作
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpKind {
+    /// 移動（元のファイルは残らない）
+    #[default]
+    Move,
+    /// コピー（元のファイルはそのまま残る）
+    Copy,
+    /// ハードリンク（元のファイルはそのまま残り、容量を使わない）
+    Hardlink,
+}
+
+/// 移動先に同名のファイルが既にある場合の扱い
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// 連番を付けて両方残す
+    #[default]
+    Rename,
+    /// 移動しない
+    Skip,
+    /// 既存のファイルを上書きする
+    Overwrite,
+    /// 既存のファイルをゴミ箱に移してから移動する
+    Trash,
+    /// 移動元の方が新しい（更新日時が後の）場合だけ上書きし、そうでなければ移動しない
+    KeepNewer,
+    /// 移動元の方が大きい場合だけ上書きし、そうでなければ移動しない
+    KeepLarger,
+}
+
+impl ConflictStrategy {
+    /// 移動先の既存ファイルを置き換える（元に戻せなくなる）可能性がある戦略か
+    pub fn replaces_existing(self) -> bool {
+        matches!(
+            self,
+            Self::Overwrite | Self::Trash | Self::KeepNewer | Self::KeepLarger
+        )
+    }
+}
+
+/// 移動元 `source` と移動先の既存ファイル `existing` を比べて、実際に行う扱いを決める
+///
+/// `KeepNewer`・`KeepLarger` は比較の結果に応じて `Overwrite` か `Skip` になり、
+/// それ以外の戦略はそのまま返します。比較に必要なメタデータを取得できない場合は、
+/// どちらも失わないよう `Rename` にします。
+pub fn resolve_conflict(
+    strategy: ConflictStrategy,
+    source: &Path,
+    existing: &Path,
+) -> ConflictStrategy {
+    let keep_source = match strategy {
+        ConflictStrategy::KeepNewer => {
+            let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified());
+            match (modified(source), modified(existing)) {
+                (Ok(source), Ok(existing)) => source > existing,
+                _ => return ConflictStrategy::Rename,
+            }
+        }
+        ConflictStrategy::KeepLarger => match (fs::metadata(source), fs::metadata(existing)) {
+            (Ok(source), Ok(existing)) => source.len() > existing.len(),
+            _ => return ConflictStrategy::Rename,
+        },
+        strategy => return strategy,
+    };
+    if keep_source {
+        ConflictStrategy::Overwrite
+    } else {
+        ConflictStrategy::Skip
+    }
+}
+
+/// 移動先の既存ファイルを置き換えた方法
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Replaced {
+    /// 上書きした
+    Overwritten,
+    /// ゴミ箱に移した
+    Trashed,
+    /// ゴミ箱に移せなかったため、連番を付けて両方残した（エラーメッセージ）
+    TrashFailed(String),
+}
+
+/// ファイル移動に使われた方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveMethod {
+    /// 同一ファイルシステム内での rename
+    Rename,
+    /// コピー後に元ファイルを削除（異なるファイルシステム間など）
+    Copy,
+    /// 元ファイルへのハードリンクを作成（元ファイルは残る）
+    Hardlink,
 }
 
 /// 移動先に同名ファイルが存在する場合、連番付きの新しいファイル名を生成する
@@ -31,32 +140,156 @@ pub struct MoveResult {
 ///
 /// # Example
 /// `report.pdf` → `report_1.pdf` → `report_2.pdf` ...
+#[cfg(any(test, feature = "heic"))]
 pub fn generate_unique_path(dest_dir: &Path, filename: &str) -> PathBuf {
-    let base_path = dest_dir.join(filename);
+    generate_unique_path_with(dest_dir, filename, |path| path.exists())
+}
 
-    // ファイルが存在しなければそのまま返す
-    if !base_path.exists() {
-        return base_path;
+/// 1つの拡張子として扱う複数部分の拡張子（ドットなし、小文字）
+const COMPOUND_EXTENSIONS: &[&str] = &["tar.gz", "tar.bz2", "tar.xz"];
+
+/// ファイル名を stem と拡張子（ドットなし）に分割する
+///
+/// `archive.tar.gz` のような既知の複数部分の拡張子は1つの拡張子として扱い、
+/// `("archive", Some("tar.gz"))` を返します。それ以外は `Path::file_stem` と
+/// `Path::extension` と同じ分割です。
+pub fn split_compound_extension(filename: &str) -> (&str, Option<&str>) {
+    let lower = filename.to_ascii_lowercase();
+    for compound in COMPOUND_EXTENSIONS {
+        if lower.len() > compound.len() + 1 && lower.ends_with(&format!(".{}", compound)) {
+            let dot = filename.len() - compound.len() - 1;
+            return (&filename[..dot], Some(&filename[dot + 1..]));
+        }
     }
 
-    // ファイル名を stem と extension に分割
     let path = Path::new(filename);
     let stem = path
         .file_stem()
         .and_then(|s| s.to_str())
         .unwrap_or(filename);
-    let extension = path.extension().and_then(|s| s.to_str());
+    (stem, path.extension().and_then(|s| s.to_str()))
+}
+
+/// stem を切り詰めてファイル名をバイト数の上限に収める
+///
+/// ファイル名は `stem` + `suffix` + `.extension` で、まず `stem` を UTF-8 の文字境界で
+/// 切り詰めます。拡張子と `suffix` だけで上限を超える場合は stem を空にし、拡張子も
+/// 切り詰めます（収まらなければ拡張子を付けません）。`suffix` は名前を区別するために
+/// 使われるため切り詰めません。
+///
+/// # Arguments
+/// * `stem` - 拡張子を除いたファイル名
+/// * `suffix` - stem の後ろに付ける文字列（連番など）
+/// * `extension` - 拡張子（ドットなし）
+/// * `max_bytes` - ファイル名のバイト数の上限
+pub fn fit_file_name(
+    stem: &str,
+    suffix: &str,
+    extension: Option<&str>,
+    max_bytes: usize,
+) -> String {
+    let budget = max_bytes.saturating_sub(suffix.len());
+    let extension = extension
+        .map(|ext| truncate_on_char_boundary(ext, budget.saturating_sub(1)))
+        .filter(|ext| !ext.is_empty());
+    let stem = truncate_on_char_boundary(
+        stem,
+        budget.saturating_sub(extension.map_or(0, |ext| ext.len() + 1)),
+    );
+    match extension {
+        Some(ext) => format!("{}{}.{}", stem, suffix, ext),
+        None => format!("{}{}", stem, suffix),
+    }
+}
+
+/// 文字列を UTF-8 の文字境界で `max_bytes` 以下に切り詰める
+fn truncate_on_char_boundary(s: &str, max_bytes: usize) -> &str {
+    let mut end = max_bytes.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// 任意の「使用済み」判定で連番付きの重複しないパスを生成する
+///
+/// 実際のファイルシステムではなく、予約済みパスの集合などに対して
+/// 重複回避を行いたい場合に使用します。命名規則は `generate_unique_path` と同じです。
+///
+/// # Arguments
+/// * `dest_dir` - 移動先ディレクトリ
+/// * `filename` - 元のファイル名
+/// * `is_taken` - パスが既に使用されているかを判定する関数
+pub fn generate_unique_path_with<F>(dest_dir: &Path, filename: &str, is_taken: F) -> PathBuf
+where
+    F: Fn(&Path) -> bool,
+{
+    generate_unique_path_limited(dest_dir, filename, UniqueNaming::default(), is_taken)
+}
+
+/// 重複回避のファイル名の付け方
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UniqueNaming<'a> {
+    /// ファイル名のバイト数の上限（`None` なら無制限）
+    pub max_bytes: Option<usize>,
+    /// 連番の付け方（`None` なら `stem_N.ext`）
+    pub template: Option<&'a DedupTemplate>,
+    /// 選んだ名前を空ファイルを作成して予約するか（並列に移動・コピーする場合）
+    ///
+    /// ハードリンクは既存のパスを置き換えないため、この指定に関係なく予約しません。
+    pub reserve: bool,
+}
+
+/// ファイル名の長さの上限を守りつつ、連番付きの重複しないパスを生成する
+///
+/// `naming.max_bytes` を指定すると、元の名前・連番付きの名前のどちらも stem を切り詰めて
+/// 上限に収めます（連番は切り詰めず、拡張子は連番と合わせて収まらない場合だけ切り詰めます）。
+/// 切り詰めで同名になった場合も連番で区別されます。`naming.template` がなければ命名規則は
+/// `generate_unique_path` と同じで、あればそのテンプレートで連番付きの名前を組み立てます。
+///
+/// # Arguments
+/// * `dest_dir` - 移動先ディレクトリ
+/// * `filename` - 元のファイル名
+/// * `naming` - 重複回避のファイル名の付け方
+/// * `is_taken` - パスが既に使用されているかを判定する関数
+pub fn generate_unique_path_limited<F>(
+    dest_dir: &Path,
+    filename: &str,
+    naming: UniqueNaming<'_>,
+    is_taken: F,
+) -> PathBuf
+where
+    F: Fn(&Path) -> bool,
+{
+    // ファイル名を stem と extension に分割（`.tar.gz` などは1つの拡張子として扱う）
+    let (stem, extension) = split_compound_extension(filename);
+
+    let max_bytes = naming.max_bytes;
+    let base_path = match max_bytes {
+        Some(max) if filename.len() > max => dest_dir.join(fit_file_name(stem, "", extension, max)),
+        _ => dest_dir.join(filename),
+    };
+
+    // ファイルが存在しなければそのまま返す
+    if !is_taken(&base_path) {
+        return base_path;
+    }
 
     // 連番を付けて重複しない名前を探す
     let mut counter = 1u32;
     loop {
-        let new_filename = match extension {
-            Some(ext) => format!("{}_{}.{}", stem, counter, ext),
-            None => format!("{}_{}", stem, counter),
+        let new_filename = match naming.template {
+            Some(template) => template.render(stem, extension, counter as usize, max_bytes),
+            None => fit_file_name(
+                stem,
+                &format!("_{}", counter),
+                extension,
+                max_bytes.unwrap_or(usize::MAX),
+            ),
         };
 
         let new_path = dest_dir.join(&new_filename);
-        if !new_path.exists() {
+        if !is_taken(&new_path) {
             debug!(
                 "Generated unique filename: {} -> {}",
                 filename, new_filename
@@ -77,10 +310,12 @@ pub fn generate_unique_path(dest_dir: &Path, filename: &str) -> PathBuf {
                 .duration_since(std::time::UNIX_EPOCH)
                 .map(|d| d.as_millis())
                 .unwrap_or(0);
-            let fallback_filename = match extension {
-                Some(ext) => format!("{}_{}_{}.{}", stem, counter, timestamp, ext),
-                None => format!("{}_{}_{}", stem, counter, timestamp),
-            };
+            let fallback_filename = fit_file_name(
+                stem,
+                &format!("_{}_{}", counter, timestamp),
+                extension,
+                max_bytes.unwrap_or(usize::MAX),
+            );
             return dest_dir.join(fallback_filename);
         }
     }
@@ -102,80 +337,371 @@ pub fn ensure_directory(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// 一時的なファイルシステムエラーに対する再試行の設定
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RetryPolicy {
+    /// 最大再試行回数（0 の場合は再試行しない）
+    pub retries: u32,
+    /// 初回の再試行までの待ち時間（ミリ秒）。以降は試行ごとに倍になる
+    pub delay_ms: u64,
+}
+
+impl RetryPolicy {
+    /// 再試行しない設定
+    pub const NONE: RetryPolicy = RetryPolicy {
+        retries: 0,
+        delay_ms: 0,
+    };
+}
+
+/// 一時的（再試行で解消しうる）なI/Oエラーかどうかを判定
+///
+/// `EAGAIN`・`EINTR`・タイムアウト・`EBUSY`（Windowsでは共有/ロック違反）を一時的なエラーとし、
+/// `NotFound` や `PermissionDenied` などは恒久的なエラーとして扱います。
+pub fn is_transient_error(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted | io::ErrorKind::TimedOut => true,
+        io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied => false,
+        _ => {
+            // EBUSY / ETXTBSY（Unix）、ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION（Windows）
+            let busy: &[i32] = if cfg!(windows) { &[32, 33] } else { &[16, 26] };
+            err.raw_os_error().is_some_and(|code| busy.contains(&code))
+        }
+    }
+}
+
+/// 一時的なエラーの場合のみ、待ち時間を倍にしながら操作を再試行する
+///
+/// # Arguments
+/// * `policy` - 再試行の設定
+/// * `op` - 実行する操作
+///
+/// # Returns
+/// 操作の結果。再試行回数を使い切った場合は最後のエラー
+pub fn retry_transient<T, F>(policy: RetryPolicy, mut op: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < policy.retries && is_transient_error(&e) => {
+                let delay = policy.delay_ms.saturating_mul(1 << attempt.min(16));
+                attempt += 1;
+                warn!(
+                    "Transient error ({}), retrying in {}ms ({}/{})",
+                    e, delay, attempt, policy.retries
+                );
+                thread::sleep(Duration::from_millis(delay));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 /// ファイルを移動する
 ///
 /// `std::fs::rename` を使用してファイルを移動します。
 /// 異なるファイルシステム間の移動の場合は、コピー＆削除にフォールバックします。
 ///
+/// 各ファイル操作で一時的なエラーが発生した場合は `policy` に従って再試行します。
+///
 /// # Arguments
 /// * `source` - 移動元のファイルパス
 /// * `destination` - 移動先のファイルパス
+/// * `policy` - 一時的なエラーに対する再試行の設定
 ///
 /// # Returns
-/// 成功時は `Ok(())`、失敗時はエラー
-pub fn move_file(source: &Path, destination: &Path) -> Result<()> {
+/// 成功時は移動に使われた方法、失敗時はエラー
+pub fn move_file(source: &Path, destination: &Path, policy: RetryPolicy) -> Result<MoveMethod> {
     // まず rename を試行（同一ファイルシステム内なら高速）
-    match fs::rename(source, destination) {
+    match retry_transient(policy, || fs::rename(source, destination)) {
         Ok(()) => {
             debug!(
                 "Moved file (rename): {} -> {}",
                 source.display(),
                 destination.display()
             );
-            Ok(())
+            Ok(MoveMethod::Rename)
         }
         Err(e) => {
             // rename が失敗した場合（異なるファイルシステム間など）
             // コピー＆削除にフォールバック
             debug!("rename failed ({}), falling back to copy+delete", e);
+            move_by_copy(source, destination, policy)?;
+            Ok(MoveMethod::Copy)
+        }
+    }
+}
 
-            fs::copy(source, destination).with_context(|| {
-                format!(
-                    "Failed to copy file from {} to {}",
-                    source.display(),
-                    destination.display()
-                )
-            })?;
+/// コピーしてから元ファイルを削除することでファイルを移動する
+///
+/// `fs::copy` はプラットフォームによって更新日時を引き継がないため、コピー前に読んだ
+/// 更新日時・アクセス日時（Unixではパーミッションも）をコピー先に明示的に設定し直します。
+/// メタデータの設定に失敗しても内容は移動できているため、警告に留めます。
+///
+/// # Arguments
+/// * `source` - 移動元のファイルパス
+/// * `destination` - 移動先のファイルパス
+/// * `policy` - 一時的なエラーに対する再試行の設定
+pub fn move_by_copy(source: &Path, destination: &Path, policy: RetryPolicy) -> Result<()> {
+    let metadata = fs::metadata(source)
+        .with_context(|| format!("Failed to read metadata: {}", source.display()))?;
 
-            fs::remove_file(source).with_context(|| {
-                format!(
-                    "Failed to remove original file after copy: {}",
-                    source.display()
-                )
-            })?;
+    retry_transient(policy, || fs::copy(source, destination)).with_context(|| {
+        format!(
+            "Failed to copy file from {} to {}",
+            source.display(),
+            destination.display()
+        )
+    })?;
 
-            debug!(
-                "Moved file (copy+delete): {} -> {}",
-                source.display(),
-                destination.display()
-            );
-            Ok(())
-        }
+    let accessed = filetime::FileTime::from_last_access_time(&metadata);
+    let modified = filetime::FileTime::from_last_modification_time(&metadata);
+    if let Err(e) = filetime::set_file_times(destination, accessed, modified) {
+        warn!(
+            "Failed to preserve timestamps of {}: {}",
+            destination.display(),
+            e
+        );
+    }
+    #[cfg(unix)]
+    if let Err(e) = fs::set_permissions(destination, metadata.permissions()) {
+        warn!(
+            "Failed to preserve permissions of {}: {}",
+            destination.display(),
+            e
+        );
     }
+
+    retry_transient(policy, || fs::remove_file(source)).with_context(|| {
+        format!(
+            "Failed to remove original file after copy: {}",
+            source.display()
+        )
+    })?;
+
+    debug!(
+        "Moved file (copy+delete): {} -> {}",
+        source.display(),
+        destination.display()
+    );
+    Ok(())
 }
 
 /// ファイルを移動する（重複回避付き）
 ///
 /// 移動先に同名ファイルが存在する場合、連番を付けてリネームします。
+/// 名前は予約しないため、同じ移動先へ並列に移動する場合は
+/// `UniqueNaming::reserve` を指定して `move_file_with_dedup_as` を使ってください。
 ///
 /// # Arguments
 /// * `source` - 移動元のファイルパス
 /// * `dest_dir` - 移動先ディレクトリ
 ///
 /// # Returns
-/// 成功時は `MoveResult`、失敗時はエラー
-pub fn move_file_with_dedup(source: &Path, dest_dir: &Path) -> Result<MoveResult> {
+/// 成功時は `OpResult`、失敗時はエラー
+pub fn move_file_with_dedup(source: &Path, dest_dir: &Path) -> Result<OpResult> {
     let filename = source
         .file_name()
         .and_then(|n| n.to_str())
         .with_context(|| format!("Invalid filename: {}", source.display()))?;
 
+    move_file_with_dedup_as(
+        source,
+        dest_dir,
+        filename,
+        UniqueNaming::default(),
+        RetryPolicy::NONE,
+        ConflictStrategy::Rename,
+    )
+}
+
+/// ファイルを指定したファイル名で移動する（重複回避付き）
+///
+/// `move_file_with_dedup` と同様ですが、移動先のファイル名を指定できます。
+///
+/// # Arguments
+/// * `source` - 移動元のファイルパス
+/// * `dest_dir` - 移動先ディレクトリ
+/// * `filename` - 移動先でのファイル名（重複時はこれに連番が付く）
+/// * `naming` - 重複回避のファイル名の付け方（ファイル名の長さの上限と連番の付け方）
+/// * `retry` - 一時的なエラーに対する再試行の設定
+/// * `on_conflict` - 移動先に同名のファイルが既にある場合の扱い
+///
+/// # Returns
+/// 成功時は `OpResult`、失敗時はエラー
+pub fn move_file_with_dedup_as(
+    source: &Path,
+    dest_dir: &Path,
+    filename: &str,
+    naming: UniqueNaming<'_>,
+    retry: RetryPolicy,
+    on_conflict: ConflictStrategy,
+) -> Result<OpResult> {
+    transfer_with_dedup_as(
+        source,
+        dest_dir,
+        filename,
+        naming,
+        retry,
+        OpKind::Move,
+        on_conflict,
+    )
+}
+
+/// ファイルを指定したファイル名でコピーする（重複回避付き）
+///
+/// `move_file_with_dedup_as` と同様ですが、`fs::copy` を使い、元のファイルは削除しません。
+/// 引数は `move_file_with_dedup_as` と同じです。
+pub fn copy_file_with_dedup_as(
+    source: &Path,
+    dest_dir: &Path,
+    filename: &str,
+    naming: UniqueNaming<'_>,
+    retry: RetryPolicy,
+    on_conflict: ConflictStrategy,
+) -> Result<OpResult> {
+    transfer_with_dedup_as(
+        source,
+        dest_dir,
+        filename,
+        naming,
+        retry,
+        OpKind::Copy,
+        on_conflict,
+    )
+}
+
+/// ファイルのハードリンクを指定したファイル名で作成する（重複回避付き）
+///
+/// `move_file_with_dedup_as` と同様ですが、`fs::hard_link` を使い、元のファイルは残します。
+/// 移動先が別のファイルシステムにある場合はエラーになります。引数は `move_file_with_dedup_as` と同じです。
+pub fn hardlink_file_with_dedup_as(
+    source: &Path,
+    dest_dir: &Path,
+    filename: &str,
+    naming: UniqueNaming<'_>,
+    retry: RetryPolicy,
+    on_conflict: ConflictStrategy,
+) -> Result<OpResult> {
+    transfer_with_dedup_as(
+        source,
+        dest_dir,
+        filename,
+        naming,
+        retry,
+        OpKind::Hardlink,
+        on_conflict,
+    )
+}
+
+/// ハードリンクを作れない別のファイルシステムであれば、分かりやすいエラーを返す
+pub fn ensure_same_filesystem(source: &Path, dest_dir: &Path) -> Result<()> {
+    if same_filesystem(source, dest_dir) == Some(false) {
+        bail!(
+            "Cannot hardlink {} into {}: they are on different filesystems (use --copy instead)",
+            source.display(),
+            dest_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// 重複回避した移動先に、`kind` に応じてファイルを移動・コピー・ハードリンクする
+///
+/// `ConflictStrategy::Skip` の場合、移動先の存在確認は呼び出し側で行う前提です。
+/// 確認の後に同名のファイルが現れた場合は、`Rename` と同じく連番を付けます。
+fn transfer_with_dedup_as(
+    source: &Path,
+    dest_dir: &Path,
+    filename: &str,
+    naming: UniqueNaming<'_>,
+    retry: RetryPolicy,
+    kind: OpKind,
+    on_conflict: ConflictStrategy,
+) -> Result<OpResult> {
     // 移動先ディレクトリを作成
     ensure_directory(dest_dir)?;
+    if kind == OpKind::Hardlink {
+        ensure_same_filesystem(source, dest_dir)?;
+    }
 
-    // 重複回避した移動先パスを生成
     let original_dest = dest_dir.join(filename);
-    let final_dest = generate_unique_path(dest_dir, filename);
+
+    // 既存のファイルの扱いを決める（ゴミ箱に移せなければ連番を付けて両方残す）
+    let replaced = if original_dest.exists() {
+        match resolve_conflict(on_conflict, source, &original_dest) {
+            ConflictStrategy::Overwrite => Some(Replaced::Overwritten),
+            ConflictStrategy::Trash => match trash::delete(&original_dest) {
+                Ok(()) => {
+                    info!("Moved existing file to trash: {}", original_dest.display());
+                    Some(Replaced::Trashed)
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to move existing file to trash, keeping both: {}: {}",
+                        original_dest.display(),
+                        e
+                    );
+                    Some(Replaced::TrashFailed(e.to_string()))
+                }
+            },
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    // ハードリンクは既存のパスには作成できない（置き換えない）ので、名前を選びながら作成する
+    if kind == OpKind::Hardlink {
+        let overwrite = replaced == Some(Replaced::Overwritten);
+        let final_dest =
+            hardlink_no_replace(source, &original_dest, filename, naming, retry, overwrite)?;
+        let was_renamed = final_dest != original_dest;
+        if was_renamed {
+            info!(
+                "File renamed to avoid duplicate: {} -> {}",
+                filename,
+                final_dest.file_name().unwrap_or_default().to_string_lossy()
+            );
+        }
+        debug!(
+            "Hardlinked file: {} -> {}",
+            source.display(),
+            final_dest.display()
+        );
+        return Ok(OpResult {
+            source: source.to_path_buf(),
+            destination: final_dest,
+            was_renamed,
+            replaced,
+            kind,
+            method: MoveMethod::Hardlink,
+        });
+    }
+
+    // 重複回避した移動先パスを生成する。並列に移動している場合は、空ファイルを
+    // 排他的に作成して予約する（他のスレッドが同じ名前を選ばないようにするため）
+    let reserved = Cell::new(false);
+    let final_dest = if replaced == Some(Replaced::Overwritten) {
+        original_dest.clone()
+    } else if naming.reserve {
+        generate_unique_path_limited(dest_dir, filename, naming, |p| match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(p)
+        {
+            Ok(_) => {
+                reserved.set(true);
+                false
+            }
+            Err(e) => e.kind() == io::ErrorKind::AlreadyExists,
+        })
+    } else {
+        generate_unique_path_limited(dest_dir, filename, naming, |p| p.symlink_metadata().is_ok())
+    };
     let was_renamed = final_dest != original_dest;
 
     if was_renamed {
@@ -186,16 +712,109 @@ pub fn move_file_with_dedup(source: &Path, dest_dir: &Path) -> Result<MoveResult
         );
     }
 
-    // 実際に移動
-    move_file(source, &final_dest)?;
+    // 実際に移動（またはコピー）。予約した空ファイルは上書きされる
+    let transferred = match kind {
+        OpKind::Move => move_file(source, &final_dest, retry),
+        _ => retry_transient(retry, || fs::copy(source, &final_dest))
+            .with_context(|| {
+                format!(
+                    "Failed to copy file from {} to {}",
+                    source.display(),
+                    final_dest.display()
+                )
+            })
+            .map(|_| {
+                debug!(
+                    "Copied file: {} -> {}",
+                    source.display(),
+                    final_dest.display()
+                );
+                MoveMethod::Copy
+            }),
+    };
+    let method = match transferred {
+        Ok(method) => method,
+        Err(e) => {
+            // 失敗した場合は予約した空ファイルを残さない
+            if reserved.get() {
+                let _ = fs::remove_file(&final_dest);
+            }
+            return Err(e);
+        }
+    };
 
-    Ok(MoveResult {
+    Ok(OpResult {
         source: source.to_path_buf(),
         destination: final_dest,
         was_renamed,
+        replaced,
+        kind,
+        method,
     })
 }
 
+/// 既存のファイルを置き換えずに、重複回避した名前でハードリンクを作成する
+///
+/// `fs::hard_link` は既にあるパスには作成できないため、作成に失敗した名前は使用済みとして
+/// 次の連番を試します。空ファイルで名前を予約する必要はありません。
+/// `overwrite` の場合は隠しファイル名で作成してから `rename` で `dest` を置き換えます。
+///
+/// # Returns
+/// 作成したリンクのパス
+fn hardlink_no_replace(
+    source: &Path,
+    dest: &Path,
+    filename: &str,
+    naming: UniqueNaming<'_>,
+    retry: RetryPolicy,
+    overwrite: bool,
+) -> Result<PathBuf> {
+    let dest_dir = dest.parent().unwrap_or(Path::new("."));
+    let link_error = RefCell::new(None);
+    let try_link = |p: &Path| match retry_transient(retry, || fs::hard_link(source, p)) {
+        Ok(()) => false,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => true,
+        Err(e) => {
+            *link_error.borrow_mut() = Some(e);
+            false
+        }
+    };
+    let linked = if overwrite {
+        generate_unique_path_limited(
+            dest_dir,
+            &format!(".{}.link", filename),
+            UniqueNaming::default(),
+            try_link,
+        )
+    } else {
+        generate_unique_path_limited(dest_dir, filename, naming, try_link)
+    };
+    if let Some(e) = link_error.into_inner() {
+        return Err(e).with_context(|| {
+            format!(
+                "Failed to create hardlink {} -> {}",
+                linked.display(),
+                source.display()
+            )
+        });
+    }
+    if !overwrite {
+        return Ok(linked);
+    }
+
+    if let Err(e) = retry_transient(retry, || fs::rename(&linked, dest)) {
+        let _ = fs::remove_file(&linked);
+        return Err(e).with_context(|| {
+            format!(
+                "Failed to replace {} with a hardlink to {}",
+                dest.display(),
+                source.display()
+            )
+        });
+    }
+    Ok(dest.to_path_buf())
+}
+
 /// パスからファイルの拡張子を取得する（小文字で返す）
 ///
 /// # Arguments
@@ -209,6 +828,183 @@ pub fn get_extension(path: &Path) -> Option<String> {
         .map(|ext| ext.to_lowercase())
 }
 
+/// 1行に1項目を記述したリストファイルを読み込む
+///
+/// 前後の空白は取り除き、空行と `#` で始まる行は無視します。
+///
+/// # Arguments
+/// * `path` - リストファイルのパス
+pub fn read_list_file(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read list file: {}", path.display()))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// 1行に1つずつ書かれたパスの一覧を読み込む
+///
+/// `find` などの出力をそのまま読めるよう、コメントは扱わず前後の空白も残します
+/// （行末の `\r` と空行だけを取り除きます）。
+pub fn read_path_list(reader: impl io::BufRead) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for line in reader.lines() {
+        let line = line.context("Failed to read file list")?;
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if !line.is_empty() {
+            paths.push(PathBuf::from(line));
+        }
+    }
+    Ok(paths)
+}
+
+/// ファイル内容のSHA-256ハッシュを16進文字列で取得する
+///
+/// 大きなファイルでもメモリを使い過ぎないよう、チャンク単位で読み込みます。
+///
+/// # Arguments
+/// * `path` - ファイルパス
+///
+/// # Returns
+/// 小文字16進表記のハッシュ文字列
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)
+        .with_context(|| format!("Failed to open file for hashing: {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("Failed to read file for hashing: {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// 2つのファイルの内容が同一かどうかを判定する
+///
+/// サイズが異なれば読み込まずに `false` を返し、同じ場合はハッシュで比較します。
+/// `b` が存在しない場合は `false` を返します。
+pub fn files_are_identical(a: &Path, b: &Path) -> Result<bool> {
+    let Ok(b_meta) = fs::metadata(b) else {
+        return Ok(false);
+    };
+    let a_meta =
+        fs::metadata(a).with_context(|| format!("Failed to read metadata: {}", a.display()))?;
+    if !a_meta.is_file() || !b_meta.is_file() || a_meta.len() != b_meta.len() {
+        return Ok(false);
+    }
+    Ok(hash_file(a)? == hash_file(b)?)
+}
+
+/// `dest` が既に `source` を `kind` で転送した結果になっているかを判定する
+///
+/// ハードリンクは同じ実体を指す場合、コピーは同じ実体か同じ内容の場合に `true` を返します。
+/// 元のファイルが残らない移動では常に `false` です。
+pub fn is_existing_transfer(source: &Path, dest: &Path, kind: OpKind) -> Result<bool> {
+    match kind {
+        OpKind::Move => Ok(false),
+        OpKind::Hardlink => Ok(same_file(source, dest)),
+        OpKind::Copy => Ok(same_file(source, dest) || files_are_identical(source, dest)?),
+    }
+}
+
+/// 2つのパスが同じ実体（inode）を指すかを判定する（判定できない場合は `false`）
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (a, b);
+        false
+    }
+}
+
+/// `root` 以下の空のディレクトリを深い階層から順に削除し、削除した数を返す
+///
+/// `root` 自体は空でも削除しません。シンボリックリンクと隠しディレクトリ（`.git` など）の
+/// 中には立ち入らず、それら自体も削除しません。中身が空になったディレクトリも削除されます。
+/// 途中のディレクトリを読めない・削除できない場合は警告して残りの削除を続け、
+/// エラーになるのは `root` 自体を読めない場合だけです。
+pub fn remove_empty_dirs(root: &Path) -> Result<usize> {
+    let mut removed = 0;
+    for entry in fs::read_dir(root)
+        .with_context(|| format!("Failed to read directory: {}", root.display()))?
+    {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                warn!(
+                    "Failed to read directory entry in {}: {}",
+                    root.display(),
+                    e
+                );
+                continue;
+            }
+        };
+        if is_symlink(&path) || !is_directory(&path) || is_hidden(&path) {
+            continue;
+        }
+        match remove_empty_dirs(&path) {
+            Ok(count) => removed += count,
+            Err(e) => {
+                warn!("Skipping folder while removing empty folders: {:#}", e);
+                continue;
+            }
+        }
+
+        let is_empty = match fs::read_dir(&path) {
+            Ok(mut entries) => entries.next().is_none(),
+            Err(e) => {
+                warn!("Failed to read directory {}: {}", path.display(), e);
+                false
+            }
+        };
+        if !is_empty {
+            continue;
+        }
+        match fs::remove_dir(&path) {
+            Ok(()) => {
+                debug!("Removed empty folder: {}", path.display());
+                removed += 1;
+            }
+            Err(e) => warn!("Failed to remove empty folder {}: {}", path.display(), e),
+        }
+    }
+    Ok(removed)
+}
+
+/// ファイルの先頭のバイト（マジックナンバー）から種類を推定し、対応する拡張子を返す
+///
+/// 判定できない場合や読み込めない場合は `None` を返します。
+pub fn sniff_extension(path: &Path) -> Option<&'static str> {
+    match infer::get_from_path(path) {
+        Ok(kind) => kind.map(|kind| kind.extension()),
+        Err(e) => {
+            debug!("Failed to read file header: {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
 /// ディレクトリかどうかを判定
 pub fn is_directory(path: &Path) -> bool {
     path.is_dir()
@@ -219,6 +1015,89 @@ pub fn is_file(path: &Path) -> bool {
     path.is_file()
 }
 
+/// 実行権限を持つファイルかどうかを判定（Unixのみ、その他のOSでは常に `false`）
+#[cfg(unix)]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// 実行権限を持つファイルかどうかを判定（Unixのみ、その他のOSでは常に `false`）
+#[cfg(not(unix))]
+pub fn is_executable(_path: &Path) -> bool {
+    false
+}
+
+/// 現在のユーザーがディレクトリにファイルを作成・削除できるかを判定
+///
+/// Unixでは `access(2)` で書き込みと検索の権限を確かめます（所有者やグループも考慮）。
+#[cfg(unix)]
+pub fn is_writable_dir(dir: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `path` はNUL終端された有効な文字列で、呼び出しの間は生存している
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
+/// 現在のユーザーがディレクトリにファイルを作成・削除できるかを判定
+///
+/// Unix以外では読み取り専用の属性だけを確かめます。
+#[cfg(not(unix))]
+pub fn is_writable_dir(dir: &Path) -> bool {
+    fs::metadata(dir).is_ok_and(|m| m.is_dir() && !m.permissions().readonly())
+}
+
+/// 他のプロセスが使用中（開いている・ロックしている）ためのI/Oエラーかどうかを判定
+///
+/// Windowsでは共有違反・ロック違反、Unixでは実行中のプログラム（`ETXTBSY`）を使用中とします。
+pub fn is_in_use_error(err: &io::Error) -> bool {
+    let in_use: &[i32] = if cfg!(windows) { &[32, 33] } else { &[26] };
+    err.raw_os_error()
+        .is_some_and(|code| in_use.contains(&code))
+}
+
+/// 他のプロセスが使用中のファイルかどうかを判定
+///
+/// Windowsでは共有なしで書き込み用に開けるかを確かめます。
+#[cfg(windows)]
+pub fn is_file_locked(path: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    match fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .share_mode(0)
+        .open(path)
+    {
+        Ok(_) => false,
+        Err(e) => is_in_use_error(&e),
+    }
+}
+
+/// 他のプロセスが使用中のファイルかどうかを判定
+///
+/// Unixでは開いているだけのファイルは検出できないため、排他ロック（`flock(2)`）を
+/// 取れるかで判定するベストエフォートの確認です。
+#[cfg(not(windows))]
+pub fn is_file_locked(path: &Path) -> bool {
+    use fs2::FileExt;
+
+    match fs::File::open(path) {
+        Ok(file) => matches!(
+            file.try_lock_exclusive(),
+            Err(e) if e.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+        ),
+        Err(e) => is_in_use_error(&e),
+    }
+}
+
 /// シンボリックリンクかどうかを判定
 pub fn is_symlink(path: &Path) -> bool {
     path.symlink_metadata()
@@ -226,9 +1105,174 @@ pub fn is_symlink(path: &Path) -> bool {
         .unwrap_or(false)
 }
 
+/// `link` に `original` を指すシンボリックリンクを作成する
+pub fn create_symlink(original: &Path, link: &Path) -> Result<()> {
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(original, link);
+    #[cfg(windows)]
+    let result = std::os::windows::fs::symlink_file(original, link);
+
+    result.with_context(|| {
+        format!(
+            "Failed to create symlink {} -> {}",
+            link.display(),
+            original.display()
+        )
+    })
+}
+
+/// ハードリンクの作成に使われた方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkMethod {
+    /// ハードリンク
+    Hardlink,
+    /// 別のファイルシステムのためコピーで代用
+    Copy,
+}
+
+/// 2つのパスが同じファイルシステム上にあるかを判定（判定できない場合は `None`）
+pub fn same_filesystem(a: &Path, b: &Path) -> Option<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let a = fs::metadata(a).ok()?;
+        let b = fs::metadata(b).ok()?;
+        Some(a.dev() == b.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (a, b);
+        None
+    }
+}
+
+/// `link` に `original` のハードリンクを作成する
+///
+/// `link` のディレクトリが別のファイルシステムにあるとハードリンクを作れないため、
+/// その場合はコピーで代用します。
+pub fn create_hardlink(original: &Path, link: &Path) -> Result<LinkMethod> {
+    let link_dir = link.parent().unwrap_or(Path::new("."));
+    if same_filesystem(original, link_dir) == Some(false) {
+        fs::copy(original, link).with_context(|| {
+            format!(
+                "Failed to copy {} -> {}",
+                original.display(),
+                link.display()
+            )
+        })?;
+        return Ok(LinkMethod::Copy);
+    }
+
+    fs::hard_link(original, link).with_context(|| {
+        format!(
+            "Failed to create hardlink {} -> {}",
+            link.display(),
+            original.display()
+        )
+    })?;
+    Ok(LinkMethod::Hardlink)
+}
+
+/// 文字列をフォルダ名として使えるように整える
+///
+/// パス区切りやWindowsで使えない文字、制御文字を `_` に置き換え、
+/// 前後の空白と末尾のドットを取り除きます。
+/// 結果が空、または `.` / `..` になる場合は `None` を返します。
+pub fn sanitize_folder_name(name: &str) -> Option<String> {
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let trimmed = replaced.trim().trim_end_matches('.').trim_end();
+
+    if trimmed.is_empty() || trimmed == "." || trimmed == ".." {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+/// 隠しファイル・隠しディレクトリかどうかを判定
+///
+/// `.` で始まる名前を隠しファイルとみなします。Windowsでは隠し属性も考慮します。
+pub fn is_hidden(path: &Path) -> bool {
+    let dotfile = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.'));
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        dotfile
+            || fs::metadata(path).is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+    }
+    #[cfg(not(windows))]
+    dotfile
+}
+
+/// 最終更新から `age` が経過していないかどうかを判定
+///
+/// 更新日時が未来の場合は `true`、取得できない場合は `false` を返します。
+pub fn is_modified_within(path: &Path, age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .map_or(true, |elapsed| elapsed < age)
+        })
+}
+
+/// 整理済みのファイルに付ける拡張属性の名前
+pub const SORTED_MARKER_ATTR: &str = "user.smart-sorter.sorted";
+
+/// ファイルに整理済みの印（拡張属性）を付ける
+///
+/// 印は移動を妨げないよう付けられる場合にだけ付けます。拡張属性に対応していない
+/// ファイルシステムやプラットフォームなどで付けられなかった場合は `false` を返します。
+pub fn mark_sorted(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        match xattr::set(path, SORTED_MARKER_ATTR, b"1") {
+            Ok(()) => true,
+            Err(e) => {
+                debug!("Failed to mark as sorted {}: {}", path.display(), e);
+                false
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// ファイルに整理済みの印（拡張属性）が付いているかを判定
+///
+/// 拡張属性を読み取れない場合は `false` を返します。
+pub fn is_marked_sorted(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        matches!(xattr::get(path, SORTED_MARKER_ATTR), Ok(Some(_)))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
     use std::fs::File;
     use tempfile::tempdir;
 
@@ -274,6 +1318,67 @@ mod tests {
         assert_eq!(result, dir.path().join("README_1"));
     }
 
+    #[test]
+    fn test_split_compound_extension() {
+        assert_eq!(
+            split_compound_extension("archive.tar.gz"),
+            ("archive", Some("tar.gz"))
+        );
+        assert_eq!(
+            split_compound_extension("Backup.TAR.XZ"),
+            ("Backup", Some("TAR.XZ"))
+        );
+        assert_eq!(split_compound_extension("notes.gz"), ("notes", Some("gz")));
+        assert_eq!(split_compound_extension("README"), ("README", None));
+        // 拡張子だけのファイル名は従来どおり
+        assert_eq!(split_compound_extension(".tar.gz"), (".tar", Some("gz")));
+
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("archive.tar.gz")).unwrap();
+        assert_eq!(
+            generate_unique_path(dir.path(), "archive.tar.gz"),
+            dir.path().join("archive_1.tar.gz")
+        );
+    }
+
+    #[test]
+    fn test_generate_unique_path_limited_truncates_stem() {
+        let dir = tempdir().unwrap();
+        // マルチバイト文字を含む長いファイル名
+        let filename = format!("{}.jpeg", "写真".repeat(40));
+        let max = 50;
+
+        let mut seen = HashSet::new();
+        for _ in 0..12 {
+            let naming = UniqueNaming {
+                max_bytes: Some(max),
+                template: None,
+                reserve: false,
+            };
+            let path = generate_unique_path_limited(dir.path(), &filename, naming, |p| p.exists());
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap();
+            assert!(name.len() <= max, "{} exceeds the limit", name);
+            assert!(name.ends_with(".jpeg"));
+            assert!(seen.insert(name.to_string()), "{} is not unique", name);
+            File::create(&path).unwrap();
+        }
+        assert!(seen.iter().any(|n| n.ends_with("_10.jpeg")));
+    }
+
+    #[test]
+    fn test_fit_file_name_on_char_boundary() {
+        assert_eq!(fit_file_name("あいう", "", Some("txt"), 11), "あい.txt");
+        assert_eq!(fit_file_name("あいう", "_1", Some("txt"), 11), "あ_1.txt");
+        assert_eq!(fit_file_name("short", "", None, 100), "short");
+        // 拡張子と連番だけで上限を超える場合は拡張子も切り詰め、連番は残す
+        assert_eq!(
+            fit_file_name("photo", "_1", Some("verylongext"), 8),
+            "_1.veryl"
+        );
+        assert_eq!(fit_file_name("photo", "_12", Some("txt"), 4), "p_12");
+        assert_eq!(fit_file_name("photo", "", Some("拡張子"), 5), "p.拡");
+    }
+
     #[test]
     fn test_get_extension() {
         assert_eq!(
@@ -304,6 +1409,22 @@ mod tests {
         ensure_directory(&new_dir).unwrap();
     }
 
+    #[test]
+    fn test_remove_empty_dirs() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Images").join("2024")).unwrap();
+        fs::create_dir_all(dir.path().join("Documents")).unwrap();
+        fs::write(dir.path().join("Documents").join("notes.txt"), "").unwrap();
+        fs::create_dir_all(dir.path().join(".git").join("refs")).unwrap();
+
+        // 中身が空になった親フォルダも削除し、対象ディレクトリ自体と隠しディレクトリは残す
+        assert_eq!(remove_empty_dirs(dir.path()).unwrap(), 2);
+        assert!(!dir.path().join("Images").exists());
+        assert!(dir.path().join("Documents").exists());
+        assert!(dir.path().join(".git").join("refs").exists());
+        assert_eq!(remove_empty_dirs(dir.path()).unwrap(), 0);
+    }
+
     #[test]
     fn test_move_file_basic() {
         let dir = tempdir().unwrap();
@@ -313,10 +1434,364 @@ mod tests {
         // ソースファイルを作成
         fs::write(&source, "test content").unwrap();
 
-        move_file(&source, &dest).unwrap();
+        let method = move_file(&source, &dest, RetryPolicy::NONE).unwrap();
+
+        assert_eq!(method, MoveMethod::Rename);
 
         assert!(!source.exists());
         assert!(dest.exists());
         assert_eq!(fs::read_to_string(&dest).unwrap(), "test content");
     }
+
+    #[test]
+    fn test_copy_file_with_dedup() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("report.pdf");
+        let dest_dir = dir.path().join("Documents");
+        fs::write(&source, "content").unwrap();
+        fs::create_dir(&dest_dir).unwrap();
+        fs::write(dest_dir.join("report.pdf"), "other").unwrap();
+
+        let result = copy_file_with_dedup_as(
+            &source,
+            &dest_dir,
+            "report.pdf",
+            UniqueNaming::default(),
+            RetryPolicy::NONE,
+            ConflictStrategy::Rename,
+        )
+        .unwrap();
+
+        assert_eq!(result.kind, OpKind::Copy);
+        assert!(result.was_renamed);
+        assert_eq!(result.destination, dest_dir.join("report_1.pdf"));
+        assert_eq!(fs::read_to_string(&result.destination).unwrap(), "content");
+        // 元のファイルは残る
+        assert!(source.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_file_with_dedup() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("report.pdf");
+        let dest_dir = dir.path().join("Documents");
+        fs::write(&source, "content").unwrap();
+        fs::create_dir(&dest_dir).unwrap();
+        fs::write(dest_dir.join("report.pdf"), "other").unwrap();
+
+        let result = hardlink_file_with_dedup_as(
+            &source,
+            &dest_dir,
+            "report.pdf",
+            UniqueNaming::default(),
+            RetryPolicy::NONE,
+            ConflictStrategy::Rename,
+        )
+        .unwrap();
+
+        assert_eq!(result.kind, OpKind::Hardlink);
+        assert_eq!(result.method, MoveMethod::Hardlink);
+        assert_eq!(result.destination, dest_dir.join("report_1.pdf"));
+        // 元のファイルと同じ実体を指す
+        assert_eq!(
+            fs::metadata(&source).unwrap().ino(),
+            fs::metadata(&result.destination).unwrap().ino()
+        );
+
+        // 上書きでは既存のファイルをリンクで置き換え、一時的な名前を残さない
+        let result = hardlink_file_with_dedup_as(
+            &source,
+            &dest_dir,
+            "report.pdf",
+            UniqueNaming::default(),
+            RetryPolicy::NONE,
+            ConflictStrategy::Overwrite,
+        )
+        .unwrap();
+        assert_eq!(result.destination, dest_dir.join("report.pdf"));
+        assert_eq!(result.replaced, Some(Replaced::Overwritten));
+        assert_eq!(
+            fs::metadata(&source).unwrap().ino(),
+            fs::metadata(dest_dir.join("report.pdf")).unwrap().ino()
+        );
+        assert_eq!(fs::read_dir(&dest_dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_resolve_conflict() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("source.txt");
+        let existing = dir.path().join("existing.txt");
+        fs::write(&source, "short").unwrap();
+        fs::write(&existing, "much longer content").unwrap();
+        let now = filetime::FileTime::now();
+        let hour_ago = filetime::FileTime::from_unix_time(now.unix_seconds() - 3600, 0);
+        filetime::set_file_mtime(&source, now).unwrap();
+        filetime::set_file_mtime(&existing, hour_ago).unwrap();
+
+        let resolve = |strategy| resolve_conflict(strategy, &source, &existing);
+        assert_eq!(
+            resolve(ConflictStrategy::KeepNewer),
+            ConflictStrategy::Overwrite
+        );
+        assert_eq!(
+            resolve(ConflictStrategy::KeepLarger),
+            ConflictStrategy::Skip
+        );
+        assert_eq!(resolve(ConflictStrategy::Trash), ConflictStrategy::Trash);
+        // 比較できない場合は両方残す
+        assert_eq!(
+            resolve_conflict(
+                ConflictStrategy::KeepNewer,
+                &source,
+                &dir.path().join("gone")
+            ),
+            ConflictStrategy::Rename
+        );
+    }
+
+    #[test]
+    fn test_concurrent_moves_get_unique_names() {
+        let dir = tempdir().unwrap();
+        let dest_dir = dir.path().join("Documents");
+        fs::create_dir(&dest_dir).unwrap();
+        let sources: Vec<PathBuf> = (0..16)
+            .map(|i| {
+                let sub = dir.path().join(format!("src{}", i));
+                fs::create_dir(&sub).unwrap();
+                let source = sub.join("report.pdf");
+                fs::write(&source, i.to_string()).unwrap();
+                source
+            })
+            .collect();
+
+        // 名前を予約すれば、同じ名前への移動を同時に行っても上書きせずにすべて別名になる
+        let naming = UniqueNaming {
+            reserve: true,
+            ..Default::default()
+        };
+        thread::scope(|scope| {
+            for source in &sources {
+                let dest_dir = &dest_dir;
+                scope.spawn(move || {
+                    move_file_with_dedup_as(
+                        source,
+                        dest_dir,
+                        "report.pdf",
+                        naming,
+                        RetryPolicy::NONE,
+                        ConflictStrategy::Rename,
+                    )
+                    .unwrap()
+                });
+            }
+        });
+
+        let mut contents: Vec<u32> = fs::read_dir(&dest_dir)
+            .unwrap()
+            .map(|e| {
+                fs::read_to_string(e.unwrap().path())
+                    .unwrap()
+                    .parse()
+                    .unwrap()
+            })
+            .collect();
+        contents.sort_unstable();
+        assert_eq!(contents, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_hash_file() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        let c = dir.path().join("c.txt");
+        fs::write(&a, "same content").unwrap();
+        fs::write(&b, "same content").unwrap();
+        fs::write(&c, "other content").unwrap();
+
+        assert_eq!(hash_file(&a).unwrap(), hash_file(&b).unwrap());
+        assert_ne!(hash_file(&a).unwrap(), hash_file(&c).unwrap());
+        assert_eq!(hash_file(&a).unwrap().len(), 64);
+
+        assert!(files_are_identical(&a, &b).unwrap());
+        assert!(!files_are_identical(&a, &c).unwrap());
+        assert!(!files_are_identical(&a, &dir.path().join("missing.txt")).unwrap());
+    }
+
+    #[test]
+    fn test_is_existing_transfer() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "same content").unwrap();
+        fs::write(&b, "same content").unwrap();
+
+        // 同じ内容のコピーは転送済み、別の実体なのでリンクとしては未転送
+        assert!(is_existing_transfer(&a, &b, OpKind::Copy).unwrap());
+        assert!(!is_existing_transfer(&a, &b, OpKind::Move).unwrap());
+        #[cfg(unix)]
+        {
+            assert!(!is_existing_transfer(&a, &b, OpKind::Hardlink).unwrap());
+            let link = dir.path().join("link.txt");
+            fs::hard_link(&a, &link).unwrap();
+            assert!(is_existing_transfer(&a, &link, OpKind::Hardlink).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_move_by_copy_preserves_metadata() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("photo.jpg");
+        let destination = dir.path().join("moved.jpg");
+        fs::write(&source, "jpg").unwrap();
+        let modified = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&source, modified).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&source, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        // 別のファイルシステムへの移動と同じ経路（コピー＆削除）で移動する
+        move_by_copy(&source, &destination, RetryPolicy::NONE).unwrap();
+
+        assert!(!source.exists());
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "jpg");
+        let metadata = fs::metadata(&destination).unwrap();
+        assert_eq!(
+            filetime::FileTime::from_last_modification_time(&metadata),
+            modified
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let script = dir.path().join("run");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        assert!(!is_executable(&script));
+
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(is_executable(&script));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_file_locked() {
+        use fs2::FileExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("report.pdf");
+        fs::write(&path, "pdf").unwrap();
+        assert!(!is_file_locked(&path));
+
+        let holder = fs::File::open(&path).unwrap();
+        holder.lock_exclusive().unwrap();
+        assert!(is_file_locked(&path));
+
+        holder.unlock().unwrap();
+        assert!(!is_file_locked(&path));
+    }
+
+    #[test]
+    fn test_read_list_file() {
+        let dir = tempdir().unwrap();
+        let list = dir.path().join("list.txt");
+        fs::write(&list, "# comment\nreport.pdf\n\n  photo.jpg  \n").unwrap();
+
+        assert_eq!(
+            read_list_file(&list).unwrap(),
+            vec!["report.pdf".to_string(), "photo.jpg".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_read_path_list() {
+        let list = "photo.jpg\r\n\n# not a comment.txt\n dir/notes.txt\n";
+        assert_eq!(
+            read_path_list(list.as_bytes()).unwrap(),
+            vec![
+                PathBuf::from("photo.jpg"),
+                PathBuf::from("# not a comment.txt"),
+                PathBuf::from(" dir/notes.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_retry_transient_succeeds_within_budget() {
+        let policy = RetryPolicy {
+            retries: 3,
+            delay_ms: 1,
+        };
+        let mut attempts = 0;
+
+        // 2回一時的なエラーで失敗し、3回目で成功する操作
+        let result = retry_transient(policy, || {
+            attempts += 1;
+            if attempts <= 2 {
+                Err(io::Error::from(io::ErrorKind::WouldBlock))
+            } else {
+                Ok(attempts)
+            }
+        });
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_retry_transient_gives_up() {
+        let policy = RetryPolicy {
+            retries: 1,
+            delay_ms: 1,
+        };
+        let mut attempts = 0;
+        let result: io::Result<()> = retry_transient(policy, || {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::Interrupted))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn test_retry_skips_permanent_errors() {
+        let policy = RetryPolicy {
+            retries: 5,
+            delay_ms: 1,
+        };
+        let mut attempts = 0;
+        let result: io::Result<()> = retry_transient(policy, || {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_sanitize_folder_name() {
+        assert_eq!(
+            sanitize_folder_name("ProjectX"),
+            Some("ProjectX".to_string())
+        );
+        assert_eq!(sanitize_folder_name("a/b:c"), Some("a_b_c".to_string()));
+        assert_eq!(sanitize_folder_name(" notes. "), Some("notes".to_string()));
+        assert_eq!(sanitize_folder_name(".."), None);
+        assert_eq!(sanitize_folder_name("  "), None);
+    }
 }
@@ -0,0 +1,201 @@
+//! IPCモジュール（Unixのみ）
+//!
+//! Unixドメインソケットで接続してきたクライアントに、進行状況と結果を
+//! JSON Lines 形式で送信します。GUIや常駐サービスが標準出力を解析せずに
+//! 実行を監視するためのものです。
+
+use crate::config::Category;
+use crate::observer::{SortEvent, SortObserver};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+/// 1回の送信を待つ上限（読まないクライアントで処理が止まらないように）
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// ソケットに接続したクライアントへイベントを送信するオブザーバー
+pub struct IpcObserver {
+    /// 待ち受け中のソケット
+    listener: UnixListener,
+    /// ソケットファイルのパス（終了時に削除する）
+    path: PathBuf,
+    /// 接続中のクライアント
+    clients: Vec<UnixStream>,
+    /// 上書きされたカテゴリのフォルダ名（結果の件数のキーに使う）
+    folder_names: HashMap<Category, String>,
+}
+
+impl IpcObserver {
+    /// ソケットを作成して待ち受けを開始する
+    ///
+    /// 同じパスに以前のソケットが残っている場合は削除してから作成します。
+    pub fn bind(path: &Path) -> Result<Self> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                anyhow::bail!(
+                    "IPC socket path exists and is not a socket: {}",
+                    path.display()
+                );
+            }
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to remove stale socket: {}", path.display()))?;
+        }
+
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("Failed to bind IPC socket: {}", path.display()))?;
+        listener
+            .set_nonblocking(true)
+            .context("Failed to configure IPC socket")?;
+
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+            clients: Vec::new(),
+            folder_names: HashMap::new(),
+        })
+    }
+
+    /// 結果のカテゴリごとの件数を、上書きされたフォルダ名をキーにして送る
+    pub fn with_folder_names(mut self, folder_names: HashMap<Category, String>) -> Self {
+        self.folder_names = folder_names;
+        self
+    }
+
+    /// 接続待ちのクライアントを受け入れる
+    fn accept_pending(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let configured = stream.set_nonblocking(false).is_ok()
+                        && stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok();
+                    if configured {
+                        debug!("IPC client connected");
+                        self.clients.push(stream);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    debug!("Failed to accept IPC client: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// イベントを1行のJSONに変換する（結果の件数はフォルダ名をキーにする）
+    fn serialize(&self, event: &SortEvent) -> Result<String> {
+        let mut value = serde_json::to_value(event)?;
+        if let SortEvent::Finished { summary } = event {
+            value["summary"] = summary.to_json_value(&self.folder_names)?;
+        }
+        Ok(serde_json::to_string(&value)?)
+    }
+}
+
+impl SortObserver for IpcObserver {
+    fn on_event(&mut self, event: &SortEvent) {
+        self.accept_pending();
+
+        let mut line = match self.serialize(event) {
+            Ok(line) => line,
+            Err(e) => {
+                debug!("Failed to serialize IPC event: {:#}", e);
+                return;
+            }
+        };
+        line.push('\n');
+
+        // 切断されたクライアントと、読まずに送信が時間切れになったクライアントは以降の送信対象から外す
+        self.clients
+            .retain_mut(|client| match client.write_all(line.as_bytes()) {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!("IPC client disconnected: {}", e);
+                    false
+                }
+            });
+    }
+}
+
+impl Drop for IpcObserver {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sorter::{Sorter, SorterConfig};
+    use std::io::{BufRead, BufReader};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_client_receives_summary() {
+        let dir = tempdir().unwrap();
+        let socket_dir = tempdir().unwrap();
+        let socket = socket_dir.path().join("sorter.sock");
+        fs::write(dir.path().join("notes.txt"), "x").unwrap();
+
+        let folder_names = HashMap::from([(Category::Documents, "書類".to_string())]);
+        let observer = IpcObserver::bind(&socket)
+            .unwrap()
+            .with_folder_names(folder_names.clone());
+        let client = UnixStream::connect(&socket).unwrap();
+        // 途中で切断されるクライアントがいても処理は続く
+        drop(UnixStream::connect(&socket).unwrap());
+
+        let sorter = Sorter::new(SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            folder_names,
+            ..Default::default()
+        })
+        .with_observer(Box::new(observer));
+        sorter.run().unwrap();
+        drop(sorter);
+
+        let events: Vec<serde_json::Value> = BufReader::new(client)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        let kinds: Vec<&str> = events
+            .iter()
+            .map(|e| e["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(kinds, vec!["started", "moved", "finished"]);
+
+        let summary = &events[2]["summary"];
+        assert_eq!(summary["moved_files"], 1);
+        assert_eq!(summary["category_counts"]["書類"], 1);
+        assert!(!socket.exists());
+    }
+
+    #[test]
+    fn test_client_that_never_reads_is_dropped() {
+        let socket_dir = tempdir().unwrap();
+        let socket = socket_dir.path().join("sorter.sock");
+        let mut observer = IpcObserver::bind(&socket).unwrap();
+        let _client = UnixStream::connect(&socket).unwrap();
+
+        // 送信バッファが埋まっても止まらず、読まないクライアントを外す
+        let event = SortEvent::Failed {
+            source: PathBuf::from("x".repeat(4096)),
+            error: "error".to_string(),
+        };
+        observer.on_event(&event);
+        assert_eq!(observer.clients.len(), 1);
+        for _ in 0..10_000 {
+            if observer.clients.is_empty() {
+                break;
+            }
+            observer.on_event(&event);
+        }
+        assert!(observer.clients.is_empty());
+    }
+}
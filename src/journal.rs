@@ -0,0 +1,178 @@
+//! ジャーナルモジュール
+//!
+//! 実行中に行った移動を記録し、対象ディレクトリにJSONとして書き出します。
+//! 書き出したジャーナルは `--undo` で移動を取り消す際に読み込まれます。
+
+use crate::output::{say, Reporter};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 対象ディレクトリに書き出すジャーナルのファイル名
+pub const JOURNAL_FILE_NAME: &str = ".smart-sorter-journal.json";
+
+/// 1件の移動の記録
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// 移動元のパス
+    pub source: PathBuf,
+    /// 移動先のパス
+    pub destination: PathBuf,
+}
+
+/// 1回の実行で行った移動の記録
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Journal {
+    /// 実行した順の移動記録
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// 移動を記録する
+    ///
+    /// 別の作業ディレクトリから取り消せるよう、相対パスは絶対パスにして記録します。
+    pub fn record(&mut self, source: &Path, destination: &Path) {
+        self.entries.push(JournalEntry {
+            source: absolute(source),
+            destination: absolute(destination),
+        });
+    }
+
+    /// 記録済みの移動先がさらに移動された場合に、その移動先を更新する
+    pub fn relocate(&mut self, from: &Path, to: &Path) {
+        let from = absolute(from);
+        for entry in self.entries.iter_mut().filter(|e| e.destination == from) {
+            entry.destination = absolute(to);
+        }
+    }
+
+    /// 対象ディレクトリ内のジャーナルファイルのパス
+    pub fn path_in(target_dir: &Path) -> PathBuf {
+        target_dir.join(JOURNAL_FILE_NAME)
+    }
+
+    /// 対象ディレクトリにジャーナルを書き出す（既存のジャーナルは上書き）
+    ///
+    /// # Returns
+    /// 書き出したファイルのパス
+    pub fn save(&self, target_dir: &Path) -> Result<PathBuf> {
+        let path = Self::path_in(target_dir);
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize journal")?;
+        fs::write(&path, json)
+            .with_context(|| format!("Failed to write journal: {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// 対象ディレクトリのジャーナルを読み込む（なければ `None`）
+    pub fn load(target_dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path_in(target_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read journal: {}", path.display()))?;
+        let journal = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse journal: {}", path.display()))?;
+        Ok(Some(journal))
+    }
+
+    /// 対象ディレクトリのジャーナルを削除する
+    pub fn remove(target_dir: &Path) -> Result<()> {
+        let path = Self::path_in(target_dir);
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove journal: {}", path.display()))
+    }
+}
+
+/// 相対パスを現在の作業ディレクトリからの絶対パスにする
+pub fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// ジャーナルによる移動の取り消しの結果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UndoReport {
+    /// 元の場所に戻したファイル数
+    pub reverted: usize,
+    /// 元の場所が別のファイルで埋まっていたため、連番を付けて戻したファイル数
+    pub renamed: usize,
+    /// 移動先から既になくなっていたため、戻せなかったファイル（移動先のパス）
+    pub missing: Vec<PathBuf>,
+    /// 戻す際にエラーになったファイル数
+    pub errors: usize,
+}
+
+impl UndoReport {
+    /// 取り消しの結果を表示
+    pub fn print(&self, dry_run: bool, reporter: &Reporter) {
+        say!(reporter);
+        if dry_run {
+            say!(reporter, "{}", "=== Dry Run Undo Summary ===".cyan().bold());
+            say!(
+                reporter,
+                "Moves to be reverted: {}",
+                self.reverted.to_string().cyan()
+            );
+        } else {
+            say!(reporter, "{}", "=== Undo Summary ===".green().bold());
+            say!(
+                reporter,
+                "Moves reverted: {}",
+                self.reverted.to_string().green()
+            );
+        }
+        if self.renamed > 0 {
+            say!(
+                reporter,
+                "Restored under a new name (original path taken): {}",
+                self.renamed.to_string().yellow()
+            );
+        }
+        if !self.missing.is_empty() {
+            say!(
+                reporter,
+                "Missing (no longer at destination): {}",
+                self.missing.len().to_string().yellow()
+            );
+            for path in &self.missing {
+                say!(reporter, "  {}", path.display());
+            }
+        }
+        if self.errors > 0 {
+            say!(reporter, "Errors: {}", self.errors.to_string().red());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_relocate_and_save() {
+        let dir = tempdir().unwrap();
+        let mut journal = Journal::default();
+        journal.record(Path::new("/a/x.txt"), Path::new("/a/Documents/x.txt"));
+        journal.relocate(Path::new("/a/Documents/x.txt"), Path::new("/a/x_1.txt"));
+
+        let path = journal.save(dir.path()).unwrap();
+        assert_eq!(path, dir.path().join(JOURNAL_FILE_NAME));
+
+        let loaded: Journal = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(loaded, journal);
+        assert_eq!(loaded.entries[0].destination, PathBuf::from("/a/x_1.txt"));
+        assert_eq!(Journal::load(dir.path()).unwrap(), Some(journal));
+
+        Journal::remove(dir.path()).unwrap();
+        assert_eq!(Journal::load(dir.path()).unwrap(), None);
+    }
+}
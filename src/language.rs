@@ -0,0 +1,55 @@
+//! 文書の言語判定モジュール
+//!
+//! テキストファイルの先頭部分だけを読み、主な言語を判定します。
+//! バイナリのファイルや判定の確度が低い場合は言語なしとして扱います。
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// 言語判定に読み込む先頭部分のバイト数
+pub const LANGUAGE_SAMPLE_BYTES: u64 = 8 * 1024;
+
+/// テキストファイルの主な言語の英語名（`English`・`French` など）
+///
+/// 読み込めない・テキストでない・確度が低い場合は `None` を返します。
+pub fn detect_text_language(path: &Path) -> Option<&'static str> {
+    let mut sample = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(LANGUAGE_SAMPLE_BYTES)
+        .read_to_end(&mut sample)
+        .ok()?;
+    let text = sample_text(&sample)?;
+    let info = whatlang::detect(text)?;
+    info.is_reliable().then(|| info.lang().eng_name())
+}
+
+/// 先頭部分をテキストとして解釈する（バイナリなら `None`）
+///
+/// 末尾で途切れたマルチバイト文字は切り捨てます。
+fn sample_text(sample: &[u8]) -> Option<&str> {
+    if sample.contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(text) => Some(text),
+        // 途中に不正なバイトがある場合はバイナリとみなす
+        Err(e) if e.error_len().is_some() => None,
+        Err(e) => std::str::from_utf8(&sample[..e.valid_up_to()]).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_text() {
+        assert_eq!(sample_text("héllo".as_bytes()), Some("héllo"));
+        // 途切れたマルチバイト文字は切り捨てる
+        assert_eq!(sample_text(&"hé".as_bytes()[..2]), Some("h"));
+        assert_eq!(sample_text(b"PK\x03\x04\x00\x00"), None);
+        assert_eq!(sample_text(b"\xff\xfeabc"), None);
+    }
+}
@@ -0,0 +1,62 @@
+//! smart-sorter ライブラリ
+//!
+//! ファイルを拡張子に基づいてカテゴリフォルダに振り分ける処理を、CLI以外の
+//! アプリケーション（GUIなど）から使うためのライブラリです。CLI（`smart-sorter` バイナリ）も
+//! このライブラリの上に作られています。
+//!
+//! 移動の計画だけを取得する場合は [`Sorter::plan`] を、実際に整理する場合は
+//! [`Sorter::run`] を使います。実行中の表示は既定で標準出力に出るため、表示が不要な場合は
+//! [`Sorter::with_reporter`] に [`output::Reporter::silent`] を渡して止めるか
+//! [`output::Reporter::capture`] で行ごとに受け取り、進み具合は [`observer::SortObserver`] で受け取ります。
+//!
+//! ```no_run
+//! use smart_sorter::{output::Reporter, Sorter, SorterConfig};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let config = SorterConfig {
+//!     target_dir: "/home/me/Downloads".into(),
+//!     skip_hidden: true,
+//!     ..Default::default()
+//! };
+//! let sorter = Sorter::new(config).with_reporter(Reporter::silent());
+//! for plan in sorter.plan()? {
+//!     println!("{} -> {}", plan.source.display(), plan.destination.display());
+//! }
+//! let stats = sorter.run()?;
+//! println!("{} files moved", stats.moved_files);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod archive;
+pub mod config;
+#[cfg(feature = "heic")]
+pub mod convert;
+pub mod date;
+pub mod dedup;
+pub mod file_ops;
+#[cfg(unix)]
+pub mod ipc;
+pub mod journal;
+pub mod language;
+pub mod lifetime;
+#[cfg(unix)]
+pub mod mime;
+pub mod observer;
+pub mod oplog;
+pub mod output;
+pub mod preflight;
+pub mod prompt;
+pub mod rule;
+pub mod schema;
+pub mod size;
+pub mod sorter;
+pub mod stability;
+pub mod sync;
+pub mod template;
+pub mod tree;
+pub mod verify;
+pub mod watch;
+
+pub use config::Category;
+pub use sorter::{FilePlan, SortStats, Sorter, SorterConfig};
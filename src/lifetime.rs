@@ -0,0 +1,284 @@
+//! 累計統計モジュール
+//!
+//! 実際にファイルを移動した実行ごとの統計を設定ディレクトリのJSONファイルに積み上げ、
+//! これまでに整理したファイルの累計を保持します。
+//! 同時に複数の実行が更新してもよいよう、読み書きはファイルロックの下で行います。
+
+use crate::config::Category;
+use crate::output::{say, Reporter};
+use crate::sorter::SortStats;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// 累計統計を保存するファイル名
+pub const LIFETIME_STATS_FILE_NAME: &str = "lifetime-stats.json";
+
+/// これまでの実行の累計
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifetimeStats {
+    /// 記録された実行回数
+    pub runs: usize,
+    /// 処理対象になったファイル数
+    pub total_files: usize,
+    /// 移動したファイル数
+    pub moved_files: usize,
+    /// 重複回避でリネームされたファイル数
+    pub renamed_files: usize,
+    /// スキップされたファイル数
+    pub skipped_files: usize,
+    /// エラー数
+    pub error_count: usize,
+    /// カテゴリフォルダ名ごとの移動したファイル数
+    pub category_counts: BTreeMap<String, usize>,
+}
+
+impl LifetimeStats {
+    /// 1回の実行の統計を累計に加える（カテゴリは `folder_names` で上書きされたフォルダ名で数える）
+    pub fn merge(&mut self, stats: &SortStats, folder_names: &HashMap<Category, String>) {
+        self.runs += 1;
+        self.total_files += stats.total_files;
+        self.moved_files += stats.moved_files;
+        self.renamed_files += stats.renamed_files;
+        self.skipped_files += stats.skipped_files;
+        self.error_count += stats.error_count;
+        for (category, count) in &stats.category_counts {
+            *self
+                .category_counts
+                .entry(category.folder_name_in(folder_names).to_string())
+                .or_insert(0) += count;
+        }
+    }
+
+    /// 累計を表示
+    pub fn print(&self, reporter: &Reporter) {
+        say!(reporter);
+        say!(reporter, "{}", "=== Lifetime Stats ===".green().bold());
+        say!(
+            reporter,
+            "Runs recorded: {}",
+            self.runs.to_string().yellow()
+        );
+        say!(
+            reporter,
+            "Total files found: {}",
+            self.total_files.to_string().yellow()
+        );
+        say!(
+            reporter,
+            "Files moved: {}",
+            self.moved_files.to_string().green()
+        );
+        if self.renamed_files > 0 {
+            say!(
+                reporter,
+                "Files renamed (due to conflicts): {}",
+                self.renamed_files.to_string().yellow()
+            );
+        }
+        if self.skipped_files > 0 {
+            say!(
+                reporter,
+                "Files skipped: {}",
+                self.skipped_files.to_string().yellow()
+            );
+        }
+        if self.error_count > 0 {
+            say!(reporter, "Errors: {}", self.error_count.to_string().red());
+        }
+
+        say!(reporter);
+        say!(reporter, "{}", "Category breakdown:".bold());
+        // 組み込みカテゴリを表示順に並べ、それ以外（設定変更前の名前など）は後ろに続ける
+        let known: Vec<&str> = Category::all().iter().map(|c| c.folder_name()).collect();
+        let ordered = known
+            .iter()
+            .filter_map(|name| self.category_counts.get_key_value(*name))
+            .chain(
+                self.category_counts
+                    .iter()
+                    .filter(|(name, _)| !known.contains(&name.as_str())),
+            );
+        for (name, count) in ordered {
+            if *count > 0 {
+                say!(reporter, "  {}: {}", name, count);
+            }
+        }
+    }
+}
+
+/// 累計統計を保存するファイル
+#[derive(Debug, Clone)]
+pub struct LifetimeStore {
+    path: PathBuf,
+}
+
+impl LifetimeStore {
+    /// 指定したファイルを累計統計の保存先にする
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// 既定の保存先（`$XDG_CONFIG_HOME/smart-sorter/` または `~/.config/smart-sorter/`）
+    ///
+    /// ホームディレクトリが分からない場合は `None` を返します。
+    pub fn default_path() -> Option<PathBuf> {
+        let non_empty = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+        let config_dir = non_empty("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                non_empty("HOME")
+                    .or_else(|| non_empty("USERPROFILE"))
+                    .map(|home| PathBuf::from(home).join(".config"))
+            })?;
+        Some(
+            config_dir
+                .join("smart-sorter")
+                .join(LIFETIME_STATS_FILE_NAME),
+        )
+    }
+
+    /// 累計を読み込む（まだ記録がなければ空の累計）
+    pub fn load(&self) -> Result<LifetimeStats> {
+        if !self.path.exists() {
+            return Ok(LifetimeStats::default());
+        }
+        let mut file = File::open(&self.path)
+            .with_context(|| format!("Failed to open lifetime stats: {}", self.path.display()))?;
+        FileExt::lock_shared(&file)
+            .with_context(|| format!("Failed to lock lifetime stats: {}", self.path.display()))?;
+        let stats = self.read_from(&mut file);
+        FileExt::unlock(&file)
+            .with_context(|| format!("Failed to unlock lifetime stats: {}", self.path.display()))?;
+        stats
+    }
+
+    /// 1回の実行の統計を累計に加えて保存する
+    ///
+    /// 読み込みから書き込みまで排他ロックを保持するため、
+    /// 同時に実行された別のプロセスの更新が失われることはありません。
+    ///
+    /// # Returns
+    /// 更新後の累計
+    pub fn record(
+        &self,
+        stats: &SortStats,
+        folder_names: &HashMap<Category, String>,
+    ) -> Result<LifetimeStats> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!(
+                    "Failed to create lifetime stats directory: {}",
+                    parent.display()
+                )
+            })?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open lifetime stats: {}", self.path.display()))?;
+        FileExt::lock_exclusive(&file)
+            .with_context(|| format!("Failed to lock lifetime stats: {}", self.path.display()))?;
+
+        let result = self.read_from(&mut file).and_then(|mut lifetime| {
+            lifetime.merge(stats, folder_names);
+            self.write_to(&mut file, &lifetime)?;
+            Ok(lifetime)
+        });
+
+        FileExt::unlock(&file)
+            .with_context(|| format!("Failed to unlock lifetime stats: {}", self.path.display()))?;
+        result
+    }
+
+    /// ロック済みのファイルから累計を読み込む（空のファイルは空の累計）
+    fn read_from(&self, file: &mut File) -> Result<LifetimeStats> {
+        let mut content = String::new();
+        file.read_to_string(&mut content)
+            .with_context(|| format!("Failed to read lifetime stats: {}", self.path.display()))?;
+        if content.trim().is_empty() {
+            return Ok(LifetimeStats::default());
+        }
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse lifetime stats: {}", self.path.display()))
+    }
+
+    /// ロック済みのファイルの内容を累計で置き換える
+    fn write_to(&self, file: &mut File, lifetime: &LifetimeStats) -> Result<()> {
+        let json =
+            serde_json::to_string_pretty(lifetime).context("Failed to serialize lifetime stats")?;
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(json.as_bytes()))
+            .and_then(|_| file.sync_all())
+            .with_context(|| format!("Failed to write lifetime stats: {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
+
+    fn run_stats(moved: usize, images: usize) -> SortStats {
+        SortStats {
+            total_files: moved,
+            moved_files: moved,
+            category_counts: HashMap::from([
+                (Category::Images, images),
+                (Category::Documents, moved - images),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_accumulates_runs() {
+        let dir = tempdir().unwrap();
+        let store = LifetimeStore::new(dir.path().join("nested").join(LIFETIME_STATS_FILE_NAME));
+        assert_eq!(store.load().unwrap(), LifetimeStats::default());
+
+        store.record(&run_stats(3, 1), &HashMap::new()).unwrap();
+        let lifetime = store.record(&run_stats(5, 4), &HashMap::new()).unwrap();
+
+        assert_eq!(store.load().unwrap(), lifetime);
+        assert_eq!(lifetime.runs, 2);
+        assert_eq!(lifetime.moved_files, 8);
+        assert_eq!(lifetime.category_counts["Images"], 5);
+        assert_eq!(lifetime.category_counts["Documents"], 3);
+    }
+
+    #[test]
+    fn test_record_uses_folder_names() {
+        let dir = tempdir().unwrap();
+        let store = LifetimeStore::new(dir.path().join(LIFETIME_STATS_FILE_NAME));
+        let folder_names = HashMap::from([(Category::Images, "Photos".to_string())]);
+
+        let lifetime = store.record(&run_stats(3, 2), &folder_names).unwrap();
+
+        assert_eq!(lifetime.category_counts["Photos"], 2);
+        assert!(!lifetime.category_counts.contains_key("Images"));
+    }
+
+    #[test]
+    fn test_print_goes_through_reporter() {
+        let mut lifetime = LifetimeStats::default();
+        lifetime.merge(&run_stats(3, 2), &HashMap::new());
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        lifetime.print(&Reporter::capture(move |line| {
+            sink.lock().unwrap().push(line.to_string())
+        }));
+        assert!(lines.lock().unwrap().contains(&"  Images: 2".to_string()));
+    }
+}
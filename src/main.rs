@@ -9,46 +9,369 @@
 //! - 再帰処理: サブディレクトリ内も探索可能
 
 mod cli;
-mod config;
-mod file_ops;
-mod sorter;
 
-use anyhow::Result;
-use cli::Args;
+use smart_sorter::{config, file_ops, lifetime, output, rule, schema, sorter, stability};
+#[cfg(unix)]
+use smart_sorter::{ipc, mime};
+
+use anyhow::{Context, Result};
+use cli::{Args, DumpFormat};
 use colored::Colorize;
+use lifetime::LifetimeStore;
+use output::{detail, OutputFormat, ProgressAwareWriter, Reporter};
 use sorter::{Sorter, SorterConfig};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufReader, IsTerminal};
+use std::path::Path;
 use tracing::Level;
 use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::EnvFilter;
 
 fn main() -> Result<()> {
     // CLI引数をパース
     let args = Args::parse_args();
 
+    // 色付けは --no-color・NO_COLOR・ターミナル以外への出力では行わない
+    if !use_color(
+        args.no_color,
+        std::env::var_os("NO_COLOR").as_deref(),
+        std::io::stdout().is_terminal(),
+    ) {
+        colored::control::set_override(false);
+    }
+
     // ロギングを初期化
-    init_logging(args.verbose);
+    // JSON出力の場合は人向けの表示を抑止し、ログは標準エラー出力に出す
+    // --quiet の場合はファイルごとの表示を抑止し、警告とエラーだけを標準エラー出力に出す
+    let json_output = args.format == OutputFormat::Json;
+    let reporter = if json_output {
+        Reporter::silent()
+    } else {
+        Reporter::stdout().with_detail(!args.quiet)
+    };
+    let level = log_level(args.quiet, args.verbose, args.check);
+    init_logging(level, json_output || args.quiet);
 
-    // バナー表示
-    print_banner();
+    if let (Some(min), Some(max)) = (args.min_size, args.max_size) {
+        if min > max {
+            anyhow::bail!(
+                "--min-size ({} bytes) is larger than --max-size ({} bytes)",
+                min,
+                max
+            );
+        }
+    }
+
+    if let (Some(older), Some(newer)) = (args.older_than, args.newer_than) {
+        if newer >= older {
+            anyhow::bail!("The --newer-than cutoff must be earlier than the --older-than cutoff");
+        }
+    }
+
+    // 標準入力から一覧を読むと、移動前の確認に答えられない
+    let files_from_stdin = args.files_from.as_deref() == Some(Path::new("-"));
+    if files_from_stdin && !args.yes && !args.dry_run {
+        anyhow::bail!("--files-from - reads the file list from stdin; add --yes or --dry-run");
+    }
+
+    // 設定ファイルを読み込み（環境変数 → --config の順に重ねる）
+    let layered = config::load_layered_config(&config::config_layers(&args.config))?;
+
+    // 2つ目以降の対象ディレクトリは通常の整理でのみ扱える
+    let mut target_dirs = args.target_dirs.into_iter();
+    let target_dir = target_dirs.next().unwrap_or_default();
+    let more_target_dirs: Vec<_> = target_dirs.collect();
+    let sorts_normally = !(args.check
+        || args.report_name_collisions
+        || args.fragmentation
+        || args.extension_report
+        || args.stats_only
+        || args.watch
+        || args.unflatten
+        || args.flatten
+        || args.undo
+        || args.apply_plan.is_some());
+    if !more_target_dirs.is_empty() && !sorts_normally {
+        anyhow::bail!("Multiple target directories are only supported when sorting");
+    }
+    if !more_target_dirs.is_empty() && args.files_from.is_some() {
+        anyhow::bail!("--files-from takes a single target directory");
+    }
+
+    // 振り分けルールは設定ファイルで定義されたカテゴリも振り分け先にできる
+    let custom_categories = layered.custom_categories()?;
+    let rules = args
+        .rule
+        .iter()
+        .map(|rule| rule::Rule::parse_with_custom(rule, &custom_categories))
+        .collect::<Result<Vec<_>>>()?;
 
     // ソーター設定を作成
-    let config = SorterConfig {
-        target_dir: args.target_dir,
-        dry_run: args.dry_run,
+    let mut config = SorterConfig {
+        target_dir,
+        more_target_dirs,
+        dry_run: args.dry_run || args.check || args.stats_only,
         recursive: args.recursive,
+        max_depth: args.max_depth,
+        preserve_tree: args.preserve_tree,
+        subfolder_by_ext: args.subfolder_by_ext,
+        others_by_ext: args.others_by_ext,
+        follow_symlinks: args.follow_symlinks,
+        min_size: args.min_size,
+        max_size: args.max_size,
+        modified_before: args.older_than,
+        modified_after: args.newer_than,
+        exclude: args.exclude,
+        include: args.include,
+        match_path: args.match_path,
+        collapse_singletons: args.collapse_singletons,
+        prune_empty: args.prune_empty,
+        rename_template: args.rename_template,
+        dest_template: args.dest_template,
+        batch_size: args.batch_folders.map(|n| n.get()),
+        merge_content_duplicates: args.merge_content_duplicates,
+        dedupe_first: args.dedupe_first,
+        dedupe_delete: args.dedupe_delete,
+        dedup_window: args.dedup_window.map(|n| n.get()),
+        dedup_content: args.dedup_content,
+        archive_output: args.archive_output,
+        archive_delete_originals: args.delete_originals,
+        symlink_farm: args.symlink_farm,
+        hardlink_view: args.hardlink_view,
+        copy: args.copy,
+        hardlink: args.hardlink,
+        // 実際に移動した場合は常に記録し、--undo で取り消せるようにする
+        write_journal: true,
+        sync_into: args.sync_into,
+        output_dir: args.output,
+        log_file: args.log_file,
+        progress: !args.quiet && !args.no_progress,
+        export_plan: args.export_plan,
+        max_filename_bytes: args.max_filename_bytes.map(usize::from),
+        dedup_template: args.dedup_template,
+        detect_executables: args.detect_executables.then_some(args.executables_category),
+        columns: args.columns,
+        tree: args.tree,
+        files_from: match &args.files_from {
+            Some(_) if files_from_stdin => Some(file_ops::read_path_list(io::stdin().lock())?),
+            Some(path) => {
+                let file = File::open(path)
+                    .with_context(|| format!("Failed to read file list: {}", path.display()))?;
+                Some(file_ops::read_path_list(BufReader::new(file))?)
+            }
+            None => None,
+        },
+        assume_existing: match &args.assume_existing {
+            Some(path) => file_ops::read_list_file(path)?.into_iter().collect(),
+            None => Default::default(),
+        },
+        code_by_language: args.code_by_language,
+        doc_by_language: args.doc_by_language,
+        #[cfg(feature = "heic")]
+        convert_heic: args.convert_heic,
+        #[cfg(feature = "heic")]
+        keep_heic: args.keep_heic,
+        retry: file_ops::RetryPolicy {
+            retries: args.retries,
+            delay_ms: args.retry_delay,
+        },
+        jobs: usize::from(args.jobs),
+        atomic: args.atomic,
+        on_conflict: args.on_conflict,
+        handle_downloads: args.handle_downloads,
+        fragment_extensions: if args.fragment_ext.is_empty() {
+            config::DEFAULT_FRAGMENT_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect()
+        } else {
+            args.fragment_ext
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect()
+        },
+        date_from_name: args.date_from_name,
+        by_date: args.by_date,
+        deep_ext_match: args.deep_ext_match,
+        detect_content: args.detect_content,
+        fix_extensions: args.fix_extensions,
+        by_repo: args.by_repo,
+        stabilize_wait: args.stabilize_wait.map(stability::StabilityPolicy::new),
+        keep_at_root: layered
+            .keep_at_root
+            .iter()
+            .chain(&args.keep_at_root)
+            .cloned()
+            .collect(),
+        keep_at_root_ignore_case: args.keep_at_root_ignore_case,
+        mark_sorted: args.mark_sorted,
+        skip_marked: args.skip_marked,
+        skip_hidden: !args.include_hidden,
+        empty_files: args.empty_files,
+        local_configs: args.local_configs,
+        dir_category_files: args.dir_category_files,
+        rules,
+        group_by_prefix: args.group_by_prefix,
+        preflight: args.preflight,
+        confirm_move: true,
+        assume_yes: args.yes,
+        confirm_per_category: args.confirm_per_category,
+        visual_summary: args.visual_summary,
+        print_folders: args.print_folders,
+        output_format: args.format,
+        print_folder_counts: args.folder_counts,
+        newest_first: args.latest.is_some(),
+        max_files: args.latest.map(|n| n.get()),
+        min_files_per_category: args.min_files,
+        force: args.force,
+        validate_after_move: args.validate_after_move,
+        validate_rehash: args.rehash,
+        lifetime_stats: LifetimeStore::default_path(),
+        #[cfg(unix)]
+        system_mime: if args.use_system_mime {
+            let database = mime::MimeDatabase::load_system();
+            if database.is_none() {
+                tracing::warn!("System MIME database not found; using the built-in mapping");
+            }
+            database
+        } else {
+            None
+        },
+        extension_overrides: layered.to_overrides()?,
+        custom_categories,
+        cli_overrides: args.map.into_iter().collect(),
+        folder_names: layered
+            .to_folder_names()?
+            .into_iter()
+            .chain(args.category_name)
+            .collect(),
+        ..Default::default()
     };
+    if args.safe {
+        config.apply_safe_preset()?;
+    }
+    config::check_folder_names(&config.folder_names, &config.custom_categories)?;
+
+    // 解決済みの設定を出力して終了
+    if let Some(format) = args.dump_config {
+        let resolved = config.resolved();
+        let output = match format {
+            DumpFormat::Toml => resolved.to_toml()?,
+            DumpFormat::Json => resolved.to_json()?,
+        };
+        println!("{}", output);
+        return Ok(());
+    }
+
+    // JSON出力のスキーマを出力して終了
+    if args.print_schema {
+        println!("{}", schema::output_schema_json()?);
+        return Ok(());
+    }
+
+    // 累計統計を表示して終了
+    if args.lifetime_stats {
+        match &config.lifetime_stats {
+            Some(path) => LifetimeStore::new(path).load()?.print(&reporter),
+            None => anyhow::bail!("Cannot locate lifetime stats: home directory is unknown"),
+        }
+        return Ok(());
+    }
+
+    // 組み込みの拡張子のカテゴリを変える上書きを知らせる（--strict-config ならエラー）
+    let collisions = config.override_collisions();
+    if !collisions.is_empty() {
+        let list: Vec<String> = collisions.iter().map(|c| format!("  {}", c)).collect();
+        if args.strict_config {
+            anyhow::bail!(
+                "{} built-in extension mappings are overridden (--strict-config):\n{}",
+                collisions.len(),
+                list.join("\n")
+            );
+        }
+        for collision in &collisions {
+            tracing::warn!("Built-in extension mapping overridden: {}", collision);
+        }
+    }
+
+    // 整理が必要かを調べ、終了コードで返す（--verbose の場合のみ通常の出力を表示）
+    if args.check {
+        let sorter = Sorter::new(config).with_reporter(reporter.clone());
+        let pending = if args.verbose && !args.quiet {
+            print_banner(&reporter);
+            sorter
+                .run()
+                .map(|stats| stats.moved_files + stats.removed_duplicates)
+        } else {
+            sorter.check()
+        };
+        match pending {
+            Ok(pending) => std::process::exit(check_exit_code(pending)),
+            Err(e) => {
+                eprintln!("{} {}", "✗ Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // バナー表示
+    print_banner(&reporter);
 
     // 実行前の確認（実際の移動時のみ）
-    if !config.dry_run {
-        print_warning();
+    let report_only = args.report_name_collisions
+        || args.fragmentation
+        || args.extension_report
+        || args.stats_only;
+    if !config.dry_run && !report_only {
+        print_warning(&reporter);
     }
 
     // ソーターを実行
-    let sorter = Sorter::new(config);
-    match sorter.run() {
-        Ok(_stats) => {
-            println!();
-            println!("{}", "✓ Operation completed successfully.".green().bold());
+    #[cfg(unix)]
+    let ipc_observer = match &args.ipc_socket {
+        Some(path) => {
+            Some(ipc::IpcObserver::bind(path)?.with_folder_names(config.folder_names.clone()))
+        }
+        None => None,
+    };
+    let sorter = Sorter::new(config).with_reporter(reporter.clone());
+    #[cfg(unix)]
+    let sorter = match ipc_observer {
+        Some(observer) => sorter.with_observer(Box::new(observer)),
+        None => sorter,
+    };
+    let result = if args.report_name_collisions {
+        sorter.report_name_collisions().map(|_| ())
+    } else if args.fragmentation {
+        sorter.report_fragmentation().map(|_| ())
+    } else if args.extension_report {
+        sorter.report_extensions().map(|_| ())
+    } else if args.stats_only {
+        sorter.stats_only().map(|_| ())
+    } else if args.watch {
+        sorter.watch().map(|_| ())
+    } else if args.unflatten {
+        sorter.unflatten().map(|_| ())
+    } else if args.flatten {
+        sorter.flatten().map(|_| ())
+    } else if args.undo {
+        sorter.undo().map(|_| ())
+    } else if let Some(path) = &args.apply_plan {
+        sorter.apply_plan(path).map(|_| ())
+    } else {
+        sorter.run().map(|_| ())
+    };
+
+    match result {
+        Ok(()) => {
+            detail!(reporter);
+            detail!(
+                reporter,
+                "{}",
+                "✓ Operation completed successfully.".green().bold()
+            );
             Ok(())
         }
         Err(e) => {
@@ -59,27 +382,70 @@ fn main() -> Result<()> {
     }
 }
 
-/// ロギングを初期化
-fn init_logging(verbose: bool) {
-    let level = if verbose { Level::DEBUG } else { Level::INFO };
+/// `--check` で整理が必要な場合の終了コード（1 はエラーに使用）
+const CHECK_PENDING_EXIT_CODE: i32 = 2;
+
+/// `--check` の終了コード（整理済みなら 0）
+fn check_exit_code(pending: usize) -> i32 {
+    if pending == 0 {
+        0
+    } else {
+        CHECK_PENDING_EXIT_CODE
+    }
+}
+
+/// ログの出力レベル
+///
+/// `--quiet` の場合と、`--verbose` なしの `--check` の場合は警告とエラーだけを出します。
+fn log_level(quiet: bool, verbose: bool, check: bool) -> Level {
+    if quiet || (check && !verbose) {
+        Level::WARN
+    } else if verbose {
+        Level::DEBUG
+    } else {
+        Level::INFO
+    }
+}
 
+/// ロギングを初期化
+///
+/// `to_stderr` なら標準出力を機械可読な出力のために空けておくため、ログを標準エラー出力に出します。
+fn init_logging(level: Level, to_stderr: bool) {
     let filter = EnvFilter::from_default_env()
         .add_directive(level.into())
         .add_directive("hyper=warn".parse().unwrap())
         .add_directive("reqwest=warn".parse().unwrap());
 
+    // 進捗バーの表示中はログを進捗バーの上に出す
+    let writer = if to_stderr {
+        BoxMakeWriter::new(|| ProgressAwareWriter(std::io::stderr()))
+    } else {
+        BoxMakeWriter::new(|| ProgressAwareWriter(std::io::stdout()))
+    };
+
     tracing_subscriber::fmt()
         .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(colored::control::SHOULD_COLORIZE.should_colorize())
         .with_span_events(FmtSpan::NONE)
         .with_target(false)
         .without_time()
         .init();
 }
 
-/// バナーを表示
-fn print_banner() {
-    println!();
-    println!(
+/// 出力を色付けするか
+///
+/// `--no-color` 指定時、環境変数 `NO_COLOR` が空でない値で設定されている場合
+/// （<https://no-color.org/>）、標準出力がターミナルでない場合は色付けしません。
+fn use_color(no_color_flag: bool, no_color_env: Option<&OsStr>, stdout_is_terminal: bool) -> bool {
+    !no_color_flag && no_color_env.map_or(true, OsStr::is_empty) && stdout_is_terminal
+}
+
+/// バナーを表示（`--quiet` では表示しない）
+fn print_banner(reporter: &Reporter) {
+    detail!(reporter);
+    detail!(
+        reporter,
         "{}",
         r#"
   ╔═══════════════════════════════════════════╗
@@ -93,24 +459,55 @@ fn print_banner() {
     );
 }
 
-/// 警告を表示（実際の移動実行時）
-fn print_warning() {
-    println!(
+/// 警告を表示（実際の移動実行時、`--quiet` では表示しない）
+fn print_warning(reporter: &Reporter) {
+    detail!(
+        reporter,
         "{}",
         "⚠️  WARNING: This will move files. Use --dry-run first to preview."
             .yellow()
             .bold()
     );
-    println!();
+    detail!(reporter);
 }
 
 #[cfg(test)]
 mod tests {
-    use super::print_banner;
+    use super::{
+        check_exit_code, log_level, print_banner, use_color, Level, Reporter,
+        CHECK_PENDING_EXIT_CODE,
+    };
+    use std::ffi::OsStr;
 
     #[test]
     fn test_banner_does_not_panic() {
         // バナー表示がパニックしないことを確認
-        print_banner();
+        print_banner(&Reporter::default());
+    }
+
+    #[test]
+    fn test_check_exit_code() {
+        assert_eq!(check_exit_code(0), 0);
+        assert_eq!(check_exit_code(3), CHECK_PENDING_EXIT_CODE);
+        assert_ne!(CHECK_PENDING_EXIT_CODE, 1);
+    }
+
+    #[test]
+    fn test_log_level() {
+        assert_eq!(log_level(false, false, false), Level::INFO);
+        assert_eq!(log_level(false, true, false), Level::DEBUG);
+        assert_eq!(log_level(true, true, false), Level::WARN);
+        // --check は --verbose がなければ情報ログも出さない
+        assert_eq!(log_level(false, false, true), Level::WARN);
+        assert_eq!(log_level(false, true, true), Level::DEBUG);
+    }
+
+    #[test]
+    fn test_use_color() {
+        assert!(use_color(false, None, true));
+        assert!(use_color(false, Some(OsStr::new("")), true));
+        assert!(!use_color(true, None, true));
+        assert!(!use_color(false, Some(OsStr::new("1")), true));
+        assert!(!use_color(false, None, false));
     }
 }
@@ -0,0 +1,227 @@
+//! システムMIMEデータベースモジュール（Unixのみ）
+//!
+//! shared-mime-info の `globs2` や `/etc/mime.types` から拡張子とMIMEタイプの対応を読み込み、
+//! MIMEタイプからカテゴリを判定します。デスクトップ環境と同じ分類にしたい場合に使います。
+
+use crate::config::Category;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::debug;
+
+/// 拡張子 → MIMEタイプ の対応表
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MimeDatabase {
+    /// 小文字の拡張子（ドットなし） → MIMEタイプ
+    by_extension: HashMap<String, String>,
+}
+
+impl MimeDatabase {
+    /// システムのMIMEデータベースを読み込む
+    ///
+    /// `$XDG_DATA_HOME` / `$XDG_DATA_DIRS` 配下の `mime/globs2` を優先度の高い順に重ね、
+    /// 最後に `/etc/mime.types` で不足分を補います。どれも読めない場合は `None` を返します。
+    pub fn load_system() -> Option<Self> {
+        let mut database = Self::default();
+        let mut found = false;
+
+        for path in globs2_paths() {
+            if let Ok(content) = fs::read_to_string(&path) {
+                debug!("Loading MIME globs: {}", path.display());
+                database.merge_missing(Self::parse_globs2(&content));
+                found = true;
+            }
+        }
+        if let Ok(content) = fs::read_to_string("/etc/mime.types") {
+            debug!("Loading MIME types: /etc/mime.types");
+            database.merge_missing(Self::parse_mime_types(&content));
+            found = true;
+        }
+
+        found.then_some(database)
+    }
+
+    /// shared-mime-info の `globs2`（`重み:MIMEタイプ:*.拡張子` の行）を解析する
+    ///
+    /// `*.拡張子` 以外のパターンは無視します。同じ拡張子は重みの大きい行を優先します。
+    pub fn parse_globs2(content: &str) -> Self {
+        let mut weighted: HashMap<String, (u32, String)> = HashMap::new();
+        for line in content.lines().filter(|l| !l.starts_with('#')) {
+            let mut fields = line.split(':');
+            let (Some(weight), Some(mime), Some(glob)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Some(ext) = simple_extension_glob(glob) else {
+                continue;
+            };
+            let weight = weight.trim().parse().unwrap_or(0);
+            let entry = weighted
+                .entry(ext)
+                .or_insert_with(|| (weight, mime.to_string()));
+            if weight > entry.0 {
+                *entry = (weight, mime.to_string());
+            }
+        }
+
+        Self {
+            by_extension: weighted
+                .into_iter()
+                .map(|(ext, (_, mime))| (ext, mime))
+                .collect(),
+        }
+    }
+
+    /// `/etc/mime.types`（`MIMEタイプ 拡張子 拡張子...` の行）を解析する
+    pub fn parse_mime_types(content: &str) -> Self {
+        let mut by_extension = HashMap::new();
+        for line in content.lines().filter(|l| !l.trim_start().starts_with('#')) {
+            let mut fields = line.split_whitespace();
+            let Some(mime) = fields.next() else {
+                continue;
+            };
+            for ext in fields {
+                by_extension
+                    .entry(ext.to_lowercase())
+                    .or_insert_with(|| mime.to_string());
+            }
+        }
+        Self { by_extension }
+    }
+
+    /// まだ対応のない拡張子だけを取り込む
+    fn merge_missing(&mut self, other: Self) {
+        for (ext, mime) in other.by_extension {
+            self.by_extension.entry(ext).or_insert(mime);
+        }
+    }
+
+    /// 拡張子（小文字、ドットなし）のMIMEタイプ
+    pub fn mime_type(&self, extension: &str) -> Option<&str> {
+        self.by_extension.get(extension).map(String::as_str)
+    }
+
+    /// 拡張子からMIMEタイプを経由してカテゴリを判定する（判定できない場合は `None`）
+    pub fn category(&self, extension: &str) -> Option<Category> {
+        self.mime_type(extension).and_then(category_for_mime)
+    }
+}
+
+/// MIMEタイプからカテゴリを判定する
+///
+/// `image/*`・`video/*`・`audio/*` はそのまま対応するカテゴリに、
+/// `text/*` は文書系のものを Documents、それ以外（ソースコードなど）を Code にします。
+/// `application/*` はアーカイブと文書のみを判定し、それ以外は `None` です。
+pub fn category_for_mime(mime: &str) -> Option<Category> {
+    let (top, sub) = mime.split_once('/')?;
+    match top {
+        "image" => Some(Category::Images),
+        "video" => Some(Category::Videos),
+        "audio" => Some(Category::Music),
+        "text" => Some(match sub {
+            "plain" | "csv" | "rtf" | "markdown" | "tab-separated-values" => Category::Documents,
+            _ => Category::Code,
+        }),
+        "application" => {
+            const ARCHIVES: &[&str] = &[
+                "zip",
+                "gzip",
+                "x-tar",
+                "x-compressed-tar",
+                "x-bzip",
+                "x-bzip2",
+                "x-bzip-compressed-tar",
+                "x-xz",
+                "x-xz-compressed-tar",
+                "x-7z-compressed",
+                "x-rar",
+                "vnd.rar",
+                "x-rar-compressed",
+                "zstd",
+                "x-zstd-compressed-tar",
+            ];
+            const DOCUMENT_PREFIXES: &[&str] = &[
+                "pdf",
+                "msword",
+                "rtf",
+                "epub+zip",
+                "vnd.ms-",
+                "vnd.openxmlformats-officedocument.",
+                "vnd.oasis.opendocument.",
+            ];
+            if ARCHIVES.contains(&sub) {
+                Some(Category::Archives)
+            } else if DOCUMENT_PREFIXES.iter().any(|p| sub.starts_with(p)) {
+                Some(Category::Documents)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// `*.ext` 形式の単純なパターンなら拡張子（小文字）を返す
+fn simple_extension_glob(glob: &str) -> Option<String> {
+    let ext = glob.strip_prefix("*.")?;
+    let simple = !ext.is_empty() && !ext.contains(['*', '?', '[', '.']);
+    simple.then(|| ext.to_lowercase())
+}
+
+/// 読み込む `globs2` のパス（優先度の高い順）
+fn globs2_paths() -> Vec<PathBuf> {
+    let non_empty = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+    let data_home = non_empty("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    let data_dirs: Vec<PathBuf> = match non_empty("XDG_DATA_DIRS") {
+        Some(dirs) => std::env::split_paths(&dirs).collect(),
+        None => vec![
+            PathBuf::from("/usr/local/share"),
+            PathBuf::from("/usr/share"),
+        ],
+    };
+
+    data_home
+        .into_iter()
+        .chain(data_dirs)
+        .map(|dir| dir.join("mime").join("globs2"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_globs2_and_categorize() {
+        let database = MimeDatabase::parse_globs2(
+            "# comment\n\
+             50:image/png:*.png\n\
+             50:text/x-python3:*.py\n\
+             50:application/x-compressed-tar:*.tar.gz\n\
+             50:application/pdf:*.pdf\n\
+             10:text/plain:*.PDF:cs\n\
+             50:application/x-core:core\n",
+        );
+        assert_eq!(database.mime_type("png"), Some("image/png"));
+        assert_eq!(database.category("png"), Some(Category::Images));
+        assert_eq!(database.category("py"), Some(Category::Code));
+        // 重みの大きい行を優先
+        assert_eq!(database.category("pdf"), Some(Category::Documents));
+        // *.ext 以外のパターンは無視
+        assert_eq!(database.mime_type("gz"), None);
+        assert_eq!(database.mime_type("core"), None);
+    }
+
+    #[test]
+    fn test_parse_mime_types() {
+        let database = MimeDatabase::parse_mime_types(
+            "# comment\nimage/jpeg\t\tjpeg jpg jpe\napplication/x-unknown-thing\tunk\n",
+        );
+        assert_eq!(database.category("jpe"), Some(Category::Images));
+        // 判定できないMIMEタイプは組み込みマッピングに任せる
+        assert_eq!(database.category("unk"), None);
+    }
+}
@@ -0,0 +1,55 @@
+//! 実行状況の通知モジュール
+//!
+//! ソート処理の進行状況と結果を、標準出力とは別の経路で外部に伝えるための
+//! オブザーバーを定義します。
+
+use crate::config::Category;
+use crate::file_ops::OpResult;
+use crate::sorter::{FilePlan, SortStats};
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// ソート処理中に発生するイベント
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SortEvent {
+    /// 移動を開始した
+    Started {
+        /// 移動する予定のファイル数
+        total: usize,
+    },
+    /// ファイルを移動した
+    Moved {
+        source: PathBuf,
+        destination: PathBuf,
+        category: Category,
+    },
+    /// ファイルの移動に失敗した
+    Failed { source: PathBuf, error: String },
+    /// 処理が完了した
+    Finished { summary: Box<SortStats> },
+}
+
+/// ソート処理のイベントを受け取るオブザーバー
+///
+/// `--jobs` で移動を並列に行う場合は、移動を担当するスレッドから通知されます。
+pub trait SortObserver: Send {
+    /// イベントを受け取る
+    ///
+    /// 通知の失敗でソート処理を止めないよう、エラーは実装側で処理します。
+    fn on_event(&mut self, event: &SortEvent);
+}
+
+/// 1ファイルを移動する直前に呼ばれるフック
+///
+/// `false` を返すとそのファイルは移動せず、スキップとして数えます。
+/// `--jobs` で並列に移動する場合は複数のスレッドから同時に呼ばれます。
+pub type BeforeMoveHook = Arc<dyn Fn(&FilePlan) -> bool + Send + Sync>;
+
+/// 1ファイルの移動（コピー・ハードリンクを含む）を試みた直後に呼ばれるフック
+///
+/// 移動に成功した場合は実際の移動先を含む結果を、失敗した場合はエラーを受け取ります。
+pub type AfterMoveHook = Arc<dyn Fn(&FilePlan, &Result<OpResult>) + Send + Sync>;
@@ -0,0 +1,159 @@
+//! 操作ログモジュール
+//!
+//! 移動（Dry Runでは移動の予定）を1件ごとに JSON Lines 形式でファイルに追記します。
+//! ジャーナルと違い実行をまたいで蓄積され、取り消しではなく監査のための記録です。
+
+use crate::config::Category;
+use crate::date::Date;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// 記録する操作の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// 移動
+    Move,
+    /// コピー（`--copy`）
+    Copy,
+    /// ハードリンク（`--hardlink`）
+    Hardlink,
+    /// Dry Runでの移動の予定
+    DryRun,
+}
+
+/// 操作の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Ok,
+    Error,
+}
+
+/// 操作ログの1行
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationEntry<'a> {
+    /// 記録した時刻（UTC、RFC 3339）
+    pub timestamp: String,
+    pub operation: Operation,
+    pub source: &'a Path,
+    /// 移動先（失敗した場合は `None`）
+    pub destination: Option<&'a Path>,
+    pub category: &'a Category,
+    /// 重複回避のためにリネームされたか
+    pub renamed: bool,
+    pub result: Outcome,
+    /// 失敗した場合のエラーメッセージ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 追記モードで開いた操作ログ
+///
+/// 複数のスレッドから同時に書き込めます。書き込みに失敗しても整理は止めず、
+/// 最初の失敗だけを警告します。
+pub struct OperationLog {
+    path: PathBuf,
+    file: Mutex<File>,
+    /// 書き込みの失敗を警告したか
+    warned: AtomicBool,
+}
+
+impl OperationLog {
+    /// 操作ログを追記モードで開く（なければ作成する）
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file: {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            warned: AtomicBool::new(false),
+        })
+    }
+
+    /// 1件の操作を1行のJSONとして追記する
+    pub fn record(&self, entry: &OperationEntry<'_>) {
+        let result = serde_json::to_string(entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| {
+                let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+                writeln!(file, "{}", line).map_err(anyhow::Error::from)
+            });
+        if let Err(e) = result {
+            if !self.warned.swap(true, Ordering::Relaxed) {
+                warn!("Failed to write log file {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// 時刻をUTCのRFC 3339形式（`2024-05-01T12:34:56Z`）にする
+pub fn rfc3339(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let date = Date::from_days_since_epoch((secs / 86_400) as i64);
+    let time_of_day = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        date.year,
+        date.month,
+        date.day,
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rfc3339() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_714_566_896);
+        assert_eq!(rfc3339(time), "2024-05-01T12:34:56Z");
+    }
+
+    #[test]
+    fn test_record_appends_lines() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("ops.jsonl");
+        for _ in 0..2 {
+            let log = OperationLog::open(&path).unwrap();
+            log.record(&OperationEntry {
+                timestamp: rfc3339(SystemTime::now()),
+                operation: Operation::Move,
+                source: Path::new("photo.jpg"),
+                destination: Some(Path::new("Images/photo.jpg")),
+                category: &Category::Images,
+                renamed: false,
+                result: Outcome::Ok,
+                error: None,
+            });
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["operation"], "move");
+        assert_eq!(lines[0]["result"], "ok");
+        assert!(lines[0].get("error").is_none());
+    }
+}
@@ -0,0 +1,512 @@
+//! 出力フォーマットモジュール
+//!
+//! ターミナル幅の取得や、プレビュー表示の整形など
+//! 表示に関する補助処理を担当します。
+//!
+//! 人向けの表示は `say!` で `Reporter` に出力し、機械可読な出力を選んだ場合はまとめて抑止します。
+//! 機械可読な出力そのものは `Reporter::emit` で出し、こちらは抑止しません。
+//! ファイルごとの行などの詳細は `detail!` で出力し、`--quiet` で抑止します。
+//! 進捗バーの表示中は、どちらの出力も進捗バーを一時的に消してから書き込みます。
+
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+use unicode_width::UnicodeWidthStr;
+
+/// 実行結果の出力形式
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// 人向けの装飾付きテキスト
+    #[default]
+    Text,
+    /// 実行結果の統計だけをJSONで出力
+    Json,
+}
+
+/// 人向けの表示の出力先
+///
+/// `Sorter` ごとに持たせるため、ライブラリとして組み込む場合も他の `Sorter` や
+/// プロセス全体の設定に影響せずに、表示を止めたり行ごとに取り込んだりできます。
+#[derive(Clone)]
+pub struct Reporter {
+    /// 人向けの表示（`say!`）を出力するか
+    human: bool,
+    /// ファイルごとの行などの詳細な表示（`detail!`）を出力するか
+    detail: bool,
+    /// 行の出力先（`None` なら標準出力）
+    sink: Option<LineSink>,
+}
+
+/// `Reporter::capture` で表示の各行を受け取る関数
+type LineSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+impl Default for Reporter {
+    fn default() -> Self {
+        Self::stdout()
+    }
+}
+
+impl fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reporter")
+            .field("human", &self.human)
+            .field("detail", &self.detail)
+            .field("captured", &self.sink.is_some())
+            .finish()
+    }
+}
+
+impl Reporter {
+    /// 標準出力に表示する（既定）
+    pub fn stdout() -> Self {
+        Self {
+            human: true,
+            detail: true,
+            sink: None,
+        }
+    }
+
+    /// 何も表示しない（JSON出力など）
+    pub fn silent() -> Self {
+        Self {
+            human: false,
+            detail: false,
+            sink: None,
+        }
+    }
+
+    /// 表示する各行を、標準出力の代わりに `sink` に渡す
+    pub fn capture<F>(sink: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        Self {
+            human: true,
+            detail: true,
+            sink: Some(Arc::new(sink)),
+        }
+    }
+
+    /// 詳細な表示（`detail!`）を出力するかを設定する（`--quiet` で `false`）
+    pub fn with_detail(mut self, enabled: bool) -> Self {
+        self.detail = enabled;
+        self
+    }
+
+    /// 人向けの表示を出力するか
+    pub fn is_enabled(&self) -> bool {
+        self.human
+    }
+
+    /// 詳細な表示を出力するか（人向けの表示を止めている間は常に `false`）
+    pub fn detail_enabled(&self) -> bool {
+        self.human && self.detail
+    }
+
+    /// 人向けの表示を1行出力する（`say!` から使う）
+    pub fn say(&self, args: fmt::Arguments<'_>) {
+        if self.human {
+            self.write_line(args);
+        }
+    }
+
+    /// 詳細な表示を1行出力する（`detail!` から使う）
+    pub fn detail(&self, args: fmt::Arguments<'_>) {
+        if self.detail_enabled() {
+            self.write_line(args);
+        }
+    }
+
+    /// 機械可読な出力（`--format json` の統計など）を出す
+    ///
+    /// 人向けの表示を止めていても出力します。`capture` の場合は `sink` に渡します。
+    pub fn emit(&self, args: fmt::Arguments<'_>) {
+        self.write_line(args);
+    }
+
+    fn write_line(&self, args: fmt::Arguments<'_>) {
+        match &self.sink {
+            Some(sink) => sink(&args.to_string()),
+            None => print_line(args),
+        }
+    }
+}
+
+/// 人向けの表示を `Reporter` に出す（`Reporter::silent()` なら何も出さない）
+///
+/// 最初の引数に出力先の `Reporter` を渡します（例: `say!(self.reporter, "{}", n)`）。
+#[macro_export]
+macro_rules! say {
+    ($out:expr) => {
+        $out.say(format_args!(""))
+    };
+    ($out:expr, $($arg:tt)*) => {
+        $out.say(format_args!($($arg)*))
+    };
+}
+pub use say;
+
+/// ファイルごとの行などの詳細な表示を `Reporter` に出す
+///
+/// `say!` と違い、サマリーを残して出力を減らす `with_detail(false)` の間も出しません。
+#[macro_export]
+macro_rules! detail {
+    ($out:expr) => {
+        $out.detail(format_args!(""))
+    };
+    ($out:expr, $($arg:tt)*) => {
+        $out.detail(format_args!($($arg)*))
+    };
+}
+pub use detail;
+
+/// 表示中の進捗バー
+static ACTIVE_PROGRESS: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// 進捗バーの表示中はそれを一時的に消して `f` を実行する
+fn suspend_progress<R>(f: impl FnOnce() -> R) -> R {
+    let active = ACTIVE_PROGRESS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    match active {
+        Some(bar) => bar.suspend(f),
+        None => f(),
+    }
+}
+
+/// 1行を標準出力に出す（進捗バーの表示中はその上に出す）
+pub fn print_line(args: fmt::Arguments<'_>) {
+    suspend_progress(|| println!("{}", args));
+}
+
+/// 進捗バーを崩さずに書き込むライター（ログの出力先に使う）
+pub struct ProgressAwareWriter<W>(pub W);
+
+impl<W: Write> Write for ProgressAwareWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        suspend_progress(|| self.0.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// 処理したファイル数・総数・残り時間の目安を標準エラー出力に表示する進捗バー
+///
+/// 標準エラー出力がターミナルでない場合は何も表示しません。
+/// 表示中の `say!`・`detail!` とログは進捗バーの上に出力され、`Drop` で進捗バーは消えます。
+pub struct Progress {
+    bar: ProgressBar,
+    /// 表示中の進捗バーとして登録したか
+    registered: bool,
+}
+
+impl Progress {
+    /// 進捗バーを表示する（`enabled` でない場合や `reporter` が表示を止めている場合は表示しない）
+    pub fn start(total: usize, enabled: bool, reporter: &Reporter) -> Self {
+        let bar = if enabled && reporter.is_enabled() {
+            ProgressBar::new(total as u64)
+        } else {
+            ProgressBar::hidden()
+        };
+        if bar.is_hidden() {
+            return Self {
+                bar,
+                registered: false,
+            };
+        }
+        if let Ok(style) =
+            ProgressStyle::with_template("{bar:40.cyan/blue} {pos}/{len} files (ETA {eta})")
+        {
+            bar.set_style(style.progress_chars("##-"));
+        }
+        *ACTIVE_PROGRESS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(bar.clone());
+        Self {
+            bar,
+            registered: true,
+        }
+    }
+
+    /// 1件処理したことを反映する
+    pub fn inc(&self) {
+        self.bar.inc(1);
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.registered {
+            *ACTIVE_PROGRESS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = None;
+        }
+        self.bar.finish_and_clear();
+    }
+}
+
+/// 列が狭くなりすぎないようにするための最小幅
+const MIN_COLUMN_WIDTH: usize = 12;
+
+/// 棒グラフの棒が短くなりすぎないようにするための最小幅
+const MIN_BAR_WIDTH: usize = 10;
+
+/// 棒グラフの棒に使う文字
+const BAR_CHAR: char = '█';
+
+/// 現在のターミナルの幅を取得する（ターミナルでない場合は `None`）
+pub fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(w, _)| w.0 as usize)
+}
+
+/// 文字列の表示幅を取得する（全角文字は幅2として数える）
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// 表示幅が `width` を超える場合、先頭を `…` に置き換えて末尾を残す
+///
+/// パスはファイル名側（末尾）の方が重要なため、先頭を切り詰めます。
+pub fn truncate_start(s: &str, width: usize) -> String {
+    if display_width(s) <= width {
+        return s.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+
+    let mut tail: Vec<char> = Vec::new();
+    let mut used = 1; // '…' の分
+    for c in s.chars().rev() {
+        let w = display_width(c.encode_utf8(&mut [0u8; 4]));
+        if used + w > width {
+            break;
+        }
+        used += w;
+        tail.push(c);
+    }
+    tail.reverse();
+    format!("…{}", tail.into_iter().collect::<String>())
+}
+
+/// 表示幅が `width` になるまで末尾に空白を追加する
+pub fn pad_end(s: &str, width: usize) -> String {
+    let pad = width.saturating_sub(display_width(s));
+    format!("{}{}", s, " ".repeat(pad))
+}
+
+/// 移動元・移動先の2列を縦に揃える
+///
+/// 移動元の列は最長の移動元に合わせて右側を空白で埋めます。
+/// `available` が指定され、2列の合計がそれを超える場合は
+/// 各列を切り詰めて収まるようにします。
+///
+/// # Arguments
+/// * `rows` - (移動元, 移動先) の組
+/// * `available` - 2列に使用できる表示幅（制限しない場合は `None`）
+///
+/// # Returns
+/// 整形済みの (移動元, 移動先) の組
+pub fn align_columns(rows: &[(String, String)], available: Option<usize>) -> Vec<(String, String)> {
+    let max_source = rows
+        .iter()
+        .map(|(s, _)| display_width(s))
+        .max()
+        .unwrap_or(0);
+    let max_dest = rows
+        .iter()
+        .map(|(_, d)| display_width(d))
+        .max()
+        .unwrap_or(0);
+
+    let (source_width, dest_width) = match available {
+        Some(available) if max_source + max_dest > available => {
+            let source_width = max_source.min((available / 2).max(MIN_COLUMN_WIDTH));
+            let dest_width = available.saturating_sub(source_width).max(MIN_COLUMN_WIDTH);
+            (source_width, dest_width)
+        }
+        _ => (max_source, max_dest),
+    };
+
+    rows.iter()
+        .map(|(source, dest)| {
+            (
+                pad_end(&truncate_start(source, source_width), source_width),
+                truncate_start(dest, dest_width),
+            )
+        })
+        .collect()
+}
+
+/// 件数の内訳を横棒グラフの行に整形する
+///
+/// 各行は `  ラベル  ████████ 件数 (割合%)` の形で、棒の長さは全体に占める割合に
+/// 比例します。棒の最大長は行全体が `width` に収まるように決めます。
+///
+/// # Arguments
+/// * `rows` - (ラベル, 件数) の組
+/// * `width` - 1行に使用できる表示幅
+pub fn bar_chart(rows: &[(&str, usize)], width: usize) -> Vec<String> {
+    let total: usize = rows.iter().map(|(_, count)| count).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+
+    let suffixes: Vec<String> = rows
+        .iter()
+        .map(|(_, count)| format!(" {} ({}%)", count, percent(*count, total)))
+        .collect();
+    let label_width = rows
+        .iter()
+        .map(|(label, _)| display_width(label))
+        .max()
+        .unwrap_or(0);
+    let suffix_width = suffixes.iter().map(|s| display_width(s)).max().unwrap_or(0);
+    let bar_width = width
+        .saturating_sub(2 + label_width + 2 + suffix_width)
+        .max(MIN_BAR_WIDTH);
+
+    rows.iter()
+        .zip(&suffixes)
+        .map(|((label, count), suffix)| {
+            let length = (count * bar_width + total / 2) / total;
+            format!(
+                "  {}  {}{}",
+                pad_end(label, label_width),
+                BAR_CHAR.to_string().repeat(length),
+                suffix
+            )
+        })
+        .collect()
+}
+
+/// 件数の内訳を棒なしの行に整形する（色やターミナルが使えない場合）
+pub fn plain_breakdown(rows: &[(&str, usize)]) -> Vec<String> {
+    let total: usize = rows.iter().map(|(_, count)| count).sum();
+    rows.iter()
+        .map(|(label, count)| format!("  {}: {} ({}%)", label, count, percent(*count, total)))
+        .collect()
+}
+
+/// バイト数を読みやすい単位（B・KiB・MiB・GiB・TiB）で表す
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// 全体に占める割合（四捨五入した整数のパーセント）
+fn percent(count: usize, total: usize) -> usize {
+    (count * 100 + total / 2).checked_div(total).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<(String, String)> {
+        vec![
+            ("a.jpg".to_string(), "Images/a.jpg".to_string()),
+            (
+                "long/path/report.pdf".to_string(),
+                "Documents/report.pdf".to_string(),
+            ),
+            ("画像.png".to_string(), "Images/画像.png".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_align_columns() {
+        let aligned = align_columns(&rows(), None);
+
+        // すべての移動元の列が同じ表示幅になる（= 移動先の開始位置が揃う）
+        for (source, _) in &aligned {
+            assert_eq!(display_width(source), "long/path/report.pdf".len());
+        }
+        assert_eq!(aligned[0].0, format!("a.jpg{}", " ".repeat(15)));
+        assert_eq!(aligned[1].1, "Documents/report.pdf");
+    }
+
+    #[test]
+    fn test_align_columns_truncates_to_width() {
+        let aligned = align_columns(&rows(), Some(30));
+
+        for (source, dest) in &aligned {
+            assert_eq!(display_width(source), 15);
+            assert!(display_width(source) + display_width(dest) <= 30);
+        }
+        assert_eq!(aligned[1].0, "…ath/report.pdf");
+    }
+
+    #[test]
+    fn test_truncate_start() {
+        assert_eq!(truncate_start("short", 10), "short");
+        assert_eq!(truncate_start("abcdefghij", 5), "…ghij");
+        assert_eq!(display_width(&truncate_start("画像画像画像", 5)), 5);
+    }
+
+    #[test]
+    fn test_bar_chart_is_proportional() {
+        let rows = [("Images", 6), ("Documents", 3), ("Music", 1)];
+        // ラベル9 + 件数8 + 余白4 = 21 なので、棒の最大長は20
+        let lines = bar_chart(&rows, 41);
+
+        let lengths: Vec<usize> = lines
+            .iter()
+            .map(|line| line.chars().filter(|&c| c == BAR_CHAR).count())
+            .collect();
+        assert_eq!(lengths, vec![12, 6, 2]);
+        assert!(lines.iter().all(|line| display_width(line) <= 41));
+        assert_eq!(lines[0], format!("  Images     {} 6 (60%)", "█".repeat(12)));
+
+        assert!(bar_chart(&[("Images", 0)], 41).is_empty());
+        assert_eq!(plain_breakdown(&rows)[2], "  Music: 1 (10%)");
+    }
+
+    #[test]
+    fn test_quiet_reporter_drops_detail_lines() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        let reporter = Reporter::capture(move |line| sink.lock().unwrap().push(line.to_string()))
+            .with_detail(false);
+
+        say!(reporter, "summary {}", 1);
+        detail!(reporter, "  moved {}", "a.jpg");
+
+        // --quiet ではファイルごとの行だけを抑止し、まとめの表示は残す
+        assert_eq!(*lines.lock().unwrap(), vec!["summary 1".to_string()]);
+        assert!(reporter.is_enabled());
+        assert!(!reporter.detail_enabled());
+    }
+
+    #[test]
+    fn test_progress_disabled_is_not_registered() {
+        let progress = Progress::start(10, false, &Reporter::default());
+        progress.inc();
+        assert!(!progress.registered);
+        assert!(ACTIVE_PROGRESS.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+}
@@ -0,0 +1,234 @@
+//! 実行前の確認レポートモジュール
+//!
+//! 実際に移動する前に、計画と各ファイルのメタデータから
+//! 「何件を・どこへ・どれだけ動かすか」を1画面にまとめます。
+
+use crate::file_ops::{same_filesystem, ConflictStrategy};
+use crate::output::{format_bytes, say, Reporter};
+use crate::sorter::{FilePlan, SkippedFile};
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 移動前にまとめて表示する実行内容
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreflightReport {
+    /// 移動するファイル数
+    pub files: usize,
+    /// 移動するファイルの合計バイト数
+    pub total_bytes: u64,
+    /// 新しく作成されるフォルダ（振り分け先のルートからの相対パス）
+    pub new_folders: Vec<PathBuf>,
+    /// 移動先に同名のファイルが既にあり、`on_conflict` に従って扱われるファイル数
+    pub conflicts: usize,
+    /// この実行で先に移動するファイルと移動先の名前が重なり、連番を付けてリネームされるファイル数
+    pub renamed_within_run: usize,
+    /// 移動先に同名のファイルが既にある場合の扱い
+    pub on_conflict: ConflictStrategy,
+    /// 別のファイルシステムへのコピーになるファイル数
+    pub cross_filesystem: usize,
+    /// 除外の理由ごとのスキップされるファイル数
+    pub skipped: BTreeMap<String, usize>,
+}
+
+impl PreflightReport {
+    /// 計画と除外されたファイルからレポートを作成する
+    ///
+    /// `root` は振り分け先のルートで、新しいフォルダはここからの相対パスで示します。
+    pub fn new(
+        plans: &[FilePlan],
+        skipped: &[SkippedFile],
+        root: &Path,
+        on_conflict: ConflictStrategy,
+    ) -> Self {
+        let mut report = Self {
+            files: plans.len(),
+            on_conflict,
+            ..Default::default()
+        };
+
+        let mut new_folders = BTreeSet::new();
+        let mut destinations = HashSet::new();
+        for plan in plans {
+            report.total_bytes += fs::metadata(&plan.source).map_or(0, |m| m.len());
+
+            let dest_dir = plan.dest_dir();
+            if !dest_dir.exists() {
+                new_folders.insert(
+                    dest_dir
+                        .strip_prefix(root)
+                        .unwrap_or(dest_dir)
+                        .to_path_buf(),
+                );
+            }
+            // 先に計画された移動先と重なった場合は、--on-conflict にかかわらず連番を付ける
+            if !destinations.insert(&plan.destination) {
+                report.renamed_within_run += 1;
+            } else if plan.destination.exists() {
+                report.conflicts += 1;
+            }
+            // まだない移動先は、最も近い既存の祖先で判定する
+            let existing = dest_dir.ancestors().find(|dir| dir.exists());
+            if existing.and_then(|dir| same_filesystem(&plan.source, dir)) == Some(false) {
+                report.cross_filesystem += 1;
+            }
+        }
+        report.new_folders = new_folders.into_iter().collect();
+
+        for file in skipped {
+            *report.skipped.entry(file.reason.to_string()).or_insert(0) += 1;
+        }
+
+        report
+    }
+
+    /// レポートを表示
+    pub fn print(&self, reporter: &Reporter) {
+        say!(reporter);
+        say!(reporter, "{}", "=== Preflight ===".cyan().bold());
+        say!(
+            reporter,
+            "Files to move: {}",
+            self.files.to_string().yellow()
+        );
+        say!(
+            reporter,
+            "Total size: {}",
+            format_bytes(self.total_bytes).yellow()
+        );
+        say!(
+            reporter,
+            "Folders to create: {}",
+            self.new_folders.len().to_string().yellow()
+        );
+        for folder in &self.new_folders {
+            say!(reporter, "  {}", folder.display());
+        }
+        if self.conflicts > 0 {
+            say!(
+                reporter,
+                "Name conflicts ({}): {}",
+                self.conflict_outcome(),
+                self.conflicts.to_string().yellow()
+            );
+        }
+        if self.renamed_within_run > 0 {
+            say!(
+                reporter,
+                "Name collisions within this run (will be renamed): {}",
+                self.renamed_within_run.to_string().yellow()
+            );
+        }
+        if self.cross_filesystem > 0 {
+            say!(
+                reporter,
+                "Cross-filesystem copies: {}",
+                self.cross_filesystem.to_string().yellow()
+            );
+        }
+        let skipped: usize = self.skipped.values().sum();
+        if skipped > 0 {
+            say!(reporter, "Files skipped: {}", skipped.to_string().yellow());
+            for (reason, count) in &self.skipped {
+                say!(reporter, "  {}: {}", reason, count);
+            }
+        }
+        say!(reporter);
+    }
+
+    /// 移動先に同名のファイルが既にある場合にどうなるか（`on_conflict` による）
+    fn conflict_outcome(&self) -> &'static str {
+        match self.on_conflict {
+            ConflictStrategy::Rename => "will be renamed",
+            ConflictStrategy::Skip => "will be skipped",
+            ConflictStrategy::Overwrite => "will overwrite",
+            ConflictStrategy::Trash => "old files moved to trash",
+            ConflictStrategy::KeepNewer => "replaced if newer, otherwise skipped",
+            ConflictStrategy::KeepLarger => "replaced if larger, otherwise skipped",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Category;
+    use crate::sorter::SkipReason;
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
+
+    fn plan(source: PathBuf, destination: PathBuf) -> FilePlan {
+        FilePlan {
+            source,
+            destination,
+            category: Category::Others,
+        }
+    }
+
+    #[test]
+    fn test_preflight_report_counts() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.jpg"), "12345").unwrap();
+        fs::write(root.join("sub_a.jpg"), "123").unwrap();
+        fs::write(root.join("notes.txt"), "12").unwrap();
+        fs::create_dir(root.join("Documents")).unwrap();
+        fs::write(root.join("Documents").join("notes.txt"), "").unwrap();
+
+        let plans = [
+            plan(root.join("a.jpg"), root.join("Images").join("a.jpg")),
+            plan(root.join("sub_a.jpg"), root.join("Images").join("a.jpg")),
+            plan(
+                root.join("notes.txt"),
+                root.join("Documents").join("notes.txt"),
+            ),
+        ];
+        let skipped = [SkippedFile {
+            path: root.join("index.html"),
+            reason: SkipReason::KeepAtRoot,
+        }];
+        let report = PreflightReport::new(&plans, &skipped, root, ConflictStrategy::Rename);
+
+        assert_eq!(report.files, 3);
+        assert_eq!(report.total_bytes, 10);
+        assert_eq!(report.new_folders, vec![PathBuf::from("Images")]);
+        assert_eq!(report.conflicts, 1);
+        assert_eq!(report.renamed_within_run, 1);
+        assert_eq!(report.cross_filesystem, 0);
+        assert_eq!(
+            report.skipped,
+            BTreeMap::from([("kept in place".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn test_conflict_label_follows_strategy() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "new").unwrap();
+        fs::create_dir(dir.path().join("Documents")).unwrap();
+        fs::write(dir.path().join("Documents").join("a.txt"), "old").unwrap();
+        let plans = [plan(
+            dir.path().join("a.txt"),
+            dir.path().join("Documents").join("a.txt"),
+        )];
+
+        let conflict_line = |on_conflict| {
+            let lines = Arc::new(Mutex::new(Vec::new()));
+            let sink = Arc::clone(&lines);
+            let reporter =
+                Reporter::capture(move |line| sink.lock().unwrap().push(line.to_string()));
+            PreflightReport::new(&plans, &[], dir.path(), on_conflict).print(&reporter);
+            let lines = lines.lock().unwrap();
+            lines
+                .iter()
+                .find(|line| line.starts_with("Name conflicts"))
+                .cloned()
+                .unwrap()
+        };
+
+        assert!(conflict_line(ConflictStrategy::Rename).contains("will be renamed"));
+        assert!(conflict_line(ConflictStrategy::Skip).contains("will be skipped"));
+        assert!(conflict_line(ConflictStrategy::Overwrite).contains("will overwrite"));
+    }
+}
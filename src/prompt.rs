@@ -0,0 +1,47 @@
+//! 対話プロンプトモジュール
+//!
+//! 実行前の確認など、ユーザーへの問い合わせを担当します。
+//! 入出力を引数で受け取るため、テストから任意の入力を与えられます。
+//! ソーターは質問を標準エラー出力に書き、`--format json` などの標準出力を汚しません。
+
+use anyhow::{Context, Result};
+use std::io::{BufRead, Write};
+
+/// y/N 形式で確認を求める
+///
+/// `y` または `yes`（大文字小文字を区別しない）が入力された場合のみ `true` を返します。
+/// 空行や入力の終端（EOF）は「いいえ」として扱います。
+pub fn confirm<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+) -> Result<bool> {
+    write!(output, "{question} [y/N] ").context("Failed to write prompt")?;
+    output.flush().context("Failed to flush prompt")?;
+
+    let mut answer = String::new();
+    input
+        .read_line(&mut answer)
+        .context("Failed to read answer")?;
+
+    let answer = answer.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirm_answers() {
+        let mut output = Vec::new();
+        assert!(confirm(&mut "y\n".as_bytes(), &mut output, "Proceed?").unwrap());
+        assert!(confirm(&mut "YES\n".as_bytes(), &mut output, "Proceed?").unwrap());
+        assert!(!confirm(&mut "n\n".as_bytes(), &mut output, "Proceed?").unwrap());
+        assert!(!confirm(&mut "\n".as_bytes(), &mut output, "Proceed?").unwrap());
+        assert!(!confirm(&mut "".as_bytes(), &mut output, "Proceed?").unwrap());
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("Proceed? [y/N] "));
+    }
+}
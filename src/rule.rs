@@ -0,0 +1,487 @@
+//! 振り分けルールモジュール
+//!
+//! `size > 5MB && ext == jpg => Images/Large` のような簡易な式を解析し、
+//! 条件に一致したファイルを指定のカテゴリ（とサブフォルダ）に振り分けます。
+//! ルールは起動時に検証されるため、実行途中で構文エラーが起きることはありません。
+//!
+//! # 書式
+//! `<条件> => <カテゴリ>[/<サブフォルダ>...]`
+//!
+//! 条件は `<フィールド> <演算子> <値>` を `&&`（`and`）と `||`（`or`）でつないだものです。
+//! `&&` は `||` より優先されます。
+//!
+//! | フィールド | 値 | 演算子 |
+//! |---|---|---|
+//! | `size` | バイト数（`--min-size` と同じ書式。`KB`・`MB` などは1000倍、`KiB`・`MiB` などは1024倍） | `==` `!=` `<` `<=` `>` `>=` |
+//! | `age` | 最終更新からの経過時間（`s`, `m`, `h`, `d`, `w` の単位付き可） | `==` `!=` `<` `<=` `>` `>=` |
+//! | `ext` | 拡張子（ドットなし、大文字小文字を区別しない） | `==` `!=` `~` |
+//! | `name` | ファイル名 | `==` `!=` `~` |
+//!
+//! `~` は正規表現による部分一致です。空白や記号を含む値は `"..."` で囲みます。
+
+use crate::config::Category;
+use crate::file_ops::sanitize_folder_name;
+use crate::size::parse_size;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::{Serialize, Serializer};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// ルールの評価に使うファイルの情報
+#[derive(Debug, Clone)]
+pub struct FileFacts<'a> {
+    /// ファイル名
+    pub name: &'a str,
+    /// 拡張子（小文字、ドットなし）
+    pub extension: Option<&'a str>,
+    /// ファイルサイズ（バイト）
+    pub size: u64,
+    /// 最終更新からの経過時間
+    pub age: Duration,
+}
+
+/// 比較の対象となるフィールド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Size,
+    Age,
+    Ext,
+    Name,
+}
+
+/// 比較演算子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+}
+
+impl CmpOp {
+    fn compare<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            CmpOp::Eq => left == right,
+            CmpOp::Ne => left != right,
+            CmpOp::Lt => left < right,
+            CmpOp::Le => left <= right,
+            CmpOp::Gt => left > right,
+            CmpOp::Ge => left >= right,
+            CmpOp::Match => false,
+        }
+    }
+}
+
+/// 比較する値
+#[derive(Debug, Clone)]
+enum Operand {
+    /// バイト数または秒数
+    Number(u64),
+    /// 文字列（`ext` は小文字化済み）
+    Text(String),
+    /// 正規表現
+    Pattern(Regex),
+}
+
+/// `<フィールド> <演算子> <値>` の1つの比較
+#[derive(Debug, Clone)]
+struct Comparison {
+    field: Field,
+    op: CmpOp,
+    operand: Operand,
+}
+
+impl Comparison {
+    fn matches(&self, facts: &FileFacts<'_>) -> bool {
+        match (&self.operand, self.field) {
+            (Operand::Number(n), Field::Size) => self.op.compare(facts.size, *n),
+            (Operand::Number(n), Field::Age) => self.op.compare(facts.age.as_secs(), *n),
+            (Operand::Text(text), Field::Ext) => self
+                .op
+                .compare(facts.extension.unwrap_or(""), text.as_str()),
+            (Operand::Text(text), Field::Name) => self.op.compare(facts.name, text.as_str()),
+            (Operand::Pattern(re), Field::Ext) => re.is_match(facts.extension.unwrap_or("")),
+            (Operand::Pattern(re), Field::Name) => re.is_match(facts.name),
+            _ => false,
+        }
+    }
+}
+
+/// ルールに一致したファイルの振り分け先
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleTarget {
+    /// カテゴリ
+    pub category: Category,
+    /// カテゴリフォルダ内のサブフォルダ（ない場合は空）
+    pub subfolder: PathBuf,
+}
+
+/// 条件付きの振り分けルール
+#[derive(Debug, Clone)]
+pub struct Rule {
+    source: String,
+    /// `||` でつながれた、`&&` でつながれた比較の並び
+    condition: Vec<Vec<Comparison>>,
+    /// 振り分け先
+    pub target: RuleTarget,
+}
+
+impl Rule {
+    /// ファイルがルールの条件に一致するかどうか
+    pub fn matches(&self, facts: &FileFacts<'_>) -> bool {
+        self.condition
+            .iter()
+            .any(|all| all.iter().all(|cmp| cmp.matches(facts)))
+    }
+}
+
+impl PartialEq for Rule {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Rule {
+    /// 振り分け先に組み込みカテゴリのほか `custom` のカテゴリも使えるものとしてルールを解析する
+    pub fn parse_with_custom(s: &str, custom: &[Category]) -> Result<Self> {
+        parse_rule(s, custom).with_context(|| format!("Invalid rule '{}'", s))
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse_with_custom(s, &[])
+    }
+}
+
+impl Serialize for Rule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+/// 字句
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(CmpOp),
+    And,
+    Or,
+}
+
+fn parse_rule(s: &str, custom: &[Category]) -> Result<Rule> {
+    let (condition, target) = split_arrow(s)?;
+
+    let tokens = tokenize(condition)?;
+    if tokens.is_empty() {
+        bail!("missing condition before '=>'");
+    }
+
+    let mut any = Vec::new();
+    let mut all = Vec::new();
+    let mut tokens = tokens.into_iter();
+    loop {
+        all.push(parse_comparison(&mut tokens)?);
+        match tokens.next() {
+            None => break,
+            Some(Token::And) => {}
+            Some(Token::Or) => any.push(std::mem::take(&mut all)),
+            Some(other) => bail!("expected '&&' or '||', found {:?}", other),
+        }
+    }
+    any.push(all);
+
+    Ok(Rule {
+        source: s.to_string(),
+        condition: any,
+        target: parse_target(target, custom)?,
+    })
+}
+
+/// 引用符の外にある最初の `=>` で条件と振り分け先に分ける
+fn split_arrow(s: &str) -> Result<(&str, &str)> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '=' if !in_quotes && s[i..].starts_with("=>") => {
+                return Ok((&s[..i], &s[i + 2..]));
+            }
+            _ => {}
+        }
+    }
+    bail!("expected '<condition> => <category>'")
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            let mut closed = false;
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => {
+                        closed = true;
+                        break;
+                    }
+                    '\\' => match chars.next() {
+                        Some('"') => text.push('"'),
+                        Some(other) => {
+                            text.push('\\');
+                            text.push(other);
+                        }
+                        None => text.push('\\'),
+                    },
+                    other => text.push(other),
+                }
+            }
+            if !closed {
+                bail!("unclosed '\"'");
+            }
+            tokens.push(Token::Quoted(text));
+            continue;
+        }
+
+        if "=!<>~&|".contains(c) {
+            let mut op = String::new();
+            while let Some(&c) = chars.peek() {
+                if !"=!<>~&|".contains(c) {
+                    break;
+                }
+                op.push(c);
+                chars.next();
+            }
+            tokens.push(match op.as_str() {
+                "==" => Token::Op(CmpOp::Eq),
+                "!=" => Token::Op(CmpOp::Ne),
+                "<" => Token::Op(CmpOp::Lt),
+                "<=" => Token::Op(CmpOp::Le),
+                ">" => Token::Op(CmpOp::Gt),
+                ">=" => Token::Op(CmpOp::Ge),
+                "~" => Token::Op(CmpOp::Match),
+                "&&" => Token::And,
+                "||" => Token::Or,
+                other => bail!("unknown operator '{}'", other),
+            });
+            continue;
+        }
+
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '"' || "=!<>~&|".contains(c) {
+                break;
+            }
+            word.push(c);
+            chars.next();
+        }
+        tokens.push(match word.to_lowercase().as_str() {
+            "and" => Token::And,
+            "or" => Token::Or,
+            _ => Token::Word(word),
+        });
+    }
+
+    Ok(tokens)
+}
+
+fn parse_comparison(tokens: &mut impl Iterator<Item = Token>) -> Result<Comparison> {
+    let field = match tokens.next() {
+        Some(Token::Word(word)) => match word.to_lowercase().as_str() {
+            "size" => Field::Size,
+            "age" => Field::Age,
+            "ext" => Field::Ext,
+            "name" => Field::Name,
+            _ => bail!("unknown field '{}' (expected size, age, ext or name)", word),
+        },
+        Some(other) => bail!("expected a field name, found {:?}", other),
+        None => bail!("expected a comparison"),
+    };
+
+    let op = match tokens.next() {
+        Some(Token::Op(op)) => op,
+        _ => bail!("expected an operator after '{:?}'", field),
+    };
+
+    let value = match tokens.next() {
+        Some(Token::Word(value)) | Some(Token::Quoted(value)) => value,
+        _ => bail!("expected a value after the operator"),
+    };
+
+    let operand = match field {
+        Field::Size | Field::Age => {
+            if op == CmpOp::Match {
+                bail!("'~' can only be used with ext or name");
+            }
+            let n = if field == Field::Size {
+                parse_size(&value)?
+            } else {
+                parse_age(&value)?
+            };
+            Operand::Number(n)
+        }
+        Field::Ext | Field::Name => {
+            let value = if field == Field::Ext {
+                value.trim_start_matches('.').to_lowercase()
+            } else {
+                value
+            };
+            match op {
+                CmpOp::Match => Operand::Pattern(
+                    Regex::new(&value).with_context(|| format!("invalid pattern '{}'", value))?,
+                ),
+                CmpOp::Eq | CmpOp::Ne => Operand::Text(value),
+                _ => bail!("only '==', '!=' and '~' can be used with ext or name"),
+            }
+        }
+    };
+
+    Ok(Comparison { field, op, operand })
+}
+
+/// 数値と単位に分ける（`5MB` → `(5, "mb")`）
+fn split_unit(value: &str) -> Result<(u64, String)> {
+    let digits = value.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        bail!("expected a number, found '{}'", value);
+    }
+    let number = value[..digits]
+        .parse()
+        .with_context(|| format!("number out of range: '{}'", value))?;
+    Ok((number, value[digits..].to_lowercase()))
+}
+
+fn parse_age(value: &str) -> Result<u64> {
+    let (number, unit) = split_unit(value)?;
+    let multiplier: u64 = match unit.as_str() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => bail!("unknown age unit in '{}'", value),
+    };
+    number
+        .checked_mul(multiplier)
+        .with_context(|| format!("age out of range: '{}'", value))
+}
+
+/// `Category[/サブフォルダ...]` を解析する（カテゴリは組み込みか `custom` のもの）
+fn parse_target(target: &str, custom: &[Category]) -> Result<RuleTarget> {
+    let mut parts = target.trim().split('/');
+    let category = Category::parse_with_custom(parts.next().unwrap_or(""), custom)?;
+
+    let mut subfolder = PathBuf::new();
+    for part in parts {
+        let name =
+            sanitize_folder_name(part).with_context(|| format!("invalid subfolder '{}'", part))?;
+        subfolder.push(name);
+    }
+
+    Ok(RuleTarget {
+        category,
+        subfolder,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts(name: &str, size: u64) -> FileFacts<'_> {
+        FileFacts {
+            name,
+            extension: name.rsplit_once('.').map(|(_, ext)| ext),
+            size,
+            age: Duration::from_secs(3600),
+        }
+    }
+
+    #[test]
+    fn test_size_rule() {
+        let rule: Rule = "size > 5MB && ext == JPG => Images/Large".parse().unwrap();
+        assert_eq!(
+            rule.target,
+            RuleTarget {
+                category: Category::Images,
+                subfolder: PathBuf::from("Large"),
+            }
+        );
+        assert!(rule.matches(&facts("photo.jpg", 6_000_000)));
+        assert!(!rule.matches(&facts("photo.jpg", 1024)));
+        assert!(!rule.matches(&facts("movie.mp4", 6_000_000)));
+
+        // 単位は --min-size と同じく MB が1000倍、MiB が1024倍
+        let rule: Rule = "size > 5MB => Images".parse().unwrap();
+        assert!(rule.matches(&facts("photo.jpg", 5_000_001)));
+        let rule: Rule = "size > 5MiB => Images".parse().unwrap();
+        assert!(!rule.matches(&facts("photo.jpg", 5_000_001)));
+        assert!(rule.matches(&facts("photo.jpg", 5 * 1024 * 1024 + 1)));
+
+        let rule: Rule = "age >= 2h or size == 0 => Others".parse().unwrap();
+        assert!(!rule.matches(&facts("a.txt", 1)));
+        assert!(rule.matches(&facts("a.txt", 0)));
+    }
+
+    #[test]
+    fn test_name_regex_rule() {
+        let rule: Rule = r#"name ~ "^invoice[-_ ]\d+" => Documents/Invoices"#
+            .parse()
+            .unwrap();
+        assert!(rule.matches(&facts("invoice_2024.pdf", 10)));
+        assert!(!rule.matches(&facts("my invoice.pdf", 10)));
+    }
+
+    #[test]
+    fn test_custom_category_target() {
+        let fonts = Category::custom("Fonts").unwrap();
+        let rule =
+            Rule::parse_with_custom("ext == ttf => Fonts/TrueType", std::slice::from_ref(&fonts))
+                .unwrap();
+        assert_eq!(
+            rule.target,
+            RuleTarget {
+                category: fonts,
+                subfolder: PathBuf::from("TrueType"),
+            }
+        );
+        // 定義されていないカテゴリは使えない
+        assert!("ext == ttf => Fonts".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for rule in [
+            "size > 5MB",
+            "=> Images",
+            "color == red => Images",
+            "size ~ 5 => Images",
+            "size > 5XB => Images",
+            "name < abc => Images",
+            "name ~ \"(\" => Images",
+            "ext == jpg => Pictures",
+            "ext == jpg && => Images",
+        ] {
+            assert!(rule.parse::<Rule>().is_err(), "{rule} should be rejected");
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! 出力スキーマモジュール
+//!
+//! 機械可読な出力（`--format json` の統計、`--ipc-socket` で送信するイベント、
+//! `--export-plan` で書き出す計画）のJSON Schemaを生成します。
+//! スキーマは `Serialize` と同じ型定義から生成するため、出力と常に一致します。
+
+use crate::observer::SortEvent;
+use crate::sorter::{FilePlan, SortStats};
+use anyhow::{Context, Result};
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+
+/// smart-sorter の機械可読な出力（統計・イベント・計画）のいずれか
+// スキーマの生成にだけ使う型
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum Output {
+    /// `--format json` で最後に出力する統計
+    Summary(Box<SortStats>),
+    /// `--ipc-socket` で送信する1行（1イベント）
+    Event(SortEvent),
+    /// `--export-plan` で書き出す移動の計画
+    Plan(Vec<FilePlan>),
+}
+
+/// 機械可読な出力（統計・イベント・計画）のいずれかを表すスキーマ
+pub fn output_schema() -> RootSchema {
+    let mut schema = schema_for!(Output);
+    if let Some(metadata) = schema.schema.metadata.as_mut() {
+        metadata.title = Some("smart-sorter output".to_string());
+    }
+    schema
+}
+
+/// スキーマを整形済みのJSON文字列に変換
+pub fn output_schema_json() -> Result<String> {
+    serde_json::to_string_pretty(&output_schema()).context("Failed to serialize JSON schema")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observer::SortObserver;
+    use crate::sorter::{Sorter, SorterConfig};
+    use jsonschema::JSONSchema;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
+
+    /// 受け取ったイベントをJSONとして保持するオブザーバー
+    struct Recorder(Arc<Mutex<Vec<serde_json::Value>>>);
+
+    impl SortObserver for Recorder {
+        fn on_event(&mut self, event: &SortEvent) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(serde_json::to_value(event).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_real_output_matches_schema() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("notes.pdf")).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let plans = serde_json::to_value(Sorter::new(config.clone()).plan().unwrap()).unwrap();
+        Sorter::new(config)
+            .with_observer(Box::new(Recorder(Arc::clone(&events))))
+            .run()
+            .unwrap();
+
+        let schema = serde_json::to_value(output_schema()).unwrap();
+        let validator = JSONSchema::compile(&schema).unwrap();
+        let events = events.lock().unwrap();
+        // Started, Moved x2, Finished
+        assert_eq!(events.len(), 4);
+        for event in events.iter() {
+            assert!(validator.is_valid(event), "invalid event: {}", event);
+        }
+
+        // --export-plan の計画と --format json の統計も同じスキーマに合う
+        assert!(validator.is_valid(&plans), "invalid plan: {}", plans);
+        let summary: serde_json::Value = serde_json::from_str(
+            &Sorter::new(SorterConfig {
+                target_dir: dir.path().to_path_buf(),
+                ..Default::default()
+            })
+            .stats_only()
+            .unwrap()
+            .to_json(&HashMap::new())
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(validator.is_valid(&summary), "invalid summary: {}", summary);
+
+        // スキーマに合わない出力は検出される
+        let bogus = serde_json::json!({ "event": "moved", "source": 1 });
+        assert!(!validator.is_valid(&bogus));
+    }
+}
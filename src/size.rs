@@ -0,0 +1,59 @@
+//! サイズ指定の解析モジュール
+//!
+//! `100MB`・`2GiB` のような人が読みやすいサイズの表記をバイト数に変換します。
+//! `KB`・`MB` などは1000倍（SI）、`KiB`・`MiB` などは1024倍（2進）として扱います。
+
+use anyhow::{bail, Context, Result};
+
+/// `100MB`・`2GiB`・`1.5G`・`512` 形式のサイズを解析する（単位なしはバイト）
+///
+/// 単位の大文字小文字は問わず、数値と単位の間の空白も許容します。
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let number_len = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(number_len);
+    if number.is_empty() {
+        bail!("Invalid size '{}' (expected e.g. 100MB, 2GiB, 512)", value);
+    }
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("Invalid number in size '{}'", value))?;
+
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        "t" | "tb" => 1_000_000_000_000,
+        "tib" => 1 << 40,
+        unit => bail!("Unknown size unit '{}' in '{}'", unit, value),
+    };
+
+    let bytes = (number * multiplier as f64).round();
+    if bytes > u64::MAX as f64 {
+        bail!("Size out of range: '{}'", value);
+    }
+    Ok(bytes as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("100MB").unwrap(), 100_000_000);
+        assert_eq!(parse_size("2GiB").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1.5 kib").unwrap(), 1536);
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("1.2.3MB").is_err());
+        assert!(parse_size("99999999999TB").is_err());
+    }
+}
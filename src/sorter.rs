@@ -3,45 +3,429 @@
 //! ファイル分類のコアロジックを担当します。
 //! ディレクトリの走査、ファイルの分類、移動処理を統括します。
 
-use crate::config::{get_category, get_default_category, Category};
+use crate::archive::ArchiveWriter;
+use crate::config::{
+    check_folder_names, code_language, correct_extension, get_category, get_default_category,
+    override_collisions, read_dir_category, Category, LocalConfigs, OverrideCollision,
+    DIR_CATEGORY_FILE_NAME, EXTENSION_MAP, LOCAL_CONFIG_FILE_NAME,
+};
+use crate::date::Date;
+use crate::dedup::{partition_content_duplicates, ContentDuplicate};
 use crate::file_ops::{
-    ensure_directory, generate_unique_path, get_extension, is_directory, is_file, is_symlink,
-    move_file_with_dedup,
+    copy_file_with_dedup_as, create_hardlink, create_symlink, ensure_directory,
+    ensure_same_filesystem, files_are_identical, fit_file_name, generate_unique_path_limited,
+    get_extension, hardlink_file_with_dedup_as, hash_file, is_directory, is_executable,
+    is_existing_transfer, is_file, is_file_locked, is_hidden, is_in_use_error, is_marked_sorted,
+    is_modified_within, is_symlink, is_writable_dir, mark_sorted, move_file, move_file_with_dedup,
+    move_file_with_dedup_as, remove_empty_dirs, resolve_conflict, sanitize_folder_name,
+    sniff_extension, split_compound_extension, ConflictStrategy, LinkMethod, MoveMethod, OpKind,
+    OpResult, Replaced, RetryPolicy, UniqueNaming,
 };
-use anyhow::{Context, Result};
+use crate::journal::{absolute, Journal, UndoReport, JOURNAL_FILE_NAME};
+use crate::language::detect_text_language;
+use crate::lifetime::LifetimeStore;
+#[cfg(unix)]
+use crate::mime::MimeDatabase;
+use crate::observer::{AfterMoveHook, BeforeMoveHook, SortEvent, SortObserver};
+use crate::oplog::{rfc3339, Operation, OperationEntry, OperationLog, Outcome};
+use crate::output::{self, detail, say, OutputFormat, Progress, Reporter};
+use crate::preflight::PreflightReport;
+use crate::prompt;
+use crate::rule::{FileFacts, Rule};
+use crate::stability::{file_size, settle, StabilityPolicy};
+use crate::sync::{archive_files, SyncReport};
+use crate::template::{DedupTemplate, DestTemplate, RenameTemplate, TemplateContext};
+use crate::tree::PathTree;
+use crate::verify::{verify_moves, FileProbe, MoveRecord, RealFs};
+use crate::watch::{arrived_paths, PendingFiles, WATCH_POLL_INTERVAL};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
 use colored::Colorize;
-use std::collections::HashMap;
-use std::fs;
-use std::path::{Path, PathBuf};
+use glob::Pattern;
+use notify::{RecursiveMode, Watcher};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use tracing::{debug, info, warn};
 
+/// `batch_size` 指定時の連番フォルダ名の接頭辞（`Batch_001` など）
+pub const BATCH_FOLDER_PREFIX: &str = "Batch_";
+
+/// 拡張子のないファイルを入れるサブフォルダ名（`subfolder_by_ext` と `--dest-template` の `{ext}`）
+pub const NO_EXTENSION_FOLDER: &str = "_noext";
+
+/// `others_by_ext` 指定時に拡張子のない Others のファイルを入れるサブフォルダ名
+pub const OTHERS_NO_EXTENSION_FOLDER: &str = "none";
+
+/// `by_repo` 指定時にリポジトリごとのフォルダをまとめるフォルダ名
+pub const REPOS_FOLDER: &str = "Repos";
+
+/// リポジトリのルートを示すディレクトリ名
+const VCS_MARKERS: &[&str] = &[".git", ".hg"];
+
 /// ソーターの設定
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct SorterConfig {
     /// 対象ディレクトリ
     pub target_dir: PathBuf,
+    /// 2つ目以降の対象ディレクトリ（`run` で `target_dir` の後に順に整理する）
+    pub more_target_dirs: Vec<PathBuf>,
+    /// 移動とDry Runの間、処理したファイル数と残り時間の目安を進捗バーで表示する
+    pub progress: bool,
+    /// Dry Runで重複回避後の移動計画を書き出すファイル
+    pub export_plan: Option<PathBuf>,
     /// Dry Runモード
     pub dry_run: bool,
     /// 再帰処理
     pub recursive: bool,
+    /// 再帰処理で降りるサブディレクトリの深さの上限（0 なら対象ディレクトリ直下のみ）
+    pub max_depth: Option<usize>,
+    /// これより小さい（バイト数）ファイルは除外する
+    pub min_size: Option<u64>,
+    /// これより大きい（バイト数）ファイルは除外する
+    pub max_size: Option<u64>,
+    /// 整理の対象から黙って外すファイル名のグロブパターン
+    pub exclude: Vec<String>,
+    /// 指定した場合、いずれかに一致するファイル名だけを整理の対象にするグロブパターン
+    pub include: Vec<String>,
+    /// `exclude`・`include` をファイル名ではなく対象ディレクトリからの相対パスに照合する
+    pub match_path: bool,
+    /// 移動後、今回の移動でファイルが1つだけになったカテゴリフォルダを解消し、ファイルを戻す先
+    pub collapse_singletons: Option<CollapseTarget>,
+    /// 移動（または `flatten`）の後、対象ディレクトリ以下の空のフォルダを削除する
+    pub prune_empty: bool,
+    /// 移動先のファイル名を組み立てるテンプレート
+    pub rename_template: Option<RenameTemplate>,
+    /// カテゴリに関係なく、この件数ずつ `Batch_001/` などの連番フォルダに振り分ける
+    pub batch_size: Option<usize>,
+    /// 移動先ディレクトリ（対象ディレクトリからの相対パス）を組み立てるテンプレート
+    pub dest_template: Option<DestTemplate>,
+    /// 内容が同一のファイルは最初の1つだけを移動し、残りはスキップする
+    pub merge_content_duplicates: bool,
+    /// 整理の前に、内容が同一のファイルを最初の1つだけ残してゴミ箱に移す
+    pub dedupe_first: bool,
+    /// `dedupe_first` で重複をゴミ箱に移さず、完全に削除する
+    pub dedupe_delete: bool,
+    /// 内容の重複検出で保持するハッシュ数の上限（`None` なら無制限）
+    pub dedup_window: Option<usize>,
+    /// 移動先に同名・同一内容のファイルが既にある場合、リネームして移動せず重複として数える
+    pub dedup_content: bool,
+    /// 移動の代わりに、カテゴリフォルダ構造でこのzipファイルに書き出す
+    pub archive_output: Option<PathBuf>,
+    /// zipへの書き出し後に元ファイルを削除する
+    pub archive_delete_originals: bool,
+    /// 移動の代わりに、このディレクトリにカテゴリフォルダ構造のシンボリックリンクを作成する
+    pub symlink_farm: Option<PathBuf>,
+    /// 移動の代わりに、このディレクトリにカテゴリフォルダ構造のハードリンクを作成する
+    pub hardlink_view: Option<PathBuf>,
+    /// 移動の代わりにコピーする（元のファイルはそのまま残す）
+    pub copy: bool,
+    /// 移動の代わりにハードリンクを作成する（元のファイルはそのまま残し、容量を使わない）
+    pub hardlink: bool,
+    /// 対象ディレクトリを受信箱として、このアーカイブのカテゴリ構成に振り分ける
+    pub sync_into: Option<PathBuf>,
+    /// 対象ディレクトリではなく、このディレクトリの下にカテゴリフォルダを作って振り分ける
+    pub output_dir: Option<PathBuf>,
+    /// 移動（Dry Runでは移動の予定）を1件ずつ JSON Lines で追記する操作ログのパス
+    pub log_file: Option<PathBuf>,
+    /// 移動先のファイル名のバイト数の上限（超える場合は拡張子を残して切り詰める）
+    pub max_filename_bytes: Option<usize>,
+    /// 移動先で名前が重複したときに連番を付けるファイル名のテンプレート（`None` なら `stem_N.ext`）
+    pub dedup_template: Option<DedupTemplate>,
+    /// 実行権限を持つファイルを拡張子より優先して振り分けるカテゴリ（Unixのみ）
+    pub detect_executables: Option<Category>,
+    /// Dry Runのプレビューで移動元と移動先を列で揃えて表示する
+    pub columns: bool,
+    /// Dry Runのプレビューを1行ずつではなく移動先のツリーで表示する
+    pub tree: bool,
+    /// 対象ディレクトリを走査せずに整理するファイルの一覧（`--files-from`）
+    #[serde(skip)]
+    pub files_from: Option<Vec<PathBuf>>,
+    /// Dry Runで各移動先フォルダに既に存在するものとみなすファイル名
+    pub assume_existing: BTreeSet<String>,
+    /// Codeカテゴリのファイルを言語ごとのサブフォルダ（`Code/Rust/` など）に振り分ける
+    pub code_by_language: bool,
+    /// Documentsカテゴリのテキストファイルを内容の言語ごとのサブフォルダ（`Documents/English/` など）に振り分ける
+    pub doc_by_language: bool,
+    /// 移動したHEIC/HEIF画像をJPEGに変換する
+    #[cfg(feature = "heic")]
+    pub convert_heic: bool,
+    /// HEIC/HEIF画像の変換後も元の画像を残す
+    #[cfg(feature = "heic")]
+    pub keep_heic: bool,
+    /// 一時的なファイルシステムエラーに対する再試行の設定
+    pub retry: RetryPolicy,
+    /// 移動を並列に行うスレッド数（0 と 1 は逐次）
+    pub jobs: usize,
+    /// 1件でも移動に失敗したら、それまでの移動をすべて元に戻してエラーにする
+    pub atomic: bool,
+    /// 移動先に同名のファイルが既にある場合の扱い
+    pub on_conflict: ConflictStrategy,
+    /// ダウンロードフォルダ向けの処理（`.torrent` の振り分け、ダウンロード途中のファイルの除外）
+    pub handle_downloads: bool,
+    /// `handle_downloads` 時にダウンロード途中とみなす拡張子（小文字、ドットなし）
+    pub fragment_extensions: BTreeSet<String>,
+    /// ファイル名の日付（なければ更新日時）でカテゴリフォルダ内の `年/月/` に振り分ける
+    pub date_from_name: bool,
+    /// 更新日時（取得できなければ作成日時）でカテゴリフォルダ内の `年/月/` に振り分ける
+    pub by_date: bool,
+    /// 末尾の拡張子が未知の場合、その前のドット区切りの部分でも分類を試みる
+    pub deep_ext_match: bool,
+    /// 拡張子のないファイルを先頭のバイト（マジックナンバー）から分類する
+    pub detect_content: bool,
+    /// サブディレクトリのファイルを、対象ディレクトリからの相対パスを保って振り分ける
+    /// （`a/b/photo.jpg` → `Images/a/b/photo.jpg`）
+    pub preserve_tree: bool,
+    /// シンボリックリンクのファイルとディレクトリもたどって整理する
+    ///
+    /// 移動されるのはリンク自体で、リンク先のファイルは元の場所に残ります。
+    /// リンクしたディレクトリの中のファイルは実体が移動されます。
+    pub follow_symlinks: bool,
+    /// カテゴリフォルダの中を小文字の拡張子ごとのサブフォルダに分ける
+    /// （`Code/rs/main.rs`、拡張子がなければ `_noext`）
+    pub subfolder_by_ext: bool,
+    /// Others のファイルだけを拡張子ごとのサブフォルダに分ける
+    /// （`Others/xyz/data.xyz`、拡張子がなければ `Others/none/`）
+    pub others_by_ext: bool,
+    /// 既知の拡張子の打ち間違い（`.jgp` など）を訂正して分類し、移動先の拡張子も直す
+    pub fix_extensions: bool,
+    /// 再帰時に各ファイルの祖先ディレクトリにある `.smartsorter.toml` を参照する
+    pub local_configs: bool,
+    /// ディレクトリに `.category` マーカーがあれば、そのディレクトリのファイルを記されたカテゴリに振り分ける
+    pub dir_category_files: bool,
+    /// 拡張子による分類より先に、上から順に評価する振り分けルール
+    pub rules: Vec<Rule>,
+    /// ファイル名がこの区切り文字を含む場合、最初の区切りより前の部分をフォルダ名として振り分ける
+    pub group_by_prefix: Option<String>,
+    /// 設定ファイルで定義されたカテゴリ（フォルダの探索で組み込みカテゴリと同様に扱う）
+    pub custom_categories: Vec<Category>,
+    /// 最寄りの git / hg リポジトリごとに `Repos/<リポジトリ名>/` に振り分ける
+    pub by_repo: bool,
+    /// 種類に関係なく移動せずその場に残すファイル名（完全一致）
+    pub keep_at_root: BTreeSet<String>,
+    /// `keep_at_root` の照合で大文字・小文字を区別しない
+    pub keep_at_root_ignore_case: bool,
+    /// 移動したファイルに整理済みの印（拡張属性）を付ける
+    pub mark_sorted: bool,
+    /// 整理済みの印が付いたファイルを除外する
+    pub skip_marked: bool,
+    /// 空（0バイト）のファイルの扱い
+    pub empty_files: EmptyFiles,
+    /// 隠しファイル（`.` で始まる名前）を除外し、隠しディレクトリには再帰しない
+    pub skip_hidden: bool,
+    /// 最終更新からこの秒数が経過していないファイルを除外する
+    pub min_age_secs: Option<u64>,
+    /// この時刻以降に更新されたファイルを除外する（`--older-than`）
+    pub modified_before: Option<SystemTime>,
+    /// この時刻より前に更新されたファイルを除外する（`--newer-than`）
+    pub modified_after: Option<SystemTime>,
+    /// サイズが連続して変化しなくなるまで一定間隔で確認し、書き込み中のファイルを除外する
+    pub stabilize_wait: Option<StabilityPolicy>,
+    /// 移動前にプレビューを表示し、続行するか確認する
+    pub confirm: bool,
+    /// 移動前に実行内容（件数・サイズ・作成するフォルダなど）をまとめて表示し、続行するか確認する
+    pub preflight: bool,
+    /// 実際の移動の前に、移動する件数を示して続行するか確認する
+    pub confirm_move: bool,
+    /// 続行の確認（`confirm_move`・`confirm`・`preflight`）を省略して続行する
+    pub assume_yes: bool,
+    /// 移動前にカテゴリごとに続行するか確認する
+    pub confirm_per_category: bool,
+    /// 移動の記録をジャーナルとして対象ディレクトリに書き出す
+    pub write_journal: bool,
+    /// 実際にファイルを移動した実行の統計を積み上げる累計統計ファイル
+    pub lifetime_stats: Option<PathBuf>,
+    /// サマリーの後にカテゴリの内訳を横棒グラフで表示する
+    pub visual_summary: bool,
+    /// 実行後、ファイルを受け取ったフォルダの絶対パスを1行ずつ出力する
+    pub print_folders: bool,
+    /// 実行結果の出力形式（`Json` なら最後に統計だけをJSONで出力する）
+    pub output_format: OutputFormat,
+    /// `print_folders` の各行にタブ区切りでファイル数を付ける
+    pub print_folder_counts: bool,
+    /// 更新日時の新しい順にファイルを処理する
+    pub newest_first: bool,
+    /// 処理するファイル数の上限（並べ替えの後に適用）
+    pub max_files: Option<usize>,
+    /// カテゴリごとのファイル数の下限（これ未満のカテゴリは移動せずに残す）
+    pub min_files_per_category: Option<usize>,
+    /// システムやホームディレクトリなど危険なディレクトリでも実行する
+    pub force: bool,
+    /// 移動後、移動先の存在とサイズ、移動元が消えていることを検証する
+    pub validate_after_move: bool,
+    /// `validate_after_move` 時、コピーで移動したファイルを再ハッシュして内容も検証する
+    pub validate_rehash: bool,
+    /// 組み込みマッピングより優先して参照するシステムのMIMEデータベース（Unixのみ）
+    #[cfg(unix)]
+    #[serde(skip)]
+    pub system_mime: Option<MimeDatabase>,
+    /// 設定ファイル等による拡張子マッピングの上書き（組み込みマッピングより優先）
+    #[serde(skip)]
+    pub extension_overrides: HashMap<String, Category>,
+    /// コマンドラインの `--map` による拡張子マッピングの上書き（最優先）
+    #[serde(skip)]
+    pub cli_overrides: HashMap<String, Category>,
+    /// カテゴリごとのフォルダ名の上書き（`--category-name` と設定ファイルの `[folder_names]`）
+    #[serde(skip)]
+    pub folder_names: HashMap<Category, String>,
+}
+
+impl SorterConfig {
+    /// `--safe` で除外する、更新から間もないファイルの基準（秒）
+    pub const SAFE_MIN_AGE_SECS: u64 = 60;
+
+    /// 初めて使う人向けの保守的な設定（`--safe`）を適用する
+    ///
+    /// 以下の設定を有効にします。他のオプションはそのまま維持されます。
+    /// - `confirm`: 移動前にDry Runと同じプレビューを表示し、`y` の入力で続行する
+    /// - `skip_hidden`: 隠しファイルを除外し、隠しディレクトリには再帰しない
+    /// - `min_age_secs`: 更新から60秒未満のファイルを除外する
+    /// - `write_journal`: 移動の記録を `.smart-sorter-journal.json` に書き出す
+    ///
+    /// 既存ファイルの上書きは常に行わず、同名の場合は連番でリネームします。
+    /// 既存のファイルを置き換える `on_conflict` が指定されている場合はエラーを返します。
+    pub fn apply_safe_preset(&mut self) -> Result<()> {
+        if self.on_conflict.replaces_existing() {
+            bail!(
+                "--safe cannot be combined with --on-conflict {:?}: it never replaces existing files",
+                self.on_conflict
+            );
+        }
+        self.confirm = true;
+        self.skip_hidden = true;
+        self.min_age_secs = Some(Self::SAFE_MIN_AGE_SECS);
+        self.write_journal = true;
+        Ok(())
+    }
+
+    /// 設定ファイルや `--map` による上書きのうち、組み込みの拡張子のカテゴリを変えるもの
+    pub fn override_collisions(&self) -> Vec<OverrideCollision> {
+        override_collisions(&self.resolved().extensions)
+    }
+
+    /// 最終的に有効な設定を取得する
+    pub fn resolved(&self) -> ResolvedConfig<'_> {
+        let mut extensions: BTreeMap<String, Category> = EXTENSION_MAP
+            .iter()
+            .map(|(ext, category)| (ext.to_string(), category.clone()))
+            .collect();
+        extensions.extend(
+            self.extension_overrides
+                .iter()
+                .chain(&self.cli_overrides)
+                .map(|(ext, category)| (ext.clone(), category.clone())),
+        );
+
+        let folder_names = self
+            .folder_names
+            .iter()
+            .map(|(category, folder)| (category.folder_name().to_string(), folder.clone()))
+            .collect();
+
+        ResolvedConfig {
+            options: self,
+            extensions,
+            folder_names,
+        }
+    }
+}
+
+/// 全レイヤー（環境変数・設定ファイル・CLI）を解決した後の最終的な設定
+#[derive(Debug, Serialize)]
+pub struct ResolvedConfig<'a> {
+    /// 実行オプション
+    pub options: &'a SorterConfig,
+    /// 拡張子 → カテゴリ のマッピング（組み込み + 上書き）
+    pub extensions: BTreeMap<String, Category>,
+    /// カテゴリ名 → フォルダ名 の上書き
+    pub folder_names: BTreeMap<String, String>,
+}
+
+impl ResolvedConfig<'_> {
+    /// TOML形式の文字列に変換
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).context("Failed to serialize config as TOML")
+    }
+
+    /// JSON形式の文字列に変換
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize config as JSON")
+    }
 }
 
 /// ファイル分類の計画（移動前の状態）
-#[derive(Debug, Clone)]
+///
+/// `--export-plan` でJSONに書き出し、`--apply-plan` で読み込めます。
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FilePlan {
     /// 移動元のパス
     pub source: PathBuf,
     /// 移動先のパス（重複回避前の予定パス）
-    #[allow(dead_code)]
     pub destination: PathBuf,
     /// 分類されるカテゴリ
     pub category: Category,
-    /// 移動先に重複ファイルが存在するか
-    pub has_conflict: bool,
+}
+
+impl FilePlan {
+    /// 移動先ディレクトリ（カテゴリフォルダ、またはその配下のサブフォルダ）
+    pub fn dest_dir(&self) -> &Path {
+        self.destination.parent().unwrap_or(&self.destination)
+    }
+}
+
+/// 移動中に集計する状態（`--jobs` 指定時はスレッド間で共有する）
+struct MoveState<'a> {
+    stats: SortStats,
+    journal: &'a mut Journal,
+    /// 移動後の検証のための記録
+    records: Vec<MoveRecord>,
+    /// `atomic` 時、元に戻すために控える完了した移動（移動元, 移動先, 方法）
+    completed: Vec<(PathBuf, PathBuf, MoveMethod)>,
+    /// この実行で書き込む（書き込んだ）移動先。ここにあるファイルは置き換えない
+    written: HashSet<PathBuf>,
+    /// 整理済みの印を付けられなかった警告を表示したか
+    mark_warned: bool,
+}
+
+/// 移動の計画と、計画を作るまでに除いたファイル（`Sorter::build_plans` の結果）
+struct PlannedRun {
+    /// 移動の計画
+    plans: Vec<FilePlan>,
+    /// 計画の対象にしたファイル数（内容の重複を除いた後）
+    file_count: usize,
+    /// 除外パターンに一致したファイル数
+    excluded: usize,
+    /// 対象パターンに一致しなかったファイル数
+    not_included: usize,
+    /// フィルタでスキップしたファイル
+    skipped: Vec<SkippedFile>,
+    /// 件数の上限で切り詰めた場合、切り詰める前のファイル数
+    truncated_from: Option<usize>,
+    /// 内容が重複していたため計画から除いたファイル
+    duplicates: Vec<ContentDuplicate>,
+    /// 受信箱とアーカイブの突き合わせ（`sync_into`）
+    sync_report: Option<SyncReport>,
+    /// カテゴリフォルダの中で既に振り分け済みのファイル数
+    already_sorted: usize,
+    /// 移動先が今の場所と同じだったため計画から除いたファイル数
+    in_place: usize,
+    /// ファイルの少ないカテゴリのため移動せずに残すファイル
+    left_in_place: Vec<SkippedFile>,
 }
 
 /// 分類処理の統計情報
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
 pub struct SortStats {
     /// 処理対象ファイル数
     pub total_files: usize,
@@ -49,73 +433,700 @@ pub struct SortStats {
     pub moved_files: usize,
     /// リネームされたファイル数
     pub renamed_files: usize,
+    /// 移動先の既存ファイルを上書き、またはゴミ箱に移して置き換えた数
+    pub replaced_files: usize,
     /// スキップされたファイル数
     pub skipped_files: usize,
+    /// 既に正しいカテゴリフォルダにあったため移動しなかったファイル数
+    pub already_sorted: usize,
     /// エラー数
     pub error_count: usize,
+    /// 他のプロセスが使用中のためスキップされたファイル数
+    pub locked_files: usize,
+    /// Dry Runで、実際に移動すると権限不足で失敗すると判断したファイル数
+    pub would_fail: usize,
+    /// 内容が重複していたためスキップされたファイル数
+    pub duplicate_files: usize,
+    /// 除外パターンに一致したため対象から外したファイル数
+    pub excluded_files: usize,
+    /// 整理前の重複除去で削除されたファイル数
+    pub removed_duplicates: usize,
+    /// 移動後の検証で見つかった不整合の数
+    pub discrepancies: usize,
+    /// JPEGに変換された画像数
+    pub converted_files: usize,
+    /// 解消された（ルートに戻された）カテゴリフォルダ数
+    pub collapsed_folders: usize,
+    /// 空になって削除されたカテゴリフォルダ数
+    pub removed_folders: usize,
     /// カテゴリごとのファイル数
     pub category_counts: HashMap<Category, usize>,
+    /// カテゴリごとの移動した（Dry Runでは移動する予定の）バイト数
+    ///
+    /// サイズを読めなかったファイルは0バイトとして数えます。
+    pub category_bytes: HashMap<Category, u64>,
+    /// `others_by_ext` 指定時、Others に振り分けたファイルの拡張子ごとのファイル数
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub others_extensions: BTreeMap<String, usize>,
+    /// ファイルを受け取ったフォルダ（絶対パス）ごとのファイル数
+    pub folder_counts: BTreeMap<PathBuf, usize>,
+    /// `--sync-into` での受信箱とアーカイブの突き合わせ結果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncReport>,
 }
 
 impl SortStats {
-    /// 統計情報のサマリーを表示
-    pub fn print_summary(&self, dry_run: bool) {
-        println!();
+    /// 統計を整形済みのJSON文字列に変換
+    ///
+    /// カテゴリごとの件数とバイト数は、`folder_names` で上書きされたフォルダ名をキーにします。
+    pub fn to_json(&self, folder_names: &HashMap<Category, String>) -> Result<String> {
+        serde_json::to_string_pretty(&self.to_json_value(folder_names)?)
+            .context("Failed to serialize summary")
+    }
+
+    /// 統計をJSONの値に変換（キーは `to_json` と同じ）
+    pub fn to_json_value(
+        &self,
+        folder_names: &HashMap<Category, String>,
+    ) -> Result<serde_json::Value> {
+        let mut value = serde_json::to_value(self).context("Failed to serialize summary")?;
+        let key = |category: &Category| category.folder_name_in(folder_names).to_string();
+        value["category_counts"] = serde_json::json!(self
+            .category_counts
+            .iter()
+            .map(|(category, count)| (key(category), count))
+            .collect::<BTreeMap<_, _>>());
+        value["category_bytes"] = serde_json::json!(self
+            .category_bytes
+            .iter()
+            .map(|(category, bytes)| (key(category), bytes))
+            .collect::<BTreeMap<_, _>>());
+        Ok(value)
+    }
+
+    /// ファイルを受け取ったフォルダを1行ずつ返す（`with_counts` ならタブ区切りでファイル数を付ける）
+    pub fn folder_lines(&self, with_counts: bool) -> Vec<String> {
+        self.folder_counts
+            .iter()
+            .map(|(folder, count)| {
+                if with_counts {
+                    format!("{}\t{}", folder.display(), count)
+                } else {
+                    folder.display().to_string()
+                }
+            })
+            .collect()
+    }
+
+    /// 別の実行の統計を加える（複数の対象ディレクトリの合計用）
+    ///
+    /// `--sync-into` の突き合わせ結果は件数を足し、アーカイブにしかないファイルは
+    /// 後の実行のものを使います。
+    pub fn merge(&mut self, other: SortStats) {
+        self.total_files += other.total_files;
+        self.moved_files += other.moved_files;
+        self.renamed_files += other.renamed_files;
+        self.replaced_files += other.replaced_files;
+        self.skipped_files += other.skipped_files;
+        self.already_sorted += other.already_sorted;
+        self.error_count += other.error_count;
+        self.locked_files += other.locked_files;
+        self.would_fail += other.would_fail;
+        self.duplicate_files += other.duplicate_files;
+        self.excluded_files += other.excluded_files;
+        self.removed_duplicates += other.removed_duplicates;
+        self.discrepancies += other.discrepancies;
+        self.converted_files += other.converted_files;
+        self.collapsed_folders += other.collapsed_folders;
+        self.removed_folders += other.removed_folders;
+        for (category, count) in other.category_counts {
+            *self.category_counts.entry(category).or_insert(0) += count;
+        }
+        for (category, bytes) in other.category_bytes {
+            *self.category_bytes.entry(category).or_insert(0) += bytes;
+        }
+        for (extension, count) in other.others_extensions {
+            *self.others_extensions.entry(extension).or_insert(0) += count;
+        }
+        for (folder, count) in other.folder_counts {
+            *self.folder_counts.entry(folder).or_insert(0) += count;
+        }
+        match (&mut self.sync, other.sync) {
+            (Some(total), Some(report)) => {
+                total.new_files += report.new_files;
+                total.existing_files += report.existing_files;
+                total.sorted_files += report.sorted_files;
+                total.archive_only = report.archive_only;
+            }
+            (total @ None, report) => *total = report,
+            (Some(_), None) => {}
+        }
+    }
+
+    /// 統計情報のサマリーを表示（カテゴリは `folder_names` で上書きされたフォルダ名で表示する）
+    pub fn print_summary(
+        &self,
+        dry_run: bool,
+        folder_names: &HashMap<Category, String>,
+        reporter: &Reporter,
+    ) {
+        say!(reporter);
         if dry_run {
-            println!("{}", "=== Dry Run Summary ===".cyan().bold());
+            say!(reporter, "{}", "=== Dry Run Summary ===".cyan().bold());
         } else {
-            println!("{}", "=== Summary ===".green().bold());
+            say!(reporter, "{}", "=== Summary ===".green().bold());
         }
-        println!(
+        say!(
+            reporter,
             "Total files found: {}",
             self.total_files.to_string().yellow()
         );
 
         if dry_run {
-            println!("Files to be moved: {}", self.moved_files.to_string().cyan());
+            say!(
+                reporter,
+                "Files to be moved: {}",
+                self.moved_files.to_string().cyan()
+            );
         } else {
-            println!("Files moved: {}", self.moved_files.to_string().green());
+            say!(
+                reporter,
+                "Files moved: {}",
+                self.moved_files.to_string().green()
+            );
             if self.renamed_files > 0 {
-                println!(
+                say!(
+                    reporter,
                     "Files renamed (due to conflicts): {}",
                     self.renamed_files.to_string().yellow()
                 );
             }
+            if self.replaced_files > 0 {
+                say!(
+                    reporter,
+                    "Existing files replaced: {}",
+                    self.replaced_files.to_string().yellow()
+                );
+            }
         }
 
         if self.skipped_files > 0 {
-            println!("Files skipped: {}", self.skipped_files.to_string().yellow());
+            say!(
+                reporter,
+                "Files skipped: {}",
+                self.skipped_files.to_string().yellow()
+            );
+        }
+
+        if self.already_sorted > 0 {
+            say!(
+                reporter,
+                "Already sorted: {}",
+                self.already_sorted.to_string().green()
+            );
+        }
+
+        if self.locked_files > 0 {
+            say!(
+                reporter,
+                "Files in use (skipped): {}",
+                self.locked_files.to_string().yellow()
+            );
         }
 
         if self.error_count > 0 {
-            println!("Errors: {}", self.error_count.to_string().red());
+            say!(reporter, "Errors: {}", self.error_count.to_string().red());
+        }
+
+        if self.would_fail > 0 {
+            say!(
+                reporter,
+                "Files that would fail (permission denied): {}",
+                self.would_fail.to_string().red()
+            );
+        }
+
+        if self.discrepancies > 0 {
+            say!(
+                reporter,
+                "Integrity discrepancies: {}",
+                self.discrepancies.to_string().red()
+            );
+        }
+
+        if self.converted_files > 0 {
+            say!(
+                reporter,
+                "Images converted to JPEG: {}",
+                self.converted_files.to_string().green()
+            );
+        }
+
+        if self.duplicate_files > 0 {
+            say!(
+                reporter,
+                "Content duplicates skipped: {}",
+                self.duplicate_files.to_string().yellow()
+            );
+        }
+
+        if self.excluded_files > 0 {
+            say!(
+                reporter,
+                "Files excluded by pattern: {}",
+                self.excluded_files.to_string().yellow()
+            );
+        }
+
+        if self.removed_duplicates > 0 {
+            let label = if dry_run {
+                "Content duplicates to be removed:"
+            } else {
+                "Content duplicates removed:"
+            };
+            say!(
+                reporter,
+                "{} {}",
+                label,
+                self.removed_duplicates.to_string().yellow()
+            );
         }
 
-        println!();
-        println!("{}", "Category breakdown:".bold());
-        for category in Category::all() {
-            if let Some(&count) = self.category_counts.get(category) {
-                if count > 0 {
-                    println!("  {}: {}", category.folder_name(), count);
+        if self.collapsed_folders > 0 {
+            say!(
+                reporter,
+                "Single-file folders collapsed: {}",
+                self.collapsed_folders.to_string().yellow()
+            );
+        }
+
+        if self.removed_folders > 0 {
+            say!(
+                reporter,
+                "Empty folders removed: {}",
+                self.removed_folders.to_string().yellow()
+            );
+        }
+
+        say!(reporter);
+        say!(reporter, "{}", "Category breakdown:".bold());
+        for (category, count) in self.ordered_category_counts() {
+            match self.category_bytes.get(category) {
+                Some(&bytes) => say!(
+                    reporter,
+                    "  {}: {} ({})",
+                    category.folder_name_in(folder_names),
+                    count,
+                    output::format_bytes(bytes).dimmed()
+                ),
+                None => say!(
+                    reporter,
+                    "  {}: {}",
+                    category.folder_name_in(folder_names),
+                    count
+                ),
+            }
+            if *category == Category::Others {
+                for (extension, count) in &self.others_extensions {
+                    say!(
+                        reporter,
+                        "    {}",
+                        format!("{}: {}", extension, count).dimmed()
+                    );
                 }
             }
         }
+        if !self.category_bytes.is_empty() {
+            let label = if dry_run {
+                "Total size to be moved:"
+            } else {
+                "Total size moved:"
+            };
+            say!(
+                reporter,
+                "{} {}",
+                label,
+                output::format_bytes(self.category_bytes.values().sum()).green()
+            );
+        }
+    }
+
+    /// カテゴリごとの件数を1行にまとめる（例: `Images: 3, Documents: 1`）
+    pub fn category_line(&self, folder_names: &HashMap<Category, String>) -> String {
+        self.ordered_category_counts()
+            .iter()
+            .map(|(category, count)| {
+                format!("{}: {}", category.folder_name_in(folder_names), count)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// 件数が1以上のカテゴリを表示順（組み込みカテゴリ → 定義されたカテゴリの名前順）に並べる
+    fn ordered_category_counts(&self) -> Vec<(&Category, usize)> {
+        let mut counts: Vec<(&Category, usize)> = self
+            .category_counts
+            .iter()
+            .filter(|(_, &count)| count > 0)
+            .map(|(category, &count)| (category, count))
+            .collect();
+        counts.sort();
+        counts
+    }
+
+    /// カテゴリの内訳を横棒グラフで表示する
+    ///
+    /// 色が無効（`NO_COLOR` など）か出力がターミナルでない場合は、棒なしの件数と割合を表示します。
+    pub fn print_visual_summary(
+        &self,
+        folder_names: &HashMap<Category, String>,
+        reporter: &Reporter,
+    ) {
+        let rows: Vec<(&str, usize)> = self
+            .ordered_category_counts()
+            .into_iter()
+            .map(|(category, count)| (category.folder_name_in(folder_names), count))
+            .collect();
+
+        say!(reporter);
+        say!(reporter, "{}", "Category chart:".bold());
+        if rows.is_empty() {
+            say!(reporter, "  {}", "(no files)".dimmed());
+            return;
+        }
+
+        let width = output::terminal_width()
+            .filter(|_| colored::control::SHOULD_COLORIZE.should_colorize());
+        let lines = match width {
+            Some(width) => output::bar_chart(&rows, width),
+            None => output::plain_breakdown(&rows),
+        };
+        for line in lines {
+            say!(reporter, "{}", line);
+        }
+    }
+}
+
+/// 移動の代わりに作成するリンクの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkKind {
+    /// シンボリックリンク（`symlink_farm`）
+    Symlink,
+    /// ハードリンク（`hardlink_view`）
+    Hardlink,
+}
+
+/// `collapse_singletons` で解消したカテゴリフォルダのファイルを戻す先
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollapseTarget {
+    /// 対象ディレクトリのルートに戻す
+    #[default]
+    Root,
+    /// 移動前の場所に戻す（再帰処理ではサブフォルダのファイルは元のサブフォルダに残る）
+    Origin,
+}
+
+/// 空（0バイト）のファイルの扱い
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EmptyFiles {
+    /// 拡張子に従って通常どおり分類する
+    #[default]
+    Sort,
+    /// 移動せずその場に残す
+    Skip,
+    /// 拡張子に関係なく Others に振り分ける
+    Others,
+    /// 拡張子に関係なく指定したフォルダに振り分ける
+    Folder(String),
+}
+
+impl FromStr for EmptyFiles {
+    type Err = anyhow::Error;
+
+    /// `sort` / `skip` / `others` / `folder:NAME` を解析する
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sort" => Ok(Self::Sort),
+            "skip" => Ok(Self::Skip),
+            "others" => Ok(Self::Others),
+            _ => {
+                let Some(name) = s.strip_prefix("folder:") else {
+                    bail!(
+                        "Invalid empty-file handling '{}' (expected sort, skip, others or folder:NAME)",
+                        s
+                    );
+                };
+                let folder = sanitize_folder_name(name)
+                    .with_context(|| format!("Invalid folder name for empty files: '{}'", name))?;
+                Ok(Self::Folder(folder))
+            }
+        }
+    }
+}
+
+impl fmt::Display for EmptyFiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sort => write!(f, "sort"),
+            Self::Skip => write!(f, "skip"),
+            Self::Others => write!(f, "others"),
+            Self::Folder(name) => write!(f, "folder:{}", name),
+        }
+    }
+}
+
+impl Serialize for EmptyFiles {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// ファイルをスキップした理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// ダウンロード途中のファイル
+    DownloadInProgress,
+    /// サイズが変化し続けている（書き込み中の）ファイル
+    Unstable,
+    /// 隠しファイル
+    Hidden,
+    /// 更新から間もないファイル
+    TooNew,
+    /// 移動しないよう指定された名前のファイル
+    KeepAtRoot,
+    /// 以前の整理で印が付けられたファイル
+    Marked,
+    /// 空（0バイト）のファイル
+    Empty,
+    /// 下限のサイズより小さいファイル
+    TooSmall,
+    /// 上限のサイズより大きいファイル
+    TooLarge,
+    /// 移動先に同名のファイルが既にある（`--on-conflict skip`）
+    Exists,
+    /// 移動先の同名のファイルの方が新しいか同じ（`--on-conflict keep-newer`）
+    NotNewer,
+    /// 移動先の同名のファイルの方が大きいか同じ（`--on-conflict keep-larger`）
+    NotLarger,
+    /// `--older-than` の基準より後に更新されたファイル
+    ModifiedAfterCutoff,
+    /// `--newer-than` の基準より前に更新されたファイル
+    ModifiedBeforeCutoff,
+    /// 更新日時の条件があるが、更新日時を読めなかったファイル
+    UnknownModified,
+    /// 移動前のフックが移動を断ったファイル
+    Vetoed,
+    /// 同じカテゴリのファイルが `--min-files` に満たない
+    SmallCategory,
+    /// 他のプロセスが使用中（開いている・ロックしている）のファイル
+    InUse,
+    /// 移動先が既にこのファイルのコピー・ハードリンクになっている（`--copy`・`--hardlink`）
+    AlreadyTransferred,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::DownloadInProgress => write!(f, "download in progress"),
+            SkipReason::Unstable => write!(f, "still being written"),
+            SkipReason::Hidden => write!(f, "hidden file"),
+            SkipReason::TooNew => write!(f, "modified too recently"),
+            SkipReason::KeepAtRoot => write!(f, "kept in place"),
+            SkipReason::Marked => write!(f, "marked as sorted"),
+            SkipReason::Empty => write!(f, "empty file"),
+            SkipReason::TooSmall => write!(f, "smaller than --min-size"),
+            SkipReason::TooLarge => write!(f, "larger than --max-size"),
+            SkipReason::Exists => write!(f, "destination already exists"),
+            SkipReason::NotNewer => write!(f, "destination is not older"),
+            SkipReason::NotLarger => write!(f, "destination is not smaller"),
+            SkipReason::ModifiedAfterCutoff => write!(f, "modified after --older-than"),
+            SkipReason::ModifiedBeforeCutoff => write!(f, "modified before --newer-than"),
+            SkipReason::UnknownModified => write!(f, "modification time unavailable"),
+            SkipReason::Vetoed => write!(f, "declined by hook"),
+            SkipReason::SmallCategory => write!(f, "fewer than --min-files in category"),
+            SkipReason::InUse => write!(f, "file in use"),
+            SkipReason::AlreadyTransferred => write!(f, "already at destination"),
+        }
     }
 }
 
+/// 整理対象から除外されたファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    /// ファイルのパス
+    pub path: PathBuf,
+    /// 除外された理由
+    pub reason: SkipReason,
+}
+
+/// 異なるディレクトリに存在する同名ファイルのグループ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameCollision {
+    /// 共通のファイル名
+    pub name: String,
+    /// 同名ファイルのパス（パス順）
+    pub paths: Vec<PathBuf>,
+}
+
+/// 拡張子ごとの散らばり具合
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionFragmentation {
+    /// 拡張子（小文字、ドットなし）
+    pub extension: String,
+    /// その拡張子のファイルが存在するディレクトリの数
+    pub directories: usize,
+    /// その拡張子のファイル数
+    pub files: usize,
+}
+
+/// どのカテゴリにも当てはまらない（Others になる）拡張子とそのファイル数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnrecognizedExtension {
+    /// 拡張子（小文字、ドットなし）
+    pub extension: String,
+    /// その拡張子のファイル数
+    pub files: usize,
+}
+
 /// ファイルソーター
 pub struct Sorter {
     config: SorterConfig,
+    /// `config.exclude` をコンパイルしたパターン
+    exclude: Vec<Pattern>,
+    /// `config.include` をコンパイルしたパターン
+    include: Vec<Pattern>,
+    /// 実行状況を通知するオブザーバー
+    observers: Mutex<Vec<Box<dyn SortObserver>>>,
+    /// `config.log_file` を開いた操作ログ
+    oplog: Option<Arc<OperationLog>>,
+    /// `config.log_file` を開けなかった場合のエラー（実行の開始時に報告する）
+    oplog_error: Option<String>,
+    /// 1ファイルを移動する直前に呼ぶフック（`None` なら常に移動する）
+    before_move: Option<BeforeMoveHook>,
+    /// 1ファイルの移動を試みた直後に呼ぶフック
+    after_move: Option<AfterMoveHook>,
+    /// 人向けの表示の出力先
+    reporter: Reporter,
 }
 
 impl Sorter {
     /// 新しいソーターインスタンスを作成
+    ///
+    /// 不正な除外・対象パターンは警告して無視します。
+    /// 操作ログを開けない場合は、各処理の開始時（ファイルに触れる前）にエラーを返します。
     pub fn new(config: SorterConfig) -> Self {
-        Self { config }
+        let exclude = compile_patterns(&config.exclude);
+        let include = compile_patterns(&config.include);
+        let (oplog, oplog_error) = match config.log_file.as_deref().map(OperationLog::open) {
+            Some(Ok(log)) => (Some(Arc::new(log)), None),
+            Some(Err(e)) => (None, Some(format!("{:#}", e))),
+            None => (None, None),
+        };
+        Self {
+            config,
+            exclude,
+            include,
+            observers: Mutex::new(Vec::new()),
+            oplog,
+            oplog_error,
+            before_move: None,
+            after_move: None,
+            reporter: Reporter::default(),
+        }
     }
 
-    /// メインの実行関数
-    pub fn run(&self) -> Result<SortStats> {
+    /// 人向けの表示の出力先を設定する（既定は標準出力）
+    ///
+    /// `Reporter::silent()` で表示を止め、`Reporter::capture` で各行を受け取れます。
+    pub fn with_reporter(mut self, reporter: Reporter) -> Self {
+        self.reporter = reporter;
+        self
+    }
+
+    /// 実行状況を通知するオブザーバーを追加する
+    pub fn with_observer(mut self, observer: Box<dyn SortObserver>) -> Self {
+        self.observers
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(observer);
+        self
+    }
+
+    /// 1ファイルを移動する直前に呼ぶフックを設定する（`false` を返すと移動しない）
+    ///
+    /// 実際の移動でのみ呼ばれ、Dry Run・zip出力・リンクの作成では呼ばれません。
+    pub fn with_before_move<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&FilePlan) -> bool + Send + Sync + 'static,
+    {
+        self.before_move = Some(Arc::new(hook));
+        self
+    }
+
+    /// 1ファイルの移動を試みた直後に、その結果を受け取るフックを設定する
+    pub fn with_after_move<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&FilePlan, &Result<OpResult>) + Send + Sync + 'static,
+    {
+        self.after_move = Some(Arc::new(hook));
+        self
+    }
+
+    /// 操作ログに1件の移動（またはその予定・失敗）を記録する
+    fn log_operation(
+        &self,
+        plan: &FilePlan,
+        destination: Option<&Path>,
+        renamed: bool,
+        error: Option<String>,
+    ) {
+        let Some(oplog) = &self.oplog else {
+            return;
+        };
+        let operation = if self.config.dry_run {
+            Operation::DryRun
+        } else if self.config.copy {
+            Operation::Copy
+        } else if self.config.hardlink {
+            Operation::Hardlink
+        } else {
+            Operation::Move
+        };
+        oplog.record(&OperationEntry {
+            timestamp: rfc3339(SystemTime::now()),
+            operation,
+            source: &plan.source,
+            destination,
+            category: &plan.category,
+            renamed,
+            result: if error.is_some() {
+                Outcome::Error
+            } else {
+                Outcome::Ok
+            },
+            error,
+        });
+    }
+
+    /// すべてのオブザーバーにイベントを通知する
+    fn notify(&self, event: SortEvent) {
+        for observer in lock(&self.observers).iter_mut() {
+            observer.on_event(&event);
+        }
+    }
+
+    /// 対象ディレクトリが存在し、読み取り可能なディレクトリであることを確認
+    ///
+    /// 指定された操作ログを開けなかった場合も、何も移動しないうちにエラーにします。
+    fn validate_target_dir(&self) -> Result<()> {
+        if let Some(e) = &self.oplog_error {
+            bail!("{}", e);
+        }
+
         // 対象ディレクトリの存在確認
         if !self.config.target_dir.exists() {
             anyhow::bail!(
@@ -139,391 +1150,6847 @@ impl Sorter {
             )
         })?;
 
-        println!(
+        Ok(())
+    }
+
+    /// 対象ディレクトリがシステムやホームディレクトリそのものでないことを確認
+    fn check_not_critical(&self) -> Result<()> {
+        if self.config.force {
+            return Ok(());
+        }
+
+        let target = fs::canonicalize(&self.config.target_dir).with_context(|| {
+            format!(
+                "Failed to resolve path: {}",
+                self.config.target_dir.display()
+            )
+        })?;
+        if is_critical_path(&target, &critical_paths()) {
+            anyhow::bail!(
+                "Refusing to sort critical directory: {}\n\
+                 Sorting it would reorganize system or home files. \
+                 Use --force if this is really intended.",
+                target.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// メインの実行関数
+    ///
+    /// 対象ディレクトリが複数ある場合は1つずつ整理し、合計の統計を返します。
+    pub fn run(&self) -> Result<SortStats> {
+        let mut input = io::stdin().lock();
+        if self.config.more_target_dirs.is_empty() {
+            self.run_with_input(&mut input)
+        } else {
+            self.run_each_with_input(&mut input)
+        }
+    }
+
+    /// 対象ディレクトリごとにソート処理を実行し、統計を合算する
+    ///
+    /// 各ディレクトリにはそれぞれのカテゴリフォルダを作り、ディレクトリごとの
+    /// サマリーの後に合計のサマリーを表示します。JSON出力は合計だけを出します。
+    /// いずれかのディレクトリでエラーになった場合はそこで中断します。
+    pub fn run_each_with_input<R: BufRead>(&self, input: &mut R) -> Result<SortStats> {
+        let dirs: Vec<&PathBuf> = std::iter::once(&self.config.target_dir)
+            .chain(&self.config.more_target_dirs)
+            .collect();
+        let mut total = SortStats::default();
+        for (index, dir) in dirs.iter().enumerate() {
+            say!(self.reporter);
+            say!(
+                self.reporter,
+                "{}",
+                format!("=== [{}/{}] {} ===", index + 1, dirs.len(), dir.display()).bold()
+            );
+            let sorter = self.for_target_dir(dir);
+            let result = sorter.run_with_input(input);
+            // オブザーバーは次のディレクトリでも使うので戻す
+            *lock(&self.observers) = sorter
+                .observers
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner);
+            total.merge(result?);
+        }
+
+        say!(self.reporter);
+        say!(
+            self.reporter,
+            "{}",
+            format!("Combined result of {} target directories:", dirs.len()).bold()
+        );
+        total.print_summary(
+            self.config.dry_run,
+            &self.config.folder_names,
+            &self.reporter,
+        );
+        self.print_json(&total)?;
+        Ok(total)
+    }
+
+    /// 対象ディレクトリだけを差し替えたソーターを作る（オブザーバーは移す）
+    fn for_target_dir(&self, dir: &Path) -> Sorter {
+        let mut config = self.config.clone();
+        config.target_dir = dir.to_path_buf();
+        config.more_target_dirs.clear();
+        config.output_format = OutputFormat::Text;
+        Sorter {
+            config,
+            exclude: self.exclude.clone(),
+            include: self.include.clone(),
+            observers: Mutex::new(std::mem::take(&mut *lock(&self.observers))),
+            oplog: self.oplog.clone(),
+            oplog_error: self.oplog_error.clone(),
+            before_move: self.before_move.clone(),
+            after_move: self.after_move.clone(),
+            reporter: self.reporter.clone(),
+        }
+    }
+
+    /// 確認プロンプトへの回答を `input` から読み取ってソート処理を実行
+    pub fn run_with_input<R: BufRead>(&self, input: &mut R) -> Result<SortStats> {
+        self.validate_target_dir()?;
+        self.check_not_critical()?;
+        // 置き換えた既存のファイルは元に戻せないため、atomic とは組み合わせない
+        if self.config.atomic && self.config.on_conflict.replaces_existing() {
+            bail!(
+                "--atomic cannot be combined with --on-conflict {:?}: replaced files cannot be rolled back",
+                self.config.on_conflict
+            );
+        }
+        if self.config.hardlink {
+            ensure_same_filesystem(&self.config.target_dir, self.dest_root())?;
+        }
+
+        say!(
+            self.reporter,
             "{} {}",
             "Target directory:".bold(),
             self.config.target_dir.display()
         );
 
         if self.config.dry_run {
-            println!("{}", "[DRY RUN MODE] No files will be moved.".cyan().bold());
+            say!(
+                self.reporter,
+                "{}",
+                "[DRY RUN MODE] No files will be moved.".cyan().bold()
+            );
         }
 
         if self.config.recursive {
-            println!("{}", "[RECURSIVE MODE] Processing subdirectories.".yellow());
+            say!(
+                self.reporter,
+                "{}",
+                "[RECURSIVE MODE] Processing subdirectories.".yellow()
+            );
         }
 
-        println!();
+        if let Some(archive) = &self.config.archive_output {
+            say!(
+                self.reporter,
+                "{} {}",
+                "[ARCHIVE MODE] Writing sorted files to:".yellow(),
+                archive.display()
+            );
+        }
 
-        // ファイルを収集
-        let files = self.collect_files(&self.config.target_dir)?;
-        info!("Found {} files to process", files.len());
+        if let Some(farm) = &self.config.symlink_farm {
+            say!(
+                self.reporter,
+                "{} {}",
+                "[SYMLINK FARM MODE] Linking sorted files in:".yellow(),
+                farm.display()
+            );
+        }
 
-        if files.is_empty() {
-            println!("{}", "No files found to sort.".yellow());
-            return Ok(SortStats::default());
+        if let Some(view) = &self.config.hardlink_view {
+            say!(
+                self.reporter,
+                "{} {}",
+                "[HARDLINK VIEW MODE] Linking sorted files in:".yellow(),
+                view.display()
+            );
         }
 
-        // 分類計画を作成
-        let plans = self.create_plans(&files)?;
+        if self.config.copy {
+            say!(
+                self.reporter,
+                "{}",
+                "[COPY MODE] Files will be copied; originals are left in place.".yellow()
+            );
+        }
 
-        // 実行（Dry Run または 実際の移動）
-        let stats = if self.config.dry_run {
-            self.execute_dry_run(&plans)?
-        } else {
-            self.execute_move(&plans)?
-        };
+        if self.config.hardlink {
+            say!(
+                self.reporter,
+                "{}",
+                "[HARDLINK MODE] Files will be hardlinked; originals are left in place.".yellow()
+            );
+        }
 
-        stats.print_summary(self.config.dry_run);
+        if let Some(archive) = &self.config.sync_into {
+            say!(
+                self.reporter,
+                "{} {}",
+                "[SYNC MODE] Sorting inbox into archive:".yellow(),
+                archive.display()
+            );
+        }
 
-        Ok(stats)
-    }
+        if let Some(output) = &self.config.output_dir {
+            say!(
+                self.reporter,
+                "{} {}",
+                "[OUTPUT MODE] Sorting into:".yellow(),
+                output.display()
+            );
+        }
 
-    /// ファイルを収集
-    fn collect_files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
-        let mut files = Vec::new();
+        say!(self.reporter);
 
-        for entry in fs::read_dir(dir)
-            .with_context(|| format!("Failed to read directory: {}", dir.display()))?
-        {
-            let entry = entry.with_context(|| "Failed to read directory entry")?;
-            let path = entry.path();
+        // 通常の実行・--check・--stats-only で共通の手順で計画を作成する
+        let PlannedRun {
+            mut plans,
+            file_count,
+            excluded,
+            not_included,
+            skipped,
+            truncated_from,
+            duplicates,
+            sync_report,
+            already_sorted,
+            in_place,
+            left_in_place,
+        } = self.build_plans()?;
+        self.print_skipped(&skipped);
+        if let (Some(max), Some(total)) = (self.config.max_files, truncated_from) {
+            say!(
+                self.reporter,
+                "{}",
+                format!("Processing only {} of {} files.", max, total).yellow()
+            );
+        }
+        self.print_duplicates(&duplicates);
 
-            // シンボリックリンクはスキップ
-            if is_symlink(&path) {
-                debug!("Skipping symlink: {}", path.display());
-                continue;
+        if file_count == 0 && duplicates.is_empty() && skipped.is_empty() {
+            say!(self.reporter, "{}", "No files found to sort.".yellow());
+            if let Some(report) = &sync_report {
+                report.print(self.config.dry_run, &self.reporter);
             }
+            let stats = SortStats {
+                total_files: not_included,
+                skipped_files: not_included,
+                already_sorted,
+                excluded_files: excluded,
+                ..Default::default()
+            };
+            return self.finish(stats);
+        }
+        self.print_skipped(&left_in_place);
 
-            if is_file(&path) {
-                // カテゴリフォルダ内のファイルはスキップ（無限ループ防止）
-                if self.is_category_folder(&path) {
-                    debug!("Skipping file in category folder: {}", path.display());
-                    continue;
-                }
-                files.push(path);
-            } else if is_directory(&path) && self.config.recursive {
-                // カテゴリフォルダは再帰処理しない
-                let folder_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-                if Category::all()
-                    .iter()
-                    .any(|c| c.folder_name() == folder_name)
-                {
-                    debug!("Skipping category folder: {}", path.display());
-                    continue;
-                }
+        // カテゴリごとに確認し、断られたカテゴリは除外する
+        let mut declined = 0;
+        if self.config.confirm_per_category && !self.config.dry_run {
+            (plans, declined) = self.confirm_categories(plans, input, &mut io::stderr())?;
+        }
 
-                // 再帰的にファイルを収集
-                let sub_files = self.collect_files(&path)?;
-                files.extend(sub_files);
+        // 移動前に実行内容をまとめて表示して確認
+        if self.config.preflight && !self.config.dry_run && !plans.is_empty() {
+            PreflightReport::new(&plans, &skipped, self.dest_root(), self.config.on_conflict)
+                .print(&self.reporter);
+            let question = format!("Proceed with sorting {} files?", plans.len());
+            if !self.config.assume_yes && !prompt::confirm(input, &mut io::stderr(), &question)? {
+                say!(
+                    self.reporter,
+                    "{}",
+                    "Aborted. No files were moved.".yellow()
+                );
+                return self.finish(SortStats::default());
             }
         }
 
-        Ok(files)
-    }
-
-    /// パスがカテゴリフォルダ内にあるかチェック
-    fn is_category_folder(&self, path: &Path) -> bool {
-        if let Some(parent) = path.parent() {
-            if let Some(folder_name) = parent.file_name().and_then(|n| n.to_str()) {
-                if parent.parent() == Some(&self.config.target_dir) {
-                    return Category::all()
-                        .iter()
-                        .any(|c| c.folder_name() == folder_name);
+        // 移動前にプレビューを表示して確認
+        if self.config.confirm
+            && !self.config.assume_yes
+            && !self.config.preflight
+            && !self.config.confirm_per_category
+            && !self.config.dry_run
+            && !plans.is_empty()
+        {
+            self.execute_dry_run(&plans)?;
+            say!(self.reporter);
+            let question = format!("Proceed with sorting {} files?", plans.len());
+            if !prompt::confirm(input, &mut io::stderr(), &question)? {
+                say!(
+                    self.reporter,
+                    "{}",
+                    "Aborted. No files were moved.".yellow()
+                );
+                return self.finish(SortStats::default());
+            }
+            say!(self.reporter);
+        }
+
+        // 実際の移動の前に件数を示して確認する（プレビューなどで確認済みなら重ねて聞かない）
+        if self.config.confirm_move
+            && !self.config.assume_yes
+            && !self.config.confirm
+            && !self.config.preflight
+            && !self.config.confirm_per_category
+            && self.moves_files()
+            && !plans.is_empty()
+        {
+            let verb = if self.config.copy {
+                "copying"
+            } else if self.config.hardlink {
+                "hardlinking"
+            } else {
+                "moving"
+            };
+            let question = format!("Proceed with {} {} files?", verb, plans.len());
+            if !prompt::confirm(input, &mut io::stderr(), &question)? {
+                say!(
+                    self.reporter,
+                    "{}",
+                    "Aborted. No files were moved.".yellow()
+                );
+                return self.finish(SortStats::default());
+            }
+        }
+
+        // 重複を削除してから残りを整理する
+        let mut removed_duplicates = 0;
+        let mut removal_errors = 0;
+        if self.config.dedupe_first {
+            if self.config.dry_run {
+                removed_duplicates = duplicates.len();
+            } else {
+                (removed_duplicates, removal_errors) = self.remove_duplicates(&duplicates);
+            }
+        }
+
+        // 実行（Dry Run または 実際の移動）
+        let mut journal = Journal::default();
+        let mut stats = if self.config.dry_run {
+            self.execute_dry_run(&plans)?
+        } else if let Some(archive) = &self.config.archive_output {
+            self.execute_archive(&plans, archive)?
+        } else if let Some(farm) = &self.config.symlink_farm {
+            self.execute_link_view(&plans, farm, LinkKind::Symlink)?
+        } else if let Some(view) = &self.config.hardlink_view {
+            self.execute_link_view(&plans, view, LinkKind::Hardlink)?
+        } else {
+            self.execute_move(&plans, &mut journal)?
+        };
+        stats.total_files += duplicates.len() + skipped.len() + not_included;
+        stats.skipped_files += not_included;
+        stats.excluded_files = excluded;
+        if self.config.dedupe_first {
+            stats.removed_duplicates = removed_duplicates;
+            stats.error_count += removal_errors;
+        } else {
+            stats.duplicate_files += duplicates.len();
+        }
+        stats.total_files += declined + left_in_place.len() + in_place;
+        stats.already_sorted += already_sorted + in_place;
+        stats.skipped_files += skipped.len() + declined + left_in_place.len();
+
+        // ファイルが1つだけのカテゴリフォルダを解消
+        if let Some(target) = self
+            .config
+            .collapse_singletons
+            .filter(|_| !self.config.dry_run)
+        {
+            self.collapse_singleton_folders(&plans, target, &mut stats, &mut journal)?;
+        }
+
+        // 空になったフォルダを削除（失敗しても整理自体は成功として扱う）
+        if self.config.prune_empty && self.moves_files() && !self.keeps_originals() {
+            self.prune_empty_dirs(&mut stats);
+        }
+
+        // 移動の記録を書き出す（コピー・ハードリンクは元に戻す対象がないので書き出さない）
+        if self.config.write_journal
+            && self.moves_files()
+            && !self.keeps_originals()
+            && !journal.entries.is_empty()
+        {
+            let path = journal.save(&self.config.target_dir)?;
+            say!(
+                self.reporter,
+                "{} {}",
+                "Journal written:".bold(),
+                path.display()
+            );
+        }
+
+        // 累計統計に加える（失敗しても整理自体は成功として扱う）
+        if let Some(path) = &self.config.lifetime_stats {
+            if self.moves_files() {
+                if let Err(e) = LifetimeStore::new(path).record(&stats, &self.config.folder_names) {
+                    warn!("Failed to update lifetime stats: {:#}", e);
                 }
             }
         }
-        false
+
+        stats.print_summary(
+            self.config.dry_run,
+            &self.config.folder_names,
+            &self.reporter,
+        );
+        if self.config.visual_summary {
+            stats.print_visual_summary(&self.config.folder_names, &self.reporter);
+        }
+        if let Some(mut report) = sync_report {
+            report.sorted_files = stats.moved_files;
+            report.print(self.config.dry_run, &self.reporter);
+            stats.sync = Some(report);
+        }
+
+        // スクリプト向けにフォルダの一覧を出力
+        if self.config.print_folders {
+            for line in stats.folder_lines(self.config.print_folder_counts) {
+                say!(self.reporter, "{}", line);
+            }
+        }
+
+        self.finish(stats)
+    }
+
+    /// 対象ディレクトリ以下の空のフォルダを削除し、削除した数を統計に加える
+    fn prune_empty_dirs(&self, stats: &mut SortStats) {
+        match remove_empty_dirs(&self.config.target_dir) {
+            Ok(removed) => stats.removed_folders += removed,
+            Err(e) => {
+                warn!("Failed to remove empty folders: {:#}", e);
+                stats.error_count += 1;
+            }
+        }
+    }
+
+    /// 完了を通知し、JSON出力なら統計をJSONで出力する
+    fn finish(&self, stats: SortStats) -> Result<SortStats> {
+        self.notify(SortEvent::Finished {
+            summary: Box::new(stats.clone()),
+        });
+        self.print_json(&stats)?;
+        Ok(stats)
+    }
+
+    /// JSON出力の場合、統計をJSONで `Reporter` に出す（人向けの表示を止めていても出す）
+    fn print_json(&self, stats: &SortStats) -> Result<()> {
+        if self.config.output_format == OutputFormat::Json {
+            let json = stats.to_json(&self.config.folder_names)?;
+            self.reporter.emit(format_args!("{}", json));
+        }
+        Ok(())
+    }
+
+    /// 実際にファイルを移動する実行かどうか（Dry Run・zip出力・シンボリックリンクは除く）
+    fn moves_files(&self) -> bool {
+        !self.config.dry_run
+            && self.config.archive_output.is_none()
+            && self.config.symlink_farm.is_none()
+            && self.config.hardlink_view.is_none()
+    }
+
+    /// 元のファイルをその場に残す実行かどうか（`copy`・`hardlink`）
+    fn keeps_originals(&self) -> bool {
+        self.config.copy || self.config.hardlink
+    }
+
+    /// 計画に含まれるカテゴリごとに移動するかを確認する
+    ///
+    /// # Returns
+    /// `(確認されたカテゴリの計画, 断られたカテゴリのファイル数)`
+    fn confirm_categories<R: BufRead, W: Write>(
+        &self,
+        plans: Vec<FilePlan>,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<(Vec<FilePlan>, usize)> {
+        let mut accepted = HashSet::new();
+        let mut declined = 0;
+
+        let categories: BTreeSet<&Category> = plans.iter().map(|p| &p.category).collect();
+        for category in categories {
+            let count = plans.iter().filter(|p| p.category == *category).count();
+
+            let question = format!("Move {} files to {}?", count, self.folder_name(category));
+            if prompt::confirm(input, output, &question)? {
+                accepted.insert(category.clone());
+            } else {
+                declined += count;
+            }
+        }
+
+        let plans = plans
+            .into_iter()
+            .filter(|p| accepted.contains(&p.category))
+            .collect();
+        Ok((plans, declined))
+    }
+
+    /// カテゴリフォルダ内のファイルをすべて対象ディレクトリのルートに戻す
+    ///
+    /// カテゴリフォルダ（サブフォルダを含む）のファイルをルートに移動し（重複回避付き）、
+    /// 空になったカテゴリフォルダを削除します。ジャーナルがなくても使えます。
+    pub fn unflatten(&self) -> Result<SortStats> {
+        self.validate_target_dir()?;
+        self.check_not_critical()?;
+
+        say!(
+            self.reporter,
+            "{} {}",
+            "Unflattening directory:".bold(),
+            self.config.target_dir.display()
+        );
+        if self.config.dry_run {
+            say!(
+                self.reporter,
+                "{}",
+                "[DRY RUN MODE] No files will be moved.".cyan().bold()
+            );
+        }
+        say!(self.reporter);
+
+        let mut stats = SortStats::default();
+        let mut journal = Journal::default();
+        let mut written = HashSet::new();
+
+        for category in self.categories() {
+            let folder = self.config.target_dir.join(self.folder_name(category));
+            if !is_directory(&folder) || is_symlink(&folder) {
+                continue;
+            }
+
+            let mut files = Vec::new();
+            let mut dirs = vec![folder.clone()];
+            collect_tree(&folder, &mut files, &mut dirs)?;
+            self.pull_up_files(
+                files,
+                Some(category),
+                "↩",
+                &mut written,
+                &mut stats,
+                &mut journal,
+            );
+
+            if self.config.dry_run {
+                continue;
+            }
+
+            // 深い階層から順に、空になったフォルダを削除する
+            dirs.sort_by_key(|d| Reverse(d.components().count()));
+            for dir in dirs {
+                if fs::remove_dir(&dir).is_err() {
+                    debug!("Keeping non-empty folder: {}", dir.display());
+                }
+            }
+            if !folder.exists() {
+                stats.removed_folders += 1;
+            }
+        }
+
+        if self.config.write_journal && !self.config.dry_run && !journal.entries.is_empty() {
+            let path = journal.save(&self.config.target_dir)?;
+            say!(
+                self.reporter,
+                "{} {}",
+                "Journal written:".bold(),
+                path.display()
+            );
+        }
+
+        stats.print_summary(
+            self.config.dry_run,
+            &self.config.folder_names,
+            &self.reporter,
+        );
+        self.print_json(&stats)?;
+        Ok(stats)
+    }
+
+    /// 対象ディレクトリ以下のすべてのファイルを対象ディレクトリ直下に移動する
+    ///
+    /// `unflatten` と違い、カテゴリフォルダに限らずすべてのサブフォルダが対象です。
+    /// 同名のファイルは連番でリネームし、`prune_empty` 指定時は空のフォルダを削除します。
+    /// `skip_hidden` 指定時は隠しディレクトリの中身には触れません。
+    pub fn flatten(&self) -> Result<SortStats> {
+        self.validate_target_dir()?;
+        self.check_not_critical()?;
+
+        let target = &self.config.target_dir;
+        say!(
+            self.reporter,
+            "{} {}",
+            "Flattening directory:".bold(),
+            target.display()
+        );
+        if self.config.dry_run {
+            say!(
+                self.reporter,
+                "{}",
+                "[DRY RUN MODE] No files will be moved.".cyan().bold()
+            );
+        }
+        say!(self.reporter);
+
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        collect_tree(target, &mut files, &mut dirs)?;
+        let visible = |path: &Path| {
+            !self.config.skip_hidden
+                || path
+                    .strip_prefix(target)
+                    .unwrap_or(path)
+                    .components()
+                    .all(|c| !c.as_os_str().to_string_lossy().starts_with('.'))
+        };
+        files.retain(|f| f.parent() != Some(target.as_path()) && visible(f));
+
+        let mut stats = SortStats::default();
+        let mut journal = Journal::default();
+        self.pull_up_files(
+            files,
+            None,
+            "↑",
+            &mut HashSet::new(),
+            &mut stats,
+            &mut journal,
+        );
+
+        if self.config.prune_empty && !self.config.dry_run {
+            self.prune_empty_dirs(&mut stats);
+        }
+
+        if self.config.write_journal && !self.config.dry_run && !journal.entries.is_empty() {
+            let path = journal.save(target)?;
+            say!(
+                self.reporter,
+                "{} {}",
+                "Journal written:".bold(),
+                path.display()
+            );
+        }
+
+        stats.print_summary(
+            self.config.dry_run,
+            &self.config.folder_names,
+            &self.reporter,
+        );
+        self.print_json(&stats)?;
+        Ok(stats)
+    }
+
+    /// サブフォルダ内のファイルを対象ディレクトリ直下に移動する（`unflatten`・`flatten` 共通）
+    ///
+    /// 除外・対象パターンに当てはまらないファイルと、`max_depth` より深いサブフォルダの
+    /// ファイルは対象にしません。直下に同名のファイルがある場合は `--on-conflict` に従い、
+    /// この実行で戻したファイル（`written`）同士は連番を付けて両方残します。
+    /// `category` を指定すると、戻したファイルをそのカテゴリの件数に数えます。
+    fn pull_up_files(
+        &self,
+        mut files: Vec<PathBuf>,
+        category: Option<&Category>,
+        marker: &str,
+        written: &mut HashSet<PathBuf>,
+        stats: &mut SortStats,
+        journal: &mut Journal,
+    ) {
+        let target = &self.config.target_dir;
+        if let Some(max) = self.config.max_depth {
+            files.retain(|f| {
+                let depth = f.strip_prefix(target).map_or(0, |r| r.components().count());
+                depth.saturating_sub(1) <= max
+            });
+        }
+        let (excluded, _) = self.apply_patterns(&mut files);
+        stats.excluded_files += excluded;
+        files.sort();
+
+        for file in files {
+            stats.total_files += 1;
+            let relative = file.strip_prefix(target).unwrap_or(&file).to_path_buf();
+            let Some(filename) = file.file_name().and_then(|n| n.to_str()) else {
+                warn!("Invalid filename: {}", file.display());
+                stats.error_count += 1;
+                continue;
+            };
+
+            // 直下の同名のファイルの扱いを決める（この実行で戻したファイルは置き換えない）
+            let dest = target.join(filename);
+            let on_conflict = if written.contains(&dest) {
+                ConflictStrategy::Rename
+            } else if dest.exists() {
+                resolve_conflict(self.config.on_conflict, &file, &dest)
+            } else {
+                self.config.on_conflict
+            };
+            if on_conflict == ConflictStrategy::Skip && dest.exists() {
+                detail!(
+                    self.reporter,
+                    "  {} {} {}",
+                    "⊘".yellow(),
+                    relative.display(),
+                    "(skipped: already exists)".yellow()
+                );
+                stats.skipped_files += 1;
+                continue;
+            }
+
+            if self.config.dry_run {
+                let final_dest = if dest.exists() && on_conflict.replaces_existing() {
+                    dest.clone()
+                } else {
+                    generate_unique_path_limited(target, filename, self.unique_naming(), |p| {
+                        p.exists() || written.contains(p)
+                    })
+                };
+                let dest_name = final_dest.strip_prefix(target).unwrap_or(&final_dest);
+                let note = if final_dest != dest {
+                    stats.renamed_files += 1;
+                    " (renamed)"
+                } else if dest.exists() {
+                    stats.replaced_files += 1;
+                    " (replace)"
+                } else {
+                    ""
+                };
+                let dest_label = format!("{}{}", dest_name.display(), note);
+                detail!(
+                    self.reporter,
+                    "  {} {} {} {}",
+                    "[DRY RUN]".cyan(),
+                    relative.display(),
+                    "→".cyan(),
+                    if note.is_empty() {
+                        dest_label.normal()
+                    } else {
+                        dest_label.yellow()
+                    }
+                );
+                written.insert(final_dest);
+                if let Some(category) = category {
+                    *stats.category_counts.entry(category.clone()).or_insert(0) += 1;
+                }
+                stats.moved_files += 1;
+                continue;
+            }
+
+            written.insert(dest);
+            let moved = move_file_with_dedup_as(
+                &file,
+                target,
+                filename,
+                self.unique_naming(),
+                self.config.retry,
+                on_conflict,
+            );
+            match moved {
+                Ok(result) => {
+                    journal.record(&file, &result.destination);
+                    written.insert(result.destination.clone());
+                    if let Some(category) = category {
+                        *stats.category_counts.entry(category.clone()).or_insert(0) += 1;
+                    }
+                    let relative_dest = result
+                        .destination
+                        .strip_prefix(target)
+                        .unwrap_or(&result.destination);
+                    // ゴミ箱に移せなかった場合は連番を付けて両方残し、エラーとして数える
+                    if matches!(result.replaced, Some(Replaced::TrashFailed(_))) {
+                        stats.error_count += 1;
+                    }
+                    let note = match &result.replaced {
+                        _ if result.was_renamed => {
+                            stats.renamed_files += 1;
+                            " (renamed)"
+                        }
+                        Some(Replaced::Overwritten) => {
+                            stats.replaced_files += 1;
+                            " (overwritten)"
+                        }
+                        Some(Replaced::Trashed) => {
+                            stats.replaced_files += 1;
+                            " (old file moved to trash)"
+                        }
+                        _ => "",
+                    };
+                    let dest_label = format!("{}{}", relative_dest.display(), note);
+                    detail!(
+                        self.reporter,
+                        "  {} {} {} {}",
+                        marker.green(),
+                        relative.display(),
+                        "→".green(),
+                        if note.is_empty() {
+                            dest_label.normal()
+                        } else {
+                            dest_label.yellow()
+                        }
+                    );
+                    stats.moved_files += 1;
+                }
+                Err(e) => {
+                    warn!("Failed to move file: {}", e);
+                    detail!(
+                        self.reporter,
+                        "  {} {} - {}",
+                        "✗".red(),
+                        file.display(),
+                        e.to_string().red()
+                    );
+                    stats.error_count += 1;
+                }
+            }
+        }
+    }
+
+    /// `--export-plan` で書き出した計画を読み込み、その通りに移動する
+    ///
+    /// 対象ディレクトリは走査し直さず、計画の移動先（重複回避後のパス）に移動します。
+    /// 計画の作成後に移動元がなくなっていたり、計画が別のディレクトリのものだった
+    /// 場合は、古い計画として何も移動せずにエラーにします。
+    pub fn apply_plan(&self, path: &Path) -> Result<SortStats> {
+        self.validate_target_dir()?;
+        self.check_not_critical()?;
+
+        let plans = load_plans(path)?;
+        say!(
+            self.reporter,
+            "{} {}",
+            "Applying plan:".bold(),
+            path.display()
+        );
+        say!(
+            self.reporter,
+            "{} {}",
+            "Target directory:".bold(),
+            self.config.target_dir.display()
+        );
+        if self.config.dry_run {
+            say!(
+                self.reporter,
+                "{}",
+                "[DRY RUN MODE] No files will be moved.".cyan().bold()
+            );
+        }
+        say!(self.reporter);
+
+        let target = fs::canonicalize(&self.config.target_dir).with_context(|| {
+            format!(
+                "Failed to resolve path: {}",
+                self.config.target_dir.display()
+            )
+        })?;
+        if let Some(outside) = plans.iter().find(|plan| {
+            fs::canonicalize(&plan.source).is_ok_and(|source| !source.starts_with(&target))
+        }) {
+            bail!(
+                "Plan does not belong to {}: {} is outside the target directory",
+                self.config.target_dir.display(),
+                outside.source.display()
+            );
+        }
+        // 編集された計画で振り分け先のルートの外に書き出さないようにする
+        let root = resolve_path(self.dest_root());
+        if let Some(outside) = plans.iter().find(|plan| {
+            let destination = resolve_path(&plan.destination);
+            !destination.starts_with(&root) || destination == root
+        }) {
+            bail!(
+                "Plan does not belong to {}: destination {} is outside the destination directory",
+                self.config.target_dir.display(),
+                outside.destination.display()
+            );
+        }
+        let missing: Vec<&Path> = plans
+            .iter()
+            .filter(|plan| !plan.source.is_file())
+            .map(|plan| plan.source.as_path())
+            .collect();
+        if let Some(first) = missing.first() {
+            bail!(
+                "Plan is stale: {} of {} source files no longer exist (e.g. {})\n\
+                 Export the plan again with --dry-run --export-plan.",
+                missing.len(),
+                plans.len(),
+                first.display()
+            );
+        }
+
+        if plans.is_empty() {
+            say!(self.reporter, "{}", "No files found to sort.".yellow());
+            return self.finish(SortStats::default());
+        }
+
+        let mut journal = Journal::default();
+        let mut stats = if self.config.dry_run {
+            self.execute_dry_run(&plans)?
+        } else {
+            self.execute_move(&plans, &mut journal)?
+        };
+
+        if self.config.prune_empty && self.moves_files() && !self.keeps_originals() {
+            self.prune_empty_dirs(&mut stats);
+        }
+
+        if self.config.write_journal
+            && self.moves_files()
+            && !self.keeps_originals()
+            && !journal.entries.is_empty()
+        {
+            let path = journal.save(&self.config.target_dir)?;
+            say!(
+                self.reporter,
+                "{} {}",
+                "Journal written:".bold(),
+                path.display()
+            );
+        }
+
+        stats.print_summary(
+            self.config.dry_run,
+            &self.config.folder_names,
+            &self.reporter,
+        );
+        self.finish(stats)
+    }
+
+    /// 対象ディレクトリのジャーナルを読み込み、前回の整理の移動をすべて取り消す
+    ///
+    /// 新しい移動から順に、移動先のファイルを元のパスに戻します。
+    /// 元のパスが別のファイルで埋まっている場合は連番を付けて戻し、
+    /// 移動先から既になくなっているファイルは報告だけします。
+    /// すべて処理できたらジャーナルを削除し、エラーがあれば残りだけを書き戻します。
+    pub fn undo(&self) -> Result<UndoReport> {
+        self.validate_target_dir()?;
+
+        let Some(journal) = Journal::load(&self.config.target_dir)? else {
+            bail!(
+                "No journal found in {}: nothing to undo",
+                self.config.target_dir.display()
+            );
+        };
+
+        say!(
+            self.reporter,
+            "{} {}",
+            "Undoing last sort in:".bold(),
+            self.config.target_dir.display()
+        );
+        if self.config.dry_run {
+            say!(
+                self.reporter,
+                "{}",
+                "[DRY RUN MODE] No files will be moved.".cyan().bold()
+            );
+        }
+        say!(self.reporter);
+
+        let mut report = UndoReport::default();
+        let mut failed = Vec::new();
+        let target_dir = fs::canonicalize(&self.config.target_dir)
+            .unwrap_or_else(|_| self.config.target_dir.clone());
+
+        for entry in journal.entries.iter().rev() {
+            if fs::symlink_metadata(&entry.destination).is_err() {
+                debug!(
+                    "Journal entry no longer exists: {}",
+                    entry.destination.display()
+                );
+                report.missing.push(entry.destination.clone());
+                continue;
+            }
+            let (Some(dir), Some(filename)) = (
+                entry.source.parent(),
+                entry.source.file_name().and_then(|n| n.to_str()),
+            ) else {
+                warn!("Invalid journal entry: {}", entry.source.display());
+                report.errors += 1;
+                failed.push(entry.clone());
+                continue;
+            };
+
+            if self.config.dry_run {
+                let renamed = entry.source.exists();
+                let note = if renamed { " (renamed)" } else { "" };
+                detail!(
+                    self.reporter,
+                    "  {} {} {} {}{}",
+                    "[DRY RUN]".cyan(),
+                    entry.destination.display(),
+                    "→".cyan(),
+                    entry.source.display(),
+                    note.yellow()
+                );
+                report.renamed += usize::from(renamed);
+                report.reverted += 1;
+                continue;
+            }
+
+            match move_file_with_dedup_as(
+                &entry.destination,
+                dir,
+                filename,
+                UniqueNaming::default(),
+                self.config.retry,
+                ConflictStrategy::Rename,
+            ) {
+                Ok(result) => {
+                    let arrow = "→".green();
+                    if result.was_renamed {
+                        detail!(
+                            self.reporter,
+                            "  {} {} {} {}",
+                            "↩".green(),
+                            entry.destination.display(),
+                            arrow,
+                            format!("{} (renamed)", result.destination.display()).yellow()
+                        );
+                        report.renamed += 1;
+                    } else {
+                        detail!(
+                            self.reporter,
+                            "  {} {} {} {}",
+                            "↩".green(),
+                            entry.destination.display(),
+                            arrow,
+                            result.destination.display()
+                        );
+                    }
+                    report.reverted += 1;
+                    remove_empty_parents(&entry.destination, &target_dir);
+                }
+                Err(e) => {
+                    warn!("Failed to revert move: {:#}", e);
+                    detail!(
+                        self.reporter,
+                        "  {} {} - {}",
+                        "✗".red(),
+                        entry.destination.display(),
+                        e.to_string().red()
+                    );
+                    report.errors += 1;
+                    failed.push(entry.clone());
+                }
+            }
+        }
+
+        if !self.config.dry_run {
+            if failed.is_empty() {
+                Journal::remove(&self.config.target_dir)?;
+            } else {
+                // 失敗した移動だけを残し、もう一度取り消せるようにする
+                failed.reverse();
+                Journal { entries: failed }.save(&self.config.target_dir)?;
+            }
+        }
+
+        report.print(self.config.dry_run, &self.reporter);
+        Ok(report)
+    }
+
+    /// 同名ファイルのレポートを表示する（ファイルは移動しない）
+    ///
+    /// サブディレクトリを常に再帰的に探索し、複数のディレクトリに存在する
+    /// 同じファイル名をグループとして報告します。整理時にどれだけリネームが
+    /// 発生するかを事前に把握するためのものです。
+    pub fn report_name_collisions(&self) -> Result<Vec<NameCollision>> {
+        self.validate_target_dir()?;
+
+        let collisions = self.find_name_collisions()?;
+
+        say!(
+            self.reporter,
+            "{} {}",
+            "Name collision report:".bold(),
+            self.config.target_dir.display()
+        );
+        say!(self.reporter);
+
+        if collisions.is_empty() {
+            say!(
+                self.reporter,
+                "{}",
+                "No duplicate file names found.".green()
+            );
+            return Ok(collisions);
+        }
+
+        for collision in &collisions {
+            say!(
+                self.reporter,
+                "  {} {}",
+                collision.name.yellow().bold(),
+                format!("({} files)", collision.paths.len()).yellow()
+            );
+            for path in &collision.paths {
+                let relative = path.strip_prefix(&self.config.target_dir).unwrap_or(path);
+                say!(self.reporter, "    {}", relative.display());
+            }
+        }
+
+        say!(self.reporter);
+        say!(
+            self.reporter,
+            "Collision groups: {}",
+            collisions.len().to_string().yellow()
+        );
+
+        Ok(collisions)
+    }
+
+    /// 整理が必要かどうかを何も表示せずに調べる（ファイルは移動しない）
+    ///
+    /// 通常の実行と同じ収集・除外・重複検出を行い、移動または削除が
+    /// 計画されるファイルの数を返します。0 であれば整理済みです。
+    pub fn check(&self) -> Result<usize> {
+        let (plans, duplicates) = self.planned()?;
+        let removals = if self.config.dedupe_first {
+            duplicates.len()
+        } else {
+            0
+        };
+
+        let pending = plans.len() + removals;
+        debug!("{} files would be moved or removed", pending);
+        Ok(pending)
+    }
+
+    /// 移動の計画を作成して返す（ファイルは移動せず、何も表示しない）
+    ///
+    /// 通常の実行と同じく、除外されるファイルと内容が重複するファイルを除いた上で、
+    /// 各ファイルの移動先（重複回避前）を求めます。ライブラリとして使う場合に、
+    /// 実行前に計画を確認したり、独自に表示したりするための入口です。
+    pub fn plan(&self) -> Result<Vec<FilePlan>> {
+        Ok(self.planned()?.0)
+    }
+
+    /// 対象のファイルを集めて移動の計画を作成する（表示はしない）
+    ///
+    /// # Returns
+    /// `(移動の計画, 内容が重複していたため計画から除いたファイル)`
+    fn planned(&self) -> Result<(Vec<FilePlan>, Vec<ContentDuplicate>)> {
+        self.validate_target_dir()?;
+        self.check_not_critical()?;
+
+        let run = self.build_plans()?;
+        Ok((run.plans, run.duplicates))
+    }
+
+    /// ファイルを集め、除外・フィルタ・重複検出を経て移動の計画を作成する（表示はしない）
+    ///
+    /// 通常の実行、`check`・`plan`、`stats_only` がすべてこの手順を使うため、
+    /// どれも実際に移動されるファイルについて同じ結果になります。
+    fn build_plans(&self) -> Result<PlannedRun> {
+        check_folder_names(&self.config.folder_names, &self.config.custom_categories)?;
+        // ファイルを収集し、除外パターンに一致するもの・対象パターンに一致しないものを外す
+        let mut files = self.gather_files()?;
+        let (excluded, not_included) = self.apply_patterns(&mut files);
+        if let Some(archive) = &self.config.archive_output {
+            // 出力先のzip自体を取り込まないようにする
+            let archive = fs::canonicalize(archive).unwrap_or_else(|_| archive.clone());
+            files.retain(|f| fs::canonicalize(f).map_or(true, |f| f != archive));
+        }
+        if let Some(view) = &self.config.hardlink_view {
+            // 対象ディレクトリ内のビューにある前回のリンクを取り込まないようにする
+            let view = fs::canonicalize(view).unwrap_or_else(|_| view.clone());
+            files.retain(|f| fs::canonicalize(f).map_or(true, |f| !f.starts_with(&view)));
+        }
+        // 受信箱の中にあるアーカイブのファイルは取り込まない
+        let sync_archive = match &self.config.sync_into {
+            Some(archive) => {
+                let archive = fs::canonicalize(archive).unwrap_or_else(|_| archive.clone());
+                if fs::canonicalize(&self.config.target_dir).is_ok_and(|t| t == archive) {
+                    bail!("The sync archive must differ from the inbox directory");
+                }
+                files.retain(|f| fs::canonicalize(f).map_or(true, |f| !f.starts_with(&archive)));
+                let archived = archive_files(&archive)?;
+                Some((archive, archived))
+            }
+            None => None,
+        };
+        info!("Found {} files to process", files.len());
+
+        // フィルタに該当するファイルを除外
+        let skipped;
+        (files, skipped) = self.apply_filters(files);
+
+        // 並べ替えと件数の制限
+        if self.config.newest_first {
+            sort_newest_first(&mut files);
+        }
+        let mut truncated_from = None;
+        if let Some(max) = self.config.max_files {
+            if files.len() > max {
+                truncated_from = Some(files.len());
+                files.truncate(max);
+            }
+        }
+
+        // 内容が同一のファイルを除外
+        let mut duplicates = Vec::new();
+        if self.config.merge_content_duplicates || self.config.dedupe_first {
+            (files, duplicates) = partition_content_duplicates(&files, self.config.dedup_window)?;
+        }
+
+        // 振り分け前のアーカイブと受信箱を突き合わせる
+        let sync_report = sync_archive
+            .as_ref()
+            .map(|(archive, archived)| SyncReport::reconcile(&files, archive, archived));
+
+        // カテゴリフォルダ内で既に正しいカテゴリにあるファイルを数える
+        let already_sorted = self.count_already_sorted();
+
+        // 分類計画を作成
+        let mut plans = self.create_plans(&files)?;
+
+        // 既に移動先にあるファイルは移動しない（連番付きの複製を作らない）
+        let in_place = self.drop_already_sorted(&mut plans);
+
+        // ファイルの少ないカテゴリは移動せずに残す
+        let left_in_place = self.drop_small_categories(&mut plans);
+
+        Ok(PlannedRun {
+            plans,
+            file_count: files.len(),
+            excluded,
+            not_included,
+            skipped,
+            truncated_from,
+            duplicates,
+            sync_report,
+            already_sorted,
+            in_place,
+            left_in_place,
+        })
+    }
+
+    /// ファイルを分類し、カテゴリごとの件数とサイズだけを表示する（ファイルは移動しない）
+    ///
+    /// Dry Run と違って移動先を調べず、1ファイルずつの表示もしません。
+    pub fn stats_only(&self) -> Result<SortStats> {
+        self.validate_target_dir()?;
+
+        let run = self.build_plans()?;
+        let plans = &run.plans;
+        let skipped = run.skipped.len() + run.left_in_place.len() + run.not_included;
+        let mut stats = SortStats {
+            total_files: plans.len() + skipped + run.duplicates.len() + run.in_place,
+            moved_files: plans.len(),
+            skipped_files: skipped,
+            already_sorted: run.already_sorted + run.in_place,
+            excluded_files: run.excluded,
+            ..Default::default()
+        };
+        if self.config.dedupe_first {
+            stats.removed_duplicates = run.duplicates.len();
+        } else {
+            stats.duplicate_files = run.duplicates.len();
+        }
+        for plan in plans {
+            *stats
+                .category_counts
+                .entry(plan.category.clone())
+                .or_insert(0) += 1;
+            *stats
+                .category_bytes
+                .entry(plan.category.clone())
+                .or_insert(0) += file_size(&plan.source).unwrap_or(0);
+            self.count_others_extension(&mut stats, plan);
+        }
+
+        say!(
+            self.reporter,
+            "{} {}",
+            "Stats for:".bold(),
+            self.config.target_dir.display()
+        );
+        stats.print_summary(true, &self.config.folder_names, &self.reporter);
+        if self.config.visual_summary {
+            stats.print_visual_summary(&self.config.folder_names, &self.reporter);
+        }
+        self.finish(stats)
+    }
+
+    /// 対象ディレクトリを監視し、新しいファイルを書き込みが終わり次第振り分ける
+    ///
+    /// 対象ディレクトリの直下に作成された・移動してきたファイルだけが対象で、
+    /// サイズが変化しなくなるまで待ってから移動します。振り分けのたびにそれまでの
+    /// 合計を表示し、Ctrl+C で終了するまで戻りません。
+    pub fn watch(&self) -> Result<SortStats> {
+        self.validate_target_dir()?;
+        self.check_not_critical()?;
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).context("Failed to start the file watcher")?;
+        watcher
+            .watch(&self.config.target_dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", self.config.target_dir.display()))?;
+
+        say!(
+            self.reporter,
+            "{} {}",
+            "Watching for new files in:".bold(),
+            self.config.target_dir.display()
+        );
+        if self.config.dry_run {
+            say!(
+                self.reporter,
+                "{}",
+                "[DRY RUN MODE] No files will be moved.".cyan().bold()
+            );
+        }
+        say!(self.reporter, "{}", "Press Ctrl+C to stop.".dimmed());
+
+        let policy = self
+            .config
+            .stabilize_wait
+            .unwrap_or(StabilityPolicy::new(WATCH_POLL_INTERVAL));
+        let mut pending = PendingFiles::default();
+        let mut total = SortStats::default();
+        let mut last_poll = Instant::now();
+        loop {
+            match rx.recv_timeout(policy.interval) {
+                Ok(Ok(event)) => {
+                    // 通知のパスは絶対パスのことがあるので、対象ディレクトリ直下のパスに揃える
+                    for path in arrived_paths(&event) {
+                        let Some(name) = path.file_name() else {
+                            continue;
+                        };
+                        let path = self.config.target_dir.join(name);
+                        if is_file(&path) {
+                            pending.add(path);
+                        }
+                    }
+                }
+                Ok(Err(e)) => warn!("File watcher error: {}", e),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            // 通知が続いていても一定間隔でサイズを確認する
+            if pending.is_empty() || last_poll.elapsed() < policy.interval {
+                continue;
+            }
+            last_poll = Instant::now();
+            let ready = pending.take_stable(policy.polls, file_size);
+            if ready.is_empty() {
+                continue;
+            }
+
+            // 振り分けに失敗しても監視は続ける
+            match self.sort_arrived(ready) {
+                Ok(stats) => {
+                    total.merge(stats);
+                    say!(
+                        self.reporter,
+                        "{} {} files ({})",
+                        "Sorted so far:".bold(),
+                        total.moved_files.to_string().green(),
+                        total.category_line(&self.config.folder_names)
+                    );
+                }
+                Err(e) => warn!("Failed to sort new files: {:#}", e),
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// 監視中に書き込みが終わったファイルを振り分ける
+    fn sort_arrived(&self, mut files: Vec<PathBuf>) -> Result<SortStats> {
+        // ディレクトリの走査と同じく、シンボリックリンクと smart-sorter 自身のファイルは扱わない
+        files.retain(|file| {
+            (self.config.follow_symlinks || !is_symlink(file)) && !self.is_sorter_file(file)
+        });
+        let (excluded, not_included) = self.apply_patterns(&mut files);
+        let mut skipped = Vec::new();
+        files.retain(|file| match self.skip_reason(file) {
+            Some(reason) => {
+                skipped.push(SkippedFile {
+                    path: file.clone(),
+                    reason,
+                });
+                false
+            }
+            None => true,
+        });
+        self.print_skipped(&skipped);
+
+        let plans = self.create_plans(&files)?;
+        let mut stats = if self.config.dry_run {
+            self.execute_dry_run(&plans)?
+        } else {
+            // 監視中の移動はジャーナルに残さない
+            self.execute_move(&plans, &mut Journal::default())?
+        };
+        stats.total_files += skipped.len() + not_included;
+        stats.skipped_files += skipped.len() + not_included;
+        stats.excluded_files = excluded;
+        Ok(stats)
+    }
+
+    /// 拡張子ごとの散らばり具合を表示する（ファイルは移動しない）
+    ///
+    /// サブディレクトリを常に再帰的に探索し、各拡張子のファイルがいくつの
+    /// ディレクトリに分散しているかを報告します。値が大きい拡張子ほど
+    /// 整理による効果が大きいことを示します。
+    pub fn report_fragmentation(&self) -> Result<Vec<ExtensionFragmentation>> {
+        self.validate_target_dir()?;
+
+        let fragmentation = self.measure_fragmentation()?;
+
+        say!(
+            self.reporter,
+            "{} {}",
+            "Fragmentation report:".bold(),
+            self.config.target_dir.display()
+        );
+        say!(self.reporter);
+
+        if fragmentation.is_empty() {
+            say!(
+                self.reporter,
+                "{}",
+                "No files with an extension found.".yellow()
+            );
+            return Ok(fragmentation);
+        }
+
+        let width = fragmentation
+            .iter()
+            .map(|f| f.extension.len() + 1)
+            .max()
+            .unwrap_or(0);
+        for entry in &fragmentation {
+            say!(
+                self.reporter,
+                "  {:<width$}  {} {}",
+                format!(".{}", entry.extension),
+                format!("{} dirs", entry.directories).yellow().bold(),
+                format!("({} files)", entry.files).dimmed()
+            );
+        }
+
+        Ok(fragmentation)
+    }
+
+    /// Others に分類される拡張子をファイル数の多い順に表示する（ファイルは移動しない）
+    ///
+    /// 設定ファイルや `--map` の割り当ても考慮するため、独自の設定に追加すべき
+    /// 拡張子を探すのに使えます。拡張子のないファイルは対象外です。
+    pub fn report_extensions(&self) -> Result<Vec<UnrecognizedExtension>> {
+        self.validate_target_dir()?;
+
+        let unrecognized = self.find_unrecognized_extensions()?;
+
+        say!(
+            self.reporter,
+            "{} {}",
+            "Unrecognized extensions:".bold(),
+            self.config.target_dir.display()
+        );
+        say!(self.reporter);
+
+        if unrecognized.is_empty() {
+            say!(
+                self.reporter,
+                "{}",
+                "Every extension maps to a category.".green()
+            );
+            return Ok(unrecognized);
+        }
+
+        let width = unrecognized
+            .iter()
+            .map(|u| u.extension.len() + 1)
+            .max()
+            .unwrap_or(0);
+        for entry in &unrecognized {
+            say!(
+                self.reporter,
+                "  {:<width$}  {}",
+                format!(".{}", entry.extension),
+                format!("{} files", entry.files).yellow().bold()
+            );
+        }
+
+        Ok(unrecognized)
+    }
+
+    /// Others に分類される拡張子ごとのファイル数を集計する
+    ///
+    /// ファイル数の多い順、同数の場合は拡張子順に並びます。
+    fn find_unrecognized_extensions(&self) -> Result<Vec<UnrecognizedExtension>> {
+        let files = self.collect_files(&self.config.target_dir)?;
+
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for file in files {
+            if let Some(ext) = get_extension(&file) {
+                if self.categorize_file(&file) == Category::Others {
+                    *counts.entry(ext).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut unrecognized: Vec<UnrecognizedExtension> = counts
+            .into_iter()
+            .map(|(extension, files)| UnrecognizedExtension { extension, files })
+            .collect();
+        unrecognized.sort_by_key(|u| Reverse(u.files));
+
+        Ok(unrecognized)
+    }
+
+    /// ツリー全体から拡張子ごとの散らばり具合を集計する
+    ///
+    /// ディレクトリ数の多い順、同数の場合は拡張子順に並びます。
+    /// 拡張子のないファイルは対象外です。
+    fn measure_fragmentation(&self) -> Result<Vec<ExtensionFragmentation>> {
+        let walker = Sorter::new(SorterConfig {
+            recursive: true,
+            ..self.config.clone()
+        });
+        let files = walker.collect_files(&self.config.target_dir)?;
+
+        let mut groups: BTreeMap<String, (BTreeSet<PathBuf>, usize)> = BTreeMap::new();
+        for file in files {
+            if let (Some(ext), Some(parent)) = (get_extension(&file), file.parent()) {
+                let (dirs, count) = groups.entry(ext).or_default();
+                dirs.insert(parent.to_path_buf());
+                *count += 1;
+            }
+        }
+
+        let mut fragmentation: Vec<ExtensionFragmentation> = groups
+            .into_iter()
+            .map(|(extension, (dirs, files))| ExtensionFragmentation {
+                extension,
+                directories: dirs.len(),
+                files,
+            })
+            .collect();
+        fragmentation.sort_by_key(|f| Reverse(f.directories));
+
+        Ok(fragmentation)
+    }
+
+    /// ツリー全体から同名ファイルのグループを検出する
+    ///
+    /// グループはファイル数の多い順、同数の場合はファイル名順に並びます。
+    fn find_name_collisions(&self) -> Result<Vec<NameCollision>> {
+        let walker = Sorter::new(SorterConfig {
+            recursive: true,
+            ..self.config.clone()
+        });
+        let files = walker.collect_files(&self.config.target_dir)?;
+
+        let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for file in files {
+            if let Some(name) = file.file_name().and_then(|n| n.to_str()) {
+                groups.entry(name.to_string()).or_default().push(file);
+            }
+        }
+
+        let mut collisions: Vec<NameCollision> = groups
+            .into_iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(|(name, mut paths)| {
+                paths.sort();
+                NameCollision { name, paths }
+            })
+            .collect();
+        collisions.sort_by_key(|c| Reverse(c.paths.len()));
+
+        Ok(collisions)
+    }
+
+    /// ファイルを収集
+    ///
+    /// 別の出力先に振り分ける場合、カテゴリ名のサブフォルダも通常のフォルダとして扱います。
+    fn collect_files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        // シンボリックリンクをたどる場合の循環を防ぐため、訪れたディレクトリの実体を記録する
+        let mut visited = HashSet::new();
+        if self.config.follow_symlinks {
+            visited.insert(fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf()));
+        }
+        self.collect_files_at(dir, 0, self.sorts_in_place(), &mut visited)
+    }
+
+    /// 整理の対象になりうるファイルを集める
+    ///
+    /// `files_from` があれば対象ディレクトリを走査せず、その一覧を使います。
+    fn gather_files(&self) -> Result<Vec<PathBuf>> {
+        match &self.config.files_from {
+            Some(paths) => self.listed_files(paths),
+            None => self.collect_files(&self.config.target_dir),
+        }
+    }
+
+    /// 一覧のパスのうち、整理できるファイルだけを返す
+    ///
+    /// 相対パスは現在のディレクトリから解決し、走査したときと同じく対象ディレクトリを
+    /// 起点にしたパスに直します。存在しないパスやファイルでないもの、対象ディレクトリの
+    /// 外にあるもの、システムやホームディレクトリの直下にあるものは警告してスキップします。
+    fn listed_files(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let target = fs::canonicalize(&self.config.target_dir).with_context(|| {
+            format!(
+                "Failed to resolve path: {}",
+                self.config.target_dir.display()
+            )
+        })?;
+        let mut files = Vec::new();
+        for path in paths {
+            if !self.config.follow_symlinks && is_symlink(path) {
+                warn!("Skipping symlink: {}", path.display());
+                continue;
+            } else if !path.exists() {
+                warn!("Skipping missing file: {}", path.display());
+                continue;
+            } else if !is_file(path) {
+                warn!("Skipping non-file entry: {}", path.display());
+                continue;
+            }
+
+            // リンク自体を移動するよう、親ディレクトリだけを正規化する
+            let Some(resolved) = resolve_parent(path) else {
+                warn!("Skipping unresolvable path: {}", path.display());
+                continue;
+            };
+            let Ok(relative) = resolved.strip_prefix(&target) else {
+                warn!(
+                    "Skipping file outside the target directory: {}",
+                    path.display()
+                );
+                continue;
+            };
+            if !self.config.force && resolved.parent().is_some_and(is_protected_dir) {
+                warn!("Skipping file in a critical directory: {}", path.display());
+                continue;
+            }
+
+            let path = self.config.target_dir.join(relative);
+            if self.sorts_in_place() && self.is_category_folder(&path) {
+                debug!("Skipping file in category folder: {}", path.display());
+            } else if self.is_sorter_file(&path) {
+                debug!("Skipping smart-sorter file: {}", path.display());
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// smart-sorter 自身が使うファイル（ジャーナルやローカル設定ファイル）かどうか
+    fn is_sorter_file(&self, path: &Path) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str());
+        name == Some(JOURNAL_FILE_NAME)
+            || name == Some(LOCAL_CONFIG_FILE_NAME)
+            || (self.config.dir_category_files && name == Some(DIR_CATEGORY_FILE_NAME))
+    }
+
+    /// `depth` 階層目（対象ディレクトリ直下が0）のディレクトリからファイルを収集
+    ///
+    /// `skip_categories` ならカテゴリフォルダとその中のファイルを除きます。
+    /// `follow_symlinks` 指定時は、`visited` にある実体のディレクトリには再び降りません。
+    fn collect_files_at(
+        &self,
+        dir: &Path,
+        depth: usize,
+        skip_categories: bool,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        for entry in fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+        {
+            let entry = entry.with_context(|| "Failed to read directory entry")?;
+            let path = entry.path();
+
+            // シンボリックリンクはスキップ（follow_symlinks 指定時はリンク先で判定する）
+            if !self.config.follow_symlinks && is_symlink(&path) {
+                debug!("Skipping symlink: {}", path.display());
+                continue;
+            }
+
+            if is_file(&path) {
+                // カテゴリフォルダ内のファイルはスキップ（無限ループ防止）
+                if skip_categories && self.is_category_folder(&path) {
+                    debug!("Skipping file in category folder: {}", path.display());
+                    continue;
+                }
+                // ジャーナルやローカル設定ファイル自体は整理しない
+                if self.is_sorter_file(&path) {
+                    debug!("Skipping smart-sorter file: {}", path.display());
+                    continue;
+                }
+                files.push(path);
+            } else if is_directory(&path) && self.config.recursive {
+                // 最大の深さに達したら再帰処理しない
+                if self.config.max_depth.is_some_and(|max| depth >= max) {
+                    debug!("Skipping directory beyond max depth: {}", path.display());
+                    continue;
+                }
+
+                // カテゴリフォルダは再帰処理しない
+                let folder_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                if skip_categories
+                    && self
+                        .categories()
+                        .any(|c| self.folder_name(c) == folder_name)
+                {
+                    debug!("Skipping category folder: {}", path.display());
+                    continue;
+                }
+
+                // 対象ディレクトリの中にある出力先は再帰処理しない
+                if !skip_categories && self.is_output_dir(&path) {
+                    debug!("Skipping output directory: {}", path.display());
+                    continue;
+                }
+
+                if self.config.skip_hidden && is_hidden(&path) {
+                    debug!("Skipping hidden directory: {}", path.display());
+                    continue;
+                }
+
+                // リポジトリの管理ディレクトリと振り分け済みのリポジトリフォルダは再帰処理しない
+                if self.config.by_repo
+                    && (VCS_MARKERS.contains(&folder_name)
+                        || (folder_name == REPOS_FOLDER && dir == self.config.target_dir))
+                {
+                    debug!("Skipping repository folder: {}", path.display());
+                    continue;
+                }
+
+                // シンボリックリンクの循環（祖先や訪問済みのディレクトリへのリンク）はたどらない
+                if self.config.follow_symlinks {
+                    let real = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+                    // リンク先がシステムやホームディレクトリなら、その中身は整理しない
+                    if !self.config.force && is_symlink(&path) && is_protected_dir(&real) {
+                        warn!(
+                            "Not following symlink into a critical directory: {} -> {}",
+                            path.display(),
+                            real.display()
+                        );
+                        continue;
+                    }
+                    if !visited.insert(real) {
+                        debug!("Skipping already visited directory: {}", path.display());
+                        continue;
+                    }
+                }
+
+                // 再帰的にファイルを収集
+                let sub_files =
+                    self.collect_files_at(&path, depth + 1, skip_categories, visited)?;
+                files.extend(sub_files);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// 除外パターンに一致するファイルと、対象パターンに一致しないファイルを取り除く
+    ///
+    /// 両方に一致するファイルは除外を優先します。
+    ///
+    /// # Returns
+    /// (除外パターンに一致したファイル数, 対象パターンに一致しなかったファイル数)
+    fn apply_patterns(&self, files: &mut Vec<PathBuf>) -> (usize, usize) {
+        if self.exclude.is_empty() && self.include.is_empty() {
+            return (0, 0);
+        }
+        let mut excluded = 0;
+        let mut not_included = 0;
+        files.retain(|file| {
+            if self.matches_any(&self.exclude, file) {
+                debug!("Excluded by pattern: {}", file.display());
+                excluded += 1;
+                false
+            } else if !self.include.is_empty() && !self.matches_any(&self.include, file) {
+                debug!("Not matched by any include pattern: {}", file.display());
+                not_included += 1;
+                false
+            } else {
+                true
+            }
+        });
+        (excluded, not_included)
+    }
+
+    /// ファイルがパターンのいずれかに一致するか
+    ///
+    /// 既定ではファイル名に、`match_path` なら対象ディレクトリからの相対パスに照合します。
+    fn matches_any(&self, patterns: &[Pattern], path: &Path) -> bool {
+        let subject = if self.config.match_path {
+            path.strip_prefix(&self.config.target_dir).unwrap_or(path)
+        } else {
+            match path.file_name() {
+                Some(name) => Path::new(name),
+                None => return false,
+            }
+        };
+        patterns.iter().any(|p| p.matches_path(subject))
+    }
+
+    /// この実行で振り分け先になりうる組み込みカテゴリと、設定ファイルで定義されたカテゴリ
+    ///
+    /// 無効なカテゴリと同じ名前のユーザーのフォルダを、カテゴリフォルダとして扱わないためのものです。
+    fn categories(&self) -> impl Iterator<Item = &Category> {
+        Category::all()
+            .iter()
+            .filter(|category| self.category_enabled(category))
+            .chain(&self.config.custom_categories)
+    }
+
+    /// 組み込みカテゴリが有効か（Programs・Torrents は、それを使うオプションがある場合だけ）
+    fn category_enabled(&self, category: &Category) -> bool {
+        let routed = || {
+            self.config.detect_executables.as_ref() == Some(category)
+                || self.config.cli_overrides.values().any(|c| c == category)
+                || self
+                    .config
+                    .extension_overrides
+                    .values()
+                    .any(|c| c == category)
+                || self
+                    .config
+                    .rules
+                    .iter()
+                    .any(|rule| rule.target.category == *category)
+        };
+        match category {
+            Category::Programs => routed(),
+            Category::Torrents => self.config.handle_downloads || routed(),
+            _ => true,
+        }
+    }
+
+    /// パスがカテゴリフォルダ内にあるかチェック
+    fn is_category_folder(&self, path: &Path) -> bool {
+        if let Some(parent) = path.parent() {
+            if let Some(folder_name) = parent.file_name().and_then(|n| n.to_str()) {
+                if parent.parent() == Some(&self.config.target_dir) {
+                    return self
+                        .categories()
+                        .any(|c| self.folder_name(c) == folder_name);
+                }
+            }
+        }
+        false
+    }
+
+    /// 移動先が移動元と同じ（既に振り分け済みの）計画を取り除き、その数を返す
+    fn drop_already_sorted(&self, plans: &mut Vec<FilePlan>) -> usize {
+        let before = plans.len();
+        plans.retain(|plan| {
+            if plan.source != plan.destination {
+                return true;
+            }
+            debug!("Already sorted: {}", plan.source.display());
+            false
+        });
+        before - plans.len()
+    }
+
+    /// カテゴリフォルダの中にある、拡張子がそのカテゴリに当てはまるファイルを数える
+    ///
+    /// カテゴリフォルダは整理の対象外のため、再実行しても中のファイルは動きません。
+    /// その中で分類が一致しているものを振り分け済みとして数えます。
+    fn count_already_sorted(&self) -> usize {
+        if !self.sorts_in_place() || self.config.files_from.is_some() {
+            return 0;
+        }
+        let mut count = 0;
+        for category in self.categories() {
+            let folder = self.config.target_dir.join(self.folder_name(category));
+            if !is_directory(&folder) || is_symlink(&folder) {
+                continue;
+            }
+            let mut dirs = vec![folder];
+            while let Some(dir) = dirs.pop() {
+                let Ok(entries) = fs::read_dir(&dir) else {
+                    continue;
+                };
+                for path in entries.flatten().map(|entry| entry.path()) {
+                    if is_symlink(&path) {
+                        continue;
+                    }
+                    if is_directory(&path) {
+                        dirs.push(path);
+                    } else if !self.is_sorter_file(&path)
+                        && self.categorize_file(&path) == *category
+                    {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// ファイルが `--min-files` に満たないカテゴリの計画を取り除き、スキップとして返す
+    ///
+    /// カテゴリフォルダが既にあるカテゴリは、新しいフォルダを作らないので件数に関係なく移動します。
+    fn drop_small_categories(&self, plans: &mut Vec<FilePlan>) -> Vec<SkippedFile> {
+        let Some(min) = self.config.min_files_per_category else {
+            return Vec::new();
+        };
+        let mut counts: HashMap<Category, usize> = HashMap::new();
+        for plan in plans.iter() {
+            *counts.entry(plan.category.clone()).or_insert(0) += 1;
+        }
+        let existing: HashSet<Category> = counts
+            .keys()
+            .filter(|category| self.dest_root().join(self.folder_name(category)).is_dir())
+            .cloned()
+            .collect();
+
+        let mut left_in_place = Vec::new();
+        plans.retain(|plan| {
+            if counts[&plan.category] >= min || existing.contains(&plan.category) {
+                return true;
+            }
+            left_in_place.push(SkippedFile {
+                path: plan.source.clone(),
+                reason: SkipReason::SmallCategory,
+            });
+            false
+        });
+        left_in_place
+    }
+
+    /// カテゴリのフォルダ名（`folder_names` で上書きされていればその名前）
+    fn folder_name<'a>(&'a self, category: &'a Category) -> &'a str {
+        category.folder_name_in(&self.config.folder_names)
+    }
+
+    /// 分類計画を作成
+    fn create_plans(&self, files: &[PathBuf]) -> Result<Vec<FilePlan>> {
+        let mut plans = Vec::new();
+        let mut local_configs = self
+            .config
+            .local_configs
+            .then(|| LocalConfigs::new(&self.config.target_dir, &self.config.custom_categories));
+        // ディレクトリ → `.category` マーカーのカテゴリ
+        let mut dir_categories: HashMap<PathBuf, Option<Category>> = HashMap::new();
+        // ディレクトリ → 最寄りのリポジトリ名
+        let mut repo_names: HashMap<PathBuf, Option<String>> = HashMap::new();
+        let batches = self.batch_numbers(files);
+
+        for (index, file) in files.iter().enumerate() {
+            let local_overrides = match &mut local_configs {
+                Some(local_configs) => Some(local_configs.overrides_for(file)?),
+                None => None,
+            };
+            let dir_category = match file.parent() {
+                Some(dir) if self.config.dir_category_files => match dir_categories.get(dir) {
+                    Some(category) => category.clone(),
+                    None => {
+                        let category = read_dir_category(dir, &self.config.custom_categories)?;
+                        dir_categories.insert(dir.to_path_buf(), category.clone());
+                        category
+                    }
+                },
+                _ => None,
+            };
+            let repo = match file.parent() {
+                Some(dir) if self.config.by_repo => self.repo_name(dir, &mut repo_names),
+                _ => None,
+            };
+            let rule = self.matching_rule(file);
+            // 空のファイルの振り分け先が決まっている場合はルールより優先
+            let empty_target = self.empty_file_target(file);
+            let rule = rule.filter(|_| empty_target.is_none());
+            // 空のファイル > ルール > `.category` マーカー > 拡張子による分類 の順に優先
+            let category = match (&empty_target, rule, dir_category) {
+                (Some((category, _)), _, _) => category.clone(),
+                (None, Some(rule), _) => rule.target.category.clone(),
+                (None, None, Some(category)) => category,
+                (None, None, None) => self.categorize_file_with(file, local_overrides.as_ref()),
+            };
+            let filename = file
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown");
+            // 打ち間違いと思われる拡張子を訂正したファイル名
+            let corrected_name = self.corrected_extension(file).map(|ext| {
+                let stem = file
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("unknown");
+                format!("{}.{}", stem, ext)
+            });
+            let filename = corrected_name.as_deref().unwrap_or(filename);
+            // バッチ > 空のファイル > ルール > リポジトリ > 接頭辞でのグループ化 > カテゴリによる振り分け の順に優先
+            let (dest_dir, filename) = match (rule, self.prefix_group(filename)) {
+                _ if !batches.is_empty() => (
+                    self.dest_root()
+                        .join(format!("{}{:03}", BATCH_FOLDER_PREFIX, batches[file])),
+                    filename,
+                ),
+                _ if empty_target.is_some() => (
+                    self.dest_root()
+                        .join(empty_target.as_ref().map_or("", |(_, folder)| *folder)),
+                    filename,
+                ),
+                (Some(rule), _) => (
+                    self.dest_root()
+                        .join(self.folder_name(&category))
+                        .join(&rule.target.subfolder),
+                    filename,
+                ),
+                (None, _) if repo.is_some() => (
+                    self.dest_root()
+                        .join(REPOS_FOLDER)
+                        .join(repo.as_deref().unwrap_or_default()),
+                    filename,
+                ),
+                (None, Some((folder, rest))) => (self.dest_root().join(folder), rest),
+                (None, None) => (self.destination_dir(file, &category), filename),
+            };
+            // 対象ディレクトリからの相対的なフォルダ構成を移動先でも保つ
+            let dest_dir = match file
+                .parent()
+                .map(|p| p.strip_prefix(&self.config.target_dir))
+            {
+                Some(Ok(relative))
+                    if self.config.preserve_tree && !relative.as_os_str().is_empty() =>
+                {
+                    dest_dir.join(relative)
+                }
+                _ => dest_dir,
+            };
+            let destination = match &self.config.rename_template {
+                Some(template) => {
+                    dest_dir.join(self.render_filename(template, file, &category, index + 1))
+                }
+                None => dest_dir.join(filename),
+            };
+            let destination = self.fit_destination(destination);
+
+            plans.push(FilePlan {
+                source: file.clone(),
+                destination,
+                category,
+            });
+        }
+
+        Ok(plans)
+    }
+
+    /// 移動先のファイル名を `max_filename_bytes` に収める（拡張子は残す）
+    fn fit_destination(&self, destination: PathBuf) -> PathBuf {
+        let Some(max) = self.config.max_filename_bytes else {
+            return destination;
+        };
+        let Some(filename) = destination.file_name().and_then(|n| n.to_str()) else {
+            return destination;
+        };
+        if filename.len() <= max {
+            return destination;
+        }
+        let (stem, extension) = split_compound_extension(filename);
+        destination.with_file_name(fit_file_name(stem, "", extension, max))
+    }
+
+    /// 移動先で名前が重複したときのファイル名の付け方
+    fn unique_naming(&self) -> UniqueNaming<'_> {
+        UniqueNaming {
+            max_bytes: self.config.max_filename_bytes,
+            template: self.config.dedup_template.as_ref(),
+            reserve: self.config.jobs > 1,
+        }
+    }
+
+    /// 各ファイルを入れるバッチの番号（1始まり）を求める（`batch_size` 未指定なら空）
+    ///
+    /// ファイルはパス順（`newest_first` なら処理順）に `batch_size` 件ずつ区切ります。
+    fn batch_numbers<'a>(&self, files: &'a [PathBuf]) -> HashMap<&'a PathBuf, usize> {
+        let Some(size) = self.config.batch_size else {
+            return HashMap::new();
+        };
+        let mut ordered: Vec<&PathBuf> = files.iter().collect();
+        if !self.config.newest_first {
+            ordered.sort();
+        }
+        ordered
+            .into_iter()
+            .enumerate()
+            .map(|(i, file)| (file, i / size.max(1) + 1))
+            .collect()
+    }
+
+    /// 整理対象から除外するファイルを振り分ける
+    ///
+    /// # Returns
+    /// `(整理対象のファイル, 除外されたファイル)`
+    fn apply_filters(&self, files: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<SkippedFile>) {
+        let mut kept = Vec::with_capacity(files.len());
+        let mut skipped = Vec::new();
+
+        for file in files {
+            match self.skip_reason(&file) {
+                Some(reason) => skipped.push(SkippedFile { path: file, reason }),
+                None => kept.push(file),
+            }
+        }
+
+        // サイズが変化しなくなるまで待ち、書き込み中のままのファイルは除外する
+        if let Some(policy) = self.config.stabilize_wait {
+            if !kept.is_empty() {
+                info!("Waiting for {} files to stop changing", kept.len());
+                let unstable;
+                (kept, unstable) = settle(kept, policy, file_size, std::thread::sleep);
+                skipped.extend(unstable.into_iter().map(|path| SkippedFile {
+                    path,
+                    reason: SkipReason::Unstable,
+                }));
+            }
+        }
+
+        (kept, skipped)
+    }
+
+    /// ファイルを除外すべき理由を判定する（対象とする場合は `None`）
+    fn skip_reason(&self, path: &Path) -> Option<SkipReason> {
+        if self.is_kept_at_root(path) {
+            return Some(SkipReason::KeepAtRoot);
+        }
+
+        if self.config.skip_marked && is_marked_sorted(path) {
+            return Some(SkipReason::Marked);
+        }
+
+        if self.config.empty_files == EmptyFiles::Skip && is_empty_file(path) {
+            return Some(SkipReason::Empty);
+        }
+
+        if self.config.skip_hidden && is_hidden(path) {
+            return Some(SkipReason::Hidden);
+        }
+
+        if self.config.min_size.is_some() || self.config.max_size.is_some() {
+            if let Ok(metadata) = fs::metadata(path) {
+                if self.config.min_size.is_some_and(|min| metadata.len() < min) {
+                    return Some(SkipReason::TooSmall);
+                }
+                if self.config.max_size.is_some_and(|max| metadata.len() > max) {
+                    return Some(SkipReason::TooLarge);
+                }
+            }
+        }
+
+        if let Some(min_age) = self.config.min_age_secs {
+            if is_modified_within(path, Duration::from_secs(min_age)) {
+                return Some(SkipReason::TooNew);
+            }
+        }
+
+        // 更新日時を読めないファイルは、期間内か判断できないため除外する
+        if self.config.modified_before.is_some() || self.config.modified_after.is_some() {
+            let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) else {
+                return Some(SkipReason::UnknownModified);
+            };
+            if self
+                .config
+                .modified_before
+                .is_some_and(|cutoff| modified >= cutoff)
+            {
+                return Some(SkipReason::ModifiedAfterCutoff);
+            }
+            if self
+                .config
+                .modified_after
+                .is_some_and(|cutoff| modified < cutoff)
+            {
+                return Some(SkipReason::ModifiedBeforeCutoff);
+            }
+        }
+
+        if self.config.handle_downloads {
+            if let Some(ext) = get_extension(path) {
+                if self.config.fragment_extensions.contains(&ext) {
+                    return Some(SkipReason::DownloadInProgress);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// 除外したファイルを表示
+    fn print_skipped(&self, skipped: &[SkippedFile]) {
+        for file in skipped {
+            let relative = file
+                .path
+                .strip_prefix(&self.config.target_dir)
+                .unwrap_or(&file.path);
+            detail!(
+                self.reporter,
+                "  {} {} {}",
+                "[SKIP]".yellow(),
+                relative.display(),
+                format!("({})", file.reason).yellow()
+            );
+        }
+    }
+
+    /// 移動しないよう指定された名前のファイルかどうか
+    fn is_kept_at_root(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        if self.config.keep_at_root_ignore_case {
+            self.config
+                .keep_at_root
+                .iter()
+                .any(|keep| keep.eq_ignore_ascii_case(name))
+        } else {
+            self.config.keep_at_root.contains(name)
+        }
+    }
+
+    /// 内容重複のためスキップするファイルを表示
+    fn print_duplicates(&self, duplicates: &[ContentDuplicate]) {
+        for duplicate in duplicates {
+            let relative_dup = duplicate
+                .path
+                .strip_prefix(&self.config.target_dir)
+                .unwrap_or(&duplicate.path);
+            let relative_orig = duplicate
+                .original
+                .strip_prefix(&self.config.target_dir)
+                .unwrap_or(&duplicate.original);
+            detail!(
+                self.reporter,
+                "  {} {} {}",
+                "[DUPLICATE]".yellow(),
+                relative_dup.display(),
+                format!("(same content as {})", relative_orig.display()).yellow()
+            );
+        }
+    }
+
+    /// 内容が重複しているファイルを削除する
+    ///
+    /// # Returns
+    /// (削除したファイル数, 削除に失敗したファイル数)
+    fn remove_duplicates(&self, duplicates: &[ContentDuplicate]) -> (usize, usize) {
+        let mut removed = 0;
+        let mut errors = 0;
+
+        for duplicate in duplicates {
+            let relative = duplicate
+                .path
+                .strip_prefix(&self.config.target_dir)
+                .unwrap_or(&duplicate.path);
+            // ゴミ箱に移せなければ削除せずに残し、エラーとして数える
+            let (result, note) = if self.config.dedupe_delete {
+                (
+                    fs::remove_file(&duplicate.path).map_err(|e| e.to_string()),
+                    "(duplicate removed)",
+                )
+            } else {
+                (
+                    trash::delete(&duplicate.path).map_err(|e| e.to_string()),
+                    "(duplicate moved to trash)",
+                )
+            };
+            match result {
+                Ok(()) => {
+                    detail!(
+                        self.reporter,
+                        "  {} {} {}",
+                        "✓".green(),
+                        relative.display(),
+                        note.yellow()
+                    );
+                    removed += 1;
+                }
+                Err(e) => {
+                    warn!("Failed to remove duplicate: {}", e);
+                    detail!(
+                        self.reporter,
+                        "  {} {} - {}",
+                        "✗".red(),
+                        duplicate.path.display(),
+                        e.to_string().red()
+                    );
+                    errors += 1;
+                }
+            }
+        }
+
+        (removed, errors)
+    }
+
+    /// テンプレートから移動先のファイル名を生成する
+    fn render_filename(
+        &self,
+        template: &RenameTemplate,
+        path: &Path,
+        category: &Category,
+        counter: usize,
+    ) -> String {
+        template.render(&self.template_context(path, category, counter))
+    }
+
+    /// テンプレートに埋め込む値を集める
+    ///
+    /// 日付は更新日時（`date_from_name` ならファイル名の日付を優先）で、
+    /// 取得できない場合は現在の日付を使います。
+    fn template_context<'a>(
+        &'a self,
+        path: &'a Path,
+        category: &'a Category,
+        counter: usize,
+    ) -> TemplateContext<'a> {
+        let name_date = if self.config.date_from_name {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(Date::from_file_name)
+        } else {
+            None
+        };
+        let date = name_date.unwrap_or_else(|| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .map(Date::from_system_time)
+                .unwrap_or_else(|_| Date::from_system_time(std::time::SystemTime::now()))
+        });
+
+        TemplateContext {
+            stem: path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("unknown"),
+            extension: self
+                .corrected_extension(path)
+                .or_else(|| path.extension().and_then(|s| s.to_str())),
+            category: self.folder_name(category),
+            date,
+            counter,
+        }
+    }
+
+    /// ディレクトリの最寄りのリポジトリ（`.git` / `.hg` を持つ祖先）の名前を求める
+    ///
+    /// 対象ディレクトリ自体とその外側は探しません。
+    /// 途中のディレクトリも含めて結果を `cache` に保存し、同じ探索を繰り返さないようにします。
+    fn repo_name(
+        &self,
+        dir: &Path,
+        cache: &mut HashMap<PathBuf, Option<String>>,
+    ) -> Option<String> {
+        if let Some(name) = cache.get(dir) {
+            return name.clone();
+        }
+        let name = if dir == self.config.target_dir || !dir.starts_with(&self.config.target_dir) {
+            None
+        } else if VCS_MARKERS.iter().any(|marker| dir.join(marker).exists()) {
+            dir.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(sanitize_folder_name)
+        } else {
+            dir.parent()
+                .and_then(|parent| self.repo_name(parent, cache))
+        };
+        cache.insert(dir.to_path_buf(), name.clone());
+        name
+    }
+
+    /// 空のファイルを数えるカテゴリと振り分け先フォルダ名
+    ///
+    /// 空でない、または通常どおり分類する場合は `None` です。`folder:NAME` の場合は
+    /// `NAME` のカテゴリ（組み込みカテゴリと同じ名前ならそのカテゴリ）として数えます。
+    fn empty_file_target(&self, path: &Path) -> Option<(Category, &str)> {
+        let target = match &self.config.empty_files {
+            EmptyFiles::Sort | EmptyFiles::Skip => return None,
+            EmptyFiles::Others => (Category::Others, self.folder_name(&Category::Others)),
+            EmptyFiles::Folder(name) => (
+                Category::custom(name).unwrap_or(Category::Others),
+                name.as_str(),
+            ),
+        };
+        is_empty_file(path).then_some(target)
+    }
+
+    /// ファイルに最初に一致するルールを返す
+    fn matching_rule(&self, path: &Path) -> Option<&Rule> {
+        if self.config.rules.is_empty() {
+            return None;
+        }
+
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                debug!("Cannot evaluate rules for {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let extension = get_extension(path);
+        let facts = FileFacts {
+            name: path.file_name().and_then(|n| n.to_str()).unwrap_or(""),
+            extension: extension.as_deref(),
+            size: metadata.len(),
+            age: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .unwrap_or_default(),
+        };
+
+        self.config.rules.iter().find(|rule| rule.matches(&facts))
+    }
+
+    /// `group_by_prefix` の区切り文字でファイル名を分割する
+    ///
+    /// # Returns
+    /// `(フォルダ名, 残りのファイル名)`。区切り文字を含まない場合や、
+    /// 接頭辞がフォルダ名として使えない・残りが空の場合は `None`
+    fn prefix_group<'a>(&self, filename: &'a str) -> Option<(String, &'a str)> {
+        let delimiter = self.config.group_by_prefix.as_deref()?;
+        let (prefix, rest) = filename.split_once(delimiter)?;
+        if rest.is_empty() {
+            return None;
+        }
+        Some((sanitize_folder_name(prefix)?, rest))
+    }
+
+    /// 振り分け先のルート（同期先のアーカイブ > 出力先 > 対象ディレクトリ）
+    fn dest_root(&self) -> &Path {
+        self.config
+            .sync_into
+            .as_deref()
+            .or(self.config.output_dir.as_deref())
+            .unwrap_or(&self.config.target_dir)
+    }
+
+    /// ディレクトリが振り分け先のルートかどうか
+    fn is_output_dir(&self, dir: &Path) -> bool {
+        fs::canonicalize(self.dest_root())
+            .is_ok_and(|root| fs::canonicalize(dir).is_ok_and(|dir| dir == root))
+    }
+
+    /// 対象ディレクトリ自体にカテゴリフォルダを作って整理するか
+    fn sorts_in_place(&self) -> bool {
+        let root = self.dest_root();
+        root == self.config.target_dir
+            || fs::canonicalize(root).is_ok_and(|root| {
+                fs::canonicalize(&self.config.target_dir).is_ok_and(|target| target == root)
+            })
+    }
+
+    /// ファイルの移動先ディレクトリを決定する
+    ///
+    /// 基本はカテゴリフォルダで、オプションに応じてその配下にサブフォルダを追加します。
+    fn destination_dir(&self, path: &Path, category: &Category) -> PathBuf {
+        // テンプレートが指定されていれば、ディレクトリ構成はテンプレートに従う
+        if let Some(template) = &self.config.dest_template {
+            let ctx = self.template_context(path, category, 0);
+            return self.dest_root().join(template.render(&ctx));
+        }
+
+        let mut dir = self.dest_root().join(self.folder_name(category));
+
+        // <カテゴリ>/<拡張子>/ に振り分け（訂正した拡張子があればそちらを使う）
+        if self.config.subfolder_by_ext {
+            let ext = self
+                .corrected_extension(path)
+                .map(str::to_string)
+                .or_else(|| get_extension(path));
+            dir.push(ext.as_deref().unwrap_or(NO_EXTENSION_FOLDER));
+        }
+
+        // Others/<拡張子>/ に振り分け（拡張子がなければ Others/none/）
+        if self.config.others_by_ext && *category == Category::Others {
+            dir.push(others_extension_folder(path));
+        }
+
+        // Code/<言語>/ に振り分け
+        if self.config.code_by_language && *category == Category::Code {
+            if let Some(language) = get_extension(path).and_then(|ext| code_language(&ext)) {
+                dir.push(language);
+            }
+        }
+
+        // Documents/<言語>/ に振り分け（判定できなければ Documents 直下）
+        if self.config.doc_by_language && *category == Category::Documents {
+            if let Some(language) = detect_text_language(path) {
+                dir.push(language);
+            }
+        }
+
+        // <カテゴリ>/<年>/<月>/ に振り分け（日付が得られなければカテゴリ直下）
+        let date = if self.config.date_from_name {
+            file_date(path)
+        } else if self.config.by_date {
+            metadata_date(path)
+        } else {
+            None
+        };
+        if let Some(date) = date {
+            dir.push(format!("{:04}", date.year));
+            dir.push(format!("{:02}", date.month));
+        }
+
+        dir
+    }
+
+    /// `others_by_ext` 指定時、Others に振り分けたファイルを拡張子ごとに数える
+    fn count_others_extension(&self, stats: &mut SortStats, plan: &FilePlan) {
+        if self.config.others_by_ext && plan.category == Category::Others {
+            *stats
+                .others_extensions
+                .entry(others_extension_folder(&plan.source))
+                .or_insert(0) += 1;
+        }
+    }
+
+    /// ファイルをカテゴリ分類（ローカル設定なし）
+    fn categorize_file(&self, path: &Path) -> Category {
+        self.categorize_file_with(path, None)
+    }
+
+    /// ファイルをカテゴリ分類
+    ///
+    /// 優先順位: `--map` > ローカル設定 > 設定ファイル > 組み込みマッピング
+    fn categorize_file_with(
+        &self,
+        path: &Path,
+        local_overrides: Option<&HashMap<String, Category>>,
+    ) -> Category {
+        if let Some(category) = &self.config.detect_executables {
+            if is_executable(path) {
+                return category.clone();
+            }
+        }
+
+        let corrected = self.corrected_extension(path).map(str::to_string);
+        let Some(ext) = corrected.or_else(|| get_extension(path)) else {
+            // 拡張子がない場合、指定があればファイルの先頭のバイトから判定する
+            if self.config.detect_content {
+                if let Some(ext) = sniff_extension(path) {
+                    debug!("Detected content type '{}' for {}", ext, path.display());
+                    return self.category_for_extension(ext, local_overrides);
+                }
+            }
+            return get_default_category();
+        };
+
+        let category = self.category_for_extension(&ext, local_overrides);
+        if category != Category::Others || !self.config.deep_ext_match {
+            return category;
+        }
+
+        // 末尾の拡張子が未知の場合、その前のドット区切りの部分を右から順に試す
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let segments: Vec<&str> = name.split('.').collect();
+        if segments.len() > 2 {
+            for segment in segments[1..segments.len() - 1].iter().rev() {
+                let category =
+                    self.category_for_extension(&segment.to_lowercase(), local_overrides);
+                if category != Category::Others {
+                    debug!("Deep extension match '{}' for {}", segment, path.display());
+                    return category;
+                }
+            }
+        }
+
+        category
+    }
+
+    /// `fix_extensions` 時、打ち間違いと思われる拡張子を訂正した拡張子を返す
+    ///
+    /// 設定ファイルや `--map` で割り当てられた拡張子は意図したものとして訂正しません。
+    fn corrected_extension(&self, path: &Path) -> Option<&'static str> {
+        if !self.config.fix_extensions {
+            return None;
+        }
+        let ext = get_extension(path)?;
+        if self.config.cli_overrides.contains_key(&ext)
+            || self.config.extension_overrides.contains_key(&ext)
+        {
+            return None;
+        }
+        correct_extension(&ext)
+    }
+
+    /// 拡張子（小文字、ドットなし）からカテゴリを判定
+    fn category_for_extension(
+        &self,
+        ext: &str,
+        local_overrides: Option<&HashMap<String, Category>>,
+    ) -> Category {
+        if let Some(category) = self.config.cli_overrides.get(ext) {
+            return category.clone();
+        }
+        if let Some(category) = local_overrides.and_then(|o| o.get(ext)) {
+            return category.clone();
+        }
+        if let Some(category) = self.config.extension_overrides.get(ext) {
+            return category.clone();
+        }
+        #[cfg(unix)]
+        if let Some(category) = self
+            .config
+            .system_mime
+            .as_ref()
+            .and_then(|db| db.category(ext))
+        {
+            return category;
+        }
+        if self.config.handle_downloads && ext == "torrent" {
+            return Category::Torrents;
+        }
+        get_category(ext)
+    }
+
+    /// Dry Run実行
+    fn execute_dry_run(&self, plans: &[FilePlan]) -> Result<SortStats> {
+        let mut stats = SortStats {
+            total_files: plans.len(),
+            ..Default::default()
+        };
+
+        // 表示用の行（移動元, 移動先, 付記）
+        let mut rows = Vec::with_capacity(plans.len());
+        // `--tree` で表示する移動先のツリー
+        let mut tree = PathTree::default();
+
+        // 先に計画されたファイルが使う移動先を予約し、同名ファイル同士の重複も反映する
+        // （予約した移動先 → そこへ移動する移動元）
+        let mut reserved: HashSet<PathBuf> = HashSet::new();
+        // 移動先に同一内容のファイルが既にあるため移動しないファイル
+        let mut duplicates = Vec::new();
+        // 移動先に同名のファイルがあるため移動しないファイル（--on-conflict）
+        let mut conflicts = Vec::new();
+        // 書き出す計画（移動先は重複回避後のパス）
+        let mut exported = Vec::new();
+        // 実際に移動すると権限不足で失敗しそうなファイルと理由
+        let mut would_fail = Vec::new();
+        // zip・リンクの出力では移動元にも移動先のフォルダにも書き込まない
+        let check_permissions = self.config.archive_output.is_none()
+            && self.config.symlink_farm.is_none()
+            && self.config.hardlink_view.is_none();
+
+        let progress = Progress::start(plans.len(), self.config.progress, &self.reporter);
+        for plan in plans {
+            progress.inc();
+            if let Some(duplicate) = self.existing_duplicate(plan) {
+                duplicates.push(duplicate);
+                stats.duplicate_files += 1;
+                continue;
+            }
+
+            if check_permissions {
+                if let Some(reason) =
+                    permission_problem(plan, self.keeps_originals(), is_writable_dir)
+                {
+                    would_fail.push((plan.source.clone(), reason));
+                    stats.would_fail += 1;
+                    continue;
+                }
+            }
+
+            // 前回のコピー・リンクが移動先に残っていれば、もう一度は転送しない
+            if !reserved.contains(&plan.destination) && self.already_transferred(plan) {
+                conflicts.push(SkippedFile {
+                    path: plan.source.clone(),
+                    reason: SkipReason::AlreadyTransferred,
+                });
+                stats.skipped_files += 1;
+                continue;
+            }
+
+            // 移動先の既存ファイルとの競合の扱いを決める
+            // （先に計画されたファイルとの競合は置き換えずに連番を付ける）
+            let action = if reserved.contains(&plan.destination) {
+                Some(ConflictStrategy::Rename)
+            } else if plan.destination.exists() || self.is_assumed_existing(&plan.destination) {
+                Some(resolve_conflict(
+                    self.config.on_conflict,
+                    &plan.source,
+                    &plan.destination,
+                ))
+            } else {
+                None
+            };
+            if action == Some(ConflictStrategy::Skip) {
+                conflicts.push(SkippedFile {
+                    path: plan.source.clone(),
+                    reason: self.conflict_skip_reason(),
+                });
+                stats.skipped_files += 1;
+                continue;
+            }
+
+            // カテゴリカウントとサイズを更新
+            *stats
+                .category_counts
+                .entry(plan.category.clone())
+                .or_insert(0) += 1;
+            self.count_others_extension(&mut stats, plan);
+            *stats
+                .category_bytes
+                .entry(plan.category.clone())
+                .or_insert(0) += file_size(&plan.source).unwrap_or(0);
+
+            // 相対パスを計算（表示用）
+            let relative_source = plan
+                .source
+                .strip_prefix(&self.config.target_dir)
+                .unwrap_or(&plan.source);
+
+            let dest_dir = plan.dest_dir();
+
+            // 重複がある場合の移動先ファイル名を計算
+            let filename = plan
+                .destination
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown");
+            let replaces = matches!(
+                action,
+                Some(ConflictStrategy::Overwrite | ConflictStrategy::Trash)
+            );
+            let final_dest = if replaces {
+                plan.destination.clone()
+            } else {
+                generate_unique_path_limited(dest_dir, filename, self.unique_naming(), |p| {
+                    p.exists() || reserved.contains(p) || self.is_assumed_existing(p)
+                })
+            };
+            self.log_operation(
+                plan,
+                Some(&final_dest),
+                !replaces && final_dest != plan.destination,
+                None,
+            );
+            reserved.insert(final_dest.clone());
+            if self.config.export_plan.is_some() {
+                exported.push(FilePlan {
+                    source: absolute(&plan.source),
+                    destination: absolute(&final_dest),
+                    ..plan.clone()
+                });
+            }
+
+            let relative_dest = final_dest
+                .strip_prefix(self.dest_root())
+                .unwrap_or(&final_dest);
+
+            let note = if replaces {
+                stats.replaced_files += 1;
+                if action == Some(ConflictStrategy::Trash) {
+                    "(old file to trash)".yellow()
+                } else {
+                    "(overwrite)".yellow()
+                }
+            } else if final_dest != plan.destination {
+                stats.renamed_files += 1;
+                "(renamed)".yellow()
+            } else {
+                format!("[{}]", plan.category).blue()
+            };
+
+            if self.config.tree {
+                tree.insert(relative_dest);
+            }
+            rows.push((
+                relative_source.display().to_string(),
+                relative_dest.display().to_string(),
+                note,
+            ));
+            stats.moved_files += 1;
+        }
+        drop(progress);
+
+        // 表示
+        let arrow = "→".cyan();
+        let tag = if self.config.copy {
+            "[DRY RUN] (copy)"
+        } else if self.config.hardlink {
+            "[DRY RUN] (hardlink)"
+        } else {
+            "[DRY RUN]"
+        };
+        if self.config.tree {
+            if !rows.is_empty() {
+                detail!(
+                    self.reporter,
+                    "  {} {}",
+                    tag.cyan(),
+                    self.dest_root().display()
+                );
+                for line in tree.render() {
+                    detail!(self.reporter, "  {}", line);
+                }
+            }
+        } else if self.config.columns {
+            let cells: Vec<(String, String)> = rows
+                .iter()
+                .map(|(source, dest, _)| (source.clone(), dest.clone()))
+                .collect();
+            // "  " + タグ + " " + " → " + " " + 付記 の分を除いた幅を2列に割り当てる
+            let note_width = rows
+                .iter()
+                .map(|(_, _, note)| output::display_width(note))
+                .max()
+                .unwrap_or(0);
+            let available = output::terminal_width()
+                .map(|w| w.saturating_sub(2 + tag.len() + 1 + 3 + 1 + note_width));
+
+            for ((source, dest), (_, _, note)) in
+                output::align_columns(&cells, available).iter().zip(&rows)
+            {
+                detail!(
+                    self.reporter,
+                    "  {} {} {} {} {}",
+                    tag.cyan(),
+                    source,
+                    arrow,
+                    dest,
+                    note
+                );
+            }
+        } else {
+            for (source, dest, note) in &rows {
+                detail!(
+                    self.reporter,
+                    "  {} {} {} {} {}",
+                    tag.cyan(),
+                    source,
+                    arrow,
+                    dest,
+                    note
+                );
+            }
+        }
+        self.print_skipped(&conflicts);
+        self.print_duplicates(&duplicates);
+
+        // 失敗しそうなファイルは --quiet でも表示する
+        if !would_fail.is_empty() {
+            say!(self.reporter);
+            say!(
+                self.reporter,
+                "{}",
+                "Files that would fail (permission denied):".red().bold()
+            );
+            for (path, reason) in &would_fail {
+                let relative = path.strip_prefix(&self.config.target_dir).unwrap_or(path);
+                say!(
+                    self.reporter,
+                    "  {} {} {}",
+                    "[WOULD FAIL]".red(),
+                    relative.display(),
+                    format!("({})", reason).red()
+                );
+            }
+        }
+
+        if let Some(path) = &self.config.export_plan {
+            save_plans(&exported, path)?;
+            say!(self.reporter);
+            say!(
+                self.reporter,
+                "{} {}",
+                "Plan written:".bold(),
+                path.display()
+            );
+        }
+
+        Ok(stats)
+    }
+
+    /// `on_conflict` によって移動しなかったファイルのスキップ理由
+    fn conflict_skip_reason(&self) -> SkipReason {
+        match self.config.on_conflict {
+            ConflictStrategy::KeepNewer => SkipReason::NotNewer,
+            ConflictStrategy::KeepLarger => SkipReason::NotLarger,
+            _ => SkipReason::Exists,
+        }
+    }
+
+    /// `dedup_content` 時、移動先に同一内容のファイルが既にあればその重複を返す
+    ///
+    /// 比較に失敗した場合は警告し、重複ではないものとして扱います。
+    fn existing_duplicate(&self, plan: &FilePlan) -> Option<ContentDuplicate> {
+        if !self.config.dedup_content {
+            return None;
+        }
+        match files_are_identical(&plan.source, &plan.destination) {
+            Ok(true) => Some(ContentDuplicate {
+                path: plan.source.clone(),
+                original: plan.destination.clone(),
+            }),
+            Ok(false) => None,
+            Err(e) => {
+                warn!("Failed to compare with existing file: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// `copy`・`hardlink` 時、移動先が既にこのファイルのコピー・リンクかどうか
+    ///
+    /// 再実行のたびに連番付きのコピーが増えないよう、転送済みのファイルは転送しません。
+    /// 比較に失敗した場合は警告し、転送済みではないものとして扱います。
+    fn already_transferred(&self, plan: &FilePlan) -> bool {
+        let kind = if self.config.copy {
+            OpKind::Copy
+        } else if self.config.hardlink {
+            OpKind::Hardlink
+        } else {
+            return false;
+        };
+        is_existing_transfer(&plan.source, &plan.destination, kind).unwrap_or_else(|e| {
+            warn!("Failed to compare with existing file: {:#}", e);
+            false
+        })
+    }
+
+    /// `--assume-existing` で既に存在するとみなすファイル名かどうか
+    fn is_assumed_existing(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| self.config.assume_existing.contains(n))
+    }
+
+    /// 実際のファイル移動を実行
+    fn execute_move(&self, plans: &[FilePlan], journal: &mut Journal) -> Result<SortStats> {
+        let stats = SortStats {
+            total_files: plans.len(),
+            ..Default::default()
+        };
+
+        // 移動先フォルダを事前に作成（ファイルを受け取るフォルダのみ）
+        let dest_dirs: BTreeSet<&Path> = plans.iter().map(|p| p.dest_dir()).collect();
+        // 元に戻すときに削除できるよう、新しく作るフォルダを控えておく
+        let created_dirs: BTreeSet<&Path> = if self.config.atomic {
+            dest_dirs
+                .iter()
+                .flat_map(|dir| {
+                    dir.ancestors()
+                        .take_while(|ancestor| *ancestor != self.dest_root())
+                })
+                .filter(|dir| !dir.exists())
+                .collect()
+        } else {
+            BTreeSet::new()
+        };
+        for dir in dest_dirs {
+            ensure_directory(dir)?;
+        }
+
+        self.notify(SortEvent::Started { total: plans.len() });
+
+        let state = Mutex::new(MoveState {
+            stats,
+            journal,
+            records: Vec::new(),
+            completed: Vec::new(),
+            written: HashSet::new(),
+            mark_warned: false,
+        });
+        let progress = Progress::start(plans.len(), self.config.progress, &self.reporter);
+        let jobs = self.config.jobs.clamp(1, plans.len().max(1));
+        if jobs == 1 {
+            for plan in plans {
+                self.move_plan(plan, &state);
+                progress.inc();
+            }
+        } else {
+            // 各スレッドが次の計画を取り合いながら移動する
+            debug!("Moving {} files with {} threads", plans.len(), jobs);
+            let next = AtomicUsize::new(0);
+            thread::scope(|scope| {
+                for _ in 0..jobs {
+                    scope.spawn(|| {
+                        while let Some(plan) = plans.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            self.move_plan(plan, &state);
+                            progress.inc();
+                        }
+                    });
+                }
+            });
+        }
+        drop(progress);
+        let MoveState {
+            mut stats,
+            records,
+            completed,
+            ..
+        } = state.into_inner().unwrap_or_else(PoisonError::into_inner);
+
+        // 失敗があれば、完了した移動をすべて元に戻してエラーにする
+        if self.config.atomic && stats.error_count > 0 {
+            let failures = self.rollback(&completed, &created_dirs);
+            if failures > 0 {
+                bail!(
+                    "Sort failed and {} of {} completed moves could not be rolled back",
+                    failures,
+                    completed.len()
+                );
+            }
+            bail!(
+                "Sort failed; all {} completed moves were rolled back",
+                completed.len()
+            );
+        }
+
+        if self.config.validate_after_move {
+            self.report_discrepancies(&records, &RealFs, &mut stats);
+        }
+
+        Ok(stats)
+    }
+
+    /// 1件の計画を移動し、結果を `state` に集計する
+    ///
+    /// 移動そのものはロックの外で行うため、複数のスレッドから同時に呼び出せます。
+    fn move_plan(&self, plan: &FilePlan, state: &Mutex<MoveState<'_>>) {
+        // 元に戻すことが決まったら、それ以上は移動しない
+        if self.config.atomic && lock(state).stats.error_count > 0 {
+            return;
+        }
+
+        // 組み込み側のフックが断ったファイルは移動しない
+        if self.before_move.as_ref().is_some_and(|hook| !hook(plan)) {
+            self.print_skipped(&[SkippedFile {
+                path: plan.source.clone(),
+                reason: SkipReason::Vetoed,
+            }]);
+            lock(state).stats.skipped_files += 1;
+            return;
+        }
+
+        // 他のプロセスが使用中のファイルは、途中で失敗させずに使用中として数える
+        if is_file_locked(&plan.source) {
+            self.skip_locked(plan, state);
+            return;
+        }
+
+        // 移動先に同一内容のファイルがあれば、リネームして移動せずに重複として数える
+        if let Some(duplicate) = self.existing_duplicate(plan) {
+            self.print_duplicates(std::slice::from_ref(&duplicate));
+            lock(state).stats.duplicate_files += 1;
+            return;
+        }
+
+        // 前回のコピー・リンクが移動先に残っていれば、もう一度は転送しない
+        let written = lock(state).written.contains(&plan.destination);
+        if !written && self.already_transferred(plan) {
+            self.print_skipped(&[SkippedFile {
+                path: plan.source.clone(),
+                reason: SkipReason::AlreadyTransferred,
+            }]);
+            lock(state).stats.skipped_files += 1;
+            return;
+        }
+
+        // 移動先に同名のファイルがあり、移動しないと決まれば移動しない（--on-conflict）
+        let Some(on_conflict) = self.claim_destination(plan, state) else {
+            self.print_skipped(&[SkippedFile {
+                path: plan.source.clone(),
+                reason: self.conflict_skip_reason(),
+            }]);
+            lock(state).stats.skipped_files += 1;
+            return;
+        };
+
+        let filename = plan
+            .destination
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown");
+
+        // 検証用に移動前のサイズ（と必要ならハッシュ）を控えておく
+        let expected = if self.config.validate_after_move {
+            fs::metadata(&plan.source).ok().map(|m| {
+                let hash = if self.config.validate_rehash {
+                    hash_file(&plan.source).ok()
+                } else {
+                    None
+                };
+                (m.len(), hash)
+            })
+        } else {
+            None
+        };
+
+        let transfer = if self.config.copy {
+            copy_file_with_dedup_as
+        } else if self.config.hardlink {
+            hardlink_file_with_dedup_as
+        } else {
+            move_file_with_dedup_as
+        };
+        let transferred = transfer(
+            &plan.source,
+            plan.dest_dir(),
+            filename,
+            self.unique_naming(),
+            self.config.retry,
+            on_conflict,
+        );
+        if let Some(hook) = &self.after_move {
+            hook(plan, &transferred);
+        }
+        self.record_transfer(plan, transferred, expected, state);
+    }
+
+    /// 1件の移動の結果を表示し、`state` に集計する
+    ///
+    /// `expected` は検証用に控えた移動前のサイズとハッシュです。
+    fn record_transfer(
+        &self,
+        plan: &FilePlan,
+        transferred: Result<OpResult>,
+        expected: Option<(u64, Option<String>)>,
+        state: &Mutex<MoveState<'_>>,
+    ) {
+        // 移動したサイズ（読めなければ0バイトとして数える）
+        let moved_bytes = transferred
+            .as_ref()
+            .map_or(0, |result| file_size(&result.destination).unwrap_or(0));
+
+        // 集計と表示はロックを保持したまま行う（並列時も行が混ざらないように）
+        let mut guard = lock(state);
+        let MoveState {
+            stats,
+            journal,
+            records,
+            completed,
+            written,
+            mark_warned,
+        } = &mut *guard;
+        match transferred {
+            Ok(result) => {
+                journal.record(&plan.source, &result.destination);
+                written.insert(result.destination.clone());
+                if self.config.atomic {
+                    completed.push((
+                        plan.source.clone(),
+                        result.destination.clone(),
+                        result.method,
+                    ));
+                }
+                self.log_operation(plan, Some(&result.destination), result.was_renamed, None);
+                self.notify(SortEvent::Moved {
+                    source: plan.source.clone(),
+                    destination: result.destination.clone(),
+                    category: plan.category.clone(),
+                });
+
+                if let Some((expected_size, expected_hash)) = expected {
+                    records.push(MoveRecord {
+                        source: plan.source.clone(),
+                        destination: result.destination.clone(),
+                        expected_size,
+                        expected_hash,
+                        method: result.method,
+                        kind: result.kind,
+                    });
+                }
+
+                // カテゴリカウントとサイズを更新
+                *stats
+                    .category_counts
+                    .entry(plan.category.clone())
+                    .or_insert(0) += 1;
+                self.count_others_extension(stats, plan);
+                *stats
+                    .category_bytes
+                    .entry(plan.category.clone())
+                    .or_insert(0) += moved_bytes;
+                let folder = fs::canonicalize(plan.dest_dir())
+                    .unwrap_or_else(|_| plan.dest_dir().to_path_buf());
+                *stats.folder_counts.entry(folder).or_insert(0) += 1;
+
+                // 相対パスを計算（表示用）
+                let relative_source = plan
+                    .source
+                    .strip_prefix(&self.config.target_dir)
+                    .unwrap_or(&plan.source);
+                let relative_dest = result
+                    .destination
+                    .strip_prefix(self.dest_root())
+                    .unwrap_or(&result.destination);
+
+                let arrow = "→".green();
+
+                // 既存のファイルを置き換えた場合は付記する（ゴミ箱に移せなかった場合はエラー）
+                let replaced_note = match &result.replaced {
+                    Some(Replaced::Overwritten) => Some("overwritten"),
+                    Some(Replaced::Trashed) => Some("old file moved to trash"),
+                    Some(Replaced::TrashFailed(_)) => {
+                        stats.error_count += 1;
+                        None
+                    }
+                    None => None,
+                };
+
+                if result.was_renamed {
+                    detail!(
+                        self.reporter,
+                        "  {} {} {} {}",
+                        "✓".green(),
+                        relative_source.display(),
+                        arrow,
+                        format!("{} (renamed)", relative_dest.display()).yellow()
+                    );
+                    stats.renamed_files += 1;
+                } else if let Some(note) = replaced_note {
+                    detail!(
+                        self.reporter,
+                        "  {} {} {} {}",
+                        "✓".green(),
+                        relative_source.display(),
+                        arrow,
+                        format!("{} ({})", relative_dest.display(), note).yellow()
+                    );
+                    stats.replaced_files += 1;
+                } else {
+                    detail!(
+                        self.reporter,
+                        "  {} {} {} {}",
+                        "✓".green(),
+                        relative_source.display(),
+                        arrow,
+                        relative_dest.display()
+                    );
+                }
+
+                stats.moved_files += 1;
+
+                // 整理済みの印を付ける（付けられなくても移動は成功として扱う）
+                if self.config.mark_sorted && !mark_sorted(&result.destination) && !*mark_warned {
+                    warn!(
+                        "Could not mark sorted files (extended attributes unsupported?): {}",
+                        result.destination.display()
+                    );
+                    *mark_warned = true;
+                }
+
+                // HEIC/HEIF画像をJPEGに変換（失敗しても警告のみ）
+                #[cfg(feature = "heic")]
+                if self.config.convert_heic && plan.category == Category::Images {
+                    // 変換で置き換わるファイルは検証の対象外
+                    if crate::convert::is_heic(&result.destination) {
+                        records.retain(|r| r.destination != result.destination);
+                    }
+                    self.convert_heic(&result.destination, stats);
+                }
+            }
+            // 確認の後で使用中になったファイル（Windowsの共有違反など）
+            Err(e)
+                if e.chain().any(|cause| {
+                    cause
+                        .downcast_ref::<std::io::Error>()
+                        .is_some_and(is_in_use_error)
+                }) =>
+            {
+                // skip_locked は自分でロックを取るため、先に手放す
+                drop(guard);
+                self.skip_locked(plan, state);
+            }
+            Err(e) => {
+                warn!("Failed to move file: {}", e);
+                self.log_operation(plan, None, false, Some(format!("{:#}", e)));
+                self.notify(SortEvent::Failed {
+                    source: plan.source.clone(),
+                    error: e.to_string(),
+                });
+                detail!(
+                    self.reporter,
+                    "  {} {} - {}",
+                    "✗".red(),
+                    plan.source.display(),
+                    e.to_string().red()
+                );
+                stats.error_count += 1;
+            }
+        }
+    }
+
+    /// 移動先を予約し、移動先に同名のファイルがある場合の扱いを返す（移動しないなら `None`）
+    ///
+    /// 置き換えるのは実行前からあったファイルだけです。この実行で書き込んだ（書き込む）
+    /// 移動先と重なった場合は、`--on-conflict` にかかわらず連番を付けて両方残します。
+    fn claim_destination(
+        &self,
+        plan: &FilePlan,
+        state: &Mutex<MoveState<'_>>,
+    ) -> Option<ConflictStrategy> {
+        let mut guard = lock(state);
+        if guard.written.contains(&plan.destination) {
+            return Some(ConflictStrategy::Rename);
+        }
+        if plan.destination.exists()
+            && resolve_conflict(self.config.on_conflict, &plan.source, &plan.destination)
+                == ConflictStrategy::Skip
+        {
+            return None;
+        }
+        guard.written.insert(plan.destination.clone());
+        Some(self.config.on_conflict)
+    }
+
+    /// `atomic` 時、完了した移動を新しいものから順に元に戻し、戻せなかった数を返す
+    ///
+    /// 別のファイルシステムへコピーで移動したファイルはコピーで戻すため、内容は戻りますが
+    /// inode や作成日時までは元どおりになりません。元の場所に別のファイルができていた場合は
+    /// 上書きせずに戻せなかったものとして数えます。最後に、この実行で作ったフォルダのうち
+    /// 空になったものを削除します。
+    fn rollback(
+        &self,
+        completed: &[(PathBuf, PathBuf, MoveMethod)],
+        created_dirs: &BTreeSet<&Path>,
+    ) -> usize {
+        say!(self.reporter);
+        say!(
+            self.reporter,
+            "{}",
+            format!("Rolling back {} completed moves...", completed.len())
+                .yellow()
+                .bold()
+        );
+
+        let mut failures = 0;
+        for (source, destination, method) in completed.iter().rev() {
+            if fs::symlink_metadata(source).is_ok() {
+                warn!(
+                    "Cannot roll back {}: the original path is now taken",
+                    destination.display()
+                );
+                say!(
+                    self.reporter,
+                    "  {} {} {}",
+                    "✗".red(),
+                    destination.display(),
+                    "(original path is taken)".red()
+                );
+                failures += 1;
+                continue;
+            }
+            match move_file(destination, source, self.config.retry) {
+                Ok(back) => {
+                    if *method == MoveMethod::Copy || back == MoveMethod::Copy {
+                        warn!(
+                            "Rolled back across filesystems by copying: {}",
+                            source.display()
+                        );
+                    }
+                    detail!(
+                        self.reporter,
+                        "  {} {} {} {}",
+                        "↩".yellow(),
+                        destination.display(),
+                        "→".yellow(),
+                        source.display()
+                    );
+                }
+                Err(e) => {
+                    warn!("Failed to roll back {}: {:#}", destination.display(), e);
+                    say!(
+                        self.reporter,
+                        "  {} {} - {}",
+                        "✗".red(),
+                        destination.display(),
+                        format!("{:#}", e).red()
+                    );
+                    failures += 1;
+                }
+            }
+        }
+
+        // 深いフォルダから順に、空のものだけを削除する
+        for dir in created_dirs.iter().rev() {
+            if fs::remove_dir(dir).is_ok() {
+                debug!("Removed folder created by this run: {}", dir.display());
+            }
+        }
+        failures
+    }
+
+    /// 使用中のファイルをスキップしたことを表示して統計に加える
+    fn skip_locked(&self, plan: &FilePlan, state: &Mutex<MoveState<'_>>) {
+        warn!("File in use, skipping: {}", plan.source.display());
+        self.print_skipped(&[SkippedFile {
+            path: plan.source.clone(),
+            reason: SkipReason::InUse,
+        }]);
+        lock(state).stats.locked_files += 1;
+    }
+
+    /// 移動を検証し、見つかった不整合を表示して統計に加える
+    fn report_discrepancies(
+        &self,
+        records: &[MoveRecord],
+        probe: &dyn FileProbe,
+        stats: &mut SortStats,
+    ) {
+        let discrepancies = verify_moves(records, probe);
+        if discrepancies.is_empty() {
+            debug!("Verified {} moves", records.len());
+            return;
+        }
+
+        say!(self.reporter);
+        for discrepancy in &discrepancies {
+            warn!("Integrity check failed: {}", discrepancy);
+            say!(
+                self.reporter,
+                "  {} {}",
+                "[MISMATCH]".red().bold(),
+                discrepancy
+            );
+        }
+        stats.discrepancies += discrepancies.len();
+    }
+
+    /// 移動したHEIC/HEIF画像をJPEGに変換する
+    ///
+    /// 変換の失敗は移動自体の失敗ではないため、警告として表示するだけに留めます。
+    #[cfg(feature = "heic")]
+    fn convert_heic(&self, path: &Path, stats: &mut SortStats) {
+        use crate::convert::{convert_moved_heic, LibheifConverter};
+
+        match convert_moved_heic(path, &LibheifConverter, self.config.keep_heic) {
+            Ok(Some(jpeg)) => {
+                let relative = jpeg.strip_prefix(&self.config.target_dir).unwrap_or(&jpeg);
+                detail!(
+                    self.reporter,
+                    "    {} {}",
+                    "↳ converted to".green(),
+                    relative.display()
+                );
+                stats.converted_files += 1;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to convert {}: {:#}", path.display(), e);
+                detail!(
+                    self.reporter,
+                    "    {} {}",
+                    "⚠ conversion failed:".yellow(),
+                    e.to_string().yellow()
+                );
+            }
+        }
+    }
+
+    /// 移動の代わりにカテゴリフォルダ構造でzipに書き出す
+    fn execute_archive(&self, plans: &[FilePlan], archive_path: &Path) -> Result<SortStats> {
+        let mut stats = SortStats {
+            total_files: plans.len(),
+            ..Default::default()
+        };
+
+        let mut archive = ArchiveWriter::create(archive_path)?;
+        let mut archived = Vec::new();
+
+        for plan in plans {
+            let relative_source = plan
+                .source
+                .strip_prefix(&self.config.target_dir)
+                .unwrap_or(&plan.source);
+
+            // zip内のフォルダはカテゴリフォルダ（およびサブフォルダ）の相対パス
+            let folder = plan
+                .dest_dir()
+                .strip_prefix(&self.config.target_dir)
+                .unwrap_or(Path::new(self.folder_name(&plan.category)))
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            let filename = plan
+                .destination
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown");
+
+            match archive.add_file(&plan.source, &folder, filename) {
+                Ok(entry) => {
+                    *stats
+                        .category_counts
+                        .entry(plan.category.clone())
+                        .or_insert(0) += 1;
+                    self.count_others_extension(&mut stats, plan);
+                    detail!(
+                        self.reporter,
+                        "  {} {} {} {}:{}",
+                        "✓".green(),
+                        relative_source.display(),
+                        "→".green(),
+                        archive_path.display(),
+                        entry
+                    );
+                    stats.moved_files += 1;
+                    archived.push(&plan.source);
+                }
+                Err(e) => {
+                    warn!("Failed to archive file: {}", e);
+                    detail!(
+                        self.reporter,
+                        "  {} {} - {}",
+                        "✗".red(),
+                        plan.source.display(),
+                        e.to_string().red()
+                    );
+                    stats.error_count += 1;
+                }
+            }
+        }
+
+        archive.finish()?;
+
+        // アーカイブが正常に書き終わってから元ファイルを削除する
+        if self.config.archive_delete_originals {
+            for source in archived {
+                if let Err(e) = fs::remove_file(source) {
+                    warn!("Failed to remove original {}: {}", source.display(), e);
+                    stats.error_count += 1;
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// 移動の代わりに、カテゴリフォルダ構造のリンクを作成する
+    ///
+    /// 元のファイルは一切移動せず、`view_dir` 以下のカテゴリフォルダに
+    /// 元のファイルを指すリンクを作成します。リンク名の重複は連番で回避します。
+    /// ハードリンクを作れない別のファイルシステムには、コピーで代用します。
+    fn execute_link_view(
+        &self,
+        plans: &[FilePlan],
+        view_dir: &Path,
+        kind: LinkKind,
+    ) -> Result<SortStats> {
+        let mut stats = SortStats {
+            total_files: plans.len(),
+            ..Default::default()
+        };
+        let mut copied = 0;
+
+        for plan in plans {
+            let relative_source = plan
+                .source
+                .strip_prefix(&self.config.target_dir)
+                .unwrap_or(&plan.source);
+
+            // ビュー内のフォルダはカテゴリフォルダ（およびサブフォルダ）の相対パス
+            let link_dir = view_dir.join(
+                plan.dest_dir()
+                    .strip_prefix(&self.config.target_dir)
+                    .unwrap_or(Path::new(self.folder_name(&plan.category))),
+            );
+            let filename = plan
+                .destination
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown");
+
+            let result = ensure_directory(&link_dir).and_then(|_| {
+                let original = fs::canonicalize(&plan.source).with_context(|| {
+                    format!("Failed to resolve path: {}", plan.source.display())
+                })?;
+                let link =
+                    generate_unique_path_limited(&link_dir, filename, self.unique_naming(), |p| {
+                        p.exists()
+                    });
+                let method = match kind {
+                    LinkKind::Symlink => create_symlink(&original, &link).map(|_| None)?,
+                    LinkKind::Hardlink => Some(create_hardlink(&original, &link)?),
+                };
+                Ok((link, method))
+            });
+
+            match result {
+                Ok((link, method)) => {
+                    *stats
+                        .category_counts
+                        .entry(plan.category.clone())
+                        .or_insert(0) += 1;
+                    self.count_others_extension(&mut stats, plan);
+                    let note = if method == Some(LinkMethod::Copy) {
+                        copied += 1;
+                        " (copied)".yellow()
+                    } else {
+                        "".normal()
+                    };
+                    detail!(
+                        self.reporter,
+                        "  {} {} {} {}{}",
+                        "✓".green(),
+                        relative_source.display(),
+                        "⇢".green(),
+                        link.display(),
+                        note
+                    );
+                    stats.moved_files += 1;
+                }
+                Err(e) => {
+                    warn!("Failed to link file: {}", e);
+                    detail!(
+                        self.reporter,
+                        "  {} {} - {}",
+                        "✗".red(),
+                        plan.source.display(),
+                        e.to_string().red()
+                    );
+                    stats.error_count += 1;
+                }
+            }
+        }
+
+        if copied > 0 {
+            warn!(
+                "{} files were copied instead of hardlinked because the view is on a different filesystem",
+                copied
+            );
+        }
+
+        Ok(stats)
+    }
+
+    /// ファイルが1つだけのカテゴリフォルダを解消する
+    ///
+    /// 今回の実行でファイルを受け取ったカテゴリフォルダのうち、中身がちょうど1ファイルで、
+    /// しかもそれが今回移動したファイルであるものについて、そのファイルを `target` に従って
+    /// 対象ディレクトリのルートか移動前の場所に戻し（重複回避付き）、空になったフォルダを削除します。
+    /// 実行前からあったファイルだけが入ったフォルダには触れません。
+    fn collapse_singleton_folders(
+        &self,
+        plans: &[FilePlan],
+        target: CollapseTarget,
+        stats: &mut SortStats,
+        journal: &mut Journal,
+    ) -> Result<()> {
+        // 今回移動したファイル（移動先 → 移動元）
+        let moved: HashMap<PathBuf, PathBuf> = journal
+            .entries
+            .iter()
+            .map(|entry| (entry.destination.clone(), entry.source.clone()))
+            .collect();
+        let categories: BTreeSet<&Category> = plans.iter().map(|p| &p.category).collect();
+        for category in categories {
+            let dir = self.config.target_dir.join(self.folder_name(category));
+            if !is_directory(&dir) {
+                continue;
+            }
+
+            let entries: Vec<PathBuf> = fs::read_dir(&dir)
+                .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .collect();
+
+            // 中身がちょうど1ファイルで、今回移動したファイルの場合のみ対象
+            if entries.len() != 1 || !is_file(&entries[0]) {
+                continue;
+            }
+            let Some(source) = moved.get(&absolute(&entries[0])) else {
+                continue;
+            };
+
+            let result = match target {
+                CollapseTarget::Root => move_file_with_dedup(&entries[0], &self.config.target_dir)?,
+                CollapseTarget::Origin => {
+                    let origin = source.parent().unwrap_or(&self.config.target_dir);
+                    let filename = source
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .with_context(|| format!("Invalid filename: {}", source.display()))?;
+                    move_file_with_dedup_as(
+                        &entries[0],
+                        origin,
+                        filename,
+                        UniqueNaming::default(),
+                        self.config.retry,
+                        ConflictStrategy::Rename,
+                    )?
+                }
+            };
+            journal.relocate(&entries[0], &result.destination);
+            stats
+                .folder_counts
+                .remove(&fs::canonicalize(&dir).unwrap_or_else(|_| dir.clone()));
+            fs::remove_dir(&dir)
+                .with_context(|| format!("Failed to remove directory: {}", dir.display()))?;
+            debug!("Collapsed single-file folder: {}", dir.display());
+
+            let relative_dest = result
+                .destination
+                .strip_prefix(&self.config.target_dir)
+                .unwrap_or(&result.destination);
+            detail!(
+                self.reporter,
+                "  {} {}/ {} {} {}",
+                "↩".yellow(),
+                self.folder_name(category),
+                "→".yellow(),
+                relative_dest.display(),
+                "(collapsed)".yellow()
+            );
+
+            stats.collapsed_folders += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// `path` の親フォルダのうち空になったものを、`root`（正規化済み）の手前まで削除する
+fn remove_empty_parents(path: &Path, root: &Path) {
+    for dir in path.ancestors().skip(1) {
+        let inside = fs::canonicalize(dir).is_ok_and(|d| d.starts_with(root) && d != root);
+        if !inside || fs::remove_dir(dir).is_err() {
+            break;
+        }
+        debug!("Removed empty folder: {}", dir.display());
+    }
+}
+
+/// ミューテックスをロックする（他のスレッドがパニックしていても中身をそのまま使う）
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// グロブパターンをコンパイルする（不正なパターンは警告して除く）
+fn compile_patterns(patterns: &[String]) -> Vec<Pattern> {
+    patterns
+        .iter()
+        .filter_map(|pattern| match Pattern::new(pattern) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                warn!("Ignoring invalid pattern '{}': {}", pattern, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// ディレクトリ以下のファイルとサブディレクトリを再帰的に集める（シンボリックリンクは除く）
+fn collect_tree(dir: &Path, files: &mut Vec<PathBuf>, dirs: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory: {}", dir.display()))?
+    {
+        let path = entry
+            .with_context(|| "Failed to read directory entry")?
+            .path();
+        if is_symlink(&path) {
+            continue;
+        }
+        if is_directory(&path) {
+            dirs.push(path.clone());
+            collect_tree(&path, files, dirs)?;
+        } else if is_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// 整理の対象にすると危険なディレクトリの一覧（正規化済み）
+///
+/// 主要なシステムディレクトリに加え、ホームディレクトリやWindowsのシステムフォルダを
+/// 環境変数から取得して含めます。
+fn critical_paths() -> Vec<PathBuf> {
+    const USER_ROOTS: [&str; 4] = ["/", "/home", "/Users", "/root"];
+    const ENV_VARS: [&str; 2] = ["HOME", "USERPROFILE"];
+
+    USER_ROOTS
+        .iter()
+        .map(PathBuf::from)
+        .chain(
+            ENV_VARS
+                .iter()
+                .filter_map(std::env::var_os)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from),
+        )
+        .map(|p| fs::canonicalize(&p).unwrap_or(p))
+        .chain(system_paths())
+        .collect()
+}
+
+/// システムのファイルだけが置かれるディレクトリの一覧（正規化済み）
+///
+/// `critical_paths` と違い、配下のディレクトリも整理の対象にしません。
+fn system_paths() -> Vec<PathBuf> {
+    const SYSTEM_DIRS: [&str; 7] = [
+        "/etc", "/usr", "/var", "/bin", "/sbin", "/System", "/Library",
+    ];
+    const ENV_VARS: [&str; 4] = [
+        "SystemRoot",
+        "ProgramFiles",
+        "ProgramFiles(x86)",
+        "ProgramData",
+    ];
+
+    SYSTEM_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .chain(
+            ENV_VARS
+                .iter()
+                .filter_map(std::env::var_os)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from),
+        )
+        .map(|p| fs::canonicalize(&p).unwrap_or(p))
+        .collect()
+}
+
+/// まだ存在しない部分を含むパスを正規化する
+///
+/// 存在する最も深い祖先はシンボリックリンクをたどって正規化し、残りの `.` と `..` は
+/// 字句的に解決します。
+fn resolve_path(path: &Path) -> PathBuf {
+    let path = absolute(path);
+    for ancestor in path.ancestors() {
+        let Ok(mut resolved) = fs::canonicalize(ancestor) else {
+            continue;
+        };
+        let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+        for component in rest.components() {
+            match component {
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::CurDir => {}
+                component => resolved.push(component),
+            }
+        }
+        return resolved;
+    }
+    path
+}
+
+/// 親ディレクトリだけを正規化したパス（最後の要素がシンボリックリンクでもたどらない）
+fn resolve_parent(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(fs::canonicalize(parent).ok()?.join(name))
+}
+
+/// 正規化済みのパスが危険なディレクトリかどうかを判定
+///
+/// ファイルシステムのルート（`/` や `C:\`）は常に危険とみなします。
+fn is_critical_path(path: &Path, critical: &[PathBuf]) -> bool {
+    path.parent().is_none() || critical.iter().any(|c| c == path)
+}
+
+/// 正規化済みのディレクトリが、システムやホームディレクトリそのもの、またはシステムの
+/// ディレクトリの中にあるか（シンボリックリンクのリンク先や一覧のファイルの確認に使う）
+fn is_protected_dir(dir: &Path) -> bool {
+    is_critical_path(dir, &critical_paths())
+        || system_paths().iter().any(|system| dir.starts_with(system))
+}
+
+/// ファイルを更新日時の新しい順に並べ替える
+///
+/// 更新日時が取得できないファイルは最も古いものとして扱い、同じ日時はパス順に並べます。
+fn sort_newest_first(files: &mut [PathBuf]) {
+    files.sort_by_cached_key(|path| {
+        let modified = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        (Reverse(modified), path.clone())
+    });
+}
+
+/// 空（0バイト）のファイルか（メタデータを読めない場合は `false`）
+fn is_empty_file(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.len() == 0)
+}
+
+/// ファイルの日付を求める（ファイル名に含まれる日付 > 更新日時）
+///
+/// どちらも得られない場合は `None` を返します。
+fn file_date(path: &Path) -> Option<Date> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .and_then(Date::from_file_name)
+        .or_else(|| metadata_date(path))
+}
+
+/// ファイルのメタデータの日付を求める（更新日時 > 作成日時）
+///
+/// どちらも取得できない場合は `None` を返します。
+fn metadata_date(path: &Path) -> Option<Date> {
+    let metadata = fs::metadata(path).ok()?;
+    metadata
+        .modified()
+        .or_else(|_| metadata.created())
+        .ok()
+        .map(Date::from_system_time)
+}
+
+/// Others のファイルを入れる拡張子のサブフォルダ名（小文字、拡張子がなければ `none`）
+fn others_extension_folder(path: &Path) -> String {
+    get_extension(path).unwrap_or_else(|| OTHERS_NO_EXTENSION_FOLDER.to_string())
+}
+
+/// 計画どおりに移動（`keeps_original` ならコピーかハードリンク）すると権限不足で
+/// 失敗しそうなら、その理由を返す
+///
+/// 移動元のフォルダ（元のファイルを残す場合は移動元のファイルの読み取り）と、移動先のフォルダ
+/// （まだなければ作成される最も近い既存の祖先）に `is_writable` で書き込めるかを確かめます。
+fn permission_problem(
+    plan: &FilePlan,
+    keeps_original: bool,
+    is_writable: impl Fn(&Path) -> bool,
+) -> Option<String> {
+    if keeps_original {
+        if File::open(&plan.source).is_err() {
+            return Some("source is not readable".to_string());
+        }
+    } else if let Some(dir) = plan.source.parent() {
+        if !is_writable(dir) {
+            return Some(format!("cannot remove from {}", dir.display()));
+        }
+    }
+
+    let dest_dir = plan.dest_dir();
+    let existing = dest_dir.ancestors().find(|dir| dir.exists())?;
+    if !is_writable(existing) {
+        return Some(format!("cannot write to {}", existing.display()));
+    }
+    None
+}
+
+/// 移動計画をJSONで書き出す（既存のファイルは上書き）
+fn save_plans(plans: &[FilePlan], path: &Path) -> Result<()> {
+    let json = serde_json::to_string_pretty(plans).context("Failed to serialize plan")?;
+    fs::write(path, json).with_context(|| format!("Failed to write plan: {}", path.display()))
+}
+
+/// `save_plans` で書き出した移動計画を読み込む
+fn load_plans(path: &Path) -> Result<Vec<FilePlan>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read plan: {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse plan: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_categorize_file() {
+        let dir = tempdir().unwrap();
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dry_run: true,
+            recursive: false,
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+
+        assert_eq!(
+            sorter.categorize_file(Path::new("test.jpg")),
+            Category::Images
+        );
+        assert_eq!(
+            sorter.categorize_file(Path::new("test.mp4")),
+            Category::Videos
+        );
+        assert_eq!(
+            sorter.categorize_file(Path::new("test.pdf")),
+            Category::Documents
+        );
+        assert_eq!(
+            sorter.categorize_file(Path::new("test.mp3")),
+            Category::Music
+        );
+        assert_eq!(
+            sorter.categorize_file(Path::new("test.zip")),
+            Category::Archives
+        );
+        assert_eq!(sorter.categorize_file(Path::new("test.rs")), Category::Code);
+        assert_eq!(
+            sorter.categorize_file(Path::new("test.xyz")),
+            Category::Others
+        );
+    }
+
+    #[test]
+    fn test_collect_files_non_recursive() {
+        let dir = tempdir().unwrap();
+
+        // ルートにファイルを作成
+        File::create(dir.path().join("file1.txt")).unwrap();
+        File::create(dir.path().join("file2.jpg")).unwrap();
+
+        // サブディレクトリを作成
+        fs::create_dir(dir.path().join("subdir")).unwrap();
+        File::create(dir.path().join("subdir").join("file3.txt")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dry_run: true,
+            recursive: false,
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+
+        let files = sorter.collect_files(dir.path()).unwrap();
+        assert_eq!(files.len(), 2); // サブディレクトリ内は含まれない
+    }
+
+    #[test]
+    fn test_collect_files_recursive() {
+        let dir = tempdir().unwrap();
+
+        // ルートにファイルを作成
+        File::create(dir.path().join("file1.txt")).unwrap();
+
+        // サブディレクトリを作成
+        fs::create_dir(dir.path().join("subdir")).unwrap();
+        File::create(dir.path().join("subdir").join("file2.txt")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dry_run: true,
+            recursive: true,
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+
+        let files = sorter.collect_files(dir.path()).unwrap();
+        assert_eq!(files.len(), 2); // サブディレクトリ内も含まれる
+    }
+
+    #[test]
+    fn test_collect_files_max_depth() {
+        let dir = tempdir().unwrap();
+        let deep = dir.path().join("a").join("b");
+        fs::create_dir_all(&deep).unwrap();
+        fs::create_dir_all(dir.path().join("a").join("Images")).unwrap();
+        File::create(dir.path().join("root.txt")).unwrap();
+        File::create(dir.path().join("a").join("one.txt")).unwrap();
+        File::create(dir.path().join("a").join("Images").join("photo.jpg")).unwrap();
+        File::create(deep.join("two.txt")).unwrap();
+
+        let collect = |max_depth| {
+            let sorter = Sorter::new(SorterConfig {
+                target_dir: dir.path().to_path_buf(),
+                recursive: true,
+                max_depth: Some(max_depth),
+                ..Default::default()
+            });
+            let mut names: Vec<String> = sorter
+                .collect_files(dir.path())
+                .unwrap()
+                .iter()
+                .map(|f| f.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(collect(0), vec!["root.txt"]);
+        // 深さの範囲内でもカテゴリフォルダには降りない
+        assert_eq!(collect(1), vec!["one.txt", "root.txt"]);
+        assert_eq!(collect(2), vec!["one.txt", "root.txt", "two.txt"]);
+    }
+
+    #[test]
+    fn test_create_plans() {
+        let dir = tempdir().unwrap();
+
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("document.pdf")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dry_run: true,
+            recursive: false,
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+
+        let files = sorter.collect_files(dir.path()).unwrap();
+        let plans = sorter.create_plans(&files).unwrap();
+
+        assert_eq!(plans.len(), 2);
+
+        // 各ファイルが正しいカテゴリに分類されているか
+        for plan in &plans {
+            let filename = plan.source.file_name().unwrap().to_str().unwrap();
+            match filename {
+                "photo.jpg" => assert_eq!(plan.category, Category::Images),
+                "document.pdf" => assert_eq!(plan.category, Category::Documents),
+                _ => panic!("Unexpected file: {}", filename),
+            }
+        }
+    }
+
+    #[test]
+    fn test_preserve_tree() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("photo.jpg"), "nested").unwrap();
+        fs::write(dir.path().join("a").join("photo.jpg"), "shallow").unwrap();
+        fs::write(dir.path().join("photo.jpg"), "root").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            preserve_tree: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        // 同名のファイルでも階層が異なればリネームされない
+        assert_eq!((stats.moved_files, stats.renamed_files), (3, 0));
+        let images = dir.path().join("Images");
+        assert_eq!(
+            fs::read_to_string(images.join("photo.jpg")).unwrap(),
+            "root"
+        );
+        assert_eq!(
+            fs::read_to_string(images.join("a").join("photo.jpg")).unwrap(),
+            "shallow"
+        );
+        assert_eq!(
+            fs::read_to_string(images.join("a").join("b").join("photo.jpg")).unwrap(),
+            "nested"
+        );
+    }
+
+    #[test]
+    fn test_multiple_target_dirs() {
+        let first = tempdir().unwrap();
+        let second = tempdir().unwrap();
+        File::create(first.path().join("photo.jpg")).unwrap();
+        File::create(second.path().join("photo.jpg")).unwrap();
+        File::create(second.path().join("report.pdf")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: first.path().to_path_buf(),
+            more_target_dirs: vec![second.path().to_path_buf()],
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        // ディレクトリごとにカテゴリフォルダを作り、統計は合算される
+        assert_eq!((stats.moved_files, stats.renamed_files), (3, 0));
+        assert_eq!(stats.category_counts[&Category::Images], 2);
+        assert!(first.path().join("Images").join("photo.jpg").exists());
+        assert!(second.path().join("Images").join("photo.jpg").exists());
+        assert!(second.path().join("Documents").join("report.pdf").exists());
+        assert!(!first.path().join("Documents").exists());
+    }
+
+    #[test]
+    fn test_export_and_apply_plan() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("Images")).unwrap();
+        fs::write(dir.path().join("Images").join("photo.jpg"), "old").unwrap();
+        fs::write(dir.path().join("photo.jpg"), "new").unwrap();
+        fs::write(dir.path().join("report.pdf"), "pdf").unwrap();
+        let plan_file = tempdir().unwrap();
+        let plan_path = plan_file.path().join("plan.json");
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dry_run: true,
+            export_plan: Some(plan_path.clone()),
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        // 書き出した計画には重複回避後の移動先が入る
+        let plans = load_plans(&plan_path).unwrap();
+        assert_eq!(plans.len(), 2);
+        assert!(plans
+            .iter()
+            .any(|p| p.destination.ends_with("Images/photo_1.jpg")));
+
+        let sorter = Sorter::new(SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        });
+        let stats = sorter.apply_plan(&plan_path).unwrap();
+        assert_eq!(stats.moved_files, 2);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("Images").join("photo_1.jpg")).unwrap(),
+            "new"
+        );
+        assert!(dir.path().join("Documents").join("report.pdf").exists());
+
+        // 移動元がなくなった古い計画は何も移動しない
+        let err = sorter.apply_plan(&plan_path).unwrap_err();
+        assert!(err.to_string().contains("Plan is stale"));
+    }
+
+    #[test]
+    fn test_apply_plan_rejects_escaping_destination() {
+        let dir = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        fs::write(dir.path().join("notes.txt"), "text").unwrap();
+        let plan_path = outside.path().join("plan.json");
+        let sorter = Sorter::new(SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        });
+
+        for destination in [
+            outside.path().join("pwn.txt"),
+            dir.path()
+                .join("Documents")
+                .join("..")
+                .join("..")
+                .join("pwn.txt"),
+        ] {
+            let plan = FilePlan {
+                source: dir.path().join("notes.txt"),
+                destination,
+                category: Category::Documents,
+            };
+            fs::write(&plan_path, serde_json::to_string(&[plan]).unwrap()).unwrap();
+
+            let err = sorter.apply_plan(&plan_path).unwrap_err();
+            assert!(err.to_string().contains("outside"), "{}", err);
+            assert!(dir.path().join("notes.txt").exists());
+        }
+        assert!(!outside.path().join("pwn.txt").exists());
+    }
+
+    #[test]
+    fn test_modified_window() {
+        let dir = tempdir().unwrap();
+        let now = SystemTime::now();
+        let day = Duration::from_secs(86_400);
+        for (name, age_days) in [("old.txt", 400), ("mid.txt", 60), ("new.txt", 1)] {
+            let path = dir.path().join(name);
+            File::create(&path).unwrap();
+            filetime::set_file_mtime(&path, (now - day * age_days).into()).unwrap();
+        }
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            modified_before: Some(now - day * 30),
+            modified_after: Some(now - day * 365),
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+        let files = sorter.collect_files(dir.path()).unwrap();
+        let (files, skipped) = sorter.apply_filters(files);
+
+        assert_eq!(files, vec![dir.path().join("mid.txt")]);
+        let reason = |name: &str| {
+            skipped
+                .iter()
+                .find(|s| s.path.ends_with(name))
+                .map(|s| s.reason)
+        };
+        assert_eq!(reason("old.txt"), Some(SkipReason::ModifiedBeforeCutoff));
+        assert_eq!(reason("new.txt"), Some(SkipReason::ModifiedAfterCutoff));
+    }
+
+    #[test]
+    fn test_permission_problem() {
+        let dir = tempdir().unwrap();
+        let inbox = dir.path().join("inbox");
+        fs::create_dir(&inbox).unwrap();
+        File::create(inbox.join("photo.jpg")).unwrap();
+        let plan = FilePlan {
+            source: inbox.join("photo.jpg"),
+            destination: dir.path().join("Images").join("photo.jpg"),
+            category: Category::Images,
+        };
+
+        assert_eq!(permission_problem(&plan, false, |_| true), None);
+        // 移動元のフォルダから削除できない（コピーなら問題ない）
+        let read_only_inbox = |d: &Path| d != inbox;
+        assert!(permission_problem(&plan, false, read_only_inbox)
+            .unwrap()
+            .starts_with("cannot remove from"));
+        assert_eq!(permission_problem(&plan, true, read_only_inbox), None);
+        // まだない移動先のフォルダは、作成先の既存の祖先で判断する
+        let read_only_root = |d: &Path| d != dir.path();
+        assert_eq!(
+            permission_problem(&plan, false, read_only_root),
+            Some(format!("cannot write to {}", dir.path().display()))
+        );
+    }
+
+    #[test]
+    fn test_category_bytes() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.jpg"), [0u8; 100]).unwrap();
+        fs::write(dir.path().join("b.jpg"), [0u8; 50]).unwrap();
+        fs::write(dir.path().join("c.pdf"), [0u8; 7]).unwrap();
+
+        for dry_run in [true, false] {
+            let config = SorterConfig {
+                target_dir: dir.path().to_path_buf(),
+                dry_run,
+                ..Default::default()
+            };
+            let stats = Sorter::new(config).run().unwrap();
+            assert_eq!(stats.category_bytes[&Category::Images], 150);
+            assert_eq!(stats.category_bytes[&Category::Documents], 7);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks() {
+        let dir = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        fs::write(outside.path().join("photo.jpg"), "jpg").unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("photo.jpg"),
+            dir.path().join("link.jpg"),
+        )
+        .unwrap();
+        let sub = dir.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        File::create(sub.join("a.pdf")).unwrap();
+        // 祖先のディレクトリへのリンク（循環）
+        std::os::unix::fs::symlink(dir.path(), sub.join("loop")).unwrap();
+        // システムディレクトリへのリンクはたどらない
+        std::os::unix::fs::symlink("/etc", dir.path().join("etc")).unwrap();
+
+        let mut config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            ..Default::default()
+        };
+        let files = Sorter::new(config.clone())
+            .collect_files(dir.path())
+            .unwrap();
+        assert_eq!(files, vec![sub.join("a.pdf")]);
+
+        config.follow_symlinks = true;
+        let mut files = Sorter::new(config).collect_files(dir.path()).unwrap();
+        files.sort();
+        assert_eq!(files, vec![dir.path().join("link.jpg"), sub.join("a.pdf")]);
+    }
+
+    #[test]
+    fn test_subfolder_by_ext() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("main.rs")).unwrap();
+        File::create(dir.path().join("LOGO.PNG")).unwrap();
+        File::create(dir.path().join("README")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            subfolder_by_ext: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.moved_files, 3);
+        assert!(dir.path().join("Code/rs/main.rs").exists());
+        assert!(dir.path().join("Images/png/LOGO.PNG").exists());
+        assert!(dir
+            .path()
+            .join("Others")
+            .join(NO_EXTENSION_FOLDER)
+            .join("README")
+            .exists());
+    }
+
+    #[test]
+    fn test_others_by_ext() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("data.xyz")).unwrap();
+        File::create(dir.path().join("more.XYZ")).unwrap();
+        File::create(dir.path().join("README")).unwrap();
+        File::create(dir.path().join("logo.png")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            others_by_ext: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert!(dir.path().join("Others/xyz/data.xyz").exists());
+        assert!(dir.path().join("Others/xyz/more.XYZ").exists());
+        assert!(dir.path().join("Others/none/README").exists());
+        // Others 以外のカテゴリはそのまま
+        assert!(dir.path().join("Images/logo.png").exists());
+
+        assert_eq!(stats.category_counts[&Category::Others], 3);
+        assert_eq!(stats.others_extensions["xyz"], 2);
+        assert_eq!(stats.others_extensions[OTHERS_NO_EXTENSION_FOLDER], 1);
+    }
+
+    #[test]
+    fn test_collapse_singletons() {
+        let dir = tempdir().unwrap();
+
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("a.pdf")).unwrap();
+        File::create(dir.path().join("b.pdf")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            collapse_singletons: Some(CollapseTarget::Root),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        // 1ファイルだけのImagesフォルダは解消され、ファイルはルートに戻る
+        assert_eq!(stats.collapsed_folders, 1);
+        assert!(!dir.path().join("Images").exists());
+        assert!(dir.path().join("photo.jpg").exists());
+
+        // 2ファイルあるDocumentsフォルダはそのまま
+        assert!(dir.path().join("Documents").join("a.pdf").exists());
+        assert!(dir.path().join("Documents").join("b.pdf").exists());
+    }
+
+    #[test]
+    fn test_collapse_singletons_only_touches_this_run() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Music")).unwrap();
+        File::create(dir.path().join("Music").join("old.mp3")).unwrap();
+        fs::create_dir_all(dir.path().join("inbox")).unwrap();
+        File::create(dir.path().join("inbox").join("photo.jpg")).unwrap();
+        File::create(dir.path().join("notes.txt")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            collapse_singletons: Some(CollapseTarget::Origin),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        // 実行前からあった1ファイルだけのフォルダには触れない
+        assert!(dir.path().join("Music").join("old.mp3").exists());
+        // 今回移動した1ファイルは移動前の場所に戻る
+        assert_eq!(stats.collapsed_folders, 2);
+        assert!(!dir.path().join("Images").exists());
+        assert!(dir.path().join("inbox").join("photo.jpg").exists());
+        assert!(dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_dedup_content_skips_identical_destination() {
+        let dir = tempdir().unwrap();
+        let documents = dir.path().join("Documents");
+        fs::create_dir(&documents).unwrap();
+        fs::write(documents.join("report.pdf"), "same").unwrap();
+        fs::write(documents.join("notes.txt"), "old").unwrap();
+        fs::write(dir.path().join("report.pdf"), "same").unwrap();
+        fs::write(dir.path().join("notes.txt"), "new").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dedup_content: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.duplicate_files, 1);
+        assert_eq!(stats.moved_files, 1);
+        // 同一内容のファイルは移動せずに残し、内容が異なるファイルは連番で移動する
+        assert!(dir.path().join("report.pdf").exists());
+        assert!(!documents.join("report_1.pdf").exists());
+        assert_eq!(
+            fs::read_to_string(documents.join("notes_1.txt")).unwrap(),
+            "new"
+        );
+    }
+
+    #[test]
+    fn test_merge_content_duplicates() {
+        let dir = tempdir().unwrap();
+
+        fs::write(dir.path().join("song.mp3"), "same audio").unwrap();
+        fs::write(dir.path().join("song (1).mp3"), "same audio").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            merge_content_duplicates: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.total_files, 2);
+        assert_eq!(stats.moved_files, 1);
+        assert_eq!(stats.duplicate_files, 1);
+
+        // パス順で先の "song (1).mp3" だけが移動され、もう一方はその場に残る
+        assert!(dir.path().join("Music").join("song (1).mp3").exists());
+        assert!(dir.path().join("song.mp3").exists());
+        assert!(!dir.path().join("Music").join("song.mp3").exists());
+    }
+
+    #[test]
+    fn test_extension_overrides() {
+        let config = SorterConfig {
+            extension_overrides: HashMap::from([("log".to_string(), Category::Documents)]),
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+
+        assert_eq!(
+            sorter.categorize_file(Path::new("app.LOG")),
+            Category::Documents
+        );
+        assert_eq!(
+            sorter.categorize_file(Path::new("photo.jpg")),
+            Category::Images
+        );
+    }
+
+    #[test]
+    fn test_dump_layered_config() {
+        let dir = tempdir().unwrap();
+        let base = dir.path().join("base.toml");
+        let over = dir.path().join("override.toml");
+        fs::write(
+            &base,
+            "[extensions]\nlog = \"Documents\"\njpg = \"Others\"\n",
+        )
+        .unwrap();
+        fs::write(&over, "[extensions]\njpg = \"Archives\"\n").unwrap();
+
+        let layered = crate::config::load_layered_config(&[base, over]).unwrap();
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            extension_overrides: layered.to_overrides().unwrap(),
+            ..Default::default()
+        };
+
+        let toml_dump = config.resolved().to_toml().unwrap();
+        assert!(toml_dump.contains("recursive = true"));
+        assert!(toml_dump.contains("log = \"Documents\""));
+        assert!(toml_dump.contains("jpg = \"Archives\""));
+        assert!(toml_dump.contains("png = \"Images\""));
+
+        let json_dump: serde_json::Value =
+            serde_json::from_str(&config.resolved().to_json().unwrap()).unwrap();
+        assert_eq!(json_dump["extensions"]["jpg"], "Archives");
+        assert_eq!(json_dump["options"]["recursive"], true);
+    }
+
+    #[test]
+    fn test_archive_output() {
+        let dir = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        let zip_path = out.path().join("sorted.zip");
+
+        fs::write(dir.path().join("photo.jpg"), "jpg").unwrap();
+        fs::write(dir.path().join("notes.txt"), "txt").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            archive_output: Some(zip_path.clone()),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+        assert_eq!(stats.moved_files, 2);
+
+        let mut zip = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        let mut names: Vec<String> = zip.file_names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, vec!["Documents/notes.txt", "Images/photo.jpg"]);
+
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name("Images/photo.jpg").unwrap(), &mut content)
+            .unwrap();
+        assert_eq!(content, "jpg");
+
+        // 元ファイルはそのまま残る
+        assert!(dir.path().join("photo.jpg").exists());
+        assert!(!dir.path().join("Images").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_farm() {
+        let dir = tempdir().unwrap();
+        let farm = tempdir().unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        fs::write(a.join("notes.txt"), "a").unwrap();
+        fs::write(b.join("notes.txt"), "b").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            symlink_farm: Some(farm.path().to_path_buf()),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+        assert_eq!(stats.moved_files, 2);
+
+        // 元のファイルはそのまま残る
+        assert!(a.join("notes.txt").is_file());
+        assert!(b.join("notes.txt").is_file());
+        assert!(!dir.path().join("Documents").exists());
+
+        // 同名のリンクは連番で回避され、それぞれ元のファイルを指す
+        let documents = farm.path().join("Documents");
+        let mut targets: Vec<String> = ["notes.txt", "notes_1.txt"]
+            .iter()
+            .map(|name| {
+                let link = documents.join(name);
+                assert!(is_symlink(&link));
+                fs::read_to_string(&link).unwrap()
+            })
+            .collect();
+        targets.sort();
+        assert_eq!(targets, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_undo_reverts_last_sort() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), "photo").unwrap();
+        fs::write(dir.path().join("song.mp3"), "song").unwrap();
+        fs::write(dir.path().join("notes.txt"), "notes").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            write_journal: true,
+            ..Default::default()
+        };
+        Sorter::new(config.clone()).run().unwrap();
+
+        // 元の場所が別のファイルで埋まった・移動先から消えたファイル
+        fs::write(dir.path().join("photo.jpg"), "new photo").unwrap();
+        fs::remove_file(dir.path().join("Music").join("song.mp3")).unwrap();
+
+        let report = Sorter::new(config).undo().unwrap();
+
+        assert_eq!(report.reverted, 2);
+        assert_eq!(report.renamed, 1);
+        assert_eq!(
+            report.missing,
+            vec![dir.path().join("Music").join("song.mp3")]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("photo_1.jpg")).unwrap(),
+            "photo"
+        );
+        assert!(dir.path().join("notes.txt").exists());
+        // 空になったカテゴリフォルダとジャーナルは削除される
+        assert!(!dir.path().join("Images").exists());
+        assert!(!Journal::path_in(dir.path()).exists());
+    }
+
+    #[test]
+    fn test_copy_mode() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), "photo").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            copy: true,
+            validate_after_move: true,
+            write_journal: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.moved_files, 1);
+        assert!(dir.path().join("photo.jpg").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("Images").join("photo.jpg")).unwrap(),
+            "photo"
+        );
+        // 元のファイルが残っていても不整合とはみなさない
+        assert_eq!(stats.discrepancies, 0);
+        assert!(!Journal::path_in(dir.path()).exists());
+
+        // 再実行しても同じ内容のコピーは増やさない
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            copy: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+        assert_eq!(stats.moved_files, 0);
+        assert_eq!(stats.skipped_files, 1);
+        assert!(!dir.path().join("Images").join("photo_1.jpg").exists());
+    }
+
+    #[test]
+    fn test_hardlink_mode() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), "photo").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            hardlink: true,
+            validate_after_move: true,
+            write_journal: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.moved_files, 1);
+        assert_eq!(stats.discrepancies, 0);
+        // 元のファイルとリンクは同じ内容を共有する
+        fs::write(dir.path().join("photo.jpg"), "edited").unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("Images").join("photo.jpg")).unwrap(),
+            "edited"
+        );
+        assert!(!Journal::path_in(dir.path()).exists());
+
+        // 再実行しても同じ実体へのリンクは増やさない
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            hardlink: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+        assert_eq!(stats.moved_files, 0);
+        assert_eq!(stats.skipped_files, 1);
+        assert!(!dir.path().join("Images").join("photo_1.jpg").exists());
+    }
+
+    #[test]
+    fn test_on_conflict_skip_and_overwrite() {
+        let sort_with = |on_conflict| {
+            let dir = tempdir().unwrap();
+            fs::create_dir(dir.path().join("Documents")).unwrap();
+            fs::write(dir.path().join("Documents").join("report.pdf"), "old").unwrap();
+            fs::write(dir.path().join("report.pdf"), "new").unwrap();
+            let config = SorterConfig {
+                target_dir: dir.path().to_path_buf(),
+                on_conflict,
+                ..Default::default()
+            };
+            let stats = Sorter::new(config).run().unwrap();
+            (dir, stats)
+        };
+
+        let (dir, stats) = sort_with(ConflictStrategy::Skip);
+        assert_eq!((stats.moved_files, stats.skipped_files), (0, 1));
+        assert!(dir.path().join("report.pdf").exists());
+
+        let (dir, stats) = sort_with(ConflictStrategy::Overwrite);
+        assert_eq!((stats.moved_files, stats.replaced_files), (1, 1));
+        let documents = dir.path().join("Documents");
+        assert_eq!(
+            fs::read_to_string(documents.join("report.pdf")).unwrap(),
+            "new"
+        );
+        assert_eq!(fs::read_dir(documents).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_overwrite_keeps_files_from_same_run() {
+        let dir = tempdir().unwrap();
+        for (inbox, content) in [("a", "first"), ("b", "second")] {
+            let sub = dir.path().join(inbox);
+            fs::create_dir(&sub).unwrap();
+            fs::write(sub.join("report.pdf"), content).unwrap();
+        }
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            on_conflict: ConflictStrategy::Overwrite,
+            ..Default::default()
+        };
+
+        // Dry Runでも、同じ実行の同名ファイル同士は上書きせずに連番を付ける
+        let preview = Sorter::new(SorterConfig {
+            dry_run: true,
+            ..config.clone()
+        })
+        .run()
+        .unwrap();
+        assert_eq!((preview.renamed_files, preview.replaced_files), (1, 0));
+
+        let stats = Sorter::new(config).run().unwrap();
+        assert_eq!(stats.moved_files, 2);
+        assert_eq!((stats.renamed_files, stats.replaced_files), (1, 0));
+        assert_eq!(
+            fs::read_dir(dir.path().join("Documents")).unwrap().count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_parallel_jobs() {
+        let dir = tempdir().unwrap();
+        for i in 0..20 {
+            let sub = dir.path().join(format!("inbox{}", i));
+            fs::create_dir(&sub).unwrap();
+            fs::write(sub.join("report.pdf"), i.to_string()).unwrap();
+        }
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            jobs: 4,
+            validate_after_move: true,
+            write_journal: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        // 同名のファイルが同時に移動されても、上書きせずにすべて別名で残る
+        assert_eq!(stats.moved_files, 20);
+        assert_eq!(stats.renamed_files, 19);
+        assert_eq!(stats.discrepancies, 0);
+        assert_eq!(
+            fs::read_dir(dir.path().join("Documents")).unwrap().count(),
+            20
+        );
+        let journal = Journal::load(dir.path()).unwrap().unwrap();
+        assert_eq!(journal.entries.len(), 20);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_view() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), "photo").unwrap();
+        fs::write(dir.path().join("notes.txt"), "notes").unwrap();
+        let view = dir.path().join("view");
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            hardlink_view: Some(view.clone()),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config.clone()).run().unwrap();
+        assert_eq!(stats.moved_files, 2);
+
+        // 元のファイルはそのまま残り、ビューのファイルと同じ実体を共有する
+        for (original, linked) in [
+            ("photo.jpg", view.join("Images").join("photo.jpg")),
+            ("notes.txt", view.join("Documents").join("notes.txt")),
+        ] {
+            let original = fs::metadata(dir.path().join(original)).unwrap();
+            let linked = fs::metadata(linked).unwrap();
+            assert_eq!(original.ino(), linked.ino());
+            assert_eq!(original.nlink(), 2);
+        }
+
+        // 再実行してもビュー内のリンクは取り込まれない
+        let stats = Sorter::new(config).run().unwrap();
+        assert_eq!(stats.total_files, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_detect_executables() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let binary = dir.path().join("installer.sh");
+        let script = dir.path().join("build.sh");
+        fs::write(&binary, "#!/bin/sh\n").unwrap();
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            detect_executables: Some(Category::Programs),
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+
+        // 実行権限があれば拡張子より優先される
+        assert_eq!(sorter.categorize_file(&binary), Category::Programs);
+        assert_eq!(sorter.categorize_file(&script), Category::Code);
+    }
+
+    #[test]
+    fn test_code_by_language() {
+        let dir = tempdir().unwrap();
+
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join("app.py"), "print()").unwrap();
+        fs::write(dir.path().join("notes.txt"), "text").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            code_by_language: true,
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        let code = dir.path().join("Code");
+        assert!(code.join("Rust").join("main.rs").exists());
+        assert!(code.join("Python").join("app.py").exists());
+        // Code以外のカテゴリには影響しない
+        assert!(dir.path().join("Documents").join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_doc_by_language() {
+        let dir = tempdir().unwrap();
+
+        fs::write(
+            dir.path().join("report.txt"),
+            "The quarterly report shows that our customers are happy with the new \
+             service, and we expect the number of subscribers to keep growing next year.",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("rapport.txt"),
+            "Le rapport trimestriel montre que nos clients sont satisfaits du nouveau \
+             service, et nous pensons que le nombre d'abonnés continuera de croître l'année prochaine.",
+        )
+        .unwrap();
+        fs::write(dir.path().join("scan.pdf"), b"%PDF-1.4\x00\x01binary").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            doc_by_language: true,
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        let documents = dir.path().join("Documents");
+        assert!(documents.join("English").join("report.txt").exists());
+        assert!(documents.join("French").join("rapport.txt").exists());
+        // バイナリの文書は Documents 直下
+        assert!(documents.join("scan.pdf").exists());
+    }
+
+    #[test]
+    fn test_custom_category() {
+        let dir = tempdir().unwrap();
+
+        fs::write(dir.path().join("font.ttf"), "font").unwrap();
+        fs::write(dir.path().join("photo.jpg"), "photo").unwrap();
+
+        let fonts = Category::Custom("Fonts".to_string());
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            extension_overrides: HashMap::from([("ttf".to_string(), fonts.clone())]),
+            custom_categories: vec![fonts.clone()],
+            ..Default::default()
+        };
+        let stats = Sorter::new(config.clone()).run().unwrap();
+
+        assert!(dir.path().join("Fonts").join("font.ttf").exists());
+        assert_eq!(stats.category_counts.get(&fonts), Some(&1));
+        // 再帰的に再実行してもカスタムカテゴリのフォルダは整理済みとみなす
+        assert_eq!(Sorter::new(config).check().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_rename_template() {
+        let dir = tempdir().unwrap();
+
+        fs::write(dir.path().join("a.jpg"), "a").unwrap();
+        fs::write(dir.path().join("b.jpg"), "b").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            rename_template: Some("{category}{ext}".parse().unwrap()),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        // テンプレート適用後に同名になったファイルは重複回避される
+        let images = dir.path().join("Images");
+        assert!(images.join("Images.jpg").exists());
+        assert!(images.join("Images_1.jpg").exists());
+        assert_eq!(stats.renamed_files, 1);
+    }
+
+    #[test]
+    fn test_dedup_template() {
+        let dir = tempdir().unwrap();
+
+        fs::create_dir_all(dir.path().join("Documents")).unwrap();
+        fs::write(dir.path().join("Documents").join("report.pdf"), "old").unwrap();
+        fs::write(dir.path().join("report.pdf"), "new").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dedup_template: Some("{stem} ({n}){ext}".parse().unwrap()),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        let documents = dir.path().join("Documents");
+        assert_eq!(
+            fs::read_to_string(documents.join("report (1).pdf")).unwrap(),
+            "new"
+        );
+        assert!(!documents.join("report_1.pdf").exists());
+        assert_eq!(stats.renamed_files, 1);
+    }
+
+    #[test]
+    fn test_find_name_collisions() {
+        let dir = tempdir().unwrap();
+
+        fs::create_dir_all(dir.path().join("a")).unwrap();
+        fs::create_dir_all(dir.path().join("b").join("c")).unwrap();
+        File::create(dir.path().join("untitled.txt")).unwrap();
+        File::create(dir.path().join("a").join("untitled.txt")).unwrap();
+        File::create(dir.path().join("b").join("c").join("untitled.txt")).unwrap();
+        File::create(dir.path().join("a").join("unique.txt")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let collisions = Sorter::new(config).report_name_collisions().unwrap();
+
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].name, "untitled.txt");
+        assert_eq!(collisions[0].paths.len(), 3);
+
+        // 何も移動されない
+        assert!(dir.path().join("untitled.txt").exists());
+        assert!(!dir.path().join("Documents").exists());
+    }
+
+    #[test]
+    fn test_cli_overrides_take_precedence() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("scene.xyz")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            extension_overrides: HashMap::from([("xyz".to_string(), Category::Documents)]),
+            cli_overrides: HashMap::from([("xyz".to_string(), Category::Images)]),
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        assert!(dir.path().join("Images").join("scene.xyz").exists());
+    }
+
+    #[test]
+    fn test_latest_processes_newest_files() {
+        let dir = tempdir().unwrap();
+        let now = filetime::FileTime::now().unix_seconds();
+        for (name, age) in [("old.txt", 300), ("newest.txt", 10), ("newer.txt", 100)] {
+            let path = dir.path().join(name);
+            File::create(&path).unwrap();
+            filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(now - age, 0))
+                .unwrap();
+        }
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            newest_first: true,
+            max_files: Some(2),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        let documents = dir.path().join("Documents");
+        assert!(documents.join("newest.txt").exists());
+        assert!(documents.join("newer.txt").exists());
+        assert!(dir.path().join("old.txt").exists());
+        assert_eq!(stats.moved_files, 2);
+    }
+
+    #[test]
+    fn test_files_from() {
+        let dir = tempdir().unwrap();
+        let sub = dir.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("report.pdf")).unwrap();
+        File::create(sub.join("scan.png")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            files_from: Some(vec![
+                dir.path().join("photo.jpg"),
+                sub.join("scan.png"),
+                dir.path().join("missing.txt"),
+                sub.clone(),
+            ]),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.moved_files, 2);
+        assert!(dir.path().join("Images").join("photo.jpg").exists());
+        assert!(dir.path().join("Images").join("scan.png").exists());
+        assert!(dir.path().join("report.pdf").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_from_resolves_relative_entries() {
+        let dir = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        fs::create_dir(dir.path().join("Images")).unwrap();
+        File::create(dir.path().join("Images").join("a.jpg")).unwrap();
+        File::create(dir.path().join("b.jpg")).unwrap();
+        File::create(outside.path().join("c.jpg")).unwrap();
+
+        // 現在のディレクトリからの相対パス（fd などの出力と同じ形）にする
+        let cwd = std::env::current_dir().unwrap();
+        let relative = |path: &Path| {
+            let up: PathBuf = cwd.components().skip(1).map(|_| "..").collect();
+            up.join(path.strip_prefix("/").unwrap())
+        };
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            files_from: Some(vec![
+                relative(&dir.path().join("Images").join("a.jpg")),
+                relative(&dir.path().join("b.jpg")),
+                outside.path().join("c.jpg"),
+            ]),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.moved_files, 1);
+        assert!(dir.path().join("Images").join("a.jpg").exists());
+        assert!(dir.path().join("Images").join("b.jpg").exists());
+        assert!(!dir.path().join("Images").join("a_1.jpg").exists());
+        assert!(outside.path().join("c.jpg").exists());
+    }
+
+    #[test]
+    fn test_second_run_is_idempotent() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), "jpg").unwrap();
+        fs::write(dir.path().join("report.pdf"), "pdf").unwrap();
+        File::create(dir.path().join("blank.txt")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            empty_files: "folder:Empty".parse().unwrap(),
+            ..Default::default()
+        };
+        let first = Sorter::new(config.clone()).run().unwrap();
+        assert_eq!((first.moved_files, first.already_sorted), (3, 0));
+
+        // --check・--stats-only も実際の実行と同じく移動なしと判断する
+        assert_eq!(Sorter::new(config.clone()).check().unwrap(), 0);
+        let stats = Sorter::new(config.clone()).stats_only().unwrap();
+        assert_eq!((stats.moved_files, stats.already_sorted), (0, 3));
+
+        let second = Sorter::new(config).run().unwrap();
+        assert_eq!(second.moved_files, 0);
+        assert_eq!(second.renamed_files, 0);
+        // カテゴリフォルダの2件と、移動先が移動元と同じ Empty/blank.txt
+        assert_eq!(second.already_sorted, 3);
+        assert!(dir.path().join("Empty").join("blank.txt").exists());
+        assert!(!dir.path().join("Empty").join("blank_1.txt").exists());
+    }
+
+    #[test]
+    fn test_folder_names() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            folder_names: HashMap::from([(Category::Images, "画像".to_string())]),
+            ..Default::default()
+        };
+        Sorter::new(config.clone()).run().unwrap();
+        assert!(dir.path().join("画像").join("photo.jpg").exists());
+        assert!(!dir.path().join("Images").exists());
+
+        // 名前を変えたカテゴリフォルダも再実行では中身を動かさない
+        let stats = Sorter::new(config.clone()).run().unwrap();
+        assert_eq!(stats.moved_files, 0);
+        assert!(dir.path().join("画像").join("photo.jpg").exists());
+
+        // 別のカテゴリのフォルダ名と重なる名前は実行前に拒否する
+        File::create(dir.path().join("clip.mp4")).unwrap();
+        let config = SorterConfig {
+            folder_names: HashMap::from([(Category::Images, "Videos".to_string())]),
+            ..config
+        };
+        assert!(Sorter::new(config).run().is_err());
+        assert!(dir.path().join("clip.mp4").exists());
+    }
+
+    #[test]
+    fn test_min_files_per_category() {
+        let dir = tempdir().unwrap();
+        for name in ["a.jpg", "b.png", "song.mp3"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            min_files_per_category: Some(2),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config.clone()).run().unwrap();
+
+        assert!(dir.path().join("Images").join("a.jpg").exists());
+        assert!(dir.path().join("song.mp3").exists());
+        assert!(!dir.path().join("Music").exists());
+        assert_eq!((stats.moved_files, stats.skipped_files), (2, 1));
+        assert_eq!(stats.total_files, 3);
+
+        // カテゴリフォルダが既にあれば、1件でも移動する
+        File::create(dir.path().join("c.gif")).unwrap();
+        let stats = Sorter::new(config).run().unwrap();
+        assert!(dir.path().join("Images").join("c.gif").exists());
+        assert!(dir.path().join("song.mp3").exists());
+        assert_eq!((stats.moved_files, stats.skipped_files), (1, 1));
+    }
+
+    #[test]
+    fn test_critical_path_guard() {
+        let root = Sorter::new(SorterConfig {
+            target_dir: PathBuf::from("/"),
+            ..Default::default()
+        });
+        let err = root.check_not_critical().unwrap_err();
+        assert!(err.to_string().contains("--force"));
+
+        let forced = Sorter::new(SorterConfig {
+            target_dir: PathBuf::from("/"),
+            force: true,
+            ..Default::default()
+        });
+        assert!(forced.check_not_critical().is_ok());
+
+        let dir = tempdir().unwrap();
+        let normal = Sorter::new(SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        });
+        assert!(normal.check_not_critical().is_ok());
+        assert!(is_critical_path(
+            Path::new("/home/me"),
+            &[PathBuf::from("/home/me")]
+        ));
+    }
+
+    #[test]
+    fn test_deep_ext_match() {
+        let config = SorterConfig {
+            deep_ext_match: true,
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+        assert_eq!(
+            sorter.categorize_file(Path::new("file.sql.bak")),
+            Category::Code
+        );
+        assert_eq!(
+            sorter.categorize_file(Path::new("backup.2024.PDF.old")),
+            Category::Documents
+        );
+        // 末尾の拡張子が既知ならそのまま
+        assert_eq!(
+            sorter.categorize_file(Path::new("backup.2024.sql.gz")),
+            Category::Archives
+        );
+        assert_eq!(
+            sorter.categorize_file(Path::new("photo.jpg")),
+            Category::Images
+        );
+        // 語幹は拡張子として扱わない
+        assert_eq!(
+            sorter.categorize_file(Path::new("pdf.bak")),
+            Category::Others
+        );
+
+        // 無効時は従来どおり
+        let sorter = Sorter::new(SorterConfig::default());
+        assert_eq!(
+            sorter.categorize_file(Path::new("file.sql.bak")),
+            Category::Others
+        );
+    }
+
+    #[test]
+    fn test_detect_content() {
+        let dir = tempdir().unwrap();
+        let image = dir.path().join("download");
+        fs::write(&image, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let readme = dir.path().join("README");
+        fs::write(&readme, "plain text").unwrap();
+
+        let config = SorterConfig {
+            detect_content: true,
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+        assert_eq!(sorter.categorize_file(&image), Category::Images);
+        // 判定できなければ Others
+        assert_eq!(sorter.categorize_file(&readme), Category::Others);
+
+        // 無効時は拡張子がなければ Others
+        let sorter = Sorter::new(SorterConfig::default());
+        assert_eq!(sorter.categorize_file(&image), Category::Others);
+    }
+
+    #[test]
+    fn test_unflatten_after_sort() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Documents")).unwrap();
+        fs::write(dir.path().join("Documents").join("notes.txt"), "old").unwrap();
+        fs::write(dir.path().join("notes.txt"), "new").unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("main.rs")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            code_by_language: true,
+            ..Default::default()
+        };
+        Sorter::new(config.clone()).run().unwrap();
+        assert!(dir
+            .path()
+            .join("Code")
+            .join("Rust")
+            .join("main.rs")
+            .exists());
+        // ルートに残っている同名ファイルとの衝突は連番で回避される
+        File::create(dir.path().join("photo.jpg")).unwrap();
+
+        let stats = Sorter::new(config).unflatten().unwrap();
+
+        for name in [
+            "notes.txt",
+            "notes_1.txt",
+            "photo.jpg",
+            "photo_1.jpg",
+            "main.rs",
+        ] {
+            assert!(dir.path().join(name).is_file(), "{name} should be at root");
+        }
+        for category in ["Documents", "Images", "Code"] {
+            assert!(!dir.path().join(category).exists());
+        }
+        assert_eq!(stats.moved_files, 4);
+        assert_eq!(stats.renamed_files, 1);
+        assert_eq!(stats.removed_folders, 3);
+    }
+
+    #[test]
+    fn test_flatten() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("projects").join("2024");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(nested.join("notes.txt"), "nested").unwrap();
+        fs::write(dir.path().join("projects").join("plan.md"), "plan").unwrap();
+        fs::write(dir.path().join("notes.txt"), "root").unwrap();
+        fs::write(dir.path().join(".git").join("HEAD"), "ref").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            skip_hidden: true,
+            prune_empty: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).flatten().unwrap();
+
+        assert_eq!(stats.moved_files, 2);
+        assert_eq!(stats.renamed_files, 1);
+        assert_eq!(stats.removed_folders, 2);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("notes_1.txt")).unwrap(),
+            "nested"
+        );
+        assert!(dir.path().join("plan.md").exists());
+        assert!(!dir.path().join("projects").exists());
+        assert!(dir.path().join(".git").join("HEAD").exists());
+    }
+
+    #[test]
+    fn test_flatten_respects_filters_and_on_conflict() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("projects").join("2024");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("deep.txt"), "deep").unwrap();
+        fs::write(dir.path().join("projects").join("debug.log"), "log").unwrap();
+        fs::write(dir.path().join("projects").join("notes.txt"), "nested").unwrap();
+        fs::write(dir.path().join("projects").join("plan.md"), "plan").unwrap();
+        fs::write(dir.path().join("notes.txt"), "root").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            exclude: vec!["*.log".to_string()],
+            max_depth: Some(1),
+            on_conflict: ConflictStrategy::Skip,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).flatten().unwrap();
+
+        // 除外パターン・深さの上限を守り、同名のファイルは --on-conflict に従ってスキップする
+        assert_eq!(stats.moved_files, 1);
+        assert_eq!(stats.skipped_files, 1);
+        assert_eq!(stats.excluded_files, 1);
+        assert!(dir.path().join("plan.md").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("notes.txt")).unwrap(),
+            "root"
+        );
+        assert!(dir.path().join("projects").join("debug.log").exists());
+        assert!(nested.join("deep.txt").exists());
+    }
+
+    #[test]
+    fn test_rules_route_before_categorization() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("big.jpg"), vec![0u8; 2048]).unwrap();
+        fs::write(dir.path().join("small.jpg"), vec![0u8; 10]).unwrap();
+        fs::write(dir.path().join("invoice_42.pdf"), "pdf").unwrap();
+        fs::write(dir.path().join("notes.pdf"), "pdf").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            rules: vec![
+                "ext == jpg && size > 1KB => Images/Large".parse().unwrap(),
+                r#"name ~ "^invoice_\d+" => Documents/Invoices"#.parse().unwrap(),
+            ],
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        let images = dir.path().join("Images");
+        let documents = dir.path().join("Documents");
+        assert!(images.join("Large").join("big.jpg").exists());
+        assert!(documents.join("Invoices").join("invoice_42.pdf").exists());
+        // 一致しないファイルは通常どおり分類
+        assert!(images.join("small.jpg").exists());
+        assert!(documents.join("notes.pdf").exists());
+    }
+
+    #[test]
+    fn test_lifetime_stats_sum_concurrent_runs() {
+        let store_dir = tempdir().unwrap();
+        let store = store_dir.path().join("lifetime-stats.json");
+
+        let handles: Vec<_> = [3usize, 5]
+            .into_iter()
+            .map(|count| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    let dir = tempdir().unwrap();
+                    for i in 0..count {
+                        File::create(dir.path().join(format!("photo{}.jpg", i))).unwrap();
+                    }
+                    File::create(dir.path().join("notes.pdf")).unwrap();
+                    let config = SorterConfig {
+                        target_dir: dir.path().to_path_buf(),
+                        lifetime_stats: Some(store),
+                        ..Default::default()
+                    };
+                    Sorter::new(config).run().unwrap()
+                })
+            })
+            .collect();
+        let runs: Vec<SortStats> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        let lifetime = LifetimeStore::new(&store).load().unwrap();
+        assert_eq!(lifetime.runs, 2);
+        assert_eq!(
+            lifetime.moved_files,
+            runs.iter().map(|s| s.moved_files).sum::<usize>()
+        );
+        assert_eq!(lifetime.moved_files, 10);
+        assert_eq!(lifetime.category_counts["Images"], 8);
+        assert_eq!(lifetime.category_counts["Documents"], 2);
+    }
+
+    #[test]
+    fn test_by_date_subfolders() {
+        let dir = tempdir().unwrap();
+        let photo = dir.path().join("IMG_20230715_123456.jpg");
+        File::create(&photo).unwrap();
+        // ファイル名の日付ではなく更新日時（2020-05-01）で振り分ける
+        filetime::set_file_mtime(&photo, filetime::FileTime::from_unix_time(1_588_291_200, 0))
+            .unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            by_date: true,
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        assert!(dir
+            .path()
+            .join("Images/2020/05/IMG_20230715_123456.jpg")
+            .exists());
+    }
+
+    #[test]
+    fn test_date_from_name_subfolders() {
+        let dir = tempdir().unwrap();
+        for name in [
+            "IMG_20230715_123456.jpg",
+            "2021-12-03_report.pdf",
+            "Screenshot 2022_01_09.png",
+            "scan.jpg",
+        ] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+        // ファイル名に日付がない場合は更新日時（2020-05-01）で振り分ける
+        filetime::set_file_mtime(
+            dir.path().join("scan.jpg"),
+            filetime::FileTime::from_unix_time(1_588_291_200, 0),
+        )
+        .unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            date_from_name: true,
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        let images = dir.path().join("Images");
+        assert!(images.join("2023/07/IMG_20230715_123456.jpg").exists());
+        assert!(images.join("2022/01/Screenshot 2022_01_09.png").exists());
+        assert!(images.join("2020/05/scan.jpg").exists());
+        assert!(dir
+            .path()
+            .join("Documents/2021/12/2021-12-03_report.pdf")
+            .exists());
+    }
+
+    #[test]
+    fn test_check_reports_pending_moves() {
+        let dir = tempdir().unwrap();
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("notes.pdf")).unwrap();
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        let pending = Sorter::new(config.clone())
+            .with_reporter(Reporter::capture(move |line| {
+                sink.lock().unwrap().push(line.to_string())
+            }))
+            .check()
+            .unwrap();
+        assert_eq!(pending, 2);
+        // 何も表示しない
+        assert!(lines.lock().unwrap().is_empty());
+        // 調べるだけでファイルは移動しない
+        assert!(dir.path().join("photo.jpg").exists());
+
+        // 整理後は何も移動されない
+        Sorter::new(config.clone()).run().unwrap();
+        assert_eq!(Sorter::new(config).check().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_by_repo() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("projects").join("my-app");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git").join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+        File::create(repo.join("src").join("main.rs")).unwrap();
+        File::create(repo.join("logo.png")).unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            by_repo: true,
+            ..Default::default()
+        };
+        Sorter::new(config.clone()).run().unwrap();
+
+        let sorted = dir.path().join(REPOS_FOLDER).join("my-app");
+        assert!(sorted.join("main.rs").exists());
+        assert!(sorted.join("logo.png").exists());
+        // リポジトリ外のファイルは拡張子で分類し、.git の中身は動かさない
+        assert!(dir.path().join("Images").join("photo.jpg").exists());
+        assert!(repo.join(".git").join("HEAD").exists());
+
+        // 振り分け済みのリポジトリフォルダは再実行しても動かさない
+        assert_eq!(Sorter::new(config).check().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_keep_at_root() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("INDEX.html")).unwrap();
+        File::create(dir.path().join("about.html")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            keep_at_root: BTreeSet::from(["index.html".to_string()]),
+            keep_at_root_ignore_case: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert!(dir.path().join("INDEX.html").exists());
+        assert!(dir.path().join("Code").join("about.html").exists());
+        assert_eq!(stats.skipped_files, 1);
+        assert_eq!(stats.moved_files, 1);
+    }
+
+    #[test]
+    fn test_skip_hidden() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join(".env")).unwrap();
+        fs::create_dir(dir.path().join(".cache")).unwrap();
+        File::create(dir.path().join(".cache").join("data.json")).unwrap();
+        File::create(dir.path().join("notes.txt")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            skip_hidden: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert!(dir.path().join(".env").exists());
+        assert!(dir.path().join(".cache").join("data.json").exists());
+        assert_eq!(stats.skipped_files, 1);
+        assert_eq!(stats.moved_files, 1);
+    }
+
+    #[test]
+    fn test_dir_category_marker() {
+        let dir = tempdir().unwrap();
+        let scans = dir.path().join("scans");
+        fs::create_dir(&scans).unwrap();
+        fs::write(scans.join(DIR_CATEGORY_FILE_NAME), "documents\n").unwrap();
+        File::create(scans.join("page1.jpg")).unwrap();
+        File::create(scans.join("notes.txt")).unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            dir_category_files: true,
+            ..Default::default()
+        };
+        Sorter::new(config.clone()).run().unwrap();
+
+        let documents = dir.path().join("Documents");
+        assert!(documents.join("page1.jpg").exists());
+        assert!(documents.join("notes.txt").exists());
+        // マーカーのないディレクトリは通常どおり分類し、マーカー自体は移動しない
+        assert!(dir.path().join("Images").join("photo.jpg").exists());
+        assert!(scans.join(DIR_CATEGORY_FILE_NAME).exists());
+
+        // 未知のカテゴリ名はエラー
+        fs::write(scans.join(DIR_CATEGORY_FILE_NAME), "Scans").unwrap();
+        File::create(scans.join("page2.jpg")).unwrap();
+        assert!(Sorter::new(config).run().is_err());
+    }
+
+    #[test]
+    fn test_dir_category_marker_custom_category() {
+        let dir = tempdir().unwrap();
+        let fonts_dir = dir.path().join("downloaded-fonts");
+        fs::create_dir(&fonts_dir).unwrap();
+        fs::write(fonts_dir.join(DIR_CATEGORY_FILE_NAME), "Fonts\n").unwrap();
+        File::create(fonts_dir.join("sans.otf")).unwrap();
+
+        let fonts = Category::Custom("Fonts".to_string());
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            dir_category_files: true,
+            custom_categories: vec![fonts],
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        // 設定で定義したカテゴリもマーカーに書ける
+        assert!(dir.path().join("Fonts").join("sans.otf").exists());
+    }
+
+    #[test]
+    fn test_dest_template_layouts() {
+        let run = |template: &str| {
+            let dir = tempdir().unwrap();
+            let files: Vec<PathBuf> = ["IMG_20230715_0001.jpg", "LICENSE", "report.PDF"]
+                .iter()
+                .map(|name| dir.path().join(name))
+                .collect();
+            for file in &files {
+                File::create(file).unwrap();
+            }
+            let config = SorterConfig {
+                target_dir: dir.path().to_path_buf(),
+                dest_template: Some(template.parse().unwrap()),
+                date_from_name: true,
+                ..Default::default()
+            };
+            let plans = Sorter::new(config).create_plans(&files).unwrap();
+            plans
+                .iter()
+                .map(|p| p.dest_dir().strip_prefix(dir.path()).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        let today = Date::from_system_time(SystemTime::now());
+        let year = format!("{:04}", today.year);
+        assert_eq!(
+            run("{category}/{ext}"),
+            vec![
+                PathBuf::from("Images/jpg"),
+                PathBuf::from("Others").join(NO_EXTENSION_FOLDER),
+                PathBuf::from("Documents/pdf"),
+            ]
+        );
+        assert_eq!(
+            run("{category}/{year}/{month}"),
+            vec![
+                PathBuf::from("Images/2023/07"),
+                PathBuf::from("Others")
+                    .join(&year)
+                    .join(format!("{:02}", today.month)),
+                PathBuf::from("Documents")
+                    .join(&year)
+                    .join(format!("{:02}", today.month)),
+            ]
+        );
+        assert_eq!(
+            run("sorted/{year}"),
+            vec![
+                PathBuf::from("sorted/2023"),
+                PathBuf::from("sorted").join(&year),
+                PathBuf::from("sorted").join(&year),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_batch_folders() {
+        let dir = tempdir().unwrap();
+        for i in 0..25 {
+            File::create(
+                dir.path()
+                    .join(format!("file{:02}.{}", i, ["jpg", "pdf"][i % 2])),
+            )
+            .unwrap();
+        }
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            batch_size: Some(10),
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        let count = |name: &str| fs::read_dir(dir.path().join(name)).unwrap().count();
+        assert_eq!(count("Batch_001"), 10);
+        assert_eq!(count("Batch_002"), 10);
+        assert_eq!(count("Batch_003"), 5);
+        assert!(!dir.path().join("Batch_004").exists());
+        assert!(!dir.path().join("Images").exists());
+        // パス順に区切られる
+        assert!(dir.path().join("Batch_001").join("file09.pdf").exists());
+        assert!(dir.path().join("Batch_003").join("file24.jpg").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_skip_marked_files() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            mark_sorted: true,
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        let sorted = dir.path().join("Images").join("photo.jpg");
+        if !is_marked_sorted(&sorted) {
+            // 一時ディレクトリのファイルシステムが拡張属性に対応していない
+            return;
+        }
+
+        // 手で戻されたファイルは印が残っているのでスキップされる
+        fs::rename(&sorted, dir.path().join("photo.jpg")).unwrap();
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            skip_marked: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.skipped_files, 1);
+        // 整理済みの集計（already_sorted）とは別に数える
+        assert_eq!(stats.already_sorted, 0);
+        assert_eq!(stats.moved_files, 0);
+        assert!(dir.path().join("photo.jpg").exists());
+        assert_eq!(SkipReason::Marked.to_string(), "marked as sorted");
+    }
+
+    #[test]
+    fn test_sync_into_archive() {
+        let inbox = tempdir().unwrap();
+        let archive = tempdir().unwrap();
+        fs::create_dir_all(archive.path().join("Documents")).unwrap();
+        fs::create_dir_all(archive.path().join("Music")).unwrap();
+        fs::write(archive.path().join("Documents").join("old.pdf"), "v1").unwrap();
+        fs::write(archive.path().join("Music").join("song.mp3"), "song").unwrap();
+        fs::write(inbox.path().join("photo.jpg"), "photo").unwrap();
+        fs::write(inbox.path().join("notes.txt"), "notes").unwrap();
+        fs::write(inbox.path().join("old.pdf"), "v2").unwrap();
+
+        let config = SorterConfig {
+            target_dir: inbox.path().to_path_buf(),
+            sync_into: Some(archive.path().to_path_buf()),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert!(archive.path().join("Images").join("photo.jpg").exists());
+        assert!(archive.path().join("Documents").join("notes.txt").exists());
+        assert!(archive.path().join("Documents").join("old_1.pdf").exists());
+        assert!(!inbox.path().join("Images").exists());
+        // アーカイブにしかないファイルは変更しない
+        assert!(archive.path().join("Music").join("song.mp3").exists());
+
+        let report = stats.sync.unwrap();
+        assert_eq!(report.new_files, 2);
+        assert_eq!(report.existing_files, 1);
+        assert_eq!(report.sorted_files, 3);
+        assert_eq!(
+            report.archive_only,
+            vec![PathBuf::from("Music").join("song.mp3")]
+        );
+    }
+
+    #[test]
+    fn test_empty_files_to_folder() {
+        let dir = tempdir().unwrap();
+        for name in ["photo.jpg", "notes.txt", "main.rs", "archive.zip", "README"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+        fs::write(dir.path().join("report.pdf"), "content").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            empty_files: "folder:Empty".parse().unwrap(),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        let empty = dir.path().join("Empty");
+        for name in ["photo.jpg", "notes.txt", "main.rs", "archive.zip", "README"] {
+            assert!(empty.join(name).exists(), "{} should be in Empty", name);
+        }
+        assert!(dir.path().join("Documents").join("report.pdf").exists());
+        assert!(!dir.path().join("Images").exists());
+        assert_eq!(stats.moved_files, 6);
+        // 空のファイルは振り分け先のフォルダのカテゴリとして数える
+        assert_eq!(
+            stats
+                .category_counts
+                .get(&Category::custom("Empty").unwrap()),
+            Some(&5)
+        );
+        assert_eq!(stats.category_counts.get(&Category::Others), None);
+
+        // skip では空のファイルを移動しない
+        File::create(dir.path().join("blank.png")).unwrap();
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            empty_files: EmptyFiles::Skip,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+        assert!(dir.path().join("blank.png").exists());
+        assert_eq!(stats.skipped_files, 1);
+    }
+
+    #[test]
+    fn test_size_filters() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("tiny.txt"), "a").unwrap();
+        fs::write(dir.path().join("medium.txt"), "a".repeat(100)).unwrap();
+        fs::write(dir.path().join("huge.txt"), "a".repeat(1000)).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            min_size: Some(10),
+            max_size: Some(500),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.moved_files, 1);
+        assert_eq!(stats.skipped_files, 2);
+        assert!(dir.path().join("Documents").join("medium.txt").exists());
+        assert!(dir.path().join("tiny.txt").exists());
+        assert!(dir.path().join("huge.txt").exists());
+    }
+
+    #[test]
+    fn test_exclude_patterns() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("build")).unwrap();
+        fs::write(dir.path().join("movie.mp4.part"), "a").unwrap();
+        fs::write(dir.path().join("desktop.ini"), "a").unwrap();
+        fs::write(dir.path().join("photo.jpg"), "a").unwrap();
+        fs::write(dir.path().join("build").join("out.txt"), "a").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
+            exclude: vec!["*.part".to_string(), "desktop.ini".to_string()],
+            ..Default::default()
+        };
+        let stats = Sorter::new(config.clone()).run().unwrap();
+
+        assert_eq!(stats.excluded_files, 2);
+        assert_eq!(stats.skipped_files, 0);
+        assert!(dir.path().join("movie.mp4.part").exists());
+        assert!(dir.path().join("desktop.ini").exists());
+        assert!(dir.path().join("Images").join("photo.jpg").exists());
+        assert!(dir.path().join("Documents").join("out.txt").exists());
+
+        // 相対パスに照合する
+        fs::write(dir.path().join("build").join("log.txt"), "a").unwrap();
+        let sorter = Sorter::new(SorterConfig {
+            exclude: vec!["build/*".to_string()],
+            match_path: true,
+            ..config
+        });
+        assert_eq!(sorter.check().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_include_patterns() {
+        let dir = tempdir().unwrap();
+        for name in ["a.jpg", "b.png", "skip.jpg", "notes.txt"] {
+            fs::write(dir.path().join(name), "a").unwrap();
+        }
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            include: vec!["*.jpg".to_string(), "*.png".to_string()],
+            exclude: vec!["skip.*".to_string()],
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.moved_files, 2);
+        // 除外が優先され、対象パターンに一致しないファイルはスキップとして数える
+        assert_eq!(stats.excluded_files, 1);
+        assert_eq!(stats.skipped_files, 1);
+        assert!(dir.path().join("skip.jpg").exists());
+        assert!(dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_output_dir() {
+        let dir = tempdir().unwrap();
+        let inbox = dir.path().join("inbox");
+        let output = inbox.join("sorted");
+        fs::create_dir_all(inbox.join("Images")).unwrap();
+        fs::write(inbox.join("report.pdf"), "a").unwrap();
+        fs::write(inbox.join("Images").join("photo.jpg"), "a").unwrap();
+
+        let config = SorterConfig {
+            target_dir: inbox.clone(),
+            recursive: true,
+            output_dir: Some(output.clone()),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config.clone()).run().unwrap();
+
+        // 出力先が別なら、対象ディレクトリ内のカテゴリ名のフォルダも整理する
+        assert_eq!(stats.moved_files, 2);
+        assert!(output.join("Documents").join("report.pdf").exists());
+        assert!(output.join("Images").join("photo.jpg").exists());
+        // 対象ディレクトリ内の出力先は取り込まない
+        assert_eq!(Sorter::new(config).check().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_parse_empty_files() {
+        assert_eq!("sort".parse::<EmptyFiles>().unwrap(), EmptyFiles::Sort);
+        assert_eq!("others".parse::<EmptyFiles>().unwrap(), EmptyFiles::Others);
+        assert!("folder:".parse::<EmptyFiles>().is_err());
+        assert!("delete".parse::<EmptyFiles>().is_err());
+    }
+
+    #[test]
+    fn test_fix_extensions() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jgp")).unwrap();
+        File::create(dir.path().join("clip.mp5")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            fix_extensions: true,
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        assert!(dir.path().join("Images").join("photo.jpg").exists());
+        // mp3 / mp4 のどちらか曖昧なため訂正しない
+        assert!(dir.path().join("Others").join("clip.mp5").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_system_mime_lookup() {
+        let dir = tempdir().unwrap();
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            system_mime: Some(MimeDatabase::parse_globs2(
+                "50:image/png:*.png\n50:image/x-krita:*.kra\n50:application/x-thing:*.rs\n",
+            )),
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+
+        assert_eq!(sorter.categorize_file(Path::new("a.png")), Category::Images);
+        // 組み込みマッピングにない拡張子もMIMEタイプで分類
+        assert_eq!(
+            sorter.categorize_file(Path::new("art.kra")),
+            Category::Images
+        );
+        // MIMEタイプから判定できない場合は組み込みマッピング
+        assert_eq!(sorter.categorize_file(Path::new("main.rs")), Category::Code);
+    }
+
+    #[test]
+    fn test_group_by_prefix() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("ProjectX_report.pdf")).unwrap();
+        File::create(dir.path().join("plain.pdf")).unwrap();
+        File::create(dir.path().join("_hidden.txt")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            group_by_prefix: Some("_".to_string()),
+            ..Default::default()
+        };
+        Sorter::new(config).run().unwrap();
+
+        assert!(dir.path().join("ProjectX").join("report.pdf").exists());
+        // 区切りを含まない、または接頭辞が空のファイルは通常どおり分類
+        assert!(dir.path().join("Documents").join("plain.pdf").exists());
+        assert!(dir.path().join("Documents").join("_hidden.txt").exists());
+    }
+
+    #[test]
+    fn test_sort_arrived() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), "photo").unwrap();
+        fs::write(dir.path().join(".hidden.pdf"), "hidden").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            skip_hidden: true,
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+        let stats = sorter
+            .sort_arrived(vec![
+                dir.path().join("photo.jpg"),
+                dir.path().join(".hidden.pdf"),
+            ])
+            .unwrap();
+
+        assert_eq!(stats.moved_files, 1);
+        assert_eq!(stats.skipped_files, 1);
+        assert!(dir.path().join("Images").join("photo.jpg").exists());
+        assert!(dir.path().join(".hidden.pdf").exists());
+        assert_eq!(stats.category_line(&HashMap::new()), "Images: 1");
+        let folder_names = HashMap::from([(Category::Images, "Photos".to_string())]);
+        assert_eq!(stats.category_line(&folder_names), "Photos: 1");
+    }
+
+    #[test]
+    fn test_unopenable_log_file_fails_before_moving() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            log_file: Some(dir.path().join("missing").join("ops.jsonl")),
+            ..Default::default()
+        };
+        let err = Sorter::new(config).run().unwrap_err();
+
+        assert!(format!("{:#}", err).contains("Failed to open log file"));
+        assert!(dir.path().join("photo.jpg").exists());
+        assert!(!dir.path().join("Images").exists());
+    }
+
+    #[test]
+    fn test_reporter_captures_output() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let captured = Arc::clone(&lines);
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let reporter =
+            Reporter::capture(move |line| captured.lock().unwrap().push(line.to_string()));
+        Sorter::new(config).with_reporter(reporter).run().unwrap();
+
+        let lines = lines.lock().unwrap();
+        assert!(lines.iter().any(|line| line.contains("photo.jpg")));
+        assert!(lines.iter().any(|line| line.contains("Summary")));
+    }
+
+    #[test]
+    fn test_move_hooks() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("secret.pdf")).unwrap();
+
+        let moved = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&moved);
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config)
+            .with_before_move(|plan| plan.category != Category::Documents)
+            .with_after_move(move |plan, result| {
+                assert!(result.is_ok());
+                recorded.lock().unwrap().push(plan.source.clone());
+            })
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.moved_files, 1);
+        assert_eq!(stats.skipped_files, 1);
+        assert!(dir.path().join("secret.pdf").exists());
+        assert_eq!(*moved.lock().unwrap(), vec![dir.path().join("photo.jpg")]);
+    }
+
+    #[test]
+    fn test_atomic_rolls_back_on_error() {
+        let dir = tempdir().unwrap();
+        for name in ["a.jpg", "b.png", "notes.txt", "vanished.pdf"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            atomic: true,
+            ..Default::default()
+        };
+        // 移動の直前に消えたファイルで移動を失敗させる
+        let err = Sorter::new(config)
+            .with_before_move(|plan| {
+                if plan.source.ends_with("vanished.pdf") {
+                    fs::remove_file(&plan.source).unwrap();
+                }
+                true
+            })
+            .run()
+            .unwrap_err();
+
+        assert!(err.to_string().contains("rolled back"), "{}", err);
+        for name in ["a.jpg", "b.png", "notes.txt"] {
+            assert!(
+                dir.path().join(name).exists(),
+                "{} should be restored",
+                name
+            );
+        }
+        assert!(!dir.path().join("Images").exists());
+        assert!(!dir.path().join("Documents").exists());
+        assert!(!Journal::path_in(dir.path()).exists());
+    }
+
+    #[test]
+    fn test_atomic_rejects_overwrite() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("Images")).unwrap();
+        fs::write(dir.path().join("Images/a.jpg"), "old").unwrap();
+        fs::write(dir.path().join("a.jpg"), "new").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            atomic: true,
+            on_conflict: ConflictStrategy::Overwrite,
+            ..Default::default()
+        };
+        let err = Sorter::new(config).run().unwrap_err();
+
+        assert!(err.to_string().contains("--atomic"), "{}", err);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("Images/a.jpg")).unwrap(),
+            "old"
+        );
+        assert_eq!(fs::read_to_string(dir.path().join("a.jpg")).unwrap(), "new");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_locked_file_is_skipped() {
+        use fs2::FileExt;
+
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        let holder = File::create(dir.path().join("report.pdf")).unwrap();
+        holder.lock_exclusive().unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
+
+        assert_eq!(stats.moved_files, 1);
+        assert_eq!(stats.locked_files, 1);
+        assert_eq!(stats.error_count, 0);
+        assert!(dir.path().join("report.pdf").exists());
+    }
+
+    #[test]
+    fn test_in_use_error_during_move_is_skipped() {
+        let dir = tempdir().unwrap();
+        let sorter = Sorter::new(SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        })
+        .with_reporter(Reporter::silent());
+        let plan = FilePlan {
+            source: dir.path().join("report.pdf"),
+            destination: dir.path().join("Documents/report.pdf"),
+            category: Category::Documents,
+        };
+        // 確認の後で使用中になった場合の共有違反（Unixでは ETXTBSY）
+        let code = if cfg!(windows) { 32 } else { 26 };
+        let err = anyhow::Error::from(std::io::Error::from_raw_os_error(code))
+            .context("Failed to move file");
+
+        let mut journal = Journal::default();
+        let state = Mutex::new(MoveState {
+            stats: SortStats::default(),
+            journal: &mut journal,
+            records: Vec::new(),
+            completed: Vec::new(),
+            written: HashSet::new(),
+            mark_warned: false,
+        });
+        sorter.record_transfer(&plan, Err(err), None, &state);
+
+        let stats = state.into_inner().unwrap().stats;
+        assert_eq!(stats.locked_files, 1);
+        assert_eq!(stats.error_count, 0);
+    }
+
+    #[test]
+    fn test_plan_does_not_move() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("notes.pdf")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let mut plans = Sorter::new(config).plan().unwrap();
+        plans.sort_by(|a, b| a.source.cmp(&b.source));
+
+        assert_eq!(plans.len(), 2);
+        assert_eq!(plans[0].category, Category::Documents);
+        assert_eq!(
+            plans[0].destination,
+            dir.path().join("Documents").join("notes.pdf")
+        );
+        assert_eq!(plans[1].category, Category::Images);
+        assert!(dir.path().join("photo.jpg").exists());
+        assert!(!dir.path().join("Images").exists());
+    }
+
+    #[test]
+    fn test_stats_only() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.jpg"), "12345").unwrap();
+        fs::write(dir.path().join("b.png"), "123").unwrap();
+        fs::write(dir.path().join("notes.pdf"), "1").unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dry_run: true,
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).stats_only().unwrap();
+
+        assert_eq!(stats.total_files, 3);
+        assert_eq!(stats.category_counts[&Category::Images], 2);
+        assert_eq!(stats.category_bytes[&Category::Images], 8);
+        assert_eq!(stats.category_counts[&Category::Documents], 1);
+        // ファイルもカテゴリフォルダも作られない
+        assert!(dir.path().join("a.jpg").exists());
+        assert!(!dir.path().join("Images").exists());
+    }
+
+    #[test]
+    fn test_report_extensions() {
+        let dir = tempdir().unwrap();
+        for name in ["a.xyz", "b.xyz", "c.qqq", "d.pdf", "README"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let unrecognized = Sorter::new(config).report_extensions().unwrap();
+
+        assert_eq!(
+            unrecognized,
+            vec![
+                UnrecognizedExtension {
+                    extension: "xyz".to_string(),
+                    files: 2,
+                },
+                UnrecognizedExtension {
+                    extension: "qqq".to_string(),
+                    files: 1,
+                },
+            ]
+        );
+        assert!(dir.path().join("a.xyz").exists());
+    }
+
+    #[test]
+    fn test_report_fragmentation() {
+        let dir = tempdir().unwrap();
+
+        for sub in ["a", "b"] {
+            fs::create_dir_all(dir.path().join(sub)).unwrap();
+        }
+        File::create(dir.path().join("one.pdf")).unwrap();
+        File::create(dir.path().join("a").join("two.pdf")).unwrap();
+        File::create(dir.path().join("a").join("three.pdf")).unwrap();
+        File::create(dir.path().join("b").join("four.pdf")).unwrap();
+        File::create(dir.path().join("b").join("song.mp3")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let fragmentation = Sorter::new(config).report_fragmentation().unwrap();
+
+        assert_eq!(
+            fragmentation,
+            vec![
+                ExtensionFragmentation {
+                    extension: "pdf".to_string(),
+                    directories: 3,
+                    files: 4,
+                },
+                ExtensionFragmentation {
+                    extension: "mp3".to_string(),
+                    directories: 1,
+                    files: 1,
+                },
+            ]
+        );
+        // 何も移動されない
+        assert!(dir.path().join("one.pdf").exists());
+    }
+
+    #[test]
+    fn test_dry_run_assume_existing() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("report.pdf")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dry_run: true,
+            assume_existing: BTreeSet::from(["report.pdf".to_string()]),
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+        let files = sorter.collect_files(dir.path()).unwrap();
+        let plans = sorter.create_plans(&files).unwrap();
+        let stats = sorter.execute_dry_run(&plans).unwrap();
+
+        // 実際には存在しないが、存在するとみなして report_1.pdf にリネームされる
+        assert_eq!(stats.renamed_files, 1);
+        assert!(!dir.path().join("Documents").exists());
+    }
+
+    #[test]
+    fn test_dry_run_reserves_planned_destinations() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        File::create(dir.path().join("notes.txt")).unwrap();
+        File::create(dir.path().join("sub").join("notes.txt")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            dry_run: true,
+            recursive: true,
+            ..Default::default()
+        };
+        let sorter = Sorter::new(config);
+        let files = sorter.collect_files(dir.path()).unwrap();
+        let plans = sorter.create_plans(&files).unwrap();
+
+        // 同じ名前の2ファイルは、2つ目がリネームされる予定として表示される
+        assert_eq!(sorter.execute_dry_run(&plans).unwrap().renamed_files, 1);
     }
 
-    /// 分類計画を作成
-    fn create_plans(&self, files: &[PathBuf]) -> Result<Vec<FilePlan>> {
-        let mut plans = Vec::new();
+    #[test]
+    fn test_handle_downloads() {
+        let dir = tempdir().unwrap();
 
-        for file in files {
-            let category = self.categorize_file(file);
-            let dest_dir = self.config.target_dir.join(category.folder_name());
-            let filename = file
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown");
-            let destination = dest_dir.join(filename);
-            let has_conflict = destination.exists();
+        File::create(dir.path().join("ubuntu.iso.torrent")).unwrap();
+        File::create(dir.path().join("movie.mp4.crdownload")).unwrap();
+        File::create(dir.path().join("song.mp3")).unwrap();
 
-            plans.push(FilePlan {
-                source: file.clone(),
-                destination,
-                category,
-                has_conflict,
-            });
-        }
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            handle_downloads: true,
+            fragment_extensions: BTreeSet::from(["crdownload".to_string()]),
+            ..Default::default()
+        };
+        let stats = Sorter::new(config).run().unwrap();
 
-        Ok(plans)
+        assert!(dir
+            .path()
+            .join("Torrents")
+            .join("ubuntu.iso.torrent")
+            .exists());
+        // ダウンロード途中のファイルはその場に残る
+        assert!(dir.path().join("movie.mp4.crdownload").exists());
+        assert_eq!(stats.total_files, 3);
+        assert_eq!(stats.moved_files, 2);
+        assert_eq!(stats.skipped_files, 1);
     }
 
-    /// ファイルをカテゴリ分類
-    fn categorize_file(&self, path: &Path) -> Category {
-        match get_extension(path) {
-            Some(ext) => get_category(&ext),
-            None => get_default_category(),
-        }
+    #[test]
+    fn test_torrent_without_handle_downloads() {
+        let config = SorterConfig::default();
+        let sorter = Sorter::new(config);
+        assert_eq!(
+            sorter.categorize_file(Path::new("file.torrent")),
+            Category::Others
+        );
     }
 
-    /// Dry Run実行
-    fn execute_dry_run(&self, plans: &[FilePlan]) -> Result<SortStats> {
-        let mut stats = SortStats {
-            total_files: plans.len(),
+    #[test]
+    fn test_disabled_category_folders_are_scanned() {
+        let dir = tempdir().unwrap();
+        for folder in ["Programs", "Torrents"] {
+            fs::create_dir(dir.path().join(folder)).unwrap();
+            File::create(dir.path().join(folder).join("notes.txt")).unwrap();
+        }
+
+        // Programs・Torrents を使わない実行では、同名のフォルダもふつうのフォルダとして扱う
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            recursive: true,
             ..Default::default()
         };
+        let files = Sorter::new(config.clone())
+            .collect_files(dir.path())
+            .unwrap();
+        assert_eq!(files.len(), 2);
 
-        for plan in plans {
-            // カテゴリカウントを更新
-            *stats.category_counts.entry(plan.category).or_insert(0) += 1;
+        let sorter = Sorter::new(SorterConfig {
+            handle_downloads: true,
+            ..config
+        });
+        let files = sorter.collect_files(dir.path()).unwrap();
+        assert_eq!(files, vec![dir.path().join("Programs").join("notes.txt")]);
+    }
 
-            // 相対パスを計算（表示用）
-            let relative_source = plan
-                .source
-                .strip_prefix(&self.config.target_dir)
-                .unwrap_or(&plan.source);
+    #[test]
+    fn test_safe_preset_skips_fresh_files_and_writes_journal() {
+        let dir = tempdir().unwrap();
 
-            let dest_dir = self.config.target_dir.join(plan.category.folder_name());
+        let old = dir.path().join("old.txt");
+        File::create(&old).unwrap();
+        let an_hour_ago =
+            filetime::FileTime::from_unix_time(filetime::FileTime::now().unix_seconds() - 3600, 0);
+        filetime::set_file_mtime(&old, an_hour_ago).unwrap();
+        File::create(dir.path().join("fresh.txt")).unwrap();
 
-            // 重複がある場合の移動先ファイル名を計算
-            let filename = plan
-                .source
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown");
-            let final_dest = if plan.has_conflict {
-                generate_unique_path(&dest_dir, filename)
-            } else {
-                dest_dir.join(filename)
-            };
+        let mut config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        config.apply_safe_preset().unwrap();
+        let stats = Sorter::new(config)
+            .run_with_input(&mut "y\n".as_bytes())
+            .unwrap();
 
-            let relative_dest = final_dest
-                .strip_prefix(&self.config.target_dir)
-                .unwrap_or(&final_dest);
+        // 更新から間もないファイルはその場に残る
+        assert!(dir.path().join("fresh.txt").exists());
+        assert!(dir.path().join("Documents").join("old.txt").exists());
+        assert_eq!(stats.moved_files, 1);
+        assert_eq!(stats.skipped_files, 1);
 
-            // 表示
-            let arrow = "→".cyan();
-            let category_colored = format!("[{}]", plan.category).blue();
+        let journal: Journal =
+            serde_json::from_str(&fs::read_to_string(Journal::path_in(dir.path())).unwrap())
+                .unwrap();
+        assert_eq!(journal.entries.len(), 1);
+        assert_eq!(journal.entries[0].source, old);
+        assert_eq!(
+            journal.entries[0].destination,
+            dir.path().join("Documents").join("old.txt")
+        );
+    }
 
-            if plan.has_conflict {
-                println!(
-                    "  {} {} {} {} {}",
-                    "[DRY RUN]".cyan(),
-                    relative_source.display(),
-                    arrow,
-                    relative_dest.display(),
-                    "(renamed)".yellow()
-                );
-                stats.renamed_files += 1;
-            } else {
-                println!(
-                    "  {} {} {} {} {}",
-                    "[DRY RUN]".cyan(),
-                    relative_source.display(),
-                    arrow,
-                    relative_dest.display(),
-                    category_colored
-                );
-            }
+    #[test]
+    fn test_safe_preset_rejects_replacing_strategies() {
+        let dir = tempdir().unwrap();
+        for on_conflict in [ConflictStrategy::Overwrite, ConflictStrategy::KeepNewer] {
+            let mut config = SorterConfig {
+                target_dir: dir.path().to_path_buf(),
+                on_conflict,
+                ..Default::default()
+            };
+            let err = config.apply_safe_preset().unwrap_err();
+            assert!(err.to_string().contains("--safe"), "{}", err);
+        }
 
-            stats.moved_files += 1;
+        let mut config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            on_conflict: ConflictStrategy::Skip,
+            ..Default::default()
+        };
+        config.apply_safe_preset().unwrap();
+        assert!(config.confirm);
+    }
+    #[test]
+    fn test_confirm_move() {
+        for (answer, moved) in [("y\n", true), ("n\n", false), ("", false)] {
+            let dir = tempdir().unwrap();
+            File::create(dir.path().join("photo.jpg")).unwrap();
+
+            let config = SorterConfig {
+                target_dir: dir.path().to_path_buf(),
+                confirm_move: true,
+                ..Default::default()
+            };
+            let stats = Sorter::new(config)
+                .run_with_input(&mut answer.as_bytes())
+                .unwrap();
+
+            assert_eq!(stats.moved_files, usize::from(moved), "answer {:?}", answer);
+            assert_eq!(dir.path().join("Images").join("photo.jpg").exists(), moved);
         }
+    }
 
-        Ok(stats)
+    #[test]
+    fn test_confirm_per_category() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("report.pdf")).unwrap();
+        File::create(dir.path().join("notes.txt")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            confirm_per_category: true,
+            ..Default::default()
+        };
+        // Category::all() の順に Images → Documents と尋ねられる
+        let stats = Sorter::new(config)
+            .run_with_input(&mut "n\ny\n".as_bytes())
+            .unwrap();
+
+        assert!(dir.path().join("photo.jpg").exists());
+        assert!(!dir.path().join("Images").exists());
+        assert!(dir.path().join("Documents").join("report.pdf").exists());
+        assert!(dir.path().join("Documents").join("notes.txt").exists());
+        assert_eq!(stats.moved_files, 2);
+        assert_eq!(stats.skipped_files, 1);
+        assert_eq!(stats.total_files, 3);
     }
 
-    /// 実際のファイル移動を実行
-    fn execute_move(&self, plans: &[FilePlan]) -> Result<SortStats> {
-        let mut stats = SortStats {
-            total_files: plans.len(),
+    #[test]
+    fn test_confirm_declined_moves_nothing() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("doc.pdf")).unwrap();
+
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            confirm: true,
             ..Default::default()
         };
+        let stats = Sorter::new(config)
+            .run_with_input(&mut "n\n".as_bytes())
+            .unwrap();
 
-        // カテゴリフォルダを事前に作成
-        for category in Category::all() {
-            let dir = self.config.target_dir.join(category.folder_name());
-            // 必要に応じて作成（ファイルがある場合のみ）
-            if plans.iter().any(|p| p.category == *category) {
-                ensure_directory(&dir)?;
-            }
-        }
+        assert!(dir.path().join("doc.pdf").exists());
+        assert_eq!(stats.moved_files, 0);
+    }
 
-        for plan in plans {
-            let dest_dir = self.config.target_dir.join(plan.category.folder_name());
+    #[test]
+    fn test_preflight_confirmation() {
+        let dir = tempdir().unwrap();
+        let output = tempdir().unwrap();
+        fs::write(dir.path().join("a.jpg"), "12345").unwrap();
+        fs::write(dir.path().join("b.jpg"), "123").unwrap();
+        fs::write(dir.path().join("notes.txt"), "12").unwrap();
 
-            match move_file_with_dedup(&plan.source, &dest_dir) {
-                Ok(result) => {
-                    // カテゴリカウントを更新
-                    *stats.category_counts.entry(plan.category).or_insert(0) += 1;
-
-                    // 相対パスを計算（表示用）
-                    let relative_source = plan
-                        .source
-                        .strip_prefix(&self.config.target_dir)
-                        .unwrap_or(&plan.source);
-                    let relative_dest = result
-                        .destination
-                        .strip_prefix(&self.config.target_dir)
-                        .unwrap_or(&result.destination);
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            output_dir: Some(output.path().to_path_buf()),
+            preflight: true,
+            ..Default::default()
+        };
 
-                    let arrow = "→".green();
+        // 断ると何も移動しない、--yes なら確認せずに移動する
+        let stats = Sorter::new(config.clone())
+            .with_reporter(Reporter::capture(move |line| {
+                sink.lock().unwrap().push(line.to_string())
+            }))
+            .run_with_input(&mut "n\n".as_bytes())
+            .unwrap();
+        assert_eq!(stats.moved_files, 0);
+        assert!(dir.path().join("a.jpg").exists());
+        // 作成するフォルダは振り分け先（--output）からの相対パスで示す
+        let lines = lines.lock().unwrap();
+        assert!(lines.iter().any(|line| line == "  Images"), "{:?}", lines);
 
-                    if result.was_renamed {
-                        println!(
-                            "  {} {} {} {}",
-                            "✓".green(),
-                            relative_source.display(),
-                            arrow,
-                            format!("{} (renamed)", relative_dest.display()).yellow()
-                        );
-                        stats.renamed_files += 1;
-                    } else {
-                        println!(
-                            "  {} {} {} {}",
-                            "✓".green(),
-                            relative_source.display(),
-                            arrow,
-                            relative_dest.display()
-                        );
-                    }
+        let config = SorterConfig {
+            assume_yes: true,
+            ..config
+        };
+        let stats = Sorter::new(config)
+            .run_with_input(&mut "".as_bytes())
+            .unwrap();
+        assert_eq!(stats.moved_files, 3);
+    }
 
-                    stats.moved_files += 1;
-                }
-                Err(e) => {
-                    warn!("Failed to move file: {}", e);
-                    println!(
-                        "  {} {} - {}",
-                        "✗".red(),
-                        plan.source.display(),
-                        e.to_string().red()
-                    );
-                    stats.error_count += 1;
-                }
-            }
-        }
+    #[test]
+    fn test_json_summary_goes_through_reporter() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
 
-        Ok(stats)
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        let config = SorterConfig {
+            target_dir: dir.path().to_path_buf(),
+            output_format: OutputFormat::Json,
+            ..Default::default()
+        };
+        Sorter::new(config)
+            .with_reporter(Reporter::capture(move |line| {
+                sink.lock().unwrap().push(line.to_string())
+            }))
+            .run()
+            .unwrap();
+
+        let lines = lines.lock().unwrap();
+        let summary: serde_json::Value = lines
+            .iter()
+            .find_map(|line| serde_json::from_str(line).ok())
+            .unwrap();
+        assert_eq!(summary["moved_files"], 1);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs::File;
-    use tempfile::tempdir;
+    #[test]
+    fn test_stats_to_json() {
+        let mut stats = SortStats {
+            total_files: 3,
+            moved_files: 2,
+            skipped_files: 1,
+            ..Default::default()
+        };
+        stats.category_counts.insert(Category::Images, 2);
+        stats
+            .category_counts
+            .insert(Category::Custom("Fonts".to_string()), 1);
+        stats.category_bytes.insert(Category::Images, 2048);
+
+        let to_json = |folder_names: &HashMap<Category, String>| -> serde_json::Value {
+            serde_json::from_str(&stats.to_json(folder_names).unwrap()).unwrap()
+        };
+        let json = to_json(&HashMap::new());
+        assert_eq!(json["total_files"], 3);
+        assert_eq!(json["moved_files"], 2);
+        assert_eq!(json["skipped_files"], 1);
+        // カテゴリはフォルダ名をキーにする
+        assert_eq!(json["category_counts"]["Images"], 2);
+        assert_eq!(json["category_counts"]["Fonts"], 1);
+
+        // フォルダ名を変えたカテゴリは変えた名前をキーにする
+        let json = to_json(&HashMap::from([(Category::Images, "画像".to_string())]));
+        assert_eq!(json["category_counts"]["画像"], 2);
+        assert_eq!(json["category_bytes"]["画像"], 2048);
+        assert!(json["category_counts"].get("Images").is_none());
+    }
 
     #[test]
-    fn test_categorize_file() {
+    fn test_print_folders_lists_receiving_category_folders() {
         let dir = tempdir().unwrap();
+        File::create(dir.path().join("a.pdf")).unwrap();
+        File::create(dir.path().join("b.txt")).unwrap();
+        File::create(dir.path().join("song.mp3")).unwrap();
+
         let config = SorterConfig {
             target_dir: dir.path().to_path_buf(),
-            dry_run: true,
-            recursive: false,
+            print_folders: true,
+            ..Default::default()
         };
-        let sorter = Sorter::new(config);
+        let stats = Sorter::new(config).run().unwrap();
 
+        let root = fs::canonicalize(dir.path()).unwrap();
         assert_eq!(
-            sorter.categorize_file(Path::new("test.jpg")),
-            Category::Images
-        );
-        assert_eq!(
-            sorter.categorize_file(Path::new("test.mp4")),
-            Category::Videos
-        );
-        assert_eq!(
-            sorter.categorize_file(Path::new("test.pdf")),
-            Category::Documents
-        );
-        assert_eq!(
-            sorter.categorize_file(Path::new("test.mp3")),
-            Category::Music
-        );
-        assert_eq!(
-            sorter.categorize_file(Path::new("test.zip")),
-            Category::Archives
+            stats.folder_lines(true),
+            vec![
+                format!("{}\t2", root.join("Documents").display()),
+                format!("{}\t1", root.join("Music").display()),
+            ]
         );
-        assert_eq!(sorter.categorize_file(Path::new("test.rs")), Category::Code);
         assert_eq!(
-            sorter.categorize_file(Path::new("test.xyz")),
-            Category::Others
+            stats.folder_lines(false)[0],
+            root.join("Documents").display().to_string()
         );
     }
 
     #[test]
-    fn test_collect_files_non_recursive() {
+    fn test_dedupe_first_removes_duplicates_then_sorts() {
         let dir = tempdir().unwrap();
-
-        // ルートにファイルを作成
-        File::create(dir.path().join("file1.txt")).unwrap();
-        File::create(dir.path().join("file2.jpg")).unwrap();
-
-        // サブディレクトリを作成
-        fs::create_dir(dir.path().join("subdir")).unwrap();
-        File::create(dir.path().join("subdir").join("file3.txt")).unwrap();
+        fs::write(dir.path().join("a.txt"), "same").unwrap();
+        fs::write(dir.path().join("b.txt"), "same").unwrap();
+        fs::write(dir.path().join("c.md"), "same").unwrap();
+        fs::write(dir.path().join("unique.txt"), "unique").unwrap();
 
         let config = SorterConfig {
             target_dir: dir.path().to_path_buf(),
-            dry_run: true,
-            recursive: false,
+            dedupe_first: true,
+            // テストではゴミ箱を使わずに削除する
+            dedupe_delete: true,
+            ..Default::default()
         };
-        let sorter = Sorter::new(config);
+        let stats = Sorter::new(config).run().unwrap();
 
-        let files = sorter.collect_files(dir.path()).unwrap();
-        assert_eq!(files.len(), 2); // サブディレクトリ内は含まれない
+        let documents = dir.path().join("Documents");
+        // パス順で最初のファイルだけが残り、整理される
+        assert!(documents.join("a.txt").exists());
+        assert!(documents.join("unique.txt").exists());
+        assert!(!dir.path().join("b.txt").exists());
+        assert!(!documents.join("b.txt").exists());
+        assert!(!dir.path().join("c.md").exists());
+        assert_eq!(stats.removed_duplicates, 2);
+        assert_eq!(stats.moved_files, 2);
+        assert_eq!(stats.total_files, 4);
     }
 
     #[test]
-    fn test_collect_files_recursive() {
+    fn test_local_configs_use_global_custom_categories() {
         let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+        File::create(project.join("sans.ttf")).unwrap();
+        fs::write(
+            project.join(LOCAL_CONFIG_FILE_NAME),
+            "[extensions]\nttf = \"Fonts\"\n",
+        )
+        .unwrap();
 
-        // ルートにファイルを作成
-        File::create(dir.path().join("file1.txt")).unwrap();
-
-        // サブディレクトリを作成
-        fs::create_dir(dir.path().join("subdir")).unwrap();
-        File::create(dir.path().join("subdir").join("file2.txt")).unwrap();
-
+        let fonts = Category::Custom("Fonts".to_string());
         let config = SorterConfig {
             target_dir: dir.path().to_path_buf(),
-            dry_run: true,
             recursive: true,
+            local_configs: true,
+            custom_categories: vec![fonts.clone()],
+            ..Default::default()
         };
         let sorter = Sorter::new(config);
-
         let files = sorter.collect_files(dir.path()).unwrap();
-        assert_eq!(files.len(), 2); // サブディレクトリ内も含まれる
+        let plans = sorter.create_plans(&files).unwrap();
+
+        // 全体の設定で定義したカテゴリは、ローカル設定で定義し直さなくても使える
+        assert_eq!(plans.len(), 1);
+        assert_eq!(plans[0].category, fonts);
     }
 
     #[test]
-    fn test_create_plans() {
+    fn test_local_configs_nearest_wins() {
         let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        let nested = project.join("notes");
+        fs::create_dir_all(&nested).unwrap();
 
-        File::create(dir.path().join("photo.jpg")).unwrap();
-        File::create(dir.path().join("document.pdf")).unwrap();
+        File::create(dir.path().join("readme.txt")).unwrap();
+        File::create(project.join("main.txt")).unwrap();
+        File::create(nested.join("todo.txt")).unwrap();
+        fs::write(
+            project.join(LOCAL_CONFIG_FILE_NAME),
+            "[extensions]\ntxt = \"Code\"\n",
+        )
+        .unwrap();
+        fs::write(
+            nested.join(LOCAL_CONFIG_FILE_NAME),
+            "[extensions]\ntxt = \"Others\"\n",
+        )
+        .unwrap();
 
         let config = SorterConfig {
             target_dir: dir.path().to_path_buf(),
-            dry_run: true,
-            recursive: false,
+            recursive: true,
+            local_configs: true,
+            extension_overrides: HashMap::from([("txt".to_string(), Category::Documents)]),
+            ..Default::default()
         };
         let sorter = Sorter::new(config);
-
         let files = sorter.collect_files(dir.path()).unwrap();
-        let plans = sorter.create_plans(&files).unwrap();
-
-        assert_eq!(plans.len(), 2);
+        assert_eq!(files.len(), 3);
 
-        // 各ファイルが正しいカテゴリに分類されているか
-        for plan in &plans {
-            let filename = plan.source.file_name().unwrap().to_str().unwrap();
-            match filename {
-                "photo.jpg" => assert_eq!(plan.category, Category::Images),
-                "document.pdf" => assert_eq!(plan.category, Category::Documents),
-                _ => panic!("Unexpected file: {}", filename),
-            }
-        }
+        let plans = sorter.create_plans(&files).unwrap();
+        let category_of = |name: &str| {
+            plans
+                .iter()
+                .find(|p| p.source.file_name().unwrap() == name)
+                .unwrap()
+                .category
+                .clone()
+        };
+        assert_eq!(category_of("readme.txt"), Category::Documents);
+        assert_eq!(category_of("main.txt"), Category::Code);
+        assert_eq!(category_of("todo.txt"), Category::Others);
     }
 }
@@ -0,0 +1,194 @@
+//! 書き込み中のファイルの検出モジュール
+//!
+//! ファイルサイズを一定間隔で繰り返し確認し、連続した複数回の確認で変化しなかった
+//! ファイルだけを書き込みが終わったとみなします。ダウンロードのように書き込みが
+//! 途中で一時停止するファイルを、停止中に移動してしまわないようにします。
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+/// 安定したとみなすのに必要な、サイズが同じだった連続の確認回数
+pub const STABLE_POLLS: usize = 3;
+
+/// 安定するのを待つ最大の確認回数（超えても変化しているファイルは書き込み中とみなす）
+pub const MAX_POLLS: usize = 20;
+
+/// サイズ確認の間隔と回数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StabilityPolicy {
+    /// 確認の間隔
+    pub interval: Duration,
+    /// サイズが同じだった連続の確認回数がこれに達したら安定とみなす
+    pub polls: usize,
+}
+
+impl StabilityPolicy {
+    /// 指定した間隔で `STABLE_POLLS` 回確認する設定
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            polls: STABLE_POLLS,
+        }
+    }
+}
+
+/// ファイルごとのサイズの変化を記録する
+#[derive(Debug, Clone, Default)]
+pub struct SizeTracker {
+    /// パス → (最後に確認したサイズ, そのサイズが続いた確認回数)
+    history: HashMap<PathBuf, (Option<u64>, usize)>,
+}
+
+impl SizeTracker {
+    /// サイズを記録し、`polls` 回続けて同じサイズだったかを返す
+    ///
+    /// 読めなかった（削除された・移動された）ファイルは安定とみなしません。
+    pub fn observe(&mut self, path: &Path, size: Option<u64>, polls: usize) -> bool {
+        let entry = self.history.entry(path.to_path_buf()).or_insert((size, 0));
+        if entry.0 == size {
+            entry.1 += 1;
+        } else {
+            *entry = (size, 1);
+        }
+        size.is_some() && entry.1 >= polls
+    }
+
+    /// ファイルの記録を消す（移動した・削除されたファイルを追跡し続けないように）
+    pub fn forget(&mut self, path: &Path) {
+        self.history.remove(path);
+    }
+}
+
+/// ファイルが安定するまでサイズを繰り返し確認する
+///
+/// 全ファイルが安定するか、`MAX_POLLS` 回確認するまで待ちます。
+/// サイズの取得と待機は `probe` と `sleep` で行うため、テストでは差し替えられます。
+///
+/// # Returns
+/// (安定したファイル, まだ書き込み中のファイル)
+pub fn settle<P, S>(
+    files: Vec<PathBuf>,
+    policy: StabilityPolicy,
+    mut probe: P,
+    mut sleep: S,
+) -> (Vec<PathBuf>, Vec<PathBuf>)
+where
+    P: FnMut(&Path) -> Option<u64>,
+    S: FnMut(Duration),
+{
+    let mut tracker = SizeTracker::default();
+    let mut stable = Vec::with_capacity(files.len());
+    let mut pending = files;
+
+    for poll in 0..MAX_POLLS.max(policy.polls) {
+        if poll > 0 {
+            sleep(policy.interval);
+        }
+        pending.retain(|file| {
+            if tracker.observe(file, probe(file), policy.polls) {
+                stable.push(file.clone());
+                false
+            } else {
+                true
+            }
+        });
+        if pending.is_empty() {
+            break;
+        }
+    }
+
+    for file in &pending {
+        debug!("File is still being written: {}", file.display());
+    }
+    (stable, pending)
+}
+
+/// 実際のファイルのサイズ（読めない場合は `None`）
+pub fn file_size(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|m| m.len())
+}
+
+/// `500ms`・`2s`・`1m`・`1h` 形式の時間を解析する（単位なしは秒）
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let digits = value.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        bail!("Invalid duration '{}' (expected e.g. 500ms, 2s, 1m)", value);
+    }
+    let number: u64 = value[..digits]
+        .parse()
+        .with_context(|| format!("Duration out of range: '{}'", value))?;
+    let millis = match value[digits..].to_lowercase().as_str() {
+        "ms" => Some(number),
+        "" | "s" => number.checked_mul(1000),
+        "m" => number.checked_mul(60 * 1000),
+        "h" => number.checked_mul(60 * 60 * 1000),
+        unit => bail!("Unknown duration unit '{}' in '{}'", unit, value),
+    };
+    millis
+        .map(Duration::from_millis)
+        .with_context(|| format!("Duration out of range: '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_settle_waits_past_pauses() {
+        // 伸びる → 一時停止 → 再び伸びる → 書き込み完了
+        let growing = PathBuf::from("download.iso");
+        let sizes = RefCell::new(vec![10, 20, 20, 35, 50, 50, 50, 50].into_iter());
+        let done = PathBuf::from("done.pdf");
+        let mut polls = 0;
+
+        let (stable, pending) = settle(
+            vec![growing.clone(), done.clone()],
+            StabilityPolicy::new(Duration::from_secs(1)),
+            |path| {
+                if path == growing {
+                    sizes.borrow_mut().next()
+                } else {
+                    Some(100)
+                }
+            },
+            |_| polls += 1,
+        );
+
+        assert!(pending.is_empty());
+        assert_eq!(stable, vec![done, growing]);
+        // 一時停止中（20 が2回）では安定とみなさず、50 が3回続いてから安定とみなす
+        assert_eq!(polls, 6);
+    }
+
+    #[test]
+    fn test_settle_gives_up_on_growing_file() {
+        let mut size = 0;
+        let (stable, pending) = settle(
+            vec![PathBuf::from("stream.log")],
+            StabilityPolicy::new(Duration::ZERO),
+            |_| {
+                size += 1;
+                Some(size)
+            },
+            |_| {},
+        );
+        assert!(stable.is_empty());
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("1m").unwrap(), Duration::from_secs(60));
+        assert!(parse_duration("fast").is_err());
+        assert!(parse_duration("3d").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! 同期モジュール
+//!
+//! 散らかった受信箱（整理対象のディレクトリ）と整理済みのアーカイブを突き合わせます。
+//! 受信箱のファイルはアーカイブのカテゴリ構成に振り分けられ、
+//! アーカイブにしかないファイルは報告するだけで変更しません。
+//! 対応関係はファイル名で判定します。
+
+use crate::file_ops::is_symlink;
+use crate::journal::JOURNAL_FILE_NAME;
+use crate::output::{say, Reporter};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 受信箱とアーカイブの突き合わせ結果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct SyncReport {
+    /// アーカイブに同名のファイルがなかった受信箱のファイル数
+    pub new_files: usize,
+    /// アーカイブに同名のファイルが既にあった受信箱のファイル数
+    pub existing_files: usize,
+    /// アーカイブに振り分けたファイル数
+    pub sorted_files: usize,
+    /// 受信箱に同名のファイルがないアーカイブのファイル（アーカイブからの相対パス）
+    pub archive_only: Vec<PathBuf>,
+}
+
+impl SyncReport {
+    /// 受信箱のファイルと整理前のアーカイブのファイルを突き合わせる
+    ///
+    /// `sorted_files` は実際に振り分けた後で設定します。
+    pub fn reconcile(inbox_files: &[PathBuf], archive: &Path, archive_files: &[PathBuf]) -> Self {
+        let inbox_names: HashSet<_> = inbox_files.iter().filter_map(|f| f.file_name()).collect();
+        let archive_names: HashSet<_> =
+            archive_files.iter().filter_map(|f| f.file_name()).collect();
+
+        let existing_files = inbox_files
+            .iter()
+            .filter(|f| f.file_name().is_some_and(|n| archive_names.contains(n)))
+            .count();
+        let mut archive_only: Vec<PathBuf> = archive_files
+            .iter()
+            .filter(|f| f.file_name().is_some_and(|n| !inbox_names.contains(n)))
+            .map(|f| f.strip_prefix(archive).unwrap_or(f).to_path_buf())
+            .collect();
+        archive_only.sort();
+
+        Self {
+            new_files: inbox_files.len() - existing_files,
+            existing_files,
+            sorted_files: 0,
+            archive_only,
+        }
+    }
+
+    /// 突き合わせ結果を表示
+    pub fn print(&self, dry_run: bool, reporter: &Reporter) {
+        say!(reporter);
+        say!(reporter, "{}", "=== Sync Summary ===".green().bold());
+        say!(
+            reporter,
+            "New in inbox: {}",
+            self.new_files.to_string().yellow()
+        );
+        say!(
+            reporter,
+            "Already in archive (by name): {}",
+            self.existing_files.to_string().yellow()
+        );
+        let label = if dry_run {
+            "Would sort into archive"
+        } else {
+            "Sorted into archive"
+        };
+        say!(
+            reporter,
+            "{}: {}",
+            label,
+            self.sorted_files.to_string().green()
+        );
+        say!(
+            reporter,
+            "Only in archive (left untouched): {}",
+            self.archive_only.len().to_string().yellow()
+        );
+        for path in &self.archive_only {
+            say!(reporter, "  {}", path.display());
+        }
+    }
+}
+
+/// アーカイブ内のファイルを再帰的に列挙する（アーカイブがなければ空）
+///
+/// シンボリックリンクとジャーナルは対象外です。
+pub fn archive_files(archive: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !archive.is_dir() {
+        return Ok(files);
+    }
+
+    let mut dirs = vec![archive.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+        {
+            let path = entry
+                .with_context(|| "Failed to read directory entry")?
+                .path();
+            if is_symlink(&path) {
+                continue;
+            }
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.file_name().and_then(|n| n.to_str()) != Some(JOURNAL_FILE_NAME) {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconcile_by_name() {
+        let archive = Path::new("/archive");
+        let inbox = [
+            PathBuf::from("/inbox/new.jpg"),
+            PathBuf::from("/inbox/old.pdf"),
+        ];
+        let archived = [
+            archive.join("Documents/old.pdf"),
+            archive.join("Music/song.mp3"),
+        ];
+
+        let report = SyncReport::reconcile(&inbox, archive, &archived);
+
+        assert_eq!(report.new_files, 1);
+        assert_eq!(report.existing_files, 1);
+        assert_eq!(report.archive_only, vec![PathBuf::from("Music/song.mp3")]);
+    }
+}
@@ -0,0 +1,480 @@
+//! テンプレートモジュール
+//!
+//! `{date}_{category}_{n}{ext}` のようなファイル名のテンプレートと、
+//! `{category}/{year}/{ext}` のような移動先ディレクトリのテンプレートを解析し、
+//! 移動先を組み立てます。テンプレートは起動時に検証されるため、
+//! 実行途中で不正なテンプレートによるエラーが起きることはありません。
+
+use crate::date::Date;
+use crate::sorter::NO_EXTENSION_FOLDER;
+use anyhow::{bail, Result};
+use serde::{Serialize, Serializer};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// テンプレート内のプレースホルダ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    /// 更新日（`YYYY-MM-DD`）
+    Date,
+    /// カテゴリ名
+    Category,
+    /// 実行内での通し番号（1始まり）
+    Counter,
+    /// 元の拡張子（ドット付き、拡張子なしの場合は空）
+    Ext,
+    /// 元のファイル名から拡張子を除いた部分
+    Stem,
+    /// 年（4桁）
+    Year,
+    /// 月（2桁）
+    Month,
+    /// 日（2桁）
+    Day,
+    /// 拡張子（ドットなし、小文字、拡張子なしの場合は `_noext`）
+    ExtName,
+}
+
+impl Placeholder {
+    /// ファイル名テンプレートで使えるプレースホルダ
+    const RENAME: &'static [Placeholder] = &[
+        Self::Date,
+        Self::Category,
+        Self::Counter,
+        Self::Ext,
+        Self::Stem,
+    ];
+
+    /// 移動先ディレクトリのテンプレートで使えるプレースホルダ
+    const DEST: &'static [Placeholder] = &[
+        Self::Category,
+        Self::Date,
+        Self::Year,
+        Self::Month,
+        Self::Day,
+        Self::ExtName,
+    ];
+
+    /// 重複回避のファイル名のテンプレートで使えるプレースホルダ
+    const DEDUP: &'static [Placeholder] = &[Self::Stem, Self::Counter, Self::Ext, Self::Date];
+
+    /// テンプレート内での名前（ファイル名テンプレートの `{ext}` はドット付き）
+    fn name(self) -> &'static str {
+        match self {
+            Self::Date => "date",
+            Self::Category => "category",
+            Self::Counter => "n",
+            Self::Ext | Self::ExtName => "ext",
+            Self::Stem => "stem",
+            Self::Year => "year",
+            Self::Month => "month",
+            Self::Day => "day",
+        }
+    }
+
+    /// 値を埋め込む
+    fn render(self, ctx: &TemplateContext<'_>) -> String {
+        match self {
+            Self::Date => ctx.date.to_string(),
+            Self::Category => ctx.category.to_string(),
+            Self::Counter => ctx.counter.to_string(),
+            Self::Ext => ctx
+                .extension
+                .map(|ext| format!(".{}", ext))
+                .unwrap_or_default(),
+            Self::Stem => ctx.stem.to_string(),
+            Self::Year => format!("{:04}", ctx.date.year),
+            Self::Month => format!("{:02}", ctx.date.month),
+            Self::Day => format!("{:02}", ctx.date.day),
+            Self::ExtName => ctx
+                .extension
+                .map(|ext| ext.to_lowercase())
+                .unwrap_or_else(|| NO_EXTENSION_FOLDER.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// テンプレートに埋め込む値
+#[derive(Debug, Clone)]
+pub struct TemplateContext<'a> {
+    /// 元のファイル名から拡張子を除いた部分
+    pub stem: &'a str,
+    /// 拡張子（ドットなし）
+    pub extension: Option<&'a str>,
+    /// カテゴリ名
+    pub category: &'a str,
+    /// ファイルの日付
+    pub date: Date,
+    /// 通し番号
+    pub counter: usize,
+}
+
+/// 移動先ファイル名のテンプレート
+///
+/// 使用できるプレースホルダ: `{date}`, `{category}`, `{n}`, `{ext}`, `{stem}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameTemplate {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl RenameTemplate {
+    /// テンプレートに値を埋め込んでファイル名を生成する
+    pub fn render(&self, ctx: &TemplateContext<'_>) -> String {
+        render_segments(&self.segments, ctx)
+    }
+}
+
+impl FromStr for RenameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.contains(['/', '\\']) {
+            bail!("Template must not contain path separators: '{}'", s);
+        }
+        let segments = parse_segments(s, s, Placeholder::RENAME)?;
+        if segments.is_empty() {
+            bail!("Template must not be empty");
+        }
+
+        Ok(Self {
+            source: s.to_string(),
+            segments,
+        })
+    }
+}
+
+/// 移動先ディレクトリのテンプレート（対象ディレクトリからの相対パス）
+///
+/// `/` で区切った各階層にプレースホルダを使えます。
+/// 使用できるプレースホルダ: `{category}`, `{date}`, `{year}`, `{month}`, `{day}`, `{ext}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestTemplate {
+    source: String,
+    /// 階層ごとのセグメント
+    components: Vec<Vec<Segment>>,
+}
+
+impl DestTemplate {
+    /// テンプレートに値を埋め込んで移動先ディレクトリの相対パスを生成する
+    pub fn render(&self, ctx: &TemplateContext<'_>) -> PathBuf {
+        self.components
+            .iter()
+            .map(|segments| render_segments(segments, ctx))
+            .collect()
+    }
+}
+
+impl FromStr for DestTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.contains('\\') {
+            bail!("Use '/' to separate folders in template '{}'", s);
+        }
+        let components = s
+            .split('/')
+            .map(|component| {
+                if component.is_empty() || component == "." || component == ".." {
+                    bail!(
+                        "Template must be a relative path without empty, '.' or '..' folders: '{}'",
+                        s
+                    );
+                }
+                parse_segments(component, s, Placeholder::DEST)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            source: s.to_string(),
+            components,
+        })
+    }
+}
+
+impl Serialize for DestTemplate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+/// 重複回避で連番を付けるファイル名のテンプレート
+///
+/// 使用できるプレースホルダ: `{stem}`, `{n}`, `{ext}`, `{date}`（実行した日）。
+/// 連番がないと重複を避けられないため、`{n}` は必須です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DedupTemplate {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl DedupTemplate {
+    /// 連番付きのファイル名を生成する
+    ///
+    /// `max_bytes` を指定すると、`{stem}` だけを UTF-8 の文字境界で切り詰めて上限に収めます。
+    ///
+    /// # Arguments
+    /// * `stem` - 元のファイル名から拡張子を除いた部分
+    /// * `extension` - 拡張子（ドットなし）
+    /// * `counter` - 連番（1始まり）
+    /// * `max_bytes` - ファイル名のバイト数の上限（`None` なら無制限）
+    pub fn render(
+        &self,
+        stem: &str,
+        extension: Option<&str>,
+        counter: usize,
+        max_bytes: Option<usize>,
+    ) -> String {
+        let mut ctx = TemplateContext {
+            stem: "",
+            extension,
+            category: "",
+            date: Date::from_system_time(SystemTime::now()),
+            counter,
+        };
+        let stem_count = self
+            .segments
+            .iter()
+            .filter(|s| **s == Segment::Placeholder(Placeholder::Stem))
+            .count();
+        let mut end = stem.len();
+        if let (Some(max), true) = (max_bytes, stem_count > 0) {
+            let reserved = render_segments(&self.segments, &ctx).len();
+            end = (max.saturating_sub(reserved) / stem_count).min(end);
+            while !stem.is_char_boundary(end) {
+                end -= 1;
+            }
+        }
+        ctx.stem = &stem[..end];
+        render_segments(&self.segments, &ctx)
+    }
+}
+
+impl Default for DedupTemplate {
+    /// 従来どおりの `{stem}_{n}{ext}`（例: `report_1.pdf`）
+    fn default() -> Self {
+        DEFAULT_DEDUP_TEMPLATE
+            .parse()
+            .expect("default dedup template is valid")
+    }
+}
+
+/// 重複回避のファイル名の既定のテンプレート
+pub const DEFAULT_DEDUP_TEMPLATE: &str = "{stem}_{n}{ext}";
+
+impl FromStr for DedupTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.contains(['/', '\\']) {
+            bail!("Template must not contain path separators: '{}'", s);
+        }
+        let segments = parse_segments(s, s, Placeholder::DEDUP)?;
+        if !segments.contains(&Segment::Placeholder(Placeholder::Counter)) {
+            bail!(
+                "Dedup template must contain {{n}} to number conflicting files: '{}'",
+                s
+            );
+        }
+
+        Ok(Self {
+            source: s.to_string(),
+            segments,
+        })
+    }
+}
+
+impl Serialize for DedupTemplate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+/// セグメントに値を埋め込んで連結する
+fn render_segments(segments: &[Segment], ctx: &TemplateContext<'_>) -> String {
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Literal(text) => text.clone(),
+            Segment::Placeholder(placeholder) => placeholder.render(ctx),
+        })
+        .collect()
+}
+
+/// テンプレート文字列をセグメントに分解する
+///
+/// # Arguments
+/// * `s` - 分解する文字列
+/// * `template` - エラーメッセージに表示するテンプレート全体
+/// * `allowed` - 使用できるプレースホルダ
+fn parse_segments(s: &str, template: &str, allowed: &[Placeholder]) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                if !closed {
+                    bail!("Unclosed '{{' in template '{}'", template);
+                }
+
+                let Some(placeholder) = allowed.iter().find(|p| p.name() == name).copied() else {
+                    let available: Vec<String> = allowed
+                        .iter()
+                        .map(|p| format!("{{{}}}", p.name()))
+                        .collect();
+                    bail!(
+                        "Unknown placeholder '{{{}}}' in template '{}' (available: {})",
+                        name,
+                        template,
+                        available.join(", ")
+                    );
+                };
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Placeholder(placeholder));
+            }
+            '}' => bail!("Unexpected '}}' in template '{}'", template),
+            _ => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+
+    Ok(segments)
+}
+
+impl Serialize for RenameTemplate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> TemplateContext<'static> {
+        TemplateContext {
+            stem: "IMG_0001",
+            extension: Some("jpg"),
+            category: "Images",
+            date: Date {
+                year: 2023,
+                month: 7,
+                day: 15,
+            },
+            counter: 3,
+        }
+    }
+
+    #[test]
+    fn test_dedup_template() {
+        let default = DedupTemplate::default();
+        assert_eq!(
+            default.render("report", Some("pdf"), 2, None),
+            "report_2.pdf"
+        );
+        assert_eq!(default.render("Makefile", None, 1, None), "Makefile_1");
+        assert_eq!(
+            default.render("report", Some("pdf"), 1, Some(10)),
+            "repo_1.pdf"
+        );
+
+        let template: DedupTemplate = "{stem} ({n}){ext}".parse().unwrap();
+        assert_eq!(
+            template.render("report", Some("pdf"), 3, None),
+            "report (3).pdf"
+        );
+
+        assert!("{stem}{ext}".parse::<DedupTemplate>().is_err());
+        assert!("{n}/{stem}".parse::<DedupTemplate>().is_err());
+        assert!("{stem}_{category}_{n}".parse::<DedupTemplate>().is_err());
+    }
+
+    #[test]
+    fn test_render_template() {
+        let template: RenameTemplate = "{date}_{category}_{n}{ext}".parse().unwrap();
+        assert_eq!(template.render(&context()), "2023-07-15_Images_3.jpg");
+
+        let template: RenameTemplate = "{stem}-copy{ext}".parse().unwrap();
+        assert_eq!(template.render(&context()), "IMG_0001-copy.jpg");
+    }
+
+    #[test]
+    fn test_render_without_extension() {
+        let template: RenameTemplate = "{n}{ext}".parse().unwrap();
+        let ctx = TemplateContext {
+            extension: None,
+            ..context()
+        };
+        assert_eq!(template.render(&ctx), "3");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!("{unknown}{ext}".parse::<RenameTemplate>().is_err());
+        assert!("{date".parse::<RenameTemplate>().is_err());
+        assert!("date}".parse::<RenameTemplate>().is_err());
+        assert!("a/{n}".parse::<RenameTemplate>().is_err());
+        assert!("".parse::<RenameTemplate>().is_err());
+        // 移動先ディレクトリ用のプレースホルダは使えない
+        assert!("{year}{ext}".parse::<RenameTemplate>().is_err());
+    }
+
+    #[test]
+    fn test_render_dest_template() {
+        let render = |template: &str, ctx: &TemplateContext<'_>| {
+            template.parse::<DestTemplate>().unwrap().render(ctx)
+        };
+        assert_eq!(render("{category}", &context()), PathBuf::from("Images"));
+        assert_eq!(
+            render("{category}/{year}/{ext}", &context()),
+            PathBuf::from("Images/2023/jpg")
+        );
+        assert_eq!(
+            render("by-date/{year}-{month}/{day}", &context()),
+            PathBuf::from("by-date/2023-07/15")
+        );
+
+        let ctx = TemplateContext {
+            extension: None,
+            ..context()
+        };
+        assert_eq!(
+            render("{category}/{ext}", &ctx),
+            PathBuf::from("Images").join(NO_EXTENSION_FOLDER)
+        );
+    }
+
+    #[test]
+    fn test_invalid_dest_templates() {
+        assert!("{category}/{n}".parse::<DestTemplate>().is_err());
+        assert!("{category}/{stem}".parse::<DestTemplate>().is_err());
+        assert!("/{category}".parse::<DestTemplate>().is_err());
+        assert!("{category}//{year}".parse::<DestTemplate>().is_err());
+        assert!("../{category}".parse::<DestTemplate>().is_err());
+        assert!("{category}\\{year}".parse::<DestTemplate>().is_err());
+        assert!("{year".parse::<DestTemplate>().is_err());
+        assert!("".parse::<DestTemplate>().is_err());
+    }
+}
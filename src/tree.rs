@@ -0,0 +1,98 @@
+//! 移動先のツリー表示モジュール
+//!
+//! Dry Runの計画を移動先のディレクトリごとにまとめ、`tree` コマンドのような
+//! 罫線付きの入れ子で表示します（`--tree`）。
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// 移動先のパスを入れ子にしたツリー
+///
+/// 子を持たないノードがファイル、子を持つノードがディレクトリです。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathTree {
+    children: BTreeMap<String, PathTree>,
+}
+
+impl PathTree {
+    /// 相対パスのファイルを追加する（途中のディレクトリは自動的に作られる）
+    pub fn insert(&mut self, path: &Path) {
+        let mut node = self;
+        for component in path.components() {
+            let name = component.as_os_str().to_string_lossy().into_owned();
+            node = node.children.entry(name).or_default();
+        }
+    }
+
+    /// ツリーに含まれるファイル数
+    pub fn file_count(&self) -> usize {
+        if self.children.is_empty() {
+            return 1;
+        }
+        self.children.values().map(PathTree::file_count).sum()
+    }
+
+    /// 罫線付きの行に変換する
+    ///
+    /// 同じ階層は名前順に並び、ディレクトリには末尾に `/` と配下のファイル数を付けます。
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.render_into("", &mut lines);
+        lines
+    }
+
+    fn render_into(&self, prefix: &str, lines: &mut Vec<String>) {
+        let last = self.children.len().saturating_sub(1);
+        for (index, (name, child)) in self.children.iter().enumerate() {
+            let (branch, indent) = if index == last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            if child.children.is_empty() {
+                lines.push(format!("{}{}{}", prefix, branch, name));
+            } else {
+                lines.push(format!(
+                    "{}{}{}/ ({})",
+                    prefix,
+                    branch,
+                    name,
+                    child.file_count()
+                ));
+                child.render_into(&format!("{}{}", prefix, indent), lines);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_tree() {
+        let mut tree = PathTree::default();
+        for path in [
+            "Images/b.png",
+            "Images/a.jpg",
+            "Documents/pdf/report.pdf",
+            "Documents/notes.txt",
+        ] {
+            tree.insert(Path::new(path));
+        }
+
+        assert_eq!(tree.file_count(), 4);
+        assert_eq!(
+            tree.render(),
+            vec![
+                "├── Documents/ (2)",
+                "│   ├── notes.txt",
+                "│   └── pdf/ (1)",
+                "│       └── report.pdf",
+                "└── Images/ (2)",
+                "    ├── a.jpg",
+                "    └── b.png",
+            ]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! 移動後の整合性検証モジュール
+//!
+//! 移動したファイルが期待どおり移動先に存在し、移動元から消えていることを確認します。
+//! 不安定なストレージでの気付きにくい失敗を検出するためのものです。
+
+use crate::file_ops::{hash_file, MoveMethod, OpKind};
+use anyhow::Result;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 検証のために記録する1件の移動
+#[derive(Debug, Clone)]
+pub struct MoveRecord {
+    /// 移動元のパス
+    pub source: PathBuf,
+    /// 移動先のパス
+    pub destination: PathBuf,
+    /// 移動前のファイルサイズ
+    pub expected_size: u64,
+    /// 移動前の内容のハッシュ（再ハッシュによる検証を行う場合のみ）
+    pub expected_hash: Option<String>,
+    /// 移動に使われた方法
+    pub method: MoveMethod,
+    /// 行った操作（コピーなら移動元が残っていても不整合としない）
+    pub kind: OpKind,
+}
+
+/// 検証で見つかった不整合
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// 移動先にファイルが存在しない
+    MissingDestination(PathBuf),
+    /// 移動先のサイズが移動前と異なる
+    SizeMismatch {
+        path: PathBuf,
+        expected: u64,
+        actual: u64,
+    },
+    /// 移動先の内容が移動前と異なる
+    HashMismatch(PathBuf),
+    /// 移動元にファイルが残っている
+    SourceRemains(PathBuf),
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discrepancy::MissingDestination(path) => {
+                write!(f, "destination missing: {}", path.display())
+            }
+            Discrepancy::SizeMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "size mismatch: {} (expected {} bytes, found {})",
+                path.display(),
+                expected,
+                actual
+            ),
+            Discrepancy::HashMismatch(path) => write!(f, "content mismatch: {}", path.display()),
+            Discrepancy::SourceRemains(path) => {
+                write!(f, "source still exists: {}", path.display())
+            }
+        }
+    }
+}
+
+/// 検証時にファイルシステムを参照するためのインターフェース
+///
+/// テストでは任意の状態を返す実装に差し替えられます。
+pub trait FileProbe {
+    /// ファイルが存在すればそのサイズを返す
+    fn size(&self, path: &Path) -> Option<u64>;
+
+    /// パスが存在するかどうか
+    fn exists(&self, path: &Path) -> bool;
+
+    /// ファイル内容のハッシュを計算する
+    fn hash(&self, path: &Path) -> Result<String>;
+}
+
+/// 実際のファイルシステムを参照する `FileProbe`
+pub struct RealFs;
+
+impl FileProbe for RealFs {
+    fn size(&self, path: &Path) -> Option<u64> {
+        fs::metadata(path)
+            .ok()
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn hash(&self, path: &Path) -> Result<String> {
+        hash_file(path)
+    }
+}
+
+/// 記録した移動を検証し、見つかった不整合を返す
+///
+/// rename による移動は存在とサイズのみ確認します。コピーによる移動で
+/// `expected_hash` が記録されている場合は、移動先を再ハッシュして比較します。
+pub fn verify_moves(records: &[MoveRecord], probe: &dyn FileProbe) -> Vec<Discrepancy> {
+    let mut discrepancies = Vec::new();
+
+    for record in records {
+        if record.kind == OpKind::Move && probe.exists(&record.source) {
+            discrepancies.push(Discrepancy::SourceRemains(record.source.clone()));
+        }
+
+        let Some(actual) = probe.size(&record.destination) else {
+            discrepancies.push(Discrepancy::MissingDestination(record.destination.clone()));
+            continue;
+        };
+        if actual != record.expected_size {
+            discrepancies.push(Discrepancy::SizeMismatch {
+                path: record.destination.clone(),
+                expected: record.expected_size,
+                actual,
+            });
+            continue;
+        }
+
+        if record.method == MoveMethod::Copy {
+            if let Some(expected) = &record.expected_hash {
+                let matches = probe
+                    .hash(&record.destination)
+                    .is_ok_and(|actual| &actual == expected);
+                if !matches {
+                    discrepancies.push(Discrepancy::HashMismatch(record.destination.clone()));
+                }
+            }
+        }
+    }
+
+    discrepancies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// 任意のサイズ・ハッシュを返すテスト用のファイルシステム
+    #[derive(Default)]
+    struct MockFs {
+        files: HashMap<PathBuf, (u64, String)>,
+    }
+
+    impl FileProbe for MockFs {
+        fn size(&self, path: &Path) -> Option<u64> {
+            self.files.get(path).map(|(size, _)| *size)
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.files.contains_key(path)
+        }
+
+        fn hash(&self, path: &Path) -> Result<String> {
+            self.files
+                .get(path)
+                .map(|(_, hash)| hash.clone())
+                .ok_or_else(|| anyhow::anyhow!("not found"))
+        }
+    }
+
+    fn record(name: &str, size: u64, method: MoveMethod) -> MoveRecord {
+        MoveRecord {
+            source: PathBuf::from("/src").join(name),
+            destination: PathBuf::from("/dst").join(name),
+            expected_size: size,
+            expected_hash: Some("abc".to_string()),
+            method,
+            kind: OpKind::Move,
+        }
+    }
+
+    #[test]
+    fn test_verify_reports_size_mismatch() {
+        let mut fs = MockFs::default();
+        fs.files
+            .insert(PathBuf::from("/dst/ok.txt"), (10, "abc".to_string()));
+        // 移動後にサイズが変わってしまったファイル
+        fs.files
+            .insert(PathBuf::from("/dst/short.txt"), (9, "abc".to_string()));
+
+        let records = vec![
+            record("ok.txt", 10, MoveMethod::Rename),
+            record("short.txt", 10, MoveMethod::Rename),
+        ];
+        let discrepancies = verify_moves(&records, &fs);
+
+        assert_eq!(
+            discrepancies,
+            vec![Discrepancy::SizeMismatch {
+                path: PathBuf::from("/dst/short.txt"),
+                expected: 10,
+                actual: 9,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_verify_reports_missing_remaining_and_rehash() {
+        let mut fs = MockFs::default();
+        fs.files
+            .insert(PathBuf::from("/src/left.txt"), (5, "abc".to_string()));
+        fs.files
+            .insert(PathBuf::from("/dst/left.txt"), (5, "abc".to_string()));
+        fs.files
+            .insert(PathBuf::from("/dst/copied.txt"), (5, "xyz".to_string()));
+
+        let records = vec![
+            record("gone.txt", 5, MoveMethod::Rename),
+            record("left.txt", 5, MoveMethod::Rename),
+            record("copied.txt", 5, MoveMethod::Copy),
+        ];
+        let discrepancies = verify_moves(&records, &fs);
+
+        assert_eq!(
+            discrepancies,
+            vec![
+                Discrepancy::MissingDestination(PathBuf::from("/dst/gone.txt")),
+                Discrepancy::SourceRemains(PathBuf::from("/src/left.txt")),
+                Discrepancy::HashMismatch(PathBuf::from("/dst/copied.txt")),
+            ]
+        );
+    }
+}
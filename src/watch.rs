@@ -0,0 +1,132 @@
+//! 監視モジュール
+//!
+//! `--watch` で対象ディレクトリに新しく作成された・移動してきたファイルを受け取り、
+//! サイズが変化しなくなる（書き込みが終わる）まで待ってから振り分けの対象にします。
+//! 監視そのもの（`notify` の通知の受け取り）と振り分けは `Sorter::watch` が行います。
+
+use crate::stability::SizeTracker;
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::Event;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 書き込み待ちのファイルのサイズを確認する間隔（`--stabilize-wait` 未指定時）
+pub const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 書き込みが終わるのを待っているファイル
+#[derive(Debug, Default)]
+pub struct PendingFiles {
+    files: BTreeSet<PathBuf>,
+    tracker: SizeTracker,
+}
+
+impl PendingFiles {
+    /// 待つファイルを加える（既に待っている場合は何もしない）
+    pub fn add(&mut self, path: PathBuf) {
+        self.files.insert(path);
+    }
+
+    /// 待っているファイルがないか
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// 待っているファイルのサイズを1回ずつ確認し、書き込みが終わったファイルを取り出す
+    ///
+    /// `polls` 回続けて同じサイズだったファイルを書き込みが終わったとみなします。
+    /// 読めなくなった（削除された・別の場所に移された）ファイルは待つのをやめます。
+    pub fn take_stable<P>(&mut self, polls: usize, mut probe: P) -> Vec<PathBuf>
+    where
+        P: FnMut(&Path) -> Option<u64>,
+    {
+        let mut stable = Vec::new();
+        let tracker = &mut self.tracker;
+        self.files.retain(|file| {
+            let size = probe(file);
+            let settled = tracker.observe(file, size, polls);
+            if settled || size.is_none() {
+                tracker.forget(file);
+                if settled {
+                    stable.push(file.clone());
+                }
+                false
+            } else {
+                true
+            }
+        });
+        stable
+    }
+}
+
+/// 通知から、対象ディレクトリに新しく現れた（または書き込み中の）パスを取り出す
+///
+/// 作成・書き込み・移動してきた場合のパスを返し、削除や移動していった場合は無視します。
+/// ディレクトリかどうかは判定しないため、呼び出し側で確認してください。
+pub fn arrived_paths(event: &Event) -> Vec<PathBuf> {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => Vec::new(),
+        // 移動元と移動先の両方が含まれる場合は移動先だけ
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            event.paths.last().cloned().into_iter().collect()
+        }
+        EventKind::Create(_) | EventKind::Modify(_) => event.paths.clone(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, RemoveKind};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_take_stable_waits_for_size_to_settle() {
+        let mut pending = PendingFiles::default();
+        pending.add(PathBuf::from("movie.mp4"));
+        pending.add(PathBuf::from("done.pdf"));
+        pending.add(PathBuf::from("gone.tmp"));
+
+        let mut sizes: HashMap<&str, Vec<u64>> = HashMap::new();
+        sizes.insert("movie.mp4", vec![10, 20, 30, 30, 30]);
+        sizes.insert("done.pdf", vec![5, 5, 5, 5, 5]);
+        let mut poll = 0;
+        let mut probe_round = |pending: &mut PendingFiles| {
+            let round = poll;
+            poll += 1;
+            pending.take_stable(3, |path| {
+                let name = path.to_str().unwrap();
+                sizes.get(name).map(|s| s[round])
+            })
+        };
+
+        // 消えたファイルは最初の確認で待つのをやめる
+        assert!(probe_round(&mut pending).is_empty());
+        assert!(!pending.files.contains(Path::new("gone.tmp")));
+        assert!(probe_round(&mut pending).is_empty());
+        assert_eq!(probe_round(&mut pending), vec![PathBuf::from("done.pdf")]);
+        assert!(probe_round(&mut pending).is_empty());
+        assert_eq!(probe_round(&mut pending), vec![PathBuf::from("movie.mp4")]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_arrived_paths() {
+        let event = |kind| Event::new(kind).add_path(PathBuf::from("a.jpg"));
+        assert_eq!(
+            arrived_paths(&event(EventKind::Create(CreateKind::File))),
+            vec![PathBuf::from("a.jpg")]
+        );
+        assert!(arrived_paths(&event(EventKind::Modify(ModifyKind::Name(
+            RenameMode::From
+        ))))
+        .is_empty());
+        assert!(arrived_paths(&event(EventKind::Remove(RemoveKind::File))).is_empty());
+
+        let renamed = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(PathBuf::from("a.part"))
+            .add_path(PathBuf::from("a.zip"));
+        assert_eq!(arrived_paths(&renamed), vec![PathBuf::from("a.zip")]);
+    }
+}